@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    creature::{Awake, CreatureFlags, FlagQuery, Sleeping, Wall},
+    map::{Map, Position},
+    spells::has_line_of_effect,
+    ui::{AddMessage, Message},
+};
+
+/// How far the sound of a melee blow carries, in steps around walls.
+pub const MELEE_NOISE: usize = 5;
+/// How far the sound of a harmful spell carries.
+pub const BLAST_NOISE: usize = 7;
+/// How far Alert creatures can spot the player from.
+pub const ALERT_SIGHT: i32 = 5;
+/// How far Patrolling creatures can spot the player from.
+pub const PATROL_SIGHT: i32 = 4;
+
+/// What a non-player creature is currently up to.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AiState {
+    /// Does nothing until woken up, by noise or by its cage opening.
+    Asleep,
+    /// Heard something, and is going to check it out.
+    Alert { toward: Position },
+    /// Walks its Patrol, keeping an eye out for the player.
+    Patrolling,
+    /// Knows where the player is, and is after them.
+    Hunting,
+    /// Running away from the player, see Cowardly.
+    Fleeing,
+}
+
+/// A loud action, heard by creatures up to `radius` steps away. Walls muffle
+/// it, so it has to travel around them.
+#[derive(Event)]
+pub struct Noise {
+    pub origin: Position,
+    pub radius: usize,
+}
+
+/// Wake up sleeping creatures within earshot of each noise, and draw Alert
+/// creatures towards it.
+pub fn propagate_noise(
+    mut events: EventReader<Noise>,
+    map: Res<Map>,
+    mut creatures: Query<&mut AiState>,
+    flags_query: Query<&CreatureFlags>,
+    wall_query: Query<&Wall>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    let mut woke_up = false;
+    for noise in events.read() {
+        let is_wall = |tile: &Position| {
+            map.get_entity_at(tile.x, tile.y)
+                .and_then(|entity| flags_query.get(*entity).ok())
+                .is_some_and(|flags| wall_query.flagged(flags))
+        };
+        // Breadth-first, so the radius is measured in steps and not as the crow flies.
+        let mut heard = HashSet::new();
+        let mut queue = VecDeque::from([(noise.origin, 0)]);
+        heard.insert(noise.origin);
+        while let Some((tile, distance)) = queue.pop_front() {
+            if distance == noise.radius {
+                continue;
+            }
+            for adjacent in map.get_adjacent_tiles(tile) {
+                // Walls hear the noise, but do not carry it further.
+                if heard.insert(adjacent) && !is_wall(&adjacent) {
+                    queue.push_back((adjacent, distance + 1));
+                }
+            }
+        }
+        let mut listeners: Vec<Position> = heard.into_iter().collect();
+        // Sorted, so replays wake creatures in the same order.
+        listeners.sort_by_key(|tile| (tile.x, tile.y));
+        for tile in listeners {
+            let Some(entity) = map.get_entity_at(tile.x, tile.y).copied() else {
+                continue;
+            };
+            let Ok(mut state) = creatures.get_mut(entity) else {
+                continue;
+            };
+            match *state {
+                AiState::Asleep => {
+                    commands.entity(entity).remove::<Sleeping>().insert(Awake);
+                    woke_up = true;
+                }
+                AiState::Alert { .. } | AiState::Patrolling => (),
+                AiState::Hunting | AiState::Fleeing => continue,
+            }
+            *state = AiState::Alert {
+                toward: noise.origin,
+            };
+        }
+    }
+    if woke_up {
+        text.send(AddMessage {
+            message: Message::SomethingStirs,
+        });
+    }
+}
+
+/// Whether `from` has a clear line to `to`, no more than `range` tiles away.
+pub fn can_see(
+    from: Position,
+    to: Position,
+    range: i32,
+    is_wall: impl Fn(&Position) -> bool,
+) -> bool {
+    (from.x - to.x).abs().max((from.y - to.y).abs()) <= range
+        && has_line_of_effect(from, to, is_wall)
+}
+
+/// A route walked by a creature, back to the first waypoint once it reaches the last.
+#[derive(Component, Clone, Debug)]
+pub struct Patrol {
+    pub waypoints: Vec<Position>,
+    /// The index of the waypoint being walked to.
+    pub next: usize,
+}
+
+impl Patrol {
+    /// The waypoint to walk to from `position`, moving on to the next one
+    /// if it was just reached.
+    pub fn destination(&mut self, position: Position) -> Position {
+        if self.waypoints[self.next] == position {
+            self.next = (self.next + 1) % self.waypoints.len();
+        }
+        self.waypoints[self.next]
+    }
+}
+
+/// Routes of the current floor, waiting for their creature to be summoned.
+#[derive(Resource, Default)]
+pub struct PendingPatrols {
+    pub routes: Vec<Vec<Position>>,
+}
+
+/// Hand each pending route to the creature standing on its first waypoint.
+pub fn assign_patrols(
+    mut patrols: ResMut<PendingPatrols>,
+    map: Res<Map>,
+    creatures: Query<(), (With<AiState>, Without<Patrol>)>,
+    mut commands: Commands,
+) {
+    // NOTE: Routes whose creature never shows up wait around until the next floor.
+    if patrols.routes.is_empty() {
+        return;
+    }
+    patrols.routes.retain(|route| {
+        let Some(entity) = route
+            .first()
+            .and_then(|start| map.get_entity_at(start.x, start.y))
+            .filter(|entity| creatures.contains(**entity))
+        else {
+            return true;
+        };
+        // Guards do not sleep on the job.
+        commands.entity(*entity).remove::<Sleeping>().insert((
+            Awake,
+            AiState::Patrolling,
+            Patrol {
+                waypoints: route.clone(),
+                next: 0,
+            },
+        ));
+        false
+    });
+}
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{CreatureFlags, FlagQuery, Hidden, Player, Species, Wall},
+    graphics::{apply_fog_of_war, VisualLayer},
+    map::Position,
+    sets::{modal_is_open, Animation, PlayerInput},
+    settings::{ColorPalette, Settings},
+    species::SpeciesRegistry,
+    terrain::{render_terrain, TerrainMesh},
+    text::{recolor_tag, split_text, strip_color_tags},
+    vision::VisibilityMap,
+};
+
+pub struct AsciiPlugin;
+
+impl Plugin for AsciiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AsciiMode>();
+        app.add_systems(Startup, spawn_tile_description);
+        app.add_systems(
+            Update,
+            toggle_ascii_mode
+                .run_if(not(modal_is_open))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            (
+                spawn_glyphs.run_if(|mode: Res<AsciiMode>| mode.enabled),
+                draw_glyphs,
+                describe_visible_tiles
+                    .run_if(resource_changed::<VisibilityMap>.or(resource_changed::<AsciiMode>)),
+            )
+                .chain()
+                .after(apply_fog_of_war)
+                .after(render_terrain)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// Every creature drawn as a coloured character instead of its sprite, with
+/// a written list of what the player can see. Toggled with U.
+#[derive(Resource, Default)]
+pub struct AsciiMode {
+    pub enabled: bool,
+}
+
+/// The character drawn over a creature in ASCII mode, a child of that creature.
+#[derive(Component)]
+pub struct Glyph {
+    color: Color,
+}
+
+/// A creature which already has its Glyph.
+#[derive(Component)]
+pub struct Glyphed;
+
+fn toggle_ascii_mode(input: Res<ButtonInput<KeyCode>>, mut mode: ResMut<AsciiMode>) {
+    if input.just_pressed(KeyCode::KeyU) {
+        mode.enabled = !mode.enabled;
+    }
+}
+
+/// Give each creature its glyph, the first time ASCII mode sees it, and
+/// redraw it when the creature transforms into another species.
+fn spawn_glyphs(
+    creatures: Query<
+        (Entity, &Species, Option<&Children>),
+        (With<Sprite>, Or<(Without<Glyphed>, Changed<Species>)>),
+    >,
+    mut glyphs: Query<(&mut Glyph, &mut Text2d)>,
+    registry: Res<SpeciesRegistry>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (entity, species, children) in creatures.iter() {
+        let (glyph, color) = registry.get(species).glyph();
+        let child = children
+            .into_iter()
+            .flatten()
+            .find(|child| glyphs.contains(**child));
+        if let Some(Ok((mut current, mut text))) = child.map(|child| glyphs.get_mut(*child)) {
+            current.color = color;
+            text.0 = glyph.to_string();
+            continue;
+        }
+        commands
+            .entity(entity)
+            .insert(Glyphed)
+            .with_children(|parent| {
+                parent.spawn((
+                    Glyph { color },
+                    Text2d::new(glyph),
+                    TextFont {
+                        font: asset_server.load("fonts/Play-Regular.ttf"),
+                        font_size: 2.5,
+                        ..default()
+                    },
+                    TextColor(Color::NONE),
+                    Transform::default(),
+                    VisualLayer::Overlay,
+                    Visibility::Hidden,
+                ));
+            });
+    }
+}
+
+/// In ASCII mode, hide every sprite, and tint each glyph with the fog of war
+/// its sprite would have had.
+// NOTE: This runs after apply_fog_of_war, and reuses the tint it gave the sprites.
+// Glyphs are Visible, not Inherited, as creatures in the TerrainMesh hide their own sprite.
+fn draw_glyphs(
+    mode: Res<AsciiMode>,
+    mut creatures: Query<(&mut Sprite, &Transform, &Children), With<Glyphed>>,
+    mut glyphs: Query<(&Glyph, &mut TextColor, &mut Transform, &mut Visibility), Without<Glyphed>>,
+    mut terrain: Query<&mut Visibility, (With<TerrainMesh>, Without<Glyph>)>,
+    settings: Res<Settings>,
+) {
+    for mut visibility in terrain.iter_mut() {
+        visibility.set_if_neq(if mode.enabled {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+    for (mut sprite, creature_transform, children) in creatures.iter_mut() {
+        let child = children.iter().find(|child| glyphs.contains(**child));
+        let Some(Ok((glyph, mut color, mut transform, mut visibility))) =
+            child.map(|child| glyphs.get_mut(*child))
+        else {
+            continue;
+        };
+        if !mode.enabled {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        let fog = sprite.color.to_linear();
+        let tint = recolor_tag(glyph.color, ColorPalette::Standard, settings.palette)
+            .unwrap_or(glyph.color)
+            .to_linear();
+        let tinted = LinearRgba::new(
+            tint.red * fog.red,
+            tint.green * fog.green,
+            tint.blue * fog.blue,
+            tint.alpha * fog.alpha,
+        )
+        .into();
+        if color.0 != tinted {
+            color.0 = tinted;
+        }
+        // Glyphs stay upright, whichever way their creature faces.
+        let upright = creature_transform.rotation.inverse();
+        if transform.rotation != upright {
+            transform.rotation = upright;
+        }
+        visibility.set_if_neq(Visibility::Visible);
+        sprite.color = Color::NONE;
+    }
+}
+
+#[derive(Component)]
+pub struct TileDescription;
+
+fn spawn_tile_description(mut commands: Commands) {
+    commands.spawn((
+        TileDescription,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(4.),
+            top: Val::Percent(30.),
+            max_width: Val::Percent(25.),
+            padding: UiRect::all(Val::Px(0.5)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+        Visibility::Hidden,
+    ));
+}
+
+/// Where `position` is from `origin`, in words.
+fn describe_offset(origin: &Position, position: &Position) -> String {
+    let (dx, dy) = (position.x - origin.x, position.y - origin.y);
+    let mut parts = Vec::new();
+    if dy != 0 {
+        parts.push(format!(
+            "{} {}",
+            dy.abs(),
+            if dy > 0 { "north" } else { "south" }
+        ));
+    }
+    if dx != 0 {
+        parts.push(format!(
+            "{} {}",
+            dx.abs(),
+            if dx > 0 { "east" } else { "west" }
+        ));
+    }
+    if parts.is_empty() {
+        "here".to_owned()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// In ASCII mode, list every creature the player can see, nearest first, and
+/// where it stands. The list is also logged, for screen readers.
+/// Traps which were not found yet stay out of it, like in apply_fog_of_war.
+fn describe_visible_tiles(
+    mode: Res<AsciiMode>,
+    player: Query<&Position, With<Player>>,
+    creatures: Query<(&Position, &Species, &CreatureFlags), (Without<Player>, Without<Hidden>)>,
+    walls: Query<(), With<Wall>>,
+    vision: Res<VisibilityMap>,
+    registry: Res<SpeciesRegistry>,
+    mut panel: Query<(Entity, &mut Visibility), With<TileDescription>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let Ok((panel, mut visibility)) = panel.get_single_mut() else {
+        return;
+    };
+    commands.entity(panel).despawn_descendants();
+    let Some(origin) = player.get_single().ok().filter(|_| mode.enabled) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+    let mut seen: Vec<(&Position, &Species)> = creatures
+        .iter()
+        .filter(|(position, _, flags)| vision.is_visible(position) && !walls.flagged(flags))
+        .map(|(position, species, _)| (position, species))
+        .collect();
+    seen.sort_by_key(|(position, _)| {
+        (
+            (position.x - origin.x).abs() + (position.y - origin.y).abs(),
+            position.x,
+            position.y,
+        )
+    });
+    let lines: Vec<String> = seen
+        .iter()
+        .map(|(position, species)| {
+            format!(
+                "{}: {}",
+                registry.get(species).name,
+                describe_offset(origin, position)
+            )
+        })
+        .collect();
+    let description = if lines.is_empty() {
+        "You see nothing around you.".to_owned()
+    } else {
+        format!("You see:\n{}", lines.join("\n"))
+    };
+    info!("{}", strip_color_tags(&description));
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    commands.entity(panel).with_children(|parent| {
+        for (section, color) in split_text(&description) {
+            parent.spawn((TextSpan::new(section), font.clone(), color));
+        }
+    });
+}
@@ -0,0 +1,177 @@
+use std::{path::Path, time::Duration};
+
+use bevy::{
+    audio::{DefaultSpatialScale, SpatialScale, Volume},
+    prelude::*,
+};
+
+use crate::{
+    creature::Awake,
+    events::{DrawSoul, OpenCloseDoor},
+    graphics::{EffectType, PlaceMagicVfx},
+    map::Position,
+    sets::Animation,
+    TILE_SIZE,
+};
+
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySound>();
+        // Sounds 4 tiles away are heard as if they were 1 unit away.
+        app.insert_resource(DefaultSpatialScale(SpatialScale::new_2d(
+            1. / (TILE_SIZE * 4.),
+        )));
+        app.add_systems(Startup, start_music);
+        app.add_systems(Update, (play_sounds, crossfade_music).in_set(Animation));
+    }
+}
+
+/// How loud sound effects are, from 0 to 1.
+const SOUND_VOLUME: f32 = 0.3;
+/// How loud the music is, from 0 to 1.
+const MUSIC_VOLUME: f32 = 0.5;
+/// How much of MUSIC_VOLUME fades in or out each second while switching tracks.
+const MUSIC_FADE: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sound {
+    MeleeHit,
+    /// A spell effect appearing.
+    Spell(EffectType),
+    Door {
+        open: bool,
+    },
+    SoulDraw,
+}
+
+impl Sound {
+    /// The frequency and length of the tone this sound is played as.
+    // TODO: These are placeholder blips, until there are actual sound files.
+    fn tone(&self) -> (f32, f32) {
+        match self {
+            Sound::MeleeHit => (110., 0.08),
+            Sound::Spell(effect) => match effect {
+                EffectType::HorizontalBeam | EffectType::VerticalBeam => (660., 0.1),
+                EffectType::RedBlast => (220., 0.15),
+                EffectType::GreenBlast => (440., 0.15),
+                // Creatures flash an X when they are removed.
+                EffectType::XCross => (80., 0.25),
+                EffectType::Airlock => (330., 0.2),
+                EffectType::Deflect => (1320., 0.05),
+            },
+            Sound::Door { open: true } => (392., 0.12),
+            Sound::Door { open: false } => (294., 0.12),
+            Sound::SoulDraw => (880., 0.06),
+        }
+    }
+}
+
+#[derive(Event)]
+/// Play a sound, heard from this tile if there is one.
+pub struct PlaySound {
+    pub sound: Sound,
+    pub position: Option<Position>,
+}
+
+/// Play every sound sent this frame. Spells, doors and soul draws are heard
+/// through their own events, so they don't need to send a PlaySound.
+pub fn play_sounds(
+    mut events: EventReader<PlaySound>,
+    mut magic_vfx: EventReader<PlaceMagicVfx>,
+    mut doors: EventReader<OpenCloseDoor>,
+    mut draws: EventReader<DrawSoul>,
+    positions: Query<&Position>,
+    mut pitches: ResMut<Assets<Pitch>>,
+    mut commands: Commands,
+) {
+    let sounds = events
+        .read()
+        .map(|event| (event.sound, event.position))
+        .chain(magic_vfx.read().filter_map(|event| {
+            // Long beams are heard from where they start.
+            event
+                .targets
+                .first()
+                .map(|position| (Sound::Spell(event.effect), Some(*position)))
+        }))
+        .chain(doors.read().map(|event| {
+            (
+                Sound::Door { open: event.open },
+                positions.get(event.entity).ok().copied(),
+            )
+        }))
+        .chain(draws.read().map(|_| (Sound::SoulDraw, None)));
+    for (sound, position) in sounds {
+        let (frequency, length) = sound.tone();
+        let mut player = commands.spawn((
+            AudioPlayer(pitches.add(Pitch::new(frequency, Duration::from_secs_f32(length)))),
+            PlaybackSettings::DESPAWN
+                .with_volume(Volume::new(SOUND_VOLUME))
+                .with_spatial(position.is_some()),
+        ));
+        if let Some(position) = position {
+            player.insert(Transform::from_xyz(
+                position.x as f32 * TILE_SIZE,
+                position.y as f32 * TILE_SIZE,
+                0.,
+            ));
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MusicTrack {
+    /// Played while nothing is hunting the player.
+    Hub,
+    /// Played while there are awake creatures around.
+    Combat,
+}
+
+/// One of the music tracks, which all play at once, fading in and out.
+#[derive(Component)]
+pub struct Music {
+    pub track: MusicTrack,
+}
+
+/// Start every music track found in the assets folder. Tracks are optional,
+/// the game is silent (but for its sounds) without them.
+fn start_music(asset_server: Res<AssetServer>, mut commands: Commands) {
+    for (track, path) in [
+        (MusicTrack::Hub, "music/hub.ogg"),
+        (MusicTrack::Combat, "music/combat.ogg"),
+    ] {
+        if !Path::new("assets").join(path).exists() {
+            info!("No music at assets/{}, skipping it.", path);
+            continue;
+        }
+        commands.spawn((
+            Music { track },
+            AudioPlayer::new(asset_server.load(path)),
+            PlaybackSettings::LOOP.with_volume(Volume::ZERO),
+        ));
+    }
+}
+
+/// Each frame, fade in the track which fits the situation, and fade out the other.
+pub fn crossfade_music(
+    music: Query<(&Music, &AudioSink)>,
+    awake_creatures: Query<(), With<Awake>>,
+    time: Res<Time>,
+) {
+    let playing = if awake_creatures.is_empty() {
+        MusicTrack::Hub
+    } else {
+        MusicTrack::Combat
+    };
+    let step = MUSIC_FADE * MUSIC_VOLUME * time.delta_secs();
+    for (music, sink) in music.iter() {
+        let volume = if music.track == playing {
+            (sink.volume() + step).min(MUSIC_VOLUME)
+        } else {
+            (sink.volume() - step).max(0.)
+        };
+        sink.set_volume(volume);
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{CreatureFlags, FlagEntity},
+    events::TurnManager,
+    graphics::VfxPool,
+    sets::Cleanup,
+};
+
+/// How many turns pass between each audit.
+const AUDIT_INTERVAL: usize = 10;
+
+pub struct AuditPlugin;
+
+impl Plugin for AuditPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, audit_flag_entities.after(Cleanup));
+    }
+}
+
+/// Cross-check every creature against its flag entities, and every flag entity
+/// against its creature. Flag entities which lost their creature are despawned.
+// NOTE: HP bars live in the overlay, which cleans up after dead creatures on its own.
+// They don't need auditing.
+pub fn audit_flag_entities(
+    creatures: Query<(Entity, &CreatureFlags)>,
+    flag_entities: Query<(Entity, &FlagEntity)>,
+    turn_manager: Res<TurnManager>,
+    vfx_pool: Res<VfxPool>,
+    mut last_audit: Local<Option<usize>>,
+    mut commands: Commands,
+) {
+    let turn = turn_manager.turn_count;
+    if turn % AUDIT_INTERVAL != 0 || *last_audit == Some(turn) {
+        return;
+    }
+    *last_audit = Some(turn);
+    let mut orphans = 0;
+    for (flag_entity, flag) in flag_entities.iter() {
+        let is_owned = creatures.get(flag.parent_creature).is_ok_and(|(_, flags)| {
+            flags.effects_flags == flag_entity || flags.species_flags == flag_entity
+        });
+        if !is_owned {
+            warn!(
+                "Flag entity {} lost its creature {}, despawning it.",
+                flag_entity, flag.parent_creature
+            );
+            commands.entity(flag_entity).despawn_recursive();
+            orphans += 1;
+        }
+    }
+    for (creature, flags) in creatures.iter() {
+        for flag_entity in [flags.effects_flags, flags.species_flags] {
+            let points_back = flag_entities
+                .get(flag_entity)
+                .is_ok_and(|(_, flag)| flag.parent_creature == creature);
+            if !points_back {
+                warn!(
+                    "Creature {} has a flag entity {} which does not belong to it.",
+                    creature, flag_entity
+                );
+            }
+        }
+    }
+    info!(
+        "Turn {} audit: {} creatures, {} flag entities, {} orphans.",
+        turn,
+        creatures.iter().len(),
+        flag_entities.iter().len(),
+        orphans
+    );
+    info!(
+        "VFX pool: {} reused, {} spawned, {:.0}% hit rate.",
+        vfx_pool.hits,
+        vfx_pool.misses,
+        vfx_pool.hits as f32 * 100. / (vfx_pool.hits + vfx_pool.misses).max(1) as f32
+    );
+}
@@ -0,0 +1,289 @@
+//! Pit player spellbooks against enemy compositions, over many seeded headless runs,
+//! and print the results as CSV.
+//!
+//! `cargo run --release --bin balance_sim [runs] [config.ron]`
+//!
+//! Without a config, a few built-in builds and compositions are used. A config is a
+//! BalanceConfig written in RON. Builds only replace the spells they mention.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::prelude::*;
+use redesign_tgfp::{
+    apply_player_command, headless_app, settle_turn, AnnounceGameOver, Awake, Axiom,
+    DamageOrHealCreature, GameRng, LevelGenConfig, Map, OrdDir, Player, PlayerCommand, Position,
+    Soul, SoulWheel, Species, Spell, Spellbook,
+};
+use serde::Deserialize;
+
+/// Enemies further than this are walked towards instead of cast at.
+const CAST_RANGE: i32 = 5;
+
+#[derive(Deserialize)]
+struct BalanceConfig {
+    /// How many turns a run can last before it is given up on.
+    max_turns: usize,
+    builds: Vec<Build>,
+    compositions: Vec<Composition>,
+}
+
+#[derive(Deserialize)]
+struct Build {
+    name: String,
+    spells: HashMap<Soul, Spell>,
+}
+
+#[derive(Deserialize)]
+struct Composition {
+    name: String,
+    species: Vec<(Species, u32)>,
+    creatures: usize,
+}
+
+impl Default for BalanceConfig {
+    fn default() -> Self {
+        let everywhere = |axioms: Vec<Axiom>| {
+            [
+                Soul::Saintly,
+                Soul::Ordered,
+                Soul::Artistic,
+                Soul::Unhinged,
+                Soul::Feral,
+                Soul::Vile,
+            ]
+            .into_iter()
+            .map(|soul| {
+                (
+                    soul,
+                    Spell {
+                        axioms: axioms.clone(),
+                    },
+                )
+            })
+            .collect()
+        };
+        Self {
+            max_turns: 300,
+            builds: vec![
+                Build {
+                    name: "Starter".to_owned(),
+                    spells: HashMap::new(),
+                },
+                Build {
+                    name: "Beams".to_owned(),
+                    spells: everywhere(vec![Axiom::MomentumBeam, Axiom::HealOrHarm { amount: -2 }]),
+                },
+                Build {
+                    name: "Bolts".to_owned(),
+                    spells: everywhere(vec![
+                        Axiom::Touch,
+                        Axiom::LaunchProjectile,
+                        Axiom::HealOrHarm { amount: -3 },
+                    ]),
+                },
+            ],
+            compositions: vec![
+                Composition {
+                    name: "Mixed".to_owned(),
+                    species: LevelGenConfig::default().species,
+                    creatures: 3,
+                },
+                Composition {
+                    name: "Hunters".to_owned(),
+                    species: vec![(Species::Hunter, 1)],
+                    creatures: 4,
+                },
+                Composition {
+                    name: "Shrikes".to_owned(),
+                    species: vec![(Species::Shrike, 1)],
+                    creatures: 3,
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Win,
+    Loss,
+    Timeout,
+}
+
+/// How much damage the player took, by the species which dealt it.
+#[derive(Resource, Default)]
+struct DamageTaken {
+    sources: BTreeMap<String, usize>,
+}
+
+fn tally_damage(
+    mut events: EventReader<DamageOrHealCreature>,
+    players: Query<(), With<Player>>,
+    species: Query<&Species>,
+    mut taken: ResMut<DamageTaken>,
+) {
+    for event in events.read() {
+        if event.hp_mod >= 0 || !players.contains(event.entity) {
+            continue;
+        }
+        let source = species
+            .get(event.culprit)
+            .map_or("Unknown".to_owned(), |species| format!("{:?}", species));
+        *taken.sources.entry(source).or_default() += event.hp_mod.unsigned_abs();
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let runs: u64 = args.get(1).and_then(|arg| arg.parse().ok()).unwrap_or(100);
+    let config = match args.get(2) {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path).expect("Could not read the config");
+            ron::from_str(&contents).expect("The config is invalid")
+        }
+        None => BalanceConfig::default(),
+    };
+    println!("build,composition,runs,wins,losses,timeouts,win_rate,average_turns,damage_sources");
+    for build in &config.builds {
+        for composition in &config.compositions {
+            eprintln!("Simulating {} against {}...", build.name, composition.name);
+            let (mut outcomes, mut turns) = (Vec::new(), 0);
+            let mut damage: BTreeMap<String, usize> = BTreeMap::new();
+            for seed in 0..runs {
+                let (outcome, run_turns, run_damage) =
+                    simulate(build, composition, seed, config.max_turns);
+                outcomes.push(outcome);
+                turns += run_turns;
+                for (source, amount) in run_damage {
+                    *damage.entry(source).or_default() += amount;
+                }
+            }
+            let count = |outcome| outcomes.iter().filter(|o| **o == outcome).count();
+            println!(
+                "{},{},{},{},{},{},{:.3},{:.1},{}",
+                build.name,
+                composition.name,
+                runs,
+                count(Outcome::Win),
+                count(Outcome::Loss),
+                count(Outcome::Timeout),
+                count(Outcome::Win) as f32 / runs.max(1) as f32,
+                turns as f32 / runs.max(1) as f32,
+                damage
+                    .iter()
+                    .map(|(source, amount)| format!("{}:{}", source, amount))
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            );
+        }
+    }
+}
+
+/// Play a single run to its end, or until `max_turns` have passed.
+fn simulate(
+    build: &Build,
+    composition: &Composition,
+    seed: u64,
+    max_turns: usize,
+) -> (Outcome, usize, BTreeMap<String, usize>) {
+    let mut app = headless_app();
+    app.insert_resource(GameRng::new(seed));
+    app.insert_resource(LevelGenConfig {
+        species: composition.species.clone(),
+        creatures: composition.creatures,
+        ..default()
+    });
+    app.init_resource::<DamageTaken>();
+    app.add_systems(Update, tally_damage);
+    // Spawn the cage.
+    settle_turn(&mut app);
+    let world = app.world_mut();
+    if let Ok(mut spellbook) = world
+        .query_filtered::<&mut Spellbook, With<Player>>()
+        .get_single_mut(world)
+    {
+        spellbook.spells.extend(build.spells.clone());
+    }
+    let mut outcome = Outcome::Timeout;
+    let mut turns = 0;
+    while turns < max_turns {
+        for command in choose_commands(app.world_mut()) {
+            apply_player_command(app.world_mut(), command);
+        }
+        settle_turn(&mut app);
+        turns += 1;
+        let world = app.world_mut();
+        if let Some(game_over) = world
+            .resource_mut::<Events<AnnounceGameOver>>()
+            .drain()
+            .next()
+        {
+            outcome = if game_over.victorious {
+                Outcome::Win
+            } else {
+                Outcome::Loss
+            };
+            break;
+        }
+    }
+    let damage = std::mem::take(&mut app.world_mut().resource_mut::<DamageTaken>().sources);
+    (outcome, turns, damage)
+}
+
+/// A simple-minded player: hit what is adjacent, cast at what is near,
+/// draw souls when out of spells, and walk towards the closest enemy otherwise.
+/// Only the last command ends the turn.
+fn choose_commands(world: &mut World) -> Vec<PlayerCommand> {
+    let Ok((player_pos, spellbook)) = world
+        .query_filtered::<(&Position, &Spellbook), With<Player>>()
+        .get_single(world)
+        .map(|(position, spellbook)| (*position, spellbook.clone()))
+    else {
+        return Vec::new();
+    };
+    let distance =
+        |position: &Position| (position.x - player_pos.x).abs() + (position.y - player_pos.y).abs();
+    // Ties are broken by position, to keep runs deterministic.
+    let nearest = world
+        .query_filtered::<&Position, (With<Awake>, Without<Player>)>()
+        .iter(world)
+        .copied()
+        .min_by_key(|position| (distance(position), position.x, position.y));
+    let map = world.resource::<Map>();
+    let wander = || {
+        [OrdDir::Up, OrdDir::Right, OrdDir::Down, OrdDir::Left]
+            .into_iter()
+            .find(|direction| {
+                let (dx, dy) = direction.as_offset();
+                map.is_passable(player_pos.x + dx, player_pos.y + dy)
+            })
+            .map_or(PlayerCommand::DrawSoul, PlayerCommand::Step)
+    };
+    let Some(enemy) = nearest else {
+        // Nothing left, but the victory is only announced at the end of a turn.
+        return vec![wander()];
+    };
+    let direction = OrdDir::direction_towards(player_pos, enemy).unwrap_or(OrdDir::Up);
+    if distance(&enemy) == 1 {
+        return vec![PlayerCommand::Step(direction)];
+    }
+    let soul_wheel = world.resource::<SoulWheel>();
+    let castable = soul_wheel
+        .souls
+        .iter()
+        .position(|soul| soul.is_some_and(|soul| spellbook.spells.contains_key(&soul)));
+    if let (Some(index), true) = (castable, distance(&enemy) <= CAST_RANGE) {
+        return vec![
+            PlayerCommand::Face(direction),
+            PlayerCommand::CastSoul(index, Some(enemy)),
+        ];
+    }
+    let can_draw = soul_wheel.souls.iter().any(Option::is_none)
+        && soul_wheel.draw_pile.values().sum::<usize>() > 0;
+    if castable.is_none() && can_draw {
+        return vec![PlayerCommand::DrawSoul];
+    }
+    vec![map
+        .best_manhattan_move(player_pos, enemy)
+        .map_or_else(wander, PlayerCommand::Step)]
+}
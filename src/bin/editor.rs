@@ -0,0 +1,421 @@
+//! Paint floors tile by tile, with the same characters, species and sprites as the game.
+//!
+//! `cargo run --bin editor [path] [width] [height]`
+//!
+//! - Left click paints the brush, right click erases back to floor.
+//! - Q and E pick the previous or next brush, shown at the top.
+//! - G marks a corner of a new region, and the second press completes it.
+//! - N adds the hovered tile to the current patrol, and Enter completes it.
+//! - Backspace removes the last patrol, or the last region once there are none.
+//! - Ctrl+S saves, Ctrl+L reloads from the disk.
+
+use std::f32::consts::PI;
+
+use bevy::{asset::AssetMetaCheck, prelude::*, window::PrimaryWindow};
+use redesign_tgfp::{
+    species_from_tile, strip_color_tags, tile_kind_from_char, MapFile, MapRegion, OrdDir,
+    SpeciesRegistry, SpriteSheetAtlas, AUTHORED_MAP, TILE_SIZE,
+};
+
+/// Every character which can be painted, other than floor.
+const PALETTE: &[char] = &[
+    '#', 'W', '@', 'H', 'S', 'T', '2', 'A', 'F', 'O', 'C', 'Z', 'c', 'L', 'P', '$', '%', '&', 'k',
+    '*', '(', '^', '>', '<', 'V', 'u', 'r', 'l', 'd', '7', '9', '3', '1', '8', '6', '5', '4', '~',
+    '!', '_', ':', '=', '+', 'j', 'B', 'b',
+];
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let path = args
+        .get(1)
+        .cloned()
+        .unwrap_or_else(|| AUTHORED_MAP.to_owned());
+    let size = |idx: usize| args.get(idx).and_then(|arg| arg.parse().ok()).unwrap_or(17);
+    let map = MapFile::load(&path).unwrap_or_else(|_| MapFile::new(size(2), size(3)));
+    App::new()
+        .add_plugins(
+            DefaultPlugins
+                .set(AssetPlugin {
+                    meta_check: AssetMetaCheck::Never,
+                    ..Default::default()
+                })
+                .set(ImagePlugin::default_nearest())
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "The Games Foxes Play - Editor".into(),
+                        ..default()
+                    }),
+                    ..default()
+                }),
+        )
+        .init_resource::<SpriteSheetAtlas>()
+        .init_resource::<SpeciesRegistry>()
+        .insert_resource(Editor {
+            map,
+            path,
+            brush: 0,
+            region_corner: None,
+            patrol: Vec::new(),
+            status: String::new(),
+        })
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                pick_brush,
+                paint,
+                author_regions,
+                author_patrols,
+                save_and_load,
+                render_preview.run_if(resource_changed::<Editor>),
+                render_overlays,
+                update_status,
+            )
+                .chain(),
+        )
+        .run();
+}
+
+#[derive(Resource)]
+struct Editor {
+    map: MapFile,
+    path: String,
+    /// Index into PALETTE.
+    brush: usize,
+    /// The first corner of the region being drawn.
+    region_corner: Option<usize>,
+    /// The waypoints of the patrol being drawn.
+    patrol: Vec<usize>,
+    /// The result of the last save or load.
+    status: String,
+}
+
+impl Editor {
+    fn size(&self) -> (usize, usize) {
+        (
+            self.map.rows.first().map_or(0, |row| row.chars().count()),
+            self.map.rows.len(),
+        )
+    }
+
+    /// The world position of a tile index, with the top row at the top.
+    fn world_of(&self, idx: usize) -> Vec2 {
+        let (width, height) = self.size();
+        Vec2::new(
+            (idx % width) as f32 * TILE_SIZE,
+            (height - 1 - idx / width) as f32 * TILE_SIZE,
+        )
+    }
+
+    fn tile_at(&self, world: Vec2) -> Option<usize> {
+        let (width, height) = self.size();
+        let (x, y) = (
+            (world.x / TILE_SIZE).round(),
+            height as f32 - 1. - (world.y / TILE_SIZE).round(),
+        );
+        (x >= 0. && y >= 0. && (x as usize) < width && (y as usize) < height)
+            .then_some(x as usize + y as usize * width)
+    }
+
+    fn set_tile(&mut self, idx: usize, tile: char) {
+        let width = self.size().0;
+        let row = &mut self.map.rows[idx / width];
+        let mut chars: Vec<char> = row.chars().collect();
+        if chars[idx % width] != tile {
+            chars[idx % width] = tile;
+            *row = chars.into_iter().collect();
+        }
+    }
+}
+
+/// A sprite of the preview, redrawn whenever the map changes.
+#[derive(Component)]
+struct PreviewTile;
+
+#[derive(Component)]
+struct StatusText;
+
+fn setup(editor: Res<Editor>, mut commands: Commands) {
+    let (width, height) = editor.size();
+    let mut projection = OrthographicProjection::default_2d();
+    projection.scale = 1. / 8.;
+    commands.spawn((
+        Camera2d,
+        projection,
+        Transform::from_xyz(
+            width as f32 * TILE_SIZE / 2.,
+            height as f32 * TILE_SIZE / 2.,
+            0.,
+        ),
+    ));
+    commands.spawn((
+        StatusText,
+        Text::new(""),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.),
+            left: Val::Px(8.),
+            ..default()
+        },
+    ));
+}
+
+/// The hovered tile, if the mouse is over the map.
+fn hovered_tile(
+    window: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Query<(&Camera, &GlobalTransform)>,
+    editor: &Editor,
+) -> Option<usize> {
+    let cursor = window.get_single().ok()?.cursor_position()?;
+    let (camera, transform) = camera.get_single().ok()?;
+    let world = camera.viewport_to_world_2d(transform, cursor).ok()?;
+    editor.tile_at(world)
+}
+
+fn pick_brush(keys: Res<ButtonInput<KeyCode>>, mut editor: ResMut<Editor>) {
+    if keys.just_pressed(KeyCode::KeyE) {
+        editor.brush = (editor.brush + 1) % PALETTE.len();
+    }
+    if keys.just_pressed(KeyCode::KeyQ) {
+        editor.brush = (editor.brush + PALETTE.len() - 1) % PALETTE.len();
+    }
+}
+
+fn paint(
+    buttons: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<Editor>,
+) {
+    let tile = if buttons.pressed(MouseButton::Left) {
+        PALETTE[editor.brush]
+    } else if buttons.pressed(MouseButton::Right) {
+        '.'
+    } else {
+        return;
+    };
+    if let Some(idx) = hovered_tile(&window, &camera, &editor) {
+        // Only one player may start on the floor.
+        if tile == '@' {
+            if let Some(start) = editor
+                .map
+                .rows
+                .concat()
+                .chars()
+                .position(|tile| tile == '@')
+            {
+                editor.set_tile(start, '.');
+            }
+        }
+        editor.set_tile(idx, tile);
+    }
+}
+
+fn author_regions(
+    keys: Res<ButtonInput<KeyCode>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<Editor>,
+) {
+    if !keys.just_pressed(KeyCode::KeyG) {
+        return;
+    }
+    let Some(idx) = hovered_tile(&window, &camera, &editor) else {
+        return;
+    };
+    if let Some(corner) = editor.region_corner.take() {
+        let name = format!("Region {}", editor.map.regions.len() + 1);
+        editor.status = format!("Added {}.", name);
+        editor.map.regions.push(MapRegion {
+            name,
+            corners: (corner, idx),
+        });
+    } else {
+        editor.region_corner = Some(idx);
+    }
+}
+
+fn author_patrols(
+    keys: Res<ButtonInput<KeyCode>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<Editor>,
+) {
+    if keys.just_pressed(KeyCode::KeyN) {
+        if let Some(idx) = hovered_tile(&window, &camera, &editor) {
+            editor.patrol.push(idx);
+        }
+    }
+    if keys.just_pressed(KeyCode::Enter) && editor.patrol.len() > 1 {
+        let patrol = std::mem::take(&mut editor.patrol);
+        editor.map.patrols.push(patrol);
+        editor.status = "Added a patrol.".to_owned();
+    }
+    if keys.just_pressed(KeyCode::Backspace) {
+        if editor.map.patrols.pop().is_some() {
+            editor.status = "Removed the last patrol.".to_owned();
+        } else if editor.map.regions.pop().is_some() {
+            editor.status = "Removed the last region.".to_owned();
+        }
+    }
+}
+
+fn save_and_load(keys: Res<ButtonInput<KeyCode>>, mut editor: ResMut<Editor>) {
+    if !keys.pressed(KeyCode::ControlLeft) && !keys.pressed(KeyCode::ControlRight) {
+        return;
+    }
+    if keys.just_pressed(KeyCode::KeyS) {
+        editor.status = match editor.map.save(&editor.path) {
+            Ok(()) if !editor.map.rows.concat().contains('@') => {
+                format!(
+                    "Saved to {}, but nowhere is the player's start!",
+                    editor.path
+                )
+            }
+            Ok(()) => format!("Saved to {}.", editor.path),
+            Err(error) => format!("Could not save: {}", error),
+        };
+    }
+    if keys.just_pressed(KeyCode::KeyL) {
+        match MapFile::load(&editor.path) {
+            Ok(map) => {
+                editor.map = map;
+                editor.status = format!("Loaded {}.", editor.path);
+            }
+            Err(error) => editor.status = format!("Could not load: {}", error),
+        }
+    }
+}
+
+/// Draw every painted tile as the creature it will summon in game.
+fn render_preview(
+    editor: Res<Editor>,
+    registry: Res<SpeciesRegistry>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    asset_server: Res<AssetServer>,
+    tiles: Query<Entity, With<PreviewTile>>,
+    mut commands: Commands,
+) {
+    for entity in tiles.iter() {
+        commands.entity(entity).despawn();
+    }
+    let blueprint = editor.map.to_blueprint();
+    for (idx, tile) in blueprint.tiles.iter().enumerate() {
+        if let Some(kind) = tile_kind_from_char(*tile) {
+            let position = editor.world_of(idx);
+            commands.spawn((
+                PreviewTile,
+                Sprite::from_color(kind.color(), Vec2::new(TILE_SIZE, TILE_SIZE)),
+                Transform::from_xyz(position.x, position.y, -1.),
+            ));
+        }
+        let Some((species, momentum)) = species_from_tile(*tile) else {
+            continue;
+        };
+        let species = blueprint
+            .overrides
+            .iter()
+            .find(|(overridden, _)| *overridden == idx)
+            .map_or(species, |(_, species)| *species);
+        let position = editor.world_of(idx);
+        commands.spawn((
+            PreviewTile,
+            Sprite {
+                image: asset_server.load("spritesheet.png"),
+                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index: registry.get(&species).sprite,
+                }),
+                ..default()
+            },
+            // The same rotation summon_creature gives to sprites.
+            Transform::from_xyz(position.x, position.y, 0.).with_rotation(Quat::from_rotation_z(
+                match momentum {
+                    OrdDir::Down => 0.,
+                    OrdDir::Right => PI / 2.,
+                    OrdDir::Up => PI,
+                    OrdDir::Left => 3. * PI / 2.,
+                },
+            )),
+        ));
+    }
+}
+
+/// Outline the map, its regions and its patrols.
+fn render_overlays(editor: Res<Editor>, mut gizmos: Gizmos) {
+    let (width, height) = editor.size();
+    let half = Vec2::splat(TILE_SIZE / 2.);
+    let outline = |gizmos: &mut Gizmos, from: Vec2, to: Vec2, color: Color| {
+        let (min, max) = (from.min(to) - half, from.max(to) + half);
+        gizmos.rect_2d(
+            Isometry2d::from_translation((min + max) / 2.),
+            max - min,
+            color,
+        );
+    };
+    outline(
+        &mut gizmos,
+        editor.world_of(0),
+        editor.world_of(width * height - 1),
+        Color::srgb(0.3, 0.3, 0.3),
+    );
+    for region in &editor.map.regions {
+        outline(
+            &mut gizmos,
+            editor.world_of(region.corners.0),
+            editor.world_of(region.corners.1),
+            Color::srgb(0.2, 0.8, 0.9),
+        );
+    }
+    if let Some(corner) = editor.region_corner {
+        outline(
+            &mut gizmos,
+            editor.world_of(corner),
+            editor.world_of(corner),
+            Color::srgb(0.2, 0.8, 0.9),
+        );
+    }
+    for patrol in editor
+        .map
+        .patrols
+        .iter()
+        .chain(std::iter::once(&editor.patrol))
+    {
+        for waypoint in patrol {
+            gizmos.circle_2d(
+                Isometry2d::from_translation(editor.world_of(*waypoint)),
+                TILE_SIZE / 4.,
+                Color::srgb(0.9, 0.7, 0.2),
+            );
+        }
+        for pair in patrol.windows(2) {
+            gizmos.line_2d(
+                editor.world_of(pair[0]),
+                editor.world_of(pair[1]),
+                Color::srgb(0.9, 0.7, 0.2),
+            );
+        }
+    }
+}
+
+fn update_status(
+    editor: Res<Editor>,
+    registry: Res<SpeciesRegistry>,
+    mut text: Query<&mut Text, With<StatusText>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    let brush = PALETTE[editor.brush];
+    let name = species_from_tile(brush).map_or_else(
+        || tile_kind_from_char(brush).map_or(String::new(), |kind| format!("{:?}", kind)),
+        |(species, _)| strip_color_tags(&registry.get(&species).name),
+    );
+    if let Ok(mut text) = text.get_single_mut() {
+        text.0 = format!(
+            "Brush: '{}' {}  |  {}  |  {}",
+            brush, name, editor.path, editor.status
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Stress the spell stack on a headless floor crowded with creatures, and print
+//! how long its core systems take.
+//!
+//! `cargo run --release --bin spell_bench [spells] [creatures] [seed]`
+//!
+//! Creatures are summoned through the regular turn cycle. The spells are then
+//! resolved one frame at a time, running process_axiom (and every axiom it
+//! dispatches), cleanup_synapses and teleport_entity on their own, so each of
+//! them can be timed. Nothing else runs in between: spells never slay anything,
+//! which keeps the stack as large as possible.
+
+use std::time::{Duration, Instant};
+
+use bevy::{ecs::system::SystemId, prelude::*};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use redesign_tgfp::{
+    cast_new_spell, cleanup_synapses, headless_app, process_axiom, settle_turn, teleport_entity,
+    Axiom, CastSpell, GameRng, Map, OrdDir, Player, Position, Soul, Species, Spell, SpellStack,
+    SummonCreature, TeleportEntity,
+};
+
+/// Random teleports added on top of the ones sent by axioms, each frame.
+const TELEPORTS_PER_FRAME: usize = 100;
+/// Gives up on spells which somehow never end.
+const MAX_FRAMES: usize = 1000;
+
+/// How long every run of a system took.
+struct Timings {
+    name: &'static str,
+    runs: Vec<Duration>,
+}
+
+impl Timings {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            runs: Vec::new(),
+        }
+    }
+
+    fn time(&mut self, world: &mut World, system: SystemId) {
+        let start = Instant::now();
+        world
+            .run_system(system)
+            .unwrap_or_else(|error| panic!("{} could not run: {}", self.name, error));
+        self.runs.push(start.elapsed());
+    }
+
+    fn report(&self) {
+        let total: Duration = self.runs.iter().sum();
+        let mut sorted = self.runs.clone();
+        sorted.sort();
+        let percentile = |p: usize| {
+            sorted
+                .get((sorted.len() * p / 100).min(sorted.len().saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+        println!(
+            "{:<20} {:>7} {:>12.3} {:>10.1} {:>10.1} {:>10.1}",
+            self.name,
+            self.runs.len(),
+            total.as_secs_f64() * 1000.,
+            total.as_secs_f64() * 1e6 / self.runs.len().max(1) as f64,
+            percentile(95).as_secs_f64() * 1e6,
+            sorted.last().copied().unwrap_or_default().as_secs_f64() * 1e6,
+        );
+    }
+}
+
+/// A spell of one to three Forms, each followed by one or two Functions.
+fn random_spell(rng: &mut StdRng) -> Spell {
+    let forms = [
+        Axiom::Ego,
+        Axiom::MomentumBeam,
+        Axiom::XBeam,
+        Axiom::PlusBeam,
+        Axiom::Plus,
+        Axiom::Touch,
+        Axiom::Halo { radius: 2 },
+        Axiom::LineOfSight { range: 4 },
+    ];
+    let functions = [
+        Axiom::HealOrHarm { amount: -1 },
+        Axiom::HealOrHarm { amount: 1 },
+        Axiom::Dash { max_distance: 3 },
+        Axiom::Knockback { distance: 2 },
+    ];
+    let mut axioms = Vec::new();
+    for _ in 0..rng.gen_range(1..=3) {
+        axioms.push(forms.choose(rng).unwrap().clone());
+        for _ in 0..rng.gen_range(1..=2) {
+            axioms.push(functions.choose(rng).unwrap().clone());
+        }
+    }
+    Spell { axioms }
+}
+
+/// Every unoccupied tile inside the walls of the floor.
+fn free_tiles(map: &Map) -> Vec<Position> {
+    let occupied: Vec<&Position> = map.creatures.keys().collect();
+    let (min_x, max_x) = (
+        occupied.iter().map(|p| p.x).min().unwrap_or(0),
+        occupied.iter().map(|p| p.x).max().unwrap_or(0),
+    );
+    let (min_y, max_y) = (
+        occupied.iter().map(|p| p.y).min().unwrap_or(0),
+        occupied.iter().map(|p| p.y).max().unwrap_or(0),
+    );
+    let mut tiles = Vec::new();
+    for x in min_x..=max_x {
+        for y in min_y..=max_y {
+            if map.is_passable(x, y) {
+                tiles.push(Position::new(x, y));
+            }
+        }
+    }
+    tiles
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let arg = |index: usize, default: u64| {
+        args.get(index)
+            .and_then(|arg| arg.parse().ok())
+            .unwrap_or(default)
+    };
+    let (spells, creatures, seed) = (arg(1, 2000) as usize, arg(2, 300) as usize, arg(3, 0));
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut app = headless_app();
+    app.insert_resource(GameRng::new(seed));
+    // Spawn the cage.
+    settle_turn(&mut app);
+
+    let summon_start = Instant::now();
+    let mut tiles = free_tiles(app.world().resource::<Map>());
+    tiles.shuffle(&mut rng);
+    let summoned = tiles.len().min(creatures);
+    for position in tiles.drain(..summoned) {
+        app.world_mut().send_event(SummonCreature {
+            position,
+            species: Species::Hunter,
+            momentum: OrdDir::Down,
+            summoner_tile: position,
+            summoner: None,
+            spellbook: None,
+            projectile: None,
+            owner: None,
+        });
+    }
+    settle_turn(&mut app);
+    eprintln!(
+        "Summoned {} creatures in {:.1}ms.",
+        summoned,
+        summon_start.elapsed().as_secs_f64() * 1000.
+    );
+
+    let world = app.world_mut();
+    // Walls and the like stay out of it, only the summoned creatures cast and teleport.
+    let casters: Vec<Entity> = world
+        .query_filtered::<(Entity, &Species), (With<Position>, Without<Player>)>()
+        .iter(world)
+        .filter(|(_, species)| **species == Species::Hunter)
+        .map(|(entity, _)| entity)
+        .collect();
+    if casters.is_empty() {
+        eprintln!("The floor has no creatures to cast spells.");
+        return;
+    }
+    let systems = [
+        world.register_system(cast_new_spell),
+        world.register_system(process_axiom),
+        world.register_system(cleanup_synapses),
+        world.register_system(teleport_entity),
+    ];
+    for _ in 0..spells {
+        world.send_event(CastSpell {
+            caster: *casters.choose(&mut rng).unwrap(),
+            spell: random_spell(&mut rng),
+            starting_step: 0,
+            soul_caste: Soul::Unhinged,
+            target: None,
+            from_wheel: false,
+            from_locked_slot: false,
+        });
+    }
+    world.run_system(systems[0]).unwrap();
+
+    let mut timings = [
+        Timings::new("process_axiom"),
+        Timings::new("cleanup_synapses"),
+        Timings::new("teleport_entity"),
+    ];
+    let (mut frames, mut axioms) = (0, 0);
+    while !world.resource::<SpellStack>().spells.is_empty() && frames < MAX_FRAMES {
+        axioms += world.resource::<SpellStack>().spells.len();
+        for _ in 0..TELEPORTS_PER_FRAME {
+            let destination = tiles
+                .choose(&mut rng)
+                .copied()
+                .unwrap_or(Position::new(0, 0));
+            world.send_event(TeleportEntity::new(
+                *casters.choose(&mut rng).unwrap(),
+                destination.x,
+                destination.y,
+            ));
+        }
+        for (timing, system) in timings.iter_mut().zip(&systems[1..]) {
+            timing.time(world, *system);
+        }
+        frames += 1;
+    }
+
+    println!(
+        "{} spells from {} casters, {} axioms over {} frames",
+        spells,
+        casters.len(),
+        axioms,
+        frames
+    );
+    println!(
+        "{:<20} {:>7} {:>12} {:>10} {:>10} {:>10}",
+        "system", "runs", "total (ms)", "mean (us)", "p95 (us)", "max (us)"
+    );
+    for timing in &timings {
+        timing.report();
+    }
+    let axiom_time: Duration = timings[0].runs.iter().sum();
+    println!(
+        "{:.2}us per axiom, dispatch included",
+        axiom_time.as_secs_f64() * 1e6 / axioms.max(1) as f64
+    );
+}
@@ -1,12 +1,251 @@
 use bevy::prelude::*;
 
 use crate::{
-    creature::{get_soul_sprite, Soul},
+    creature::{get_soul_sprite, Player, Soul, Spellbook},
+    equipment::{Equipment, Item},
+    focus::{cycle_focus, focus_step},
     graphics::SpriteSheetAtlas,
-    text::match_soul_with_description,
-    ui::{spawn_split_text, CasteBox, LargeCastePanel, MessageLog},
+    sets::ControlState,
+    spells::{Axiom, Spell},
+    text::{match_soul_with_description, split_text},
+    ui::{spawn_split_text, AddMessage, CasteBox, LargeCastePanel, Message, MessageLog},
 };
 
+/// How far beams and projectiles are counted as reaching, when scoring spells.
+const LONG_RANGE: i32 = 10;
+
+/// A rough idea of what a spell is good at, to compare it with another.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct SpellScore {
+    pub damage: usize,
+    pub range: i32,
+    pub utility: usize,
+}
+
+impl SpellScore {
+    pub fn total(&self) -> i32 {
+        self.damage as i32 * 2 + self.range / 2 + self.utility as i32
+    }
+}
+
+/// Score a spell from its axioms alone, without caring for walls or who stands where.
+pub fn score_spell(spell: &Spell) -> SpellScore {
+    let mut score = SpellScore::default();
+    for axiom in &spell.axioms {
+        match axiom {
+            Axiom::HealOrHarm { amount } if *amount < 0 => {
+                score.damage += amount.unsigned_abs();
+            }
+            Axiom::Touch | Axiom::Plus => score.range = score.range.max(1),
+            Axiom::Halo { radius } => score.range = score.range.max(*radius),
+            Axiom::LineOfSight { range } | Axiom::ChainFromTarget { range, .. } => {
+                score.range = score.range.max(*range);
+            }
+            Axiom::MomentumBeam
+            | Axiom::XBeam
+            | Axiom::PlusBeam
+            | Axiom::CursorTarget
+            | Axiom::LaunchProjectile => score.range = score.range.max(LONG_RANGE),
+            Axiom::Spread => score.range += 1,
+            Axiom::Abjuration | Axiom::Counterspell | Axiom::PiercingBeams | Axiom::Phasing => {
+                score.utility += 1;
+            }
+            axiom if axiom.needs_line_of_effect() => score.utility += 1,
+            _ => (),
+        }
+    }
+    score
+}
+
+/// Spells which were learned, but not equipped yet, oldest first.
+#[derive(Resource, Default)]
+pub struct SpellSuggestions {
+    pub pending: Vec<(Soul, Spell)>,
+}
+
+impl SpellSuggestions {
+    /// Suggest the player's spell of this caste, with `axiom` woven into it.
+    /// More axioms for the same caste pile onto the same suggestion.
+    pub fn learn(&mut self, caste: Soul, axiom: Axiom, spellbook: &Spellbook) {
+        if let Some((_, spell)) = self.pending.iter_mut().find(|(soul, _)| *soul == caste) {
+            spell.axioms.push(axiom);
+            return;
+        }
+        let Some(mut spell) = spellbook.spells.get(&caste).cloned() else {
+            return;
+        };
+        spell.axioms.push(axiom);
+        self.pending.push((caste, spell));
+    }
+}
+
+/// Replace the player's spell with the oldest suggestion. This takes no time.
+#[derive(Event)]
+pub struct EquipSuggestedSpell;
+
+pub fn equip_suggested_spell(
+    mut events: EventReader<EquipSuggestedSpell>,
+    mut suggestions: ResMut<SpellSuggestions>,
+    mut player: Query<&mut Spellbook, With<Player>>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for _event in events.read() {
+        if suggestions.pending.is_empty() {
+            continue;
+        }
+        let (caste, spell) = suggestions.pending.remove(0);
+        if let Ok(mut spellbook) = player.get_single_mut() {
+            spellbook.spells.insert(caste, spell);
+            text.send(AddMessage {
+                message: Message::SpellEquipped(caste),
+            });
+        }
+    }
+}
+
+/// The box in the corner offering the oldest suggestion.
+#[derive(Component)]
+pub struct SuggestionPanel;
+
+/// Y, or clicking the suggestion, equips it.
+pub fn suggestion_input(
+    input: Res<ButtonInput<KeyCode>>,
+    panel: Query<&Interaction, (Changed<Interaction>, With<SuggestionPanel>)>,
+    mut equip: EventWriter<EquipSuggestedSpell>,
+) {
+    if input.just_pressed(KeyCode::KeyY)
+        || panel
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        equip.send(EquipSuggestedSpell);
+    }
+}
+
+pub fn update_suggestion_panel(
+    suggestions: Res<SpellSuggestions>,
+    panels: Query<Entity, With<SuggestionPanel>>,
+    player: Query<&Spellbook, With<Player>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    if !suggestions.is_changed() {
+        return;
+    }
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    let (Some((caste, spell)), Ok(spellbook)) = (suggestions.pending.first(), player.get_single())
+    else {
+        return;
+    };
+    let new = score_spell(spell);
+    let old = spellbook
+        .spells
+        .get(caste)
+        .map(score_spell)
+        .unwrap_or_default();
+    let compare = |name: &str, new: i32, old: i32| {
+        let color = match new.cmp(&old) {
+            std::cmp::Ordering::Greater => "[l]",
+            std::cmp::Ordering::Less => "[r]",
+            std::cmp::Ordering::Equal => "[w]",
+        };
+        format!("{}: {}{}[w] ({})", name, color, new, old)
+    };
+    let summary = format!(
+        "New {}{} {} {} {}\n[y]Press Y or click to equip.[w]",
+        match_soul_with_string(caste),
+        if new.total() > old.total() {
+            " [l](better)[w]"
+        } else {
+            ""
+        },
+        compare("Damage", new.damage as i32, old.damage as i32),
+        compare("Range", new.range, old.range),
+        compare("Utility", new.utility as i32, old.utility as i32),
+    );
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.2,
+        ..default()
+    };
+    // NOTE: Not spawn_split_text, as LogEntry text would slide along with the message log.
+    let sections = split_text(&summary);
+    commands
+        .spawn((
+            SuggestionPanel,
+            Interaction::default(),
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(1.),
+                top: Val::Px(1.),
+                max_width: Val::Px(30.),
+                padding: UiRect::all(Val::Px(0.5)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Text::new(&sections[0].0),
+                    font.clone(),
+                    sections[0].1,
+                    PickingBehavior::IGNORE,
+                ))
+                .with_children(|parent| {
+                    for (section, color) in sections.iter().skip(1) {
+                        parent.spawn((TextSpan::new(section), font.clone(), *color));
+                    }
+                });
+        });
+}
+
+/// The castes of the caste menu, in the order of the number keys.
+const MENU_CASTES: [(KeyCode, Soul); 6] = [
+    (KeyCode::Digit1, Soul::Saintly),
+    (KeyCode::Digit2, Soul::Ordered),
+    (KeyCode::Digit3, Soul::Artistic),
+    (KeyCode::Digit4, Soul::Unhinged),
+    (KeyCode::Digit5, Soul::Feral),
+    (KeyCode::Digit6, Soul::Vile),
+];
+
+/// 1-6 show a caste, W/S, the arrow keys or Tab move through them.
+/// E or Escape closes the menu.
+pub fn caste_menu_input(
+    input: Res<ButtonInput<KeyCode>>,
+    mut caste_menu: Query<&mut LargeCastePanel>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    if input.any_just_pressed([KeyCode::KeyE, KeyCode::Escape]) {
+        next_state.set(ControlState::Player);
+        return;
+    }
+    let Ok(mut panel) = caste_menu.get_single_mut() else {
+        return;
+    };
+    let mut selected = MENU_CASTES.iter().position(|(_, caste)| *caste == panel.0);
+    for (i, (key, _)) in MENU_CASTES.iter().enumerate() {
+        if input.just_pressed(*key) {
+            selected = Some(i);
+        }
+    }
+    let step = focus_step(&input);
+    if step != 0 {
+        // Nothing is shown when the menu first opens, start from either end.
+        let start = selected.unwrap_or(if step > 0 { MENU_CASTES.len() - 1 } else { 0 });
+        selected = Some(cycle_focus(start, step, MENU_CASTES.len()));
+    }
+    if let Some(caste) = selected.map(|i| MENU_CASTES[i].1) {
+        // Only touch the panel when it changes, update_caste_box redraws it then.
+        if panel.0 != caste {
+            panel.0 = caste;
+        }
+    }
+}
+
 pub fn show_caste_menu(
     mut message: Query<&mut Visibility, (With<MessageLog>, Without<CasteBox>)>,
     mut caste_box: Query<&mut Visibility, (With<CasteBox>, Without<MessageLog>)>,
@@ -28,24 +267,42 @@ pub fn hide_caste_menu(
 }
 
 pub fn update_caste_box(
-    caste_panel: Query<&LargeCastePanel, Changed<LargeCastePanel>>,
+    caste_panel: Query<Ref<LargeCastePanel>>,
     caste_box: Query<Entity, (With<CasteBox>, Without<LargeCastePanel>)>,
+    equipment: Query<Ref<Equipment>, With<Player>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     atlas_layout: Res<SpriteSheetAtlas>,
 ) {
-    if let Ok(caste) = caste_panel.get_single() {
+    let Ok(caste) = caste_panel.get_single() else {
+        return;
+    };
+    let equipment = equipment.get_single().ok();
+    if caste.is_changed()
+        || equipment
+            .as_ref()
+            .is_some_and(|equipment| equipment.is_changed())
+    {
         let caste = caste.0;
         let caste_box = caste_box.single();
         // TODO: Instead of multiple entities, would it be interesting to
         // have these merged into a single string with \n to space them out?
         // This would be good in case there's a ton of "effects flags".
-        let (mut caste_name, mut caste_description) = (Entity::PLACEHOLDER, Entity::PLACEHOLDER);
+        let (mut caste_name, mut caste_description, mut worn_items) = (
+            Entity::PLACEHOLDER,
+            Entity::PLACEHOLDER,
+            Entity::PLACEHOLDER,
+        );
         commands.entity(caste_box).despawn_descendants();
         commands.entity(caste_box).with_children(|parent| {
             caste_name = spawn_split_text(&match_soul_with_string(&caste), parent, &asset_server);
             caste_description =
                 spawn_split_text(&match_soul_with_description(&caste), parent, &asset_server);
+            worn_items = spawn_split_text(
+                &match_equipment_with_string(equipment.as_deref()),
+                parent,
+                &asset_server,
+            );
             parent.spawn((
                 ImageNode {
                     image: asset_server.load("spritesheet.png"),
@@ -75,9 +332,33 @@ pub fn update_caste_box(
             top: Val::Px(3.5),
             ..default()
         });
+        commands.entity(worn_items).insert(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.5),
+            ..default()
+        });
     }
 }
 
+fn match_equipment_with_string(equipment: Option<&Equipment>) -> String {
+    let slot = |name: &str, item: Option<Item>| {
+        format!(
+            "{}: {}",
+            name,
+            item.map_or("[d]Nothing[w]", |item| item.name())
+        )
+    };
+    let Some(equipment) = equipment else {
+        return String::new();
+    };
+    [
+        slot("Weapon", equipment.weapon),
+        slot("Armor", equipment.armor),
+        slot("Trinket", equipment.trinket),
+    ]
+    .join(" ")
+}
+
 pub fn match_soul_with_string(soul: &Soul) -> String {
     let string = match soul {
         Soul::Saintly => "[l]Saintly Soul[w]",
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::{
+    caste::SpellSuggestions,
+    crafting::Weaving,
+    creature::{Awake, Chest, CreatureFlags, Interactable, Mimic, Player, Soul, Spellbook},
+    equipment::{equip_or_drop, Equipment, Item},
+    events::{RemoveCreature, SoulWheel, SummonCreature, TransformCreature},
+    interact::{interaction_target, Interact},
+    map::Position,
+    rng::GameRng,
+    spells::Axiom,
+    ui::{AddMessage, Message},
+};
+
+pub struct ChestPlugin;
+
+impl Plugin for ChestPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LootTable>();
+    }
+}
+
+/// Something found inside a chest.
+#[derive(Clone, Debug)]
+pub enum Loot {
+    /// Souls added to the draw pile.
+    Souls { caste: Soul, amount: usize },
+    /// An axiom added at the end of the player's spell of this caste.
+    Axiom { caste: Soul, axiom: Axiom },
+    /// Equipped immediately. Whatever was in its slot is left in the chest's place.
+    Item(Item),
+    /// The last unlocked slot of the Soul Wheel is locked to this caste.
+    SlotLock { caste: Soul },
+    /// Recipes picked in the recipe book are woven whole, see Weaving::loom.
+    Loom,
+}
+
+/// Everything chests can contain, and how likely each one is to be rolled.
+#[derive(Resource)]
+pub struct LootTable {
+    pub entries: Vec<(Loot, u32)>,
+}
+
+impl FromWorld for LootTable {
+    fn from_world(_world: &mut World) -> Self {
+        let mut entries = Vec::new();
+        for caste in [
+            Soul::Saintly,
+            Soul::Ordered,
+            Soul::Artistic,
+            Soul::Unhinged,
+            Soul::Feral,
+            Soul::Vile,
+        ] {
+            entries.push((Loot::Souls { caste, amount: 2 }, 4));
+        }
+        // NOTE: These only make sense appended to the end of the player's
+        // current spells. Check them again if those change.
+        entries.push((
+            Loot::Axiom {
+                caste: Soul::Saintly,
+                axiom: Axiom::HealOrHarm { amount: 1 },
+            },
+            1,
+        ));
+        entries.push((
+            Loot::Axiom {
+                caste: Soul::Unhinged,
+                axiom: Axiom::HealOrHarm { amount: -1 },
+            },
+            1,
+        ));
+        entries.push((
+            Loot::Axiom {
+                caste: Soul::Artistic,
+                axiom: Axiom::Spread,
+            },
+            1,
+        ));
+        entries.push((
+            Loot::Axiom {
+                caste: Soul::Ordered,
+                axiom: Axiom::RotateBelts,
+            },
+            1,
+        ));
+        // NOTE: Not Unhinged, which fills most of the starting deck, nor
+        // Ordered, whose shield already takes no time.
+        for caste in [Soul::Saintly, Soul::Artistic, Soul::Feral, Soul::Vile] {
+            entries.push((Loot::SlotLock { caste }, 1));
+        }
+        for item in [
+            Item::SerratedEdge,
+            Item::ChitinPlate,
+            Item::NacreWard,
+            Item::QuicksilverCharm,
+        ] {
+            entries.push((Loot::Item(item), 1));
+        }
+        entries.push((Loot::Loom, 1));
+        Self { entries }
+    }
+}
+
+/// Opening a chest hands out its loot, then removes it, which fires the payload
+/// of trapped chests. Mimics wake up instead.
+pub fn open_chest(
+    mut events: EventReader<Interact>,
+    position: Query<&Position>,
+    creatures: Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: Query<(), With<Interactable>>,
+    chests: Query<&Chest>,
+    mimics: Query<&Mimic>,
+    mut player: Query<(&Spellbook, &mut Equipment), With<Player>>,
+    (mut suggestions, mut weaving): (ResMut<SpellSuggestions>, ResMut<Weaving>),
+    loot_table: Res<LootTable>,
+    mut soul_wheel: ResMut<SoulWheel>,
+    mut rng: ResMut<GameRng>,
+    mut remove: EventWriter<RemoveCreature>,
+    mut summon: EventWriter<SummonCreature>,
+    mut transform: EventWriter<TransformCreature>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Some(entity) = interaction_target(event, &position, &creatures, &interactable) else {
+            continue;
+        };
+        let (_, chest_position, flags) = creatures.get(entity).unwrap();
+        if let Ok(mimic) = mimics.get(flags.species_flags) {
+            transform.send(TransformCreature {
+                entity,
+                new_species: mimic.species,
+            });
+            commands.entity(entity).insert(Awake);
+            text.send(AddMessage {
+                message: Message::MimicRevealed(mimic.species),
+            });
+            continue;
+        }
+        let Ok(chest) = chests.get(flags.species_flags) else {
+            continue;
+        };
+        for _roll in 0..chest.rolls {
+            let Ok((loot, _)) = loot_table
+                .entries
+                .choose_weighted(&mut *rng, |(_, weight)| *weight)
+            else {
+                break;
+            };
+            text.send(AddMessage {
+                message: Message::Looted(loot.clone()),
+            });
+            match loot {
+                Loot::Souls { caste, amount } => {
+                    *soul_wheel.draw_pile.entry(*caste).or_insert(0) += amount;
+                }
+                Loot::Axiom { caste, axiom } => {
+                    if let Ok((spellbook, _)) = player.get_single() {
+                        suggestions.learn(*caste, axiom.clone(), spellbook);
+                    }
+                }
+                Loot::Item(item) => {
+                    if let Ok((_, mut equipment)) = player.get_single_mut() {
+                        equip_or_drop(
+                            &mut equipment,
+                            *item,
+                            *chest_position,
+                            &mut summon,
+                            &mut text,
+                        );
+                    }
+                }
+                Loot::SlotLock { caste } => {
+                    // NOTE: Once every slot is locked, this does nothing.
+                    soul_wheel.lock_slot(*caste);
+                }
+                Loot::Loom => weaving.loom = true,
+            }
+        }
+        remove.send(RemoveCreature {
+            entity,
+            culprit: None,
+        });
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{CreatureFlags, Door, Intangible, Lever, PressurePlate},
+    events::{EndTurn, OpenCloseDoor},
+    map::{Map, Position},
+    spells::{Axiom, TriggerContingency},
+};
+
+/// Every circuit of the current floor.
+#[derive(Resource, Default)]
+pub struct Circuits {
+    pub circuits: Vec<Circuit>,
+}
+
+impl Circuits {
+    /// Is the creature on this tile controlled by a circuit?
+    pub fn is_receiver(&self, position: &Position) -> bool {
+        self.circuits
+            .iter()
+            .any(|circuit| circuit.receivers.contains(position))
+    }
+}
+
+/// Wiring between levers or pressure plates, and what they control.
+/// A circuit is powered as long as any of its emitters is.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Circuit {
+    /// The tiles of levers and pressure plates.
+    pub emitters: Vec<Position>,
+    /// The tiles of doors, which open while powered, and of creatures
+    /// with the WhenPowered or WhenUnpowered contingencies.
+    pub receivers: Vec<Position>,
+    pub powered: bool,
+}
+
+/// Once per turn, check every circuit, and tell receivers if they were
+/// just powered or unpowered.
+pub fn evaluate_circuits(
+    mut events: EventReader<EndTurn>,
+    mut circuits: ResMut<Circuits>,
+    creatures: Query<(Entity, &Position, &CreatureFlags)>,
+    levers: Query<&Lever>,
+    plates: Query<(), With<PressurePlate>>,
+    doors: Query<(), With<Door>>,
+    open_doors: Query<(), With<Intangible>>,
+    map: Res<Map>,
+    mut open: EventWriter<OpenCloseDoor>,
+    mut contingency: EventWriter<TriggerContingency>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    // NOTE: Open doors and pressure plates are intangible, and missing from the Map.
+    let creatures_at = |position: Position| {
+        creatures
+            .iter()
+            .filter(move |(_, creature_position, _)| **creature_position == position)
+    };
+    for circuit in circuits.circuits.iter_mut() {
+        let powered = circuit.emitters.iter().any(|emitter| {
+            creatures_at(*emitter).any(|(_, _, flags)| {
+                levers
+                    .get(flags.species_flags)
+                    .is_ok_and(|lever| lever.on)
+                    // Pressure plates are pressed by anything tangible on top of them,
+                    // corpses included.
+                    || (plates.contains(flags.species_flags)
+                        && (map.get_entity_at(emitter.x, emitter.y).is_some()
+                            || map.corpses.contains_key(emitter)))
+            })
+        });
+        if powered == circuit.powered {
+            continue;
+        }
+        circuit.powered = powered;
+        for receiver in &circuit.receivers {
+            for (entity, _, flags) in creatures_at(*receiver) {
+                if doors.contains(flags.species_flags) {
+                    // NOTE: A door which cannot close because something is in the
+                    // doorway will just stay open until the next time it is powered.
+                    let is_open = open_doors.contains(flags.species_flags);
+                    if is_open != powered
+                        && (powered || map.get_entity_at(receiver.x, receiver.y).is_none())
+                    {
+                        open.send(OpenCloseDoor {
+                            entity,
+                            open: powered,
+                        });
+                    }
+                } else {
+                    contingency.send(TriggerContingency {
+                        caster: entity,
+                        contingency: if powered {
+                            Axiom::WhenPowered
+                        } else {
+                            Axiom::WhenUnpowered
+                        },
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::Species,
+    map::spawn_cage,
+    mapgen::LevelGenConfig,
+    replay::ActionLog,
+    settings::{load_settings, Settings},
+    species::{SpeciesDefinition, SpeciesRegistry},
+    ui::{AddMessage, Message},
+};
+
+pub struct ContentPlugin;
+
+impl Plugin for ContentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Startup,
+            load_content_packs.after(load_settings).before(spawn_cage),
+        );
+    }
+}
+
+/// Where content packs are read from, one RON file each.
+const CONTENT_PACKS: &str = "assets/packs";
+
+/// Optional themed content, like holiday events. Active packs change how
+/// species look and act, what spawns, and which rooms appear on each floor.
+#[derive(Deserialize)]
+pub struct ContentPack {
+    /// Also the key to force this pack on or off in the Settings.
+    pub name: String,
+    /// The first and last (month, day) this pack is active, inclusive.
+    /// A range like ((12, 20), (1, 5)) wraps around the new year.
+    /// Without dates, the pack is only active if switched on in the Settings.
+    #[serde(default)]
+    pub dates: Option<((u32, u32), (u32, u32))>,
+    /// Full definitions which replace those of creatures.ron.
+    #[serde(default)]
+    pub species: HashMap<Species, SpeciesDefinition>,
+    /// Added to the species table of LevelGenConfig.
+    #[serde(default)]
+    pub spawns: Vec<(Species, u32)>,
+    /// Rows of tiles, written like the floors of the editor. See add_prefab.
+    #[serde(default)]
+    pub rooms: Vec<Vec<String>>,
+}
+
+/// The names of the content packs this run was started with. They are saved
+/// and replayed along with it, so the same seed builds the same floors.
+#[derive(Resource, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActiveContentPacks {
+    pub names: Vec<String>,
+}
+
+impl ContentPack {
+    fn is_in_season(&self, today: (u32, u32)) -> bool {
+        match self.dates {
+            Some((start, end)) if start <= end => start <= today && today <= end,
+            Some((start, end)) => start <= today || today <= end,
+            None => false,
+        }
+    }
+}
+
+/// Today's (month, day), in UTC.
+fn today() -> (u32, u32) {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86400) as i64;
+    // NOTE: From Howard Hinnant's civil_from_days, with eras of 400 years
+    // starting on March 1st so leap days fall at the end.
+    let z = days + 719468;
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    (month as u32, day as u32)
+}
+
+/// Every pack in CONTENT_PACKS which can be read, by file name.
+fn read_content_packs() -> Vec<ContentPack> {
+    let Ok(entries) = fs::read_dir(CONTENT_PACKS) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let contents = fs::read_to_string(&path).ok()?;
+            ron::from_str(&contents)
+                .inspect_err(|error| warn!("Could not read {}: {}", path.display(), error))
+                .ok()
+        })
+        .collect()
+}
+
+/// Switch on exactly the named content packs, undoing those which were active.
+/// Saves and replays use this to get back the content their run was played with.
+pub fn apply_content_packs(world: &mut World, names: &[String]) {
+    let previous = world
+        .get_resource::<ActiveContentPacks>()
+        .map(|active| active.names.clone())
+        .unwrap_or_default();
+    if previous == names {
+        return;
+    }
+    let packs = read_content_packs();
+    for name in names {
+        if !packs.iter().any(|pack| &pack.name == name) {
+            warn!("The content pack {} is missing, the run will differ.", name);
+        }
+    }
+    world.resource_scope(|world, mut registry: Mut<SpeciesRegistry>| {
+        let mut config = world.resource_mut::<LevelGenConfig>();
+        for pack in packs.iter().filter(|pack| previous.contains(&pack.name)) {
+            for species in pack.species.keys() {
+                registry.remove_override(species);
+            }
+            for spawn in &pack.spawns {
+                if let Some(index) = config.species.iter().position(|other| other == spawn) {
+                    config.species.remove(index);
+                }
+            }
+            for room in &pack.rooms {
+                if let Some(index) = config.prefabs.iter().position(|other| other == room) {
+                    config.prefabs.remove(index);
+                }
+            }
+        }
+        for pack in packs.iter().filter(|pack| names.contains(&pack.name)) {
+            for (species, definition) in &pack.species {
+                registry.override_species(*species, definition.clone());
+            }
+            config.species.extend(pack.spawns.iter().copied());
+            config.prefabs.extend(pack.rooms.iter().cloned());
+        }
+    });
+    world.insert_resource(ActiveContentPacks {
+        names: names.to_vec(),
+    });
+    if let Some(mut log) = world.get_resource_mut::<ActionLog>() {
+        log.content_packs = names.to_vec();
+    }
+}
+
+/// Switch on the content packs which are in season or forced on in the
+/// Settings, before the first floor is generated.
+// NOTE: Headless worlds only get the packs of the run they replay,
+// see apply_content_packs.
+fn load_content_packs(world: &mut World) {
+    let today = today();
+    let settings = world.resource::<Settings>();
+    let active: Vec<String> = read_content_packs()
+        .into_iter()
+        .filter(|pack| {
+            settings
+                .content_packs
+                .get(&pack.name)
+                .copied()
+                .unwrap_or_else(|| pack.is_in_season(today))
+        })
+        .map(|pack| pack.name)
+        .collect();
+    apply_content_packs(world, &active);
+    if !active.is_empty() {
+        info!("Content packs active: {}", active.join(", "));
+        world.send_event(AddMessage {
+            message: Message::ContentPacks(active),
+        });
+    }
+}
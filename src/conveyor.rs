@@ -0,0 +1,167 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    creature::{Conveyor, CreatureFlags, FlagQuery, Footprint, Species, Splitter},
+    events::{EndTurn, TeleportEntity},
+    map::{occupied_tiles, Map, Position},
+    species::{Bulk, SpeciesRegistry},
+    OrdDir,
+};
+
+pub struct ConveyorPlugin;
+
+impl Plugin for ConveyorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ResidualMomentum>();
+    }
+}
+
+/// A creature flung by Dash or Knockback, which may still be carried a bit
+/// further by the conveyor belts it crossed.
+pub struct Flung {
+    pub entity: Entity,
+    pub direction: OrdDir,
+    /// Every tile it travelled through, ending with where it should have landed.
+    pub path: Vec<Position>,
+}
+
+/// Creatures flung during this spell step, waiting for resolve_momentum.
+#[derive(Resource, Default)]
+pub struct ResidualMomentum {
+    pub flung: Vec<Flung>,
+}
+
+/// Every conveyor belt on the floor, and which way it pulls.
+// NOTE: Belts are intangible, and missing from the Map.
+fn belts(
+    conveyors: &Query<(&Position, &OrdDir, &CreatureFlags)>,
+    conveyor_query: &Query<(), With<Conveyor>>,
+) -> HashMap<Position, OrdDir> {
+    conveyors
+        .iter()
+        .filter(|(_, _, flags)| conveyor_query.flagged(flags))
+        .map(|(position, direction, _)| (*position, *direction))
+        .collect()
+}
+
+/// Once per turn, every belt pushes whatever stands on it by one tile.
+/// Splitters push off to their left and right in turn, or to the other side
+/// if that one is blocked.
+pub fn convey_creatures(
+    mut events: EventReader<EndTurn>,
+    conveyors: Query<(&Position, &OrdDir, &CreatureFlags)>,
+    conveyor_query: Query<(), With<Conveyor>>,
+    mut splitters: Query<&mut Splitter>,
+    map: Res<Map>,
+    species: Query<&Species>,
+    registry: Res<SpeciesRegistry>,
+    mut teleport: EventWriter<TeleportEntity>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    // Sorted, so replays push creatures in the same order.
+    let mut belts: Vec<(Position, OrdDir)> =
+        belts(&conveyors, &conveyor_query).into_iter().collect();
+    belts.sort_by_key(|(position, _)| (position.x, position.y));
+    let splitter_flags: HashMap<Position, Entity> = conveyors
+        .iter()
+        .filter(|(_, _, flags)| splitters.contains(flags.species_flags))
+        .map(|(position, _, flags)| (*position, flags.species_flags))
+        .collect();
+    // Large creatures standing on several belts are only pushed once.
+    let mut pushed = HashSet::new();
+    for (position, direction) in belts {
+        let Some(entity) = map.get_entity_at(position.x, position.y) else {
+            continue;
+        };
+        if !pushed.insert(*entity) {
+            continue;
+        }
+        if species
+            .get(*entity)
+            .is_ok_and(|species| registry.get(species).bulk == Bulk::Immovable)
+        {
+            continue;
+        }
+        let is_free = |direction: OrdDir| {
+            let (off_x, off_y) = direction.as_offset();
+            map.is_passable(position.x + off_x, position.y + off_y)
+        };
+        let direction = match splitter_flags
+            .get(&position)
+            .and_then(|flags| splitters.get_mut(*flags).ok())
+        {
+            Some(mut splitter) => {
+                let (left, right) = (
+                    direction.rotate_counterclockwise(),
+                    direction.rotate_clockwise(),
+                );
+                let (first, second) = if splitter.right {
+                    (right, left)
+                } else {
+                    (left, right)
+                };
+                let Some(chosen) = [first, second].into_iter().find(|side| is_free(*side)) else {
+                    continue;
+                };
+                splitter.right = chosen == left;
+                chosen
+            }
+            None => direction,
+        };
+        // NOTE: A creature blocked by another one further down the belt just waits.
+        if is_free(direction) {
+            let (off_x, off_y) = direction.as_offset();
+            teleport.send(TeleportEntity::new(
+                *entity,
+                position.x + off_x,
+                position.y + off_y,
+            ));
+        }
+    }
+}
+
+/// Flung creatures which crossed a belt keep going for one more tile, in a blend
+/// of their own direction and the pull of the last belt they crossed. A belt
+/// pulling against them cancels the extra tile, a sideways one sends them diagonally.
+pub fn resolve_momentum(
+    mut residual: ResMut<ResidualMomentum>,
+    creatures: Query<(&Position, Option<&Footprint>)>,
+    conveyors: Query<(&Position, &OrdDir, &CreatureFlags)>,
+    conveyor_query: Query<(), With<Conveyor>>,
+    map: Res<Map>,
+    mut teleport: EventWriter<TeleportEntity>,
+) {
+    if residual.flung.is_empty() {
+        return;
+    }
+    let belts = belts(&conveyors, &conveyor_query);
+    for flung in residual.flung.drain(..) {
+        let Ok((position, footprint)) = creatures.get(flung.entity) else {
+            continue;
+        };
+        // It was stopped before reaching its destination.
+        if flung.path.last() != Some(position) {
+            continue;
+        }
+        let Some(pull) = flung.path.iter().rev().find_map(|tile| belts.get(tile)) else {
+            continue;
+        };
+        let (own, belt) = (flung.direction.as_offset(), pull.as_offset());
+        let (off_x, off_y) = ((own.0 + belt.0).clamp(-1, 1), (own.1 + belt.1).clamp(-1, 1));
+        if (off_x, off_y) == (0, 0) {
+            continue;
+        }
+        let destination = Position::new(position.x + off_x, position.y + off_y);
+        if map.is_passable_for(flung.entity, &occupied_tiles(destination, footprint)) {
+            teleport.send(TeleportEntity {
+                destination,
+                entity: flung.entity,
+            });
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::f32::consts::PI;
+
+use bevy::{prelude::*, sprite::Anchor, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{Footprint, Health, Player, Species},
+    events::{RemoveCreature, SteppedOnTile},
+    graphics::{SpriteSheetAtlas, VisualLayer},
+    map::{occupied_tiles, Map, Position},
+    sets::{Animation, ControlState, PlayerInput},
+    species::SpeciesRegistry,
+    spells::spell_stack_is_empty,
+    ui::{AddMessage, Message},
+    vision::VisibilityMap,
+    OrdDir, TILE_SIZE,
+};
+
+pub struct CorpsePlugin;
+
+impl Plugin for CorpsePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnCorpse>();
+        app.add_event::<GrabCorpse>();
+        app.add_systems(
+            Update,
+            grab_input
+                .run_if(spell_stack_is_empty.and(in_state(ControlState::Player)))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            (
+                dress_corpses,
+                render_corpses
+                    .run_if(resource_changed::<Map>.or(resource_changed::<VisibilityMap>)),
+            )
+                .chain()
+                .in_set(Animation),
+        );
+    }
+}
+
+/// What is left of a slain creature. Corpses are not creatures: they are
+/// missing from Map::creatures, never block anything, and never act.
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Corpse {
+    pub species: Species,
+    /// Every tile it lies on, the bottom-left one first.
+    pub tiles: Vec<Position>,
+}
+
+impl Corpse {
+    /// Large corpses are too heavy to drag, but give cover against beams.
+    pub fn is_large(&self) -> bool {
+        self.tiles.len() > 1
+    }
+}
+
+/// Lay a corpse on the floor, unless another one is already there.
+#[derive(Event)]
+pub struct SpawnCorpse {
+    pub corpse: Corpse,
+}
+
+/// `entity` grabs the corpse under or next to it, or lets go of the one it holds.
+/// This takes no time.
+#[derive(Event)]
+pub struct GrabCorpse {
+    pub entity: Entity,
+}
+
+/// Holding a corpse, which follows one tile behind, see drag_corpses.
+#[derive(Component)]
+pub struct Dragging {
+    pub corpse: Entity,
+}
+
+/// Slain hostile creatures leave their corpse behind, unless they had no body to begin with.
+pub fn leave_corpses(
+    mut events: EventReader<RemoveCreature>,
+    creatures: Query<(&Position, &Species, &Health, Option<&Footprint>), Without<Player>>,
+    registry: Res<SpeciesRegistry>,
+    mut spawn: EventWriter<SpawnCorpse>,
+) {
+    let mut seen = HashSet::new();
+    for event in events.read().filter(|event| seen.insert(event.entity)) {
+        let Ok((position, species, health, footprint)) = creatures.get(event.entity) else {
+            continue;
+        };
+        let definition = registry.get(species);
+        // NOTE: Creatures cleared away at the end of a run are not slain.
+        if health.hp > 0 || !definition.is_hostile() || definition.is_naturally_intangible() {
+            continue;
+        }
+        spawn.send(SpawnCorpse {
+            corpse: Corpse {
+                species: *species,
+                tiles: occupied_tiles(*position, footprint),
+            },
+        });
+    }
+}
+
+pub fn spawn_corpses(
+    mut events: EventReader<SpawnCorpse>,
+    mut map: ResMut<Map>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let corpse = &event.corpse;
+        if corpse
+            .tiles
+            .iter()
+            .any(|tile| map.corpses.contains_key(tile))
+        {
+            continue;
+        }
+        let entity = commands.spawn(corpse.clone()).id();
+        for tile in &corpse.tiles {
+            map.corpses.insert(*tile, entity);
+            if corpse.is_large() {
+                map.cover.insert(*tile);
+            }
+        }
+    }
+}
+
+/// Remove a corpse from the floor, and from the Map.
+pub fn despawn_corpse(entity: Entity, corpse: &Corpse, map: &mut Map, commands: &mut Commands) {
+    for tile in &corpse.tiles {
+        map.corpses.remove(tile);
+        map.cover.remove(tile);
+    }
+    commands.entity(entity).despawn();
+}
+
+/// G grabs or lets go of a corpse.
+fn grab_input(
+    input: Res<ButtonInput<KeyCode>>,
+    player: Query<Entity, With<Player>>,
+    mut grab: EventWriter<GrabCorpse>,
+) {
+    if !input.just_pressed(KeyCode::KeyG) {
+        return;
+    }
+    if let Ok(player) = player.get_single() {
+        grab.send(GrabCorpse { entity: player });
+    }
+}
+
+/// The corpse under the grabber is preferred, then those next to it.
+pub fn grab_corpse(
+    mut events: EventReader<GrabCorpse>,
+    grabbers: Query<(&Position, Option<&Dragging>)>,
+    corpses: Query<&Corpse>,
+    map: Res<Map>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Ok((position, dragging)) = grabbers.get(event.entity) else {
+            continue;
+        };
+        if let Some(dragging) = dragging {
+            commands.entity(event.entity).remove::<Dragging>();
+            if let Ok(corpse) = corpses.get(dragging.corpse) {
+                text.send(AddMessage {
+                    message: Message::CorpseReleased(corpse.species),
+                });
+            }
+            continue;
+        }
+        let found = std::iter::once((0, 0))
+            .chain(
+                [OrdDir::Up, OrdDir::Right, OrdDir::Down, OrdDir::Left]
+                    .map(|direction| direction.as_offset()),
+            )
+            .find_map(|(dx, dy)| {
+                map.corpses
+                    .get(&Position::new(position.x + dx, position.y + dy))
+                    .and_then(|entity| corpses.get(*entity).ok().map(|corpse| (*entity, corpse)))
+            });
+        let message = match found {
+            None => Message::NothingToGrab,
+            Some((_, corpse)) if corpse.is_large() => Message::CorpseTooHeavy(corpse.species),
+            Some((entity, corpse)) => {
+                commands
+                    .entity(event.entity)
+                    .insert(Dragging { corpse: entity });
+                Message::CorpseGrabbed(corpse.species)
+            }
+        };
+        text.send(AddMessage { message });
+    }
+}
+
+/// Dragged corpses are pulled onto the tile their dragger just left. Being
+/// flung away, or stepping where the corpse cannot follow, makes the dragger let go.
+pub fn drag_corpses(
+    mut events: EventReader<SteppedOnTile>,
+    draggers: Query<&Dragging>,
+    mut corpses: Query<&mut Corpse>,
+    mut map: ResMut<Map>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Ok(dragging) = draggers.get(event.entity) else {
+            continue;
+        };
+        let Ok(mut corpse) = corpses.get_mut(dragging.corpse) else {
+            // It was devoured by the grinder, or the floor changed.
+            commands.entity(event.entity).remove::<Dragging>();
+            continue;
+        };
+        let is_adjacent = (event.origin.x - event.position.x)
+            .abs()
+            .max((event.origin.y - event.position.y).abs())
+            <= 1;
+        let is_blocked = map
+            .corpses
+            .get(&event.origin)
+            .is_some_and(|other| *other != dragging.corpse);
+        if !is_adjacent || is_blocked {
+            commands.entity(event.entity).remove::<Dragging>();
+            text.send(AddMessage {
+                message: Message::CorpseReleased(corpse.species),
+            });
+            continue;
+        }
+        for tile in &corpse.tiles {
+            map.corpses.remove(tile);
+        }
+        map.corpses.insert(event.origin, dragging.corpse);
+        corpse.tiles = vec![event.origin];
+    }
+}
+
+/// Give new corpses the sprite of the creature they were, lying on its side.
+fn dress_corpses(
+    corpses: Query<(Entity, &Corpse), Without<Sprite>>,
+    registry: Res<SpeciesRegistry>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (entity, corpse) in corpses.iter() {
+        let definition = registry.get(&corpse.species);
+        let (width, height) = definition.footprint().map_or((1., 1.), |footprint| {
+            let (width, height) = footprint.size();
+            (width as f32, height as f32)
+        });
+        commands.entity(entity).insert((
+            Sprite {
+                image: asset_server.load("spritesheet.png"),
+                custom_size: Some(Vec2::new(width * TILE_SIZE, height * TILE_SIZE)),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index: definition.sprite,
+                }),
+                // The same anchor summon_creature gives to large creatures.
+                anchor: Anchor::Custom(Vec2::new(0.5 / width - 0.5, 0.5 / height - 0.5)),
+                color: Color::NONE,
+                ..default()
+            },
+            Transform::from_rotation(if corpse.is_large() {
+                Quat::IDENTITY
+            } else {
+                Quat::from_rotation_z(PI / 2.)
+            }),
+            VisualLayer::Decal,
+        ));
+    }
+}
+
+/// Corpses are drawn greyed out where they lie, and only where the player
+/// can see them, or remembers seeing them.
+fn render_corpses(
+    mut corpses: Query<(&Corpse, &mut Transform, &mut Sprite)>,
+    vision: Res<VisibilityMap>,
+) {
+    for (corpse, mut transform, mut sprite) in corpses.iter_mut() {
+        let Some(anchor) = corpse.tiles.first() else {
+            continue;
+        };
+        transform.translation.x = anchor.x as f32 * TILE_SIZE;
+        transform.translation.y = anchor.y as f32 * TILE_SIZE;
+        sprite.color = if corpse.tiles.iter().any(|tile| vision.is_visible(tile)) {
+            Color::srgb(0.5, 0.45, 0.45)
+        } else if corpse.tiles.iter().any(|tile| vision.is_remembered(tile)) {
+            Color::srgb(0.2, 0.2, 0.2)
+        } else {
+            Color::NONE
+        };
+    }
+}
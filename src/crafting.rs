@@ -1,16 +1,72 @@
 use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    creature::{EffectDuration, Soul, StatusEffect},
+    caste::{match_soul_with_string, SpellSuggestions},
+    creature::{EffectDuration, Player, Soul, Spellbook, StatusEffect},
+    events::{EndTurn, PlayerAction, SoulWheel, TurnManager},
+    focus::{cycle_focus, focus_confirm, focus_step, Focused},
     map::Position,
-    spells::Axiom,
+    sets::{ControlState, PlayerInput},
+    spells::{validate_spell, Axiom, Spell, SpellWarning},
+    stats::RunStats,
+    text::split_text,
+    ui::{AddMessage, InvalidAction, Message},
 };
 
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CraftingRecipes>();
+        app.init_resource::<Weaving>();
+        app.init_resource::<RecipeBook>();
+        app.init_resource::<LooseAxioms>();
+        app.init_resource::<SpellEditor>();
+        app.add_event::<WeaveSoul>();
+        app.add_event::<EditSpell>();
+        app.add_systems(OnEnter(ControlState::RecipeBook), spawn_recipe_book);
+        app.add_systems(OnExit(ControlState::RecipeBook), despawn_recipe_book);
+        app.add_systems(OnEnter(ControlState::SpellEditor), spawn_spell_editor);
+        app.add_systems(OnExit(ControlState::SpellEditor), despawn_spell_editor);
+        app.add_systems(
+            Update,
+            (
+                recipe_book_input,
+                update_recipe_book.run_if(resource_changed::<RecipeBook>),
+            )
+                .chain()
+                .run_if(in_state(ControlState::RecipeBook))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            (spell_editor_input, update_spell_editor)
+                .chain()
+                .run_if(in_state(ControlState::SpellEditor))
+                .in_set(PlayerInput),
+        );
+    }
+}
+
 #[derive(Resource)]
 pub struct CraftingRecipes {
     pub recipes: HashMap<Axiom, Recipe>,
 }
 
+impl CraftingRecipes {
+    /// Every recipe, in the order they are listed in the recipe book.
+    pub fn book(&self) -> Vec<(&Axiom, &Recipe)> {
+        let mut book: Vec<_> = self.recipes.iter().collect();
+        // NOTE: HashMaps have no stable order, and replays refer to recipes
+        // by their place in the book.
+        book.sort_by_key(|(axiom, recipe)| {
+            (format!("{:?}", recipe.soul_type), format!("{:?}", axiom))
+        });
+        book
+    }
+}
+
 pub struct Recipe {
     pub dimensions: Position,
     pub souls: Vec<Position>,
@@ -112,6 +168,32 @@ impl FromWorld for CraftingRecipes {
                 ",
             ),
         );
+        crafting.recipes.insert(
+            Axiom::CursorTarget,
+            Recipe::from_string(
+                "\
+                A\n\
+                A\
+                ",
+            ),
+        );
+        crafting.recipes.insert(
+            Axiom::LineOfSight { range: 5 },
+            Recipe::from_string(
+                "\
+                SSS\
+                ",
+            ),
+        );
+        crafting.recipes.insert(
+            Axiom::ChainFromTarget { jumps: 3, range: 4 },
+            Recipe::from_string(
+                "\
+                U.U\n\
+                .U.\
+                ",
+            ),
+        );
         crafting.recipes.insert(
             Axiom::Halo { radius: 4 },
             Recipe::from_string(
@@ -162,3 +244,550 @@ impl FromWorld for CraftingRecipes {
         crafting
     }
 }
+
+/// Place one soul from the player's reserves into the `index`th recipe of the book.
+/// Once the recipe is complete, its axiom is added to the player's spell of that caste.
+#[derive(Event)]
+pub struct WeaveSoul {
+    pub index: usize,
+}
+
+/// The recipe the player is currently weaving, and how many souls it already holds.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct Weaving {
+    pub index: Option<usize>,
+    pub placed: usize,
+    /// Found in a chest. Without it, each soul must be woven by hand from the recipe book.
+    #[serde(default)]
+    pub loom: bool,
+}
+
+// NOTE: There are no cages to paint souls into yet, so the souls go straight
+// from the reserves into the spell.
+pub fn weave_soul(
+    mut events: EventReader<WeaveSoul>,
+    recipes: Res<CraftingRecipes>,
+    mut weaving: ResMut<Weaving>,
+    mut soul_wheel: ResMut<SoulWheel>,
+    player: Query<&Spellbook, With<Player>>,
+    mut suggestions: ResMut<SpellSuggestions>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut stats: ResMut<RunStats>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        let book = recipes.book();
+        let Some((axiom, recipe)) = book.get(event.index) else {
+            continue;
+        };
+        // Switching to another recipe starts over.
+        if weaving.index != Some(event.index) {
+            weaving.index = Some(event.index);
+            weaving.placed = 0;
+        }
+        let caste = recipe.soul_type;
+        // Spent souls are used first.
+        let pile = if soul_wheel.discard_pile.get(&caste).is_some_and(|n| *n > 0) {
+            soul_wheel.discard_pile.get_mut(&caste)
+        } else {
+            soul_wheel.draw_pile.get_mut(&caste)
+        };
+        match pile {
+            Some(amount) if *amount > 0 => *amount -= 1,
+            _ => {
+                text.send(AddMessage {
+                    message: Message::InvalidAction(InvalidAction::NotEnoughSouls(
+                        caste,
+                        recipe.souls.len() - weaving.placed,
+                    )),
+                });
+                turn_manager.action_this_turn = PlayerAction::Invalid;
+                continue;
+            }
+        }
+        weaving.placed += 1;
+        if weaving.placed >= recipe.souls.len() {
+            if let Ok(spellbook) = player.get_single() {
+                suggestions.learn(caste, (*axiom).clone(), spellbook);
+            }
+            stats.axioms_learned.push((*axiom).clone());
+            text.send(AddMessage {
+                message: Message::RecipeWoven((*axiom).clone(), caste),
+            });
+            weaving.index = None;
+            weaving.placed = 0;
+        }
+    }
+}
+
+/// How long to wait between each soul, when weaving a recipe from the book.
+const WEAVING_PACE: f32 = 0.2;
+
+/// Which recipe is highlighted in the recipe book, and the one
+/// being woven, if any, with how many souls were sent its way so far.
+#[derive(Resource, Default)]
+pub struct RecipeBook {
+    pub selected: usize,
+    pub weaving: Option<(usize, usize, Timer)>,
+}
+
+#[derive(Component)]
+pub struct RecipeBookPanel;
+
+fn spawn_recipe_book(mut commands: Commands, mut book: ResMut<RecipeBook>) {
+    commands.spawn((
+        RecipeBookPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(20.),
+            top: Val::Percent(15.),
+            padding: UiRect::all(Val::Px(1.)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.9)),
+    ));
+    // Force the first draw.
+    book.set_changed();
+}
+
+fn despawn_recipe_book(mut commands: Commands, panel: Query<Entity, With<RecipeBookPanel>>) {
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+/// W/S, the arrow keys or Tab move through the book, Enter weaves one soul into
+/// the highlighted recipe. With a loom, Enter instead weaves the whole recipe,
+/// one soul per turn. R or Escape closes the book.
+fn recipe_book_input(
+    input: Res<ButtonInput<KeyCode>>,
+    recipes: Res<CraftingRecipes>,
+    soul_wheel: Res<SoulWheel>,
+    weaving: Res<Weaving>,
+    mut book: ResMut<RecipeBook>,
+    mut next_state: ResMut<NextState<ControlState>>,
+    mut text: EventWriter<AddMessage>,
+    mut weave: EventWriter<WeaveSoul>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut turn_end: EventWriter<EndTurn>,
+) {
+    let length = recipes.recipes.len();
+    if length == 0 {
+        return;
+    }
+    let step = focus_step(&input);
+    if step != 0 {
+        book.selected = cycle_focus(book.selected, step, length);
+    }
+    if focus_confirm(&input) {
+        let (_, recipe) = recipes.book()[book.selected];
+        // Pick up where a previous weaving of this recipe left off.
+        let placed = if weaving.index == Some(book.selected) {
+            weaving.placed
+        } else {
+            0
+        };
+        let needed = recipe.souls.len().saturating_sub(placed);
+        if needed == 0 {
+            text.send(AddMessage {
+                message: Message::InvalidAction(InvalidAction::RecipeComplete),
+            });
+        } else if !weaving.loom {
+            // weave_soul complains if there is no soul to weave.
+            weave.send(WeaveSoul {
+                index: book.selected,
+            });
+            turn_manager.action_this_turn = PlayerAction::Weave;
+            turn_end.send(EndTurn);
+            next_state.set(ControlState::Player);
+        } else if reserves(&soul_wheel, &recipe.soul_type) < needed {
+            text.send(AddMessage {
+                message: Message::InvalidAction(InvalidAction::NotEnoughSouls(
+                    recipe.soul_type,
+                    needed,
+                )),
+            });
+        } else {
+            book.weaving = Some((
+                book.selected,
+                0,
+                Timer::from_seconds(WEAVING_PACE, TimerMode::Once),
+            ));
+            next_state.set(ControlState::Player);
+        }
+    }
+    if input.any_just_pressed([KeyCode::KeyR, KeyCode::Escape]) {
+        next_state.set(ControlState::Player);
+    }
+}
+
+/// How many souls of this caste the player has, spent or not.
+fn reserves(soul_wheel: &SoulWheel, caste: &Soul) -> usize {
+    soul_wheel.draw_pile.get(caste).copied().unwrap_or(0)
+        + soul_wheel.discard_pile.get(caste).copied().unwrap_or(0)
+}
+
+fn update_recipe_book(
+    book: Res<RecipeBook>,
+    recipes: Res<CraftingRecipes>,
+    soul_wheel: Res<SoulWheel>,
+    panel: Query<Entity, With<RecipeBookPanel>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let Ok(panel) = panel.get_single() else {
+        return;
+    };
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|parent| {
+        for (i, (axiom, recipe)) in recipes.book().into_iter().enumerate() {
+            let line = format!(
+                "{}{:?}[w] - {}x{} pattern of {} x{} (have {})",
+                if i == book.selected { "[y]> " } else { "[w]" },
+                axiom,
+                recipe.dimensions.x,
+                recipe.dimensions.y,
+                match_soul_with_string(&recipe.soul_type),
+                recipe.souls.len(),
+                reserves(&soul_wheel, &recipe.soul_type),
+            );
+            let mut line_entity = editor_line(parent, &font, &line);
+            if i == book.selected {
+                line_entity.insert(Focused);
+            }
+        }
+    });
+}
+
+/// Once a recipe is picked in the book with a loom, weave one of its souls each turn,
+/// until weave_soul completes it. Pressing any key interrupts the weaving.
+pub fn continue_weaving(
+    input: Res<ButtonInput<KeyCode>>,
+    recipes: Res<CraftingRecipes>,
+    weaving: Res<Weaving>,
+    soul_wheel: Res<SoulWheel>,
+    mut book: ResMut<RecipeBook>,
+    mut weave: EventWriter<WeaveSoul>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut turn_end: EventWriter<EndTurn>,
+    time: Res<Time>,
+) {
+    let Some((index, sent, timer)) = book.weaving.as_mut() else {
+        return;
+    };
+    let caste = recipes.book()[*index].1.soul_type;
+    if input.get_just_pressed().next().is_some() || reserves(&soul_wheel, &caste) == 0 {
+        book.weaving = None;
+        return;
+    }
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+    // weave_soul forgets the recipe once it is complete. By now, it has
+    // had the time to read the last soul sent.
+    if *sent > 0 && weaving.index != Some(*index) {
+        book.weaving = None;
+        return;
+    }
+    weave.send(WeaveSoul { index: *index });
+    turn_manager.action_this_turn = PlayerAction::Weave;
+    turn_end.send(EndTurn);
+    *sent += 1;
+    timer.reset();
+}
+
+/// Axioms taken out of spells in the spell editor, waiting to be put back in one.
+#[derive(Resource, Default, Clone)]
+pub struct LooseAxioms {
+    pub axioms: Vec<Axiom>,
+}
+
+/// One change to a spell of the player's spellbook. Indices are axiom positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpellEdit {
+    /// Swap this axiom with the one after it.
+    Swap(usize),
+    /// Take this axiom out, and into the LooseAxioms.
+    Remove(usize),
+    /// Put the `loose`th LooseAxiom back in, at this position.
+    Insert { at: usize, loose: usize },
+}
+
+/// Edit the player's spell of this caste. This takes no time.
+#[derive(Event)]
+pub struct EditSpell {
+    pub caste: Soul,
+    pub edit: SpellEdit,
+}
+
+pub fn edit_spell(
+    mut events: EventReader<EditSpell>,
+    mut player: Query<&mut Spellbook, With<Player>>,
+    mut loose: ResMut<LooseAxioms>,
+) {
+    for event in events.read() {
+        let Ok(mut spellbook) = player.get_single_mut() else {
+            continue;
+        };
+        let Some(spell) = spellbook.spells.get_mut(&event.caste) else {
+            continue;
+        };
+        let axioms = &mut spell.axioms;
+        match event.edit {
+            SpellEdit::Swap(i) if i + 1 < axioms.len() => axioms.swap(i, i + 1),
+            SpellEdit::Remove(i) if i < axioms.len() => loose.axioms.push(axioms.remove(i)),
+            SpellEdit::Insert { at, loose: index }
+                if at <= axioms.len() && index < loose.axioms.len() =>
+            {
+                axioms.insert(at, loose.axioms.remove(index));
+            }
+            // Out of bounds, likely from an outdated editor.
+            _ => (),
+        }
+    }
+}
+
+/// The castes whose spells can be edited, in the order the editor cycles through them.
+pub const EDITABLE_CASTES: [Soul; 6] = [
+    Soul::Saintly,
+    Soul::Ordered,
+    Soul::Artistic,
+    Soul::Unhinged,
+    Soul::Feral,
+    Soul::Vile,
+];
+
+/// Which spell is open in the spell editor, and what is highlighted in it.
+#[derive(Resource, Default)]
+pub struct SpellEditor {
+    /// Index into EDITABLE_CASTES.
+    pub caste: usize,
+    pub cursor: usize,
+    /// The highlighted LooseAxiom, to be inserted.
+    pub loose: usize,
+}
+
+#[derive(Component)]
+pub struct SpellEditorPanel;
+
+/// An axiom of the edited spell, which can be clicked to move the cursor to it.
+#[derive(Component)]
+pub struct SpellEditorLine(usize);
+
+fn spawn_spell_editor(mut commands: Commands, mut editor: ResMut<SpellEditor>) {
+    commands.spawn((
+        SpellEditorPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(20.),
+            top: Val::Percent(15.),
+            padding: UiRect::all(Val::Px(1.)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.9)),
+    ));
+    // Force the first draw.
+    editor.set_changed();
+}
+
+fn despawn_spell_editor(mut commands: Commands, panel: Query<Entity, With<SpellEditorPanel>>) {
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+/// A/D or Tab switch castes, W/S move the cursor and, while holding Shift, the axiom under it.
+/// Delete takes the highlighted axiom out, Q/E pick a loose axiom and Enter puts it
+/// back in after the cursor. B or Escape closes the editor.
+fn spell_editor_input(
+    input: Res<ButtonInput<KeyCode>>,
+    lines: Query<(&SpellEditorLine, &Interaction), Changed<Interaction>>,
+    player: Query<&Spellbook, With<Player>>,
+    loose: Res<LooseAxioms>,
+    mut editor: ResMut<SpellEditor>,
+    mut edit: EventWriter<EditSpell>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    if input.any_just_pressed([KeyCode::KeyB, KeyCode::Escape]) {
+        next_state.set(ControlState::Player);
+        return;
+    }
+    let Ok(spellbook) = player.get_single() else {
+        return;
+    };
+    // NOTE: Tab goes through castes here, as W/S already carry axioms around.
+    let shift = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let tab = input.just_pressed(KeyCode::Tab);
+    let caste_step =
+        if input.any_just_pressed([KeyCode::ArrowLeft, KeyCode::KeyA]) || (tab && shift) {
+            -1
+        } else if input.any_just_pressed([KeyCode::ArrowRight, KeyCode::KeyD]) || (tab && !shift) {
+            1
+        } else {
+            0
+        };
+    if caste_step != 0 {
+        editor.caste = cycle_focus(editor.caste, caste_step, EDITABLE_CASTES.len());
+        editor.cursor = 0;
+    }
+    let caste = EDITABLE_CASTES[editor.caste];
+    let length = spellbook
+        .spells
+        .get(&caste)
+        .map_or(0, |spell| spell.axioms.len());
+    let holding = input.pressed(KeyCode::ShiftLeft);
+    let mut send = |edit_kind| {
+        edit.send(EditSpell {
+            caste,
+            edit: edit_kind,
+        });
+    };
+    if input.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyW]) && editor.cursor > 0 {
+        if holding {
+            send(SpellEdit::Swap(editor.cursor - 1));
+        }
+        editor.cursor -= 1;
+    }
+    if input.any_just_pressed([KeyCode::ArrowDown, KeyCode::KeyS]) && editor.cursor + 1 < length {
+        if holding {
+            send(SpellEdit::Swap(editor.cursor));
+        }
+        editor.cursor += 1;
+    }
+    if input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace]) && editor.cursor < length {
+        send(SpellEdit::Remove(editor.cursor));
+        editor.cursor = editor.cursor.min(length.saturating_sub(2));
+    }
+    let loose_count = loose.axioms.len();
+    if loose_count > 0 {
+        if input.just_pressed(KeyCode::KeyQ) {
+            editor.loose = (editor.loose + loose_count - 1) % loose_count;
+        }
+        if input.just_pressed(KeyCode::KeyE) {
+            editor.loose = (editor.loose + 1) % loose_count;
+        }
+        if focus_confirm(&input) {
+            let at = if length == 0 { 0 } else { editor.cursor + 1 };
+            send(SpellEdit::Insert {
+                at,
+                loose: editor.loose.min(loose_count - 1),
+            });
+            editor.cursor = at;
+            editor.loose = 0;
+        }
+    }
+    for (line, interaction) in lines.iter() {
+        if *interaction == Interaction::Pressed {
+            editor.cursor = line.0;
+        }
+    }
+}
+
+fn update_spell_editor(
+    editor: Res<SpellEditor>,
+    player: Query<Ref<Spellbook>, With<Player>>,
+    loose: Res<LooseAxioms>,
+    panel: Query<Entity, With<SpellEditorPanel>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let (Ok(panel), Ok(spellbook)) = (panel.get_single(), player.get_single()) else {
+        return;
+    };
+    if !editor.is_changed() && !spellbook.is_changed() && !loose.is_changed() {
+        return;
+    }
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    let caste = EDITABLE_CASTES[editor.caste];
+    let empty = Spell { axioms: Vec::new() };
+    let spell = spellbook.spells.get(&caste).unwrap_or(&empty);
+    let warnings = validate_spell(spell);
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|parent| {
+        editor_line(
+            parent,
+            &font,
+            &format!("< {} >", match_soul_with_string(&caste)),
+        );
+        if spell.axioms.is_empty() {
+            editor_line(parent, &font, "[d]This spell has no axioms.[w]");
+        }
+        for (i, axiom) in spell.axioms.iter().enumerate() {
+            let warning = warnings.iter().find_map(|warning| match warning {
+                SpellWarning::NoForm(index) if *index == i => {
+                    Some(" [r](no Form gives it targets)[w]")
+                }
+                SpellWarning::Unreachable(index) if *index == i => Some(" [r](never reached)[w]"),
+                _ => None,
+            });
+            let mut line = editor_line(
+                parent,
+                &font,
+                &format!(
+                    "{}{:?}[w]{}",
+                    if i == editor.cursor { "[y]> " } else { "[w]" },
+                    axiom,
+                    warning.unwrap_or(""),
+                ),
+            );
+            line.insert((SpellEditorLine(i), Interaction::default()));
+            if i == editor.cursor {
+                line.insert(Focused);
+            }
+        }
+        editor_line(
+            parent,
+            &font,
+            &format!(
+                "Loose axioms: {}",
+                if loose.axioms.is_empty() {
+                    "[d]none[w]".to_owned()
+                } else {
+                    loose
+                        .axioms
+                        .iter()
+                        .enumerate()
+                        .map(|(i, axiom)| {
+                            if i == editor.loose {
+                                format!("[y]{:?}[w]", axiom)
+                            } else {
+                                format!("{:?}", axiom)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }
+            ),
+        );
+        editor_line(
+            parent,
+            &font,
+            "[d]W/S: move, Shift: carry, Delete: take out, Q/E and Enter: put in, B: close[w]",
+        );
+    });
+}
+
+fn editor_line<'a>(
+    parent: &'a mut ChildBuilder,
+    font: &TextFont,
+    text: &str,
+) -> EntityCommands<'a> {
+    let mut line = parent.spawn((Text::default(), font.clone()));
+    line.with_children(|line_parent| {
+        for (section, color) in split_text(text) {
+            line_parent.spawn((TextSpan::new(section), font.clone(), color));
+        }
+    });
+    line
+}
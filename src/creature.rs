@@ -1,10 +1,11 @@
-use bevy::{prelude::*, utils::HashMap};
-
-use crate::{
-    map::Position,
-    spells::{Axiom, CounterCondition, Spell},
-    OrdDir,
+use bevy::{
+    ecs::query::{QueryData, QueryEntityError, QueryFilter, ROQueryItem},
+    prelude::*,
+    utils::HashMap,
 };
+use serde::{Deserialize, Serialize};
+
+use crate::{equipment::Item, key_items::KeyItem, map::Position, spells::Spell, OrdDir};
 
 #[derive(Bundle)]
 pub struct Creature {
@@ -25,12 +26,55 @@ pub struct CreatureFlags {
     pub species_flags: Entity,
 }
 
+/// Which flag entity is looked at first, when both could hold a component.
+pub enum FlagPriority {
+    /// Status effects override what the species grants, like a Dizzy
+    /// creature ignoring its usual MovementStyle.
+    Effects,
+    Species,
+}
+
+/// Look up a component on both flag entities of a creature at once.
+pub trait FlagQuery<D: QueryData> {
+    /// Either the species or the effects of the creature grant this component.
+    fn flagged(&self, flags: &CreatureFlags) -> bool;
+
+    /// The component granted to the creature, with effects overriding its species.
+    fn get_flag(&self, flags: &CreatureFlags) -> Result<ROQueryItem<'_, D>, QueryEntityError<'_>> {
+        self.get_flag_by(flags, FlagPriority::Effects)
+    }
+
+    fn get_flag_by(
+        &self,
+        flags: &CreatureFlags,
+        priority: FlagPriority,
+    ) -> Result<ROQueryItem<'_, D>, QueryEntityError<'_>>;
+}
+
+impl<D: QueryData, F: QueryFilter> FlagQuery<D> for Query<'_, '_, D, F> {
+    fn flagged(&self, flags: &CreatureFlags) -> bool {
+        self.contains(flags.species_flags) || self.contains(flags.effects_flags)
+    }
+
+    fn get_flag_by(
+        &self,
+        flags: &CreatureFlags,
+        priority: FlagPriority,
+    ) -> Result<ROQueryItem<'_, D>, QueryEntityError<'_>> {
+        let (first, second) = match priority {
+            FlagPriority::Effects => (flags.effects_flags, flags.species_flags),
+            FlagPriority::Species => (flags.species_flags, flags.effects_flags),
+        };
+        self.get(first).or_else(|_| self.get(second))
+    }
+}
+
 #[derive(Component, Clone)]
 pub struct FlagEntity {
     pub parent_creature: Entity,
 }
 
-#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Soul {
     Saintly,
     Ordered,
@@ -78,22 +122,17 @@ impl Spellbook {
         }
         Spellbook { spells: book }
     }
-    pub fn empty() -> Self {
-        Spellbook {
-            spells: HashMap::new(),
-        }
-    }
 }
 
-// The graphical representation of Health: a health bar.
-#[derive(Bundle)]
-pub struct HealthIndicator {
-    pub sprite: Sprite,
-    pub visibility: Visibility,
-    pub transform: Transform,
+/// What a hostile creature leaves behind when it is removed, see DeathEffects.
+/// Kept apart from the Spellbook, so it is never learned, stolen or written down.
+#[derive(Component, Clone)]
+pub struct DeathEffect {
+    pub soul: Soul,
+    pub spell: Spell,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum StatusEffect {
     // Cannot take damage.
     Invincible,
@@ -103,6 +142,15 @@ pub enum StatusEffect {
     Dizzy,
     // The creature acts as if it was summoned by whoever cursed it.
     DimensionBond,
+    // Bounces the next hostile spell back at its caster.
+    SpellReflect,
+}
+
+impl StatusEffect {
+    /// Whether this is a curse, which Axiom::Cleanse and friends should remove.
+    pub fn is_negative(&self) -> bool {
+        matches!(self, StatusEffect::Dizzy | StatusEffect::DimensionBond)
+    }
 }
 
 #[derive(Debug)]
@@ -121,7 +169,7 @@ impl PotencyAndStacks {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EffectDuration {
     Finite { stacks: usize },
     Infinite,
@@ -146,7 +194,7 @@ pub struct StatusEffectsList {
     pub effects: HashMap<StatusEffect, PotencyAndStacks>,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Deserialize)]
 pub enum Speed {
     Slow { wait_turns: usize },
     Fast { actions_per_turn: usize },
@@ -156,6 +204,13 @@ pub enum Speed {
 #[derive(Component)]
 pub struct Player;
 
+/// Where a creature is looking, independently of where it last moved.
+/// Momentum-based forms can be aimed with this instead, see AimMode.
+#[derive(Component)]
+pub struct Facing {
+    pub direction: OrdDir,
+}
+
 #[derive(Component)]
 pub struct Hunt;
 
@@ -167,9 +222,33 @@ pub struct Stab {
 #[derive(Component)]
 pub struct Invincible;
 
+/// Extra melee damage, granted by equipment. Unlike Stab, it is never used up.
+#[derive(Component)]
+pub struct MeleeBonus {
+    pub bonus_damage: isize,
+}
+
+/// Reduces all damage taken by this much, down to a minimum of 1.
+#[derive(Component)]
+pub struct RealityShield {
+    pub amount: usize,
+}
+
+/// Soaks up damage before Health does. Healing does not restore it, and it
+/// fades away once `turns` run out, see decay_shields.
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ShieldBuffer {
+    pub amount: usize,
+    pub turns: usize,
+}
+
 #[derive(Component)]
 pub struct Dizzy;
 
+/// Used up by the first hostile spell to target this creature, see reflect_spells.
+#[derive(Component)]
+pub struct SpellReflect;
+
 #[derive(Component)]
 pub struct Sleeping {
     pub cage_idx: usize,
@@ -182,12 +261,36 @@ pub struct Awake;
 #[derive(Component)]
 pub struct Random;
 
+/// Runs away from the player while its HP is at or below `threshold`,
+/// and comes back for more once healed above it.
+#[derive(Component, Clone, Copy, Debug, Deserialize)]
+pub struct Cowardly {
+    pub threshold: usize,
+}
+
+/// How a hunting creature approaches its prey, instead of charging straight at it.
+#[derive(Component, Clone, Copy, Debug, Deserialize)]
+pub enum MovementStyle {
+    /// Prefers tiles adjacent to walls, even if it means taking the long way.
+    WallHugger,
+    /// Never ends its move adjacent to the player, unless it is attacking.
+    Skirmisher,
+    /// Orbits the player at `range` tiles away.
+    Circler { range: i32 },
+}
+
 // Vulnerable to Abjuration.
 #[derive(Component)]
 pub struct Summoned {
     pub summoner: Entity,
 }
 
+/// Left behind by another creature, which is credited with its damage and kills.
+#[derive(Component)]
+pub struct Owner {
+    pub entity: Entity,
+}
+
 // Will start dragging along creatures of this species.
 #[derive(Component)]
 pub struct Magnetic {
@@ -213,6 +316,23 @@ pub struct Spellproof;
 #[derive(Component)]
 pub struct Meleeproof;
 
+/// Bumping into it shoves it one tile away, along with any other Pushable
+/// creatures lined up behind it, see push_chains.
+#[derive(Component)]
+pub struct Pushable;
+
+/// A piece of track, which Railbound creatures follow, see roll_carts.
+#[derive(Component)]
+pub struct Railway;
+
+/// Once set in motion, it keeps rolling along the Railways under it.
+#[derive(Component)]
+pub struct Railbound;
+
+/// Sends Railbound creatures crossing its junction the way it faces.
+#[derive(Component)]
+pub struct Switch;
+
 #[derive(Component)]
 pub struct Immobile;
 
@@ -229,13 +349,265 @@ pub struct DesignatedForRemoval;
 #[derive(Component)]
 pub struct Fragile;
 
+/// Crumbles away after this many turns, see crumble_ephemeral_creatures.
+#[derive(Component)]
+pub struct Ephemeral {
+    pub turns: usize,
+}
+
+/// Stuck in mud, and spending its next step pulling free, see tread_terrain.
+#[derive(Component)]
+pub struct Mired;
+
+/// Laid in hiding, and drawn invisible until found, see reveal_traps.
+#[derive(Component)]
+pub struct Hidden;
+
+/// Was Hidden, until it was found. It can now be disarmed, see DisarmTrap.
+#[derive(Component)]
+pub struct Revealed;
+
+/// Finds Hidden creatures up to this many tiles away, diagonals included.
+#[derive(Component)]
+pub struct Perceptive {
+    pub radius: i32,
+}
+
+/// Can be used by standing next to it and pressing E, see interact.
+#[derive(Component)]
+pub struct Interactable;
+
+/// Powers its circuit while pulled, see Circuit.
+#[derive(Component)]
+pub struct Lever {
+    pub on: bool,
+}
+
+/// Powers its circuit while something stands on it, see Circuit.
+#[derive(Component)]
+pub struct PressurePlate;
+
+/// Carries whatever stands on it one tile in the direction it faces, every turn.
+#[derive(Component)]
+pub struct Conveyor;
+
+/// A belt which carries things off to its left and right in turn, instead of
+/// straight ahead, see convey_creatures.
+#[derive(Component)]
+pub struct Splitter {
+    /// Whether the next thing goes off to the right.
+    pub right: bool,
+}
+
+/// Flies one tile along `velocity` every turn, and has its `owner` cast `payload`
+/// on the first creature in its way.
+#[derive(Component, Clone)]
+pub struct Projectile {
+    pub velocity: OrdDir,
+    pub owner: Entity,
+    pub payload: Spell,
+    pub caste: Soul,
+}
+
+/// Opened with E, handing out `rolls` rewards from the LootTable.
+#[derive(Component)]
+pub struct Chest {
+    pub rolls: usize,
+}
+
+/// A door which only opens for someone carrying `key`.
+#[derive(Component)]
+pub struct Lock {
+    pub key: KeyItem,
+}
+
+/// Picked up by the player when stepped on.
+#[derive(Component)]
+pub struct KeyPickup {
+    pub item: KeyItem,
+}
+
+/// A dropped item, put on by the player when stepped on.
+#[derive(Component)]
+pub struct ItemPickup {
+    pub item: Item,
+}
+
+/// A boon granted by a Shrine for a few turns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Blessing {
+    /// Every spell cast follows each of its Forms with Spread.
+    Expanse,
+    /// Heal 1 HP every turn.
+    Renewal,
+}
+
+/// Grants its blessing for `turns` to whoever activates it, then crumbles.
+#[derive(Component)]
+pub struct Shrine {
+    pub blessing: Blessing,
+    pub turns: usize,
+}
+
+/// Under the blessing of a Shrine, see decay_blessings. Only one at a time.
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Blessed {
+    pub blessing: Blessing,
+    pub turns: usize,
+}
+
+/// Grants a ShieldBuffer to the player when stepped on.
+#[derive(Component)]
+pub struct ShieldPickup {
+    pub amount: usize,
+    pub turns: usize,
+}
+
+/// A spell scroll, picked up by the player when stepped on.
+/// The spell it teaches is the only one in its Spellbook.
+#[derive(Component)]
+pub struct ScrollPickup;
+
+/// Looks like a chest, but turns into `species` when opened.
+#[derive(Component)]
+pub struct Mimic {
+    pub species: Species,
+}
+
+/// Broad families of species, written in creatures.ron. Spells and AI look
+/// for these rather than for lists of species.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Tag {
+    Wall,
+    /// Can be torn down by spells, see DevourWall.
+    Brittle,
+    Construct,
+    Beast,
+    Mechanical,
+    Furniture,
+}
+
+/// The tags of a species, on its species flags entity.
+#[derive(Component)]
+pub struct Tags(pub Vec<Tag>);
+
+impl Tags {
+    pub fn has(&self, tag: Tag) -> bool {
+        self.0.contains(&tag)
+    }
+
+    /// How much damage of this kind the tags soak up, see Tag::resistance.
+    pub fn resistance(&self, kind: DamageKind) -> isize {
+        self.0.iter().map(|tag| tag.resistance(kind)).sum()
+    }
+}
+
+impl Tag {
+    /// How much less damage of this kind creatures with this tag take from each blow.
+    pub fn resistance(&self, kind: DamageKind) -> isize {
+        match (self, kind) {
+            // Spells struggle to get through metal plating.
+            (Tag::Construct, DamageKind::Spell) => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Where a blow comes from, see Tag::resistance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageKind {
+    Melee,
+    /// Anything cast, including knockback slams.
+    Spell,
+    /// Harmful ground, like lava.
+    Terrain,
+    /// Healing from blessings and clearing floors, which nothing resists.
+    Other,
+}
+
+/// Casts its Vile spell instead of moving while next to a creature with this tag.
+#[derive(Component)]
+pub struct Devours {
+    pub tag: Tag,
+}
+
+/// Hovers above the ground. Its sprite is drawn raised, with a shadow below.
+#[derive(Component)]
+pub struct Flying;
+
+/// A creature larger than one tile. Its Position is its bottom-left tile,
+/// and the Map knows it is standing on all the other ones too.
+#[derive(Component, Clone)]
+pub struct Footprint {
+    /// Every covered tile, relative to the creature's Position. Always includes (0, 0).
+    pub offsets: Vec<(i32, i32)>,
+}
+
+impl Footprint {
+    /// A `width` by `height` rectangle, extending right and up from the creature's Position.
+    /// 2x2 bosses, 1x3 serpents...
+    pub fn rectangle(width: i32, height: i32) -> Self {
+        Self {
+            offsets: (0..width)
+                .flat_map(|x| (0..height).map(move |y| (x, y)))
+                .collect(),
+        }
+    }
+
+    /// Every tile covered when standing on `position`.
+    pub fn tiles(&self, position: Position) -> Vec<Position> {
+        self.offsets
+            .iter()
+            .map(|(dx, dy)| Position::new(position.x + dx, position.y + dy))
+            .collect()
+    }
+
+    /// The width and height of the smallest rectangle containing this footprint.
+    pub fn size(&self) -> (i32, i32) {
+        let width = self.offsets.iter().map(|(x, _)| *x).max().unwrap_or(0) + 1;
+        let height = self.offsets.iter().map(|(_, y)| *y).max().unwrap_or(0) + 1;
+        (width, height)
+    }
+}
+
+/// One tile of a large creature drawn with a composite sprite. It follows
+/// its parent around, see render_body_parts.
+#[derive(Component)]
+pub struct BodyPart {
+    pub offset: (i32, i32),
+}
+
+/// Parts of a large creature which can be destroyed on their own,
+/// by the Footprint offset of the tile they are on.
+#[derive(Component, Clone)]
+pub struct WeakPoints {
+    pub points: HashMap<(i32, i32), WeakPoint>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct WeakPoint {
+    /// How much more damage this part can take before being destroyed.
+    pub hp: usize,
+    /// Extra damage dealt to the whole creature when this part is hit.
+    pub bonus_damage: usize,
+    /// The spell (or death effect) the creature loses once this part is destroyed.
+    pub disables: Option<Soul>,
+}
+
+impl WeakPoint {
+    pub fn is_destroyed(&self) -> bool {
+        self.hp == 0
+    }
+}
+
 #[derive(Component)]
 pub struct Health {
     pub hp: usize,
     pub max_hp: usize,
 }
 
-#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash)]
+/// Each species is defined in creatures.ron, see SpeciesRegistry.
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Species {
     Player,
     Wall,
@@ -244,6 +616,7 @@ pub enum Species {
     Apiarist,
     Shrike,
     Tinker,
+    ArchTinker,
     Second,
     Spawner,
     Airlock,
@@ -254,213 +627,34 @@ pub enum Species {
     EpsilonTail,
     CageBorder,
     CageSlot,
-}
-
-/// Get the appropriate texture from the spritesheet depending on the species type.
-pub fn get_species_sprite(species: &Species) -> usize {
-    match species {
-        Species::Player => 0,
-        Species::Wall => 3,
-        Species::WeakWall => 3,
-        Species::Hunter => 4,
-        Species::Spawner => 5,
-        Species::Airlock => 17,
-        Species::Shrike => 5,
-        Species::Apiarist => 6,
-        Species::Second => 7,
-        Species::Tinker => 8,
-        Species::Trap => 12,
-        Species::Oracle => 40,
-        Species::Abazon => 28,
-        Species::EpsilonHead => 67,
-        Species::EpsilonTail => 68,
-        Species::CageBorder => 108,
-        Species::CageSlot => 167,
-    }
-}
-
-pub fn get_species_spellbook(species: &Species) -> Spellbook {
-    match species {
-        Species::Oracle => Spellbook::new([
-            None,
-            None,
-            None,
-            Some(Spell {
-                axioms: vec![
-                    Axiom::WhenMoved,
-                    Axiom::IncrementCounter {
-                        amount: 1,
-                        count: 0,
-                    },
-                    Axiom::TerminateIfCounter {
-                        condition: CounterCondition::NotModuloOf { modulo: 5 },
-                        threshold: 0,
-                    },
-                    Axiom::Ego,
-                    Axiom::StatusEffect {
-                        effect: StatusEffect::Stab,
-                        potency: 0,
-                        stacks: EffectDuration::Infinite,
-                    },
-                    Axiom::UpgradeStatusEffect {
-                        effect: StatusEffect::Stab,
-                        potency: 1,
-                        stacks: EffectDuration::Infinite,
-                    },
-                ],
-            }),
-            None,
-            None,
-        ]),
-        Species::EpsilonHead => Spellbook::new([
-            None,
-            None,
-            None,
-            Some(Spell {
-                axioms: vec![
-                    Axiom::WhenMoved,
-                    Axiom::IncrementCounter {
-                        amount: 1,
-                        count: 0,
-                    },
-                    Axiom::TerminateIfCounter {
-                        condition: CounterCondition::NotModuloOf { modulo: 5 },
-                        threshold: 0,
-                    },
-                    Axiom::Ego,
-                    Axiom::Dash { max_distance: 5 },
-                ],
-            }),
-            None,
-            None,
-        ]),
-        Species::Second => Spellbook::new([
-            None,
-            None,
-            None,
-            None,
-            None,
-            Some(Spell {
-                axioms: vec![Axiom::Plus, Axiom::DevourWall],
-            }),
-        ]),
-        Species::Hunter => Spellbook::new([
-            Some(Spell {
-                axioms: vec![
-                    Axiom::WhenDealingDamage,
-                    Axiom::Ego,
-                    Axiom::HealOrHarm { amount: 1 },
-                ],
-            }),
-            None,
-            None,
-            None,
-            None,
-            None,
-        ]),
-        Species::Tinker => Spellbook::new([
-            None,
-            None,
-            Some(Spell {
-                axioms: vec![
-                    Axiom::WhenMoved,
-                    Axiom::IncrementCounter {
-                        amount: 1,
-                        count: 0,
-                    },
-                    Axiom::TerminateIfCounter {
-                        condition: CounterCondition::NotModuloOf { modulo: 5 },
-                        threshold: 0,
-                    },
-                    Axiom::Plus,
-                    Axiom::FilterBySpecies {
-                        species: Species::WeakWall,
-                    },
-                    Axiom::Transform {
-                        species: Species::Abazon,
-                    },
-                    Axiom::StatusEffect {
-                        effect: StatusEffect::DimensionBond,
-                        potency: 1,
-                        stacks: EffectDuration::Infinite,
-                    },
-                    Axiom::Terminate,
-                    Axiom::WhenRemoved,
-                    Axiom::Ego,
-                    Axiom::Abjuration,
-                ],
-            }),
-            None,
-            None,
-            None,
-        ]),
-        Species::Player => Spellbook::new([
-            Some(Spell {
-                axioms: vec![Axiom::Ego, Axiom::Plus, Axiom::HealOrHarm { amount: 2 }],
-            }),
-            Some(Spell {
-                axioms: vec![
-                    Axiom::Ego,
-                    Axiom::StatusEffect {
-                        effect: StatusEffect::Invincible,
-                        potency: 1,
-                        stacks: EffectDuration::Finite { stacks: 2 },
-                    },
-                ],
-            }),
-            Some(Spell {
-                axioms: vec![
-                    Axiom::Ego,
-                    Axiom::PlaceStepTrap,
-                    Axiom::PiercingBeams,
-                    Axiom::PlusBeam,
-                    Axiom::Ego,
-                    Axiom::HealOrHarm { amount: -2 },
-                ],
-            }),
-            Some(Spell {
-                axioms: vec![
-                    Axiom::PiercingBeams,
-                    Axiom::XBeam,
-                    Axiom::HealOrHarm { amount: -2 },
-                ],
-            }),
-            Some(Spell {
-                axioms: vec![
-                    Axiom::Ego,
-                    Axiom::Trace,
-                    Axiom::Dash { max_distance: 5 },
-                    Axiom::Spread,
-                    Axiom::UntargetCaster,
-                    Axiom::HealOrHarm { amount: -1 },
-                    Axiom::PurgeTargets,
-                    Axiom::Touch,
-                    Axiom::StatusEffect {
-                        effect: StatusEffect::Dizzy,
-                        potency: 1,
-                        stacks: EffectDuration::Finite { stacks: 2 },
-                    },
-                    Axiom::Dash { max_distance: 1 },
-                ],
-            }),
-            Some(Spell {
-                axioms: vec![
-                    Axiom::Ego,
-                    Axiom::StatusEffect {
-                        effect: StatusEffect::Stab,
-                        potency: 5,
-                        stacks: EffectDuration::Infinite,
-                    },
-                ],
-            }),
-        ]),
-        _ => Spellbook::empty(),
-    }
-}
-
-pub fn is_naturally_intangible(species: &Species) -> bool {
-    match species {
-        Species::Trap => true,
-        _ => false,
-    }
+    Colossus,
+    Lever,
+    PressurePlate,
+    Chest,
+    TrappedChest,
+    MimicChest,
+    Mimic,
+    Keycard,
+    Sigil,
+    LockedAirlock,
+    SealedAirlock,
+    Grinder,
+    FleetingWall,
+    ConveyorBelt,
+    ConveyorCorner,
+    ConveyorSplitter,
+    ExpanseShrine,
+    RenewalShrine,
+    Projectile,
+    Warden,
+    CleansingSalts,
+    Scroll,
+    AegisShard,
+    Cart,
+    Rail,
+    Switch,
+    SerratedEdge,
+    ChitinPlate,
+    NacreWard,
+    QuicksilverCharm,
 }
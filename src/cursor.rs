@@ -1,12 +1,19 @@
 use crate::{
-    creature::{get_species_sprite, Player, Species},
-    graphics::{SlideAnimation, SpriteSheetAtlas},
+    caste::match_soul_with_string,
+    creature::{
+        CreatureFlags, FlagQuery, Health, Player, ShieldBuffer, Species, Speed, Spellbook,
+        StatusEffectsList,
+    },
+    graphics::{SlideAnimation, SpriteSheetAtlas, VisualLayer},
+    input::hovered_tile,
     map::{Map, Position},
-    text::match_species_with_description,
-    ui::{match_species_with_string, spawn_split_text, CursorBox, MessageLog},
+    species::SpeciesRegistry,
+    text::strip_color_tags,
+    threat::{creature_power, ThreatLevel},
+    ui::{spawn_split_text, CursorBox, MessageLog},
     OrdDir, TILE_SIZE,
 };
-use bevy::prelude::*;
+use bevy::{prelude::*, window::PrimaryWindow};
 
 pub struct CursorPlugin;
 
@@ -41,7 +48,8 @@ pub fn spawn_cursor(
             }),
             ..default()
         },
-        Transform::from_translation(Vec3::new(0., 0., 3.)),
+        Transform::default(),
+        VisualLayer::Overlay,
     ));
     *message.single_mut() = Visibility::Hidden;
     *cursor_box.single_mut() = Visibility::Inherited;
@@ -77,6 +85,28 @@ pub fn cursor_step(
     }
 }
 
+/// The cursor follows the mouse, once it moves.
+pub fn hover_cursor(
+    mut mouse_moved: EventReader<CursorMoved>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    cursor: Query<&Position, With<Cursor>>,
+    mut teleporter: EventWriter<TeleportCursor>,
+) {
+    if mouse_moved.read().count() == 0 {
+        return;
+    }
+    let (Some(hovered), Ok(cursor_pos)) = (hovered_tile(&window, &camera), cursor.get_single())
+    else {
+        return;
+    };
+    if hovered != *cursor_pos {
+        teleporter.send(TeleportCursor {
+            destination: hovered,
+        });
+    }
+}
+
 #[derive(Event)]
 pub struct TeleportCursor {
     pub destination: Position,
@@ -98,38 +128,118 @@ pub fn teleport_cursor(
     }
 }
 
+/// Show the examined creature's name, health, status effects,
+/// description and spells in the CursorBox. Its name is tinted by
+/// how much of a threat it is to the player, see ThreatLevel.
 pub fn update_cursor_box(
     cursor: Query<&Cursor, Changed<Cursor>>,
-    creature_query: Query<&Species>,
+    creature_query: Query<(
+        &Species,
+        &Health,
+        &StatusEffectsList,
+        &Spellbook,
+        Option<&ShieldBuffer>,
+        &CreatureFlags,
+        Has<Player>,
+    )>,
+    speed: Query<&Speed>,
+    player: Query<&Health, With<Player>>,
     cursor_box: Query<Entity, With<CursorBox>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     atlas_layout: Res<SpriteSheetAtlas>,
+    registry: Res<SpeciesRegistry>,
 ) {
     if let Ok(examined_entity) = cursor.get_single() {
         let examined_entity = examined_entity.0;
-        let species = creature_query.get(examined_entity).unwrap();
+        let Ok((species, health, effects, spellbook, shield, flags, is_player)) =
+            creature_query.get(examined_entity)
+        else {
+            return;
+        };
+        let threat = player
+            .get_single()
+            .ok()
+            .filter(|_| !is_player)
+            .map(|player| {
+                let speed = speed.get_flag(flags).ok();
+                ThreatLevel::assess(creature_power(health.hp, spellbook, speed), player.hp)
+            });
+        let name = match threat {
+            Some(threat) => format!(
+                "{}{}[w]",
+                threat.tag(),
+                strip_color_tags(&registry.get(species).name)
+            ),
+            None => registry.get(species).name.clone(),
+        };
+        let mut active_effects: Vec<_> = effects
+            .effects
+            .iter()
+            .filter(|(_, effect)| effect.is_active())
+            .map(|(effect, _)| format!("{:?}", effect))
+            .collect();
+        // HashMaps have no stable order.
+        active_effects.sort();
+        let bulk = registry.get(species).bulk;
+        let hp = match shield {
+            Some(shield) => format!(
+                "[r]HP {}/{}[w] [c]+{}[w]",
+                health.hp, health.max_hp, shield.amount
+            ),
+            None => format!("[r]HP {}/{}[w]", health.hp, health.max_hp),
+        };
+        let hp = match threat {
+            Some(threat) => format!("{} - {}", hp, threat.name()),
+            None => hp,
+        };
+        let status = if active_effects.is_empty() {
+            format!("{} - {:?}", hp, bulk)
+        } else {
+            format!("{} - {:?} - [y]{}[w]", hp, bulk, active_effects.join(", "))
+        };
+        let mut spells: Vec<_> = spellbook
+            .spells
+            .iter()
+            .map(|(caste, spell)| {
+                let axioms: Vec<_> = spell
+                    .axioms
+                    .iter()
+                    .map(|axiom| format!("{:?}", axiom))
+                    .collect();
+                format!("{}: {}", match_soul_with_string(caste), axioms.join(", "))
+            })
+            .collect();
+        spells.sort();
+        let description = if spells.is_empty() {
+            registry.get(species).description.clone()
+        } else {
+            format!(
+                "{}\n\n{}",
+                registry.get(species).description,
+                spells.join("\n")
+            )
+        };
         let cursor_box = cursor_box.single();
         // TODO: Instead of multiple entities, would it be interesting to
         // have these merged into a single string with \n to space them out?
         // This would be good in case there's a ton of "effects flags".
-        let (mut species_name, mut species_description) =
-            (Entity::PLACEHOLDER, Entity::PLACEHOLDER);
+        let (mut species_name, mut species_status, mut species_description) = (
+            Entity::PLACEHOLDER,
+            Entity::PLACEHOLDER,
+            Entity::PLACEHOLDER,
+        );
         commands.entity(cursor_box).despawn_descendants();
         commands.entity(cursor_box).with_children(|parent| {
-            species_name =
-                spawn_split_text(&match_species_with_string(species), parent, &asset_server);
-            species_description = spawn_split_text(
-                &match_species_with_description(species),
-                parent,
-                &asset_server,
-            );
+            species_name = spawn_split_text(&name, parent, &asset_server);
+            species_status = spawn_split_text(&status, parent, &asset_server);
+            species_description = spawn_split_text(&description, parent, &asset_server);
             parent.spawn((
                 ImageNode {
                     image: asset_server.load("spritesheet.png"),
                     texture_atlas: Some(TextureAtlas {
                         layout: atlas_layout.handle.clone(),
-                        index: get_species_sprite(&species),
+                        index: registry.get(species).sprite,
                     }),
                     ..Default::default()
                 },
@@ -148,6 +258,11 @@ pub fn update_cursor_box(
             top: Val::Px(0.5),
             ..default()
         });
+        commands.entity(species_status).insert(Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(2.),
+            ..default()
+        });
         commands.entity(species_description).insert(Node {
             position_type: PositionType::Absolute,
             top: Val::Px(3.5),
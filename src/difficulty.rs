@@ -0,0 +1,370 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{Health, Player, Species},
+    events::HealthChanged,
+    focus::{cycle_focus, focus_confirm, focus_step, Focused},
+    rng::{GameRng, SetSeed},
+    save::RunMode,
+    sets::{ControlState, PlayerInput},
+    species::SpeciesRegistry,
+    text::split_text,
+    ui::{AddMessage, Message},
+};
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameDifficulty>();
+        app.init_resource::<DifficultyMenu>();
+        app.add_event::<SetDifficulty>();
+        app.add_systems(Startup, open_difficulty_menu);
+        app.add_systems(OnEnter(ControlState::DifficultyMenu), spawn_difficulty_menu);
+        app.add_systems(
+            OnExit(ControlState::DifficultyMenu),
+            despawn_difficulty_menu,
+        );
+        app.add_systems(
+            Update,
+            (
+                difficulty_menu_input,
+                update_difficulty_menu.run_if(resource_changed::<DifficultyMenu>),
+            )
+                .chain()
+                .run_if(in_state(ControlState::DifficultyMenu))
+                .in_set(PlayerInput),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DifficultyPreset {
+    Gentle,
+    #[default]
+    Standard,
+    Cruel,
+}
+
+impl DifficultyPreset {
+    const ALL: [DifficultyPreset; 3] = [
+        DifficultyPreset::Gentle,
+        DifficultyPreset::Standard,
+        DifficultyPreset::Cruel,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DifficultyPreset::Gentle => "[l]Gentle[w]",
+            DifficultyPreset::Standard => "[y]Standard[w]",
+            DifficultyPreset::Cruel => "[r]Cruel[w]",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            DifficultyPreset::Gentle => "Frailer foes, softer blows, two souls per draw.",
+            DifficultyPreset::Standard => "The game as it was meant to be played.",
+            DifficultyPreset::Cruel => "Sturdier foes, harder blows, a weaker body.",
+        }
+    }
+}
+
+/// The numbers the rules of the game are tuned with.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct GameDifficulty {
+    pub preset: DifficultyPreset,
+    /// Enemies have this percentage of the max HP of their species.
+    pub enemy_hp_percent: usize,
+    /// Damage dealt to the player, in percent of the usual amount.
+    pub damage_taken_percent: usize,
+    /// How many souls drawing gives, if there is room for them.
+    pub souls_per_draw: usize,
+    /// Added to the max HP of the player's species.
+    pub player_max_hp: isize,
+    /// How many contingencies can trigger in a single turn, before the rest fizzle.
+    /// This stops spells which trigger each other from looping forever.
+    pub contingency_limit: usize,
+    /// Whether a spell cast from the Soul Wheel which does nothing gives its soul back.
+    #[serde(default)]
+    pub refund_fizzles: bool,
+}
+
+impl Default for GameDifficulty {
+    fn default() -> Self {
+        Self::from(DifficultyPreset::default())
+    }
+}
+
+impl From<DifficultyPreset> for GameDifficulty {
+    fn from(preset: DifficultyPreset) -> Self {
+        match preset {
+            DifficultyPreset::Gentle => Self {
+                preset,
+                enemy_hp_percent: 75,
+                damage_taken_percent: 75,
+                souls_per_draw: 2,
+                player_max_hp: 2,
+                contingency_limit: 100,
+                refund_fizzles: true,
+            },
+            DifficultyPreset::Standard => Self {
+                preset,
+                enemy_hp_percent: 100,
+                damage_taken_percent: 100,
+                souls_per_draw: 1,
+                player_max_hp: 0,
+                contingency_limit: 100,
+                refund_fizzles: false,
+            },
+            DifficultyPreset::Cruel => Self {
+                preset,
+                enemy_hp_percent: 150,
+                damage_taken_percent: 150,
+                souls_per_draw: 1,
+                player_max_hp: -2,
+                contingency_limit: 50,
+                refund_fizzles: false,
+            },
+        }
+    }
+}
+
+impl GameDifficulty {
+    /// The max HP of an enemy whose species has this much.
+    pub fn enemy_max_hp(&self, max_hp: usize) -> usize {
+        (max_hp * self.enemy_hp_percent).div_ceil(100).max(1)
+    }
+
+    /// The max HP of the player, whose species has this much.
+    pub fn player_max_hp(&self, max_hp: usize) -> usize {
+        (max_hp as isize + self.player_max_hp).max(1) as usize
+    }
+
+    /// How much damage a blow of this strength deals to the player.
+    pub fn damage_taken(&self, damage: isize) -> isize {
+        (damage * self.damage_taken_percent as isize)
+            .div_euclid(100)
+            .max(1)
+    }
+}
+
+/// Switch to another difficulty. Creatures already around have their health rescaled,
+/// so this can happen at any point of a run.
+#[derive(Event)]
+pub struct SetDifficulty {
+    pub preset: DifficultyPreset,
+}
+
+pub fn set_difficulty(
+    mut events: EventReader<SetDifficulty>,
+    mut difficulty: ResMut<GameDifficulty>,
+    mut creatures: Query<(Entity, &mut Health, &Species, Has<Player>)>,
+    registry: Res<SpeciesRegistry>,
+    mut text: EventWriter<AddMessage>,
+    mut health_changed: EventWriter<HealthChanged>,
+) {
+    for event in events.read() {
+        let (old, new) = (*difficulty, GameDifficulty::from(event.preset));
+        if old == new {
+            continue;
+        }
+        for (entity, mut health, species, is_player) in creatures.iter_mut() {
+            if !is_player && !registry.get(species).is_hostile() {
+                continue;
+            }
+            let max_hp = if is_player {
+                (health.max_hp as isize - old.player_max_hp + new.player_max_hp).max(1) as usize
+            } else {
+                (health.max_hp * new.enemy_hp_percent)
+                    .div_ceil(old.enemy_hp_percent)
+                    .max(1)
+            };
+            // Wounds are kept as a proportion, rounded in the creature's favour.
+            health.hp = (health.hp * max_hp)
+                .div_ceil(health.max_hp.max(1))
+                .min(max_hp);
+            health.max_hp = max_hp;
+            health_changed.send(HealthChanged { entity });
+        }
+        *difficulty = new;
+        text.send(AddMessage {
+            message: Message::DifficultySet(event.preset),
+        });
+    }
+}
+
+/// Which preset is highlighted in the difficulty menu.
+#[derive(Resource, Default)]
+pub struct DifficultyMenu {
+    pub selected: usize,
+    /// Digits typed in to start over with another seed. Left empty, the run goes on as it is.
+    pub seed: String,
+    pub run_mode: RunMode,
+}
+
+/// Seeds are u64s, which never take more digits than this.
+const MAX_SEED_DIGITS: usize = 20;
+
+const DIGIT_KEYS: [(KeyCode, KeyCode, char); 10] = [
+    (KeyCode::Digit0, KeyCode::Numpad0, '0'),
+    (KeyCode::Digit1, KeyCode::Numpad1, '1'),
+    (KeyCode::Digit2, KeyCode::Numpad2, '2'),
+    (KeyCode::Digit3, KeyCode::Numpad3, '3'),
+    (KeyCode::Digit4, KeyCode::Numpad4, '4'),
+    (KeyCode::Digit5, KeyCode::Numpad5, '5'),
+    (KeyCode::Digit6, KeyCode::Numpad6, '6'),
+    (KeyCode::Digit7, KeyCode::Numpad7, '7'),
+    (KeyCode::Digit8, KeyCode::Numpad8, '8'),
+    (KeyCode::Digit9, KeyCode::Numpad9, '9'),
+];
+
+#[derive(Component)]
+pub struct DifficultyMenuPanel;
+
+/// The difficulty is picked before anything else.
+fn open_difficulty_menu(
+    difficulty: Res<GameDifficulty>,
+    run_mode: Res<RunMode>,
+    mut menu: ResMut<DifficultyMenu>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    menu.selected = DifficultyPreset::ALL
+        .iter()
+        .position(|preset| *preset == difficulty.preset)
+        .unwrap_or(0);
+    menu.seed.clear();
+    menu.run_mode = *run_mode;
+    next_state.set(ControlState::DifficultyMenu);
+}
+
+fn spawn_difficulty_menu(mut commands: Commands, mut menu: ResMut<DifficultyMenu>) {
+    commands.spawn((
+        DifficultyMenuPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(20.),
+            top: Val::Percent(15.),
+            padding: UiRect::all(Val::Px(1.)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.9)),
+    ));
+    // Force the first draw.
+    menu.set_changed();
+}
+
+fn despawn_difficulty_menu(
+    mut commands: Commands,
+    panel: Query<Entity, With<DifficultyMenuPanel>>,
+) {
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+/// W/S, the arrow keys or Tab move through the presets, Enter picks the highlighted one.
+/// Digits and Backspace type in a seed, which starts a new run with it.
+/// M switches between the Standard and Permadeath run modes.
+fn difficulty_menu_input(
+    input: Res<ButtonInput<KeyCode>>,
+    mut menu: ResMut<DifficultyMenu>,
+    rng: Res<GameRng>,
+    mut run_mode: ResMut<RunMode>,
+    mut set: EventWriter<SetDifficulty>,
+    mut set_seed: EventWriter<SetSeed>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    let step = focus_step(&input);
+    if step != 0 {
+        menu.selected = cycle_focus(menu.selected, step, DifficultyPreset::ALL.len());
+    }
+    for (key, numpad_key, digit) in DIGIT_KEYS {
+        if (input.just_pressed(key) || input.just_pressed(numpad_key))
+            && menu.seed.len() < MAX_SEED_DIGITS
+        {
+            menu.seed.push(digit);
+        }
+    }
+    if input.just_pressed(KeyCode::Backspace) {
+        menu.seed.pop();
+    }
+    if input.just_pressed(KeyCode::KeyM) {
+        menu.run_mode = match menu.run_mode {
+            RunMode::Standard => RunMode::Permadeath,
+            RunMode::Permadeath => RunMode::Standard,
+        };
+    }
+    if focus_confirm(&input) {
+        // Too large to be a u64, or the current one: nothing to start over with.
+        if let Ok(seed) = menu.seed.parse::<u64>() {
+            if seed != rng.seed() {
+                set_seed.send(SetSeed { seed: Some(seed) });
+            }
+        }
+        set.send(SetDifficulty {
+            preset: DifficultyPreset::ALL[menu.selected],
+        });
+        *run_mode = menu.run_mode;
+        next_state.set(ControlState::Player);
+    }
+}
+
+fn update_difficulty_menu(
+    menu: Res<DifficultyMenu>,
+    rng: Res<GameRng>,
+    panel: Query<Entity, With<DifficultyMenuPanel>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let Ok(panel) = panel.get_single() else {
+        return;
+    };
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|parent| {
+        let lines = std::iter::once((None, "Choose a difficulty:".to_owned())).chain(
+            DifficultyPreset::ALL.iter().enumerate().map(|(i, preset)| {
+                (
+                    Some(i),
+                    format!(
+                        "{}{}[w] - {}",
+                        if i == menu.selected { "[y]> " } else { "[w]" },
+                        preset.name(),
+                        preset.description(),
+                    ),
+                )
+            }),
+        );
+        let seed = if menu.seed.is_empty() {
+            format!(
+                "Seed: {} [d](type digits to start over with another)[w]",
+                rng.seed()
+            )
+        } else {
+            format!("Seed: [y]{}_[w]", menu.seed)
+        };
+        let run_mode = format!(
+            "Run mode: {} [d](M to switch)[w]",
+            menu.run_mode.description()
+        );
+        let lines = lines.chain([(None, seed), (None, run_mode)]);
+        for (i, line) in lines {
+            let mut line_entity = parent.spawn((Text::default(), font.clone()));
+            line_entity.with_children(|line_parent| {
+                for (section, color) in split_text(&line) {
+                    line_parent.spawn((TextSpan::new(section), font.clone(), color));
+                }
+            });
+            if i == Some(menu.selected) {
+                line_entity.insert(Focused);
+            }
+        }
+    });
+}
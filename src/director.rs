@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{Player, Spellbook},
+    events::{RespawnPlayer, SummonCreature, TurnManager},
+    map::{FaithsEnd, Map, Position},
+    mapgen::LevelGenConfig,
+    rng::GameRng,
+    sets::PlayerInput,
+    settings::Settings,
+    spells::{Axiom, SOUL_POWER},
+    ui::{AddMessage, Message},
+    vision::VisibilityMap,
+    OrdDir,
+};
+
+pub struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnDirector>();
+        app.add_event::<SetDirectorIntensity>();
+        app.add_systems(
+            Update,
+            follow_director_setting
+                .run_if(resource_changed::<Settings>)
+                .in_set(PlayerInput),
+        );
+    }
+}
+
+/// How many turns pass between each time the director considers repopulating a cage.
+const DIRECTOR_INTERVAL: usize = 25;
+/// Wanderers never appear closer to the player than this.
+const DIRECTOR_MIN_DISTANCE: i32 = 6;
+/// How far apart the creatures of a single wave can be placed.
+const WAVE_RADIUS: i32 = 2;
+
+/// How often cleared places are repopulated.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DirectorIntensity {
+    Off,
+    #[default]
+    Low,
+    High,
+}
+
+impl DirectorIntensity {
+    pub fn next(&self) -> Self {
+        match self {
+            DirectorIntensity::Off => DirectorIntensity::Low,
+            DirectorIntensity::Low => DirectorIntensity::High,
+            DirectorIntensity::High => DirectorIntensity::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DirectorIntensity::Off => "[l]off[w]",
+            DirectorIntensity::Low => "[y]low[w]",
+            DirectorIntensity::High => "[r]high[w]",
+        }
+    }
+
+    /// The chance, out of 100, of repopulating a cage every DIRECTOR_INTERVAL turns.
+    fn chance(&self) -> u32 {
+        match self {
+            DirectorIntensity::Off => 0,
+            DirectorIntensity::Low => 30,
+            DirectorIntensity::High => 70,
+        }
+    }
+
+    /// The most creatures which can be placed at once.
+    fn max_wave(&self) -> usize {
+        match self {
+            DirectorIntensity::Off => 0,
+            DirectorIntensity::Low => 2,
+            DirectorIntensity::High => 4,
+        }
+    }
+}
+
+/// Occasionally sends wandering creatures back into places the player has
+/// already left, so that backtracking isn't always empty.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct SpawnDirector {
+    pub intensity: DirectorIntensity,
+    last_turn: usize,
+}
+
+/// Change how often wanderers come back, see DirectorIntensity.
+#[derive(Event)]
+pub struct SetDirectorIntensity {
+    pub intensity: DirectorIntensity,
+}
+
+pub fn set_director_intensity(
+    mut events: EventReader<SetDirectorIntensity>,
+    mut director: ResMut<SpawnDirector>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        if director.intensity == event.intensity {
+            continue;
+        }
+        director.intensity = event.intensity;
+        text.send(AddMessage {
+            message: Message::DirectorIntensitySet(event.intensity),
+        });
+    }
+}
+
+/// The intensity picked in the Settings carries over to every run.
+fn follow_director_setting(
+    settings: Res<Settings>,
+    director: Res<SpawnDirector>,
+    mut set: EventWriter<SetDirectorIntensity>,
+) {
+    if settings.director != director.intensity {
+        set.send(SetDirectorIntensity {
+            intensity: settings.director,
+        });
+    }
+}
+
+/// Once every DIRECTOR_INTERVAL turns, maybe repopulate a spot the player has
+/// explored, then left: out of sight, and at least DIRECTOR_MIN_DISTANCE away.
+/// Deeper cages and stronger spellbooks bring larger waves.
+// NOTE: Wanderers are placed on floor tiles, which have no cage address. They are
+// neither Sleeping nor Awake, and don't need to be killed to open the next cage.
+pub fn direct_spawns(
+    mut respawn: EventReader<RespawnPlayer>,
+    turn_manager: Res<TurnManager>,
+    mut director: ResMut<SpawnDirector>,
+    faiths_end: Res<FaithsEnd>,
+    map: Res<Map>,
+    vision: Res<VisibilityMap>,
+    config: Res<LevelGenConfig>,
+    player: Query<(&Position, &Spellbook), With<Player>>,
+    mut rng: ResMut<GameRng>,
+    mut summon: EventWriter<SummonCreature>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if respawn.read().count() > 0 {
+        director.last_turn = turn_manager.turn_count;
+        return;
+    }
+    let turn = turn_manager.turn_count;
+    if turn == director.last_turn || !turn.is_multiple_of(DIRECTOR_INTERVAL) {
+        return;
+    }
+    director.last_turn = turn;
+    let Ok((player_pos, spellbook)) = player.get_single() else {
+        return;
+    };
+    let distance = |a: &Position, b: &Position| (a.x - b.x).abs().max((a.y - b.y).abs());
+    // Sorted, so the GameRng picks the same tile every time.
+    let mut cleared: Vec<Position> = vision
+        .remembered
+        .iter()
+        .filter(|tile| !vision.is_visible(tile) && map.is_passable(tile.x, tile.y))
+        .filter(|tile| distance(tile, player_pos) >= DIRECTOR_MIN_DISTANCE)
+        .copied()
+        .collect();
+    cleared.sort_by_key(|tile| (tile.x, tile.y));
+    if cleared.is_empty() || rng.gen_range(0..100) >= director.intensity.chance() {
+        return;
+    }
+    let centre = *cleared.choose(&mut *rng).unwrap();
+    // The whole wave arrives together.
+    let free_tiles: Vec<Position> = cleared
+        .into_iter()
+        .filter(|tile| distance(tile, &centre) <= WAVE_RADIUS)
+        .collect();
+    let strength: usize = spellbook
+        .spells
+        .values()
+        .flat_map(|spell| spell.axioms.iter().map(Axiom::cost))
+        .sum::<usize>()
+        / SOUL_POWER;
+    let wave = (1 + faiths_end.current_cage / 2 + strength / 4).min(director.intensity.max_wave());
+    let mut placed = 0;
+    for position in free_tiles.choose_multiple(&mut *rng, wave) {
+        let Ok((species, _)) = config
+            .species
+            .choose_weighted(&mut *rng, |(_, weight)| *weight)
+        else {
+            continue;
+        };
+        summon.send(SummonCreature {
+            position: *position,
+            species: *species,
+            momentum: OrdDir::Down,
+            summoner_tile: *position,
+            summoner: None,
+            spellbook: None,
+            projectile: None,
+            owner: None,
+        });
+        placed += 1;
+    }
+    if placed > 0 {
+        text.send(AddMessage {
+            message: Message::WanderersReturn,
+        });
+    }
+}
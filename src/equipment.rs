@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{
+        CreatureFlags, Health, ItemPickup, MeleeBonus, Player, RealityShield, Species, Speed,
+    },
+    events::{EndTurn, HealthChanged, RemoveCreature, SummonCreature},
+    map::{Map, Position},
+    ui::{AddMessage, Message},
+    OrdDir,
+};
+
+/// Where an item is worn. Each slot holds a single item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Weapon,
+    Armor,
+    Trinket,
+}
+
+/// A persistent bonus granted by an equipped item.
+#[derive(Clone, Copy, Debug)]
+pub enum Modifier {
+    /// Extra damage dealt by melee attacks.
+    MeleeDamage(isize),
+    MaxHp(usize),
+    /// Damage taken is reduced by this much, down to a minimum of 1.
+    RealityShield(usize),
+    /// Extra actions per turn.
+    Speed(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Item {
+    SerratedEdge,
+    ChitinPlate,
+    NacreWard,
+    QuicksilverCharm,
+}
+
+impl Item {
+    pub fn slot(&self) -> EquipmentSlot {
+        match self {
+            Item::SerratedEdge => EquipmentSlot::Weapon,
+            Item::ChitinPlate | Item::NacreWard => EquipmentSlot::Armor,
+            Item::QuicksilverCharm => EquipmentSlot::Trinket,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Item::SerratedEdge => "[r]Serrated Edge[w]",
+            Item::ChitinPlate => "[g]Chitin Plate[w]",
+            Item::NacreWard => "[a]Nacre Ward[w]",
+            Item::QuicksilverCharm => "[y]Quicksilver Charm[w]",
+        }
+    }
+
+    pub fn modifiers(&self) -> Vec<Modifier> {
+        match self {
+            Item::SerratedEdge => vec![Modifier::MeleeDamage(1)],
+            Item::ChitinPlate => vec![Modifier::MaxHp(3)],
+            Item::NacreWard => vec![Modifier::RealityShield(1)],
+            Item::QuicksilverCharm => vec![Modifier::Speed(1)],
+        }
+    }
+
+    /// The pickup left on the ground when this item is replaced.
+    pub fn species(&self) -> Species {
+        match self {
+            Item::SerratedEdge => Species::SerratedEdge,
+            Item::ChitinPlate => Species::ChitinPlate,
+            Item::NacreWard => Species::NacreWard,
+            Item::QuicksilverCharm => Species::QuicksilverCharm,
+        }
+    }
+}
+
+/// The items worn by a creature. Their modifiers are added to its effects flags
+/// by apply_equipment, whenever this changes.
+#[derive(Component, Default, Clone, Serialize, Deserialize)]
+pub struct Equipment {
+    pub weapon: Option<Item>,
+    pub armor: Option<Item>,
+    pub trinket: Option<Item>,
+    /// The max HP currently granted by items, so it can be taken back
+    /// when they are removed.
+    pub applied_max_hp: usize,
+    /// The other bonuses currently granted by items, on top of those from
+    /// other sources. Not saved, as the effects flags they were added to aren't.
+    #[serde(skip)]
+    pub applied_melee: isize,
+    #[serde(skip)]
+    pub applied_shield: usize,
+    #[serde(skip)]
+    pub applied_speed: usize,
+}
+
+impl Equipment {
+    pub fn slot_mut(&mut self, slot: EquipmentSlot) -> &mut Option<Item> {
+        match slot {
+            EquipmentSlot::Weapon => &mut self.weapon,
+            EquipmentSlot::Armor => &mut self.armor,
+            EquipmentSlot::Trinket => &mut self.trinket,
+        }
+    }
+
+    /// Put on this item, returning whatever was in its slot before.
+    pub fn equip(&mut self, item: Item) -> Option<Item> {
+        self.slot_mut(item.slot()).replace(item)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = Item> {
+        [self.weapon, self.armor, self.trinket]
+            .into_iter()
+            .flatten()
+    }
+
+    pub fn modifiers(&self) -> impl Iterator<Item = Modifier> {
+        self.items().flat_map(|item| item.modifiers())
+    }
+}
+
+/// Re-apply the modifiers of all worn items to the effects flags, on top of
+/// whatever other sources placed there.
+pub fn apply_equipment(
+    mut creatures: Query<(Entity, &mut Equipment, &mut Health, &CreatureFlags), Changed<Equipment>>,
+    bonuses: Query<(Option<&MeleeBonus>, Option<&RealityShield>, Option<&Speed>)>,
+    mut health_changed: EventWriter<HealthChanged>,
+    mut commands: Commands,
+) {
+    for (entity, mut equipment, mut health, flags) in creatures.iter_mut() {
+        let (mut melee, mut max_hp, mut shield, mut speed) = (0, 0, 0, 0);
+        for modifier in equipment.modifiers() {
+            match modifier {
+                Modifier::MeleeDamage(amount) => melee += amount,
+                Modifier::MaxHp(amount) => max_hp += amount,
+                Modifier::RealityShield(amount) => shield += amount,
+                Modifier::Speed(amount) => speed += amount,
+            }
+        }
+        let (melee_bonus, reality_shield, current_speed) = bonuses
+            .get(flags.effects_flags)
+            .unwrap_or((None, None, None));
+        let mut effects_flags = commands.entity(flags.effects_flags);
+        if melee != equipment.applied_melee {
+            let base = melee_bonus.map_or(0, |bonus| bonus.bonus_damage) - equipment.applied_melee;
+            if base + melee != 0 {
+                effects_flags.insert(MeleeBonus {
+                    bonus_damage: base + melee,
+                });
+            } else {
+                effects_flags.remove::<MeleeBonus>();
+            }
+            equipment.applied_melee = melee;
+        }
+        if shield != equipment.applied_shield {
+            let base = reality_shield
+                .map_or(0, |shield| shield.amount)
+                .saturating_sub(equipment.applied_shield);
+            if base + shield != 0 {
+                effects_flags.insert(RealityShield {
+                    amount: base + shield,
+                });
+            } else {
+                effects_flags.remove::<RealityShield>();
+            }
+            equipment.applied_shield = shield;
+        }
+        // NOTE: Slowed creatures stay slowed, whatever they wear.
+        if speed != equipment.applied_speed && !matches!(current_speed, Some(Speed::Slow { .. })) {
+            let base = match current_speed {
+                Some(Speed::Fast { actions_per_turn }) => actions_per_turn
+                    .saturating_sub(equipment.applied_speed)
+                    .max(1),
+                _ => 1,
+            };
+            if base + speed > 1 {
+                effects_flags.insert(Speed::Fast {
+                    actions_per_turn: base + speed,
+                });
+            } else {
+                effects_flags.remove::<Speed>();
+            }
+            equipment.applied_speed = speed;
+        }
+        if max_hp != equipment.applied_max_hp {
+            let applied = equipment.applied_max_hp;
+            health.max_hp = (health.max_hp + max_hp).saturating_sub(applied).max(1);
+            if max_hp > applied {
+                health.hp += max_hp - applied;
+            }
+            health.hp = health.hp.min(health.max_hp);
+            equipment.applied_max_hp = max_hp;
+            health_changed.send(HealthChanged { entity });
+        }
+    }
+}
+
+/// Put on this item, leaving whatever it replaces on the ground at `position`.
+pub fn equip_or_drop(
+    equipment: &mut Equipment,
+    item: Item,
+    position: Position,
+    summon: &mut EventWriter<SummonCreature>,
+    text: &mut EventWriter<AddMessage>,
+) {
+    let Some(old) = equipment.equip(item) else {
+        return;
+    };
+    summon.send(SummonCreature {
+        position,
+        species: old.species(),
+        momentum: OrdDir::Down,
+        summoner_tile: position,
+        summoner: None,
+        spellbook: None,
+        projectile: None,
+        owner: None,
+    });
+    text.send(AddMessage {
+        message: Message::ItemDropped(old),
+    });
+}
+
+/// Once per turn, put on the items the player has just stepped onto.
+// NOTE: Not those left under its feet by the swap itself, or waiting in place
+// would trade the same two items back and forth.
+pub fn pick_up_items(
+    mut events: EventReader<EndTurn>,
+    mut player: Query<(&Position, &mut Equipment), With<Player>>,
+    creatures: Query<&CreatureFlags>,
+    pickups: Query<&ItemPickup>,
+    map: Res<Map>,
+    mut last_position: Local<Option<Position>>,
+    mut remove: EventWriter<RemoveCreature>,
+    mut summon: EventWriter<SummonCreature>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Ok((player_position, mut equipment)) = player.get_single_mut() else {
+        return;
+    };
+    if last_position.replace(*player_position) == Some(*player_position) {
+        return;
+    }
+    for entity in map.intangible_at(player_position) {
+        let Ok(flags) = creatures.get(*entity) else {
+            continue;
+        };
+        let Ok(pickup) = pickups.get(flags.species_flags) else {
+            continue;
+        };
+        remove.send(RemoveCreature {
+            entity: *entity,
+            culprit: None,
+        });
+        text.send(AddMessage {
+            message: Message::ItemEquipped(pickup.item),
+        });
+        equip_or_drop(
+            &mut equipment,
+            pickup.item,
+            *player_position,
+            &mut summon,
+            &mut text,
+        );
+    }
+}
@@ -2,25 +2,36 @@ use std::{cmp::min, f32::consts::PI};
 
 use bevy::{
     prelude::*,
+    sprite::Anchor,
     utils::{HashMap, HashSet},
 };
-use rand::{seq::IteratorRandom, thread_rng};
+use rand::{seq::IteratorRandom, Rng};
 
 use crate::{
+    ai::{can_see, AiState, Noise, Patrol, ALERT_SIGHT, MELEE_NOISE, PATROL_SIGHT},
+    audio::{PlaySound, Sound},
     creature::{
-        get_soul_sprite, get_species_spellbook, get_species_sprite, is_naturally_intangible, Awake,
-        Creature, CreatureFlags, DesignatedForRemoval, Dizzy, Door, EffectDuration, FlagEntity,
-        Fragile, Health, HealthIndicator, Hunt, Immobile, Intangible, Invincible, Magnetic,
-        Magnetized, Meleeproof, NoDropSoul, Player, PotencyAndStacks, Random, Sleeping, Soul,
-        Species, Speed, Spellbook, Spellproof, Stab, StatusEffect, StatusEffectsList, Summoned,
-        Wall,
+        get_soul_sprite, Awake, Blessed, BodyPart, Cowardly, Creature, CreatureFlags, DamageKind,
+        DeathEffect, DesignatedForRemoval, Devours, Dizzy, Door, EffectDuration, Ephemeral, Facing,
+        FlagEntity, FlagPriority, FlagQuery, Footprint, Fragile, Health, Hidden, Hunt, Immobile,
+        Intangible, Invincible, Magnetic, Magnetized, MeleeBonus, Meleeproof, Mired, MovementStyle,
+        NoDropSoul, Owner, Player, PotencyAndStacks, Projectile, Pushable, Random, RealityShield,
+        ShieldBuffer, Sleeping, Soul, Species, Speed, SpellReflect, Spellbook, Stab, StatusEffect,
+        StatusEffectsList, Summoned, Tags, Wall, WeakPoints,
     },
+    difficulty::GameDifficulty,
+    equipment::Equipment,
     graphics::{
-        get_effect_sprite, EffectSequence, EffectType, MagicEffect, MagicVfx, PlaceMagicVfx,
-        Screenshake, SlideAnimation, SpriteSheetAtlas,
+        get_effect_sprite, AnimationQueue, AnimationStep, EffectSequence, EffectType, HeldAt,
+        MagicEffect, MagicVfx, PlaceMagicVfx, Screenshake, SlideAnimation, SpriteSheetAtlas,
+        VisualLayer,
     },
-    map::{spawn_cage, FaithsEnd, Map, Position},
-    spells::{walk_grid, Axiom, CastSpell, TriggerContingency},
+    map::{occupied_tiles, spawn_cage, FaithsEnd, Map, Position},
+    rng::GameRng,
+    shrine::bless_spell,
+    species::{DeathEffects, SpeciesRegistry},
+    spells::{walk_grid, Axiom, CastSpell, SpellFizzled, TriggerContingency},
+    stats::RunStats,
     ui::{AddMessage, AnnounceGameOver, InvalidAction, Message, SoulSlot},
     OrdDir, TILE_SIZE,
 };
@@ -35,7 +46,9 @@ impl Plugin for EventPlugin {
         app.add_event::<TransformCreature>();
         app.add_event::<SteppedOnTile>();
         app.add_event::<CreatureCollision>();
+        app.add_event::<Deflect>();
         app.add_event::<AlterMomentum>();
+        app.add_event::<TurnFacing>();
         app.add_event::<DamageOrHealCreature>();
         app.add_event::<OpenCloseDoor>();
         app.add_event::<RemoveCreature>();
@@ -46,6 +59,7 @@ impl Plugin for EventPlugin {
         app.add_event::<DrawSoul>();
         app.add_event::<UseWheelSoul>();
         app.add_event::<MagnetFollow>();
+        app.add_event::<PassTurn>();
         app.init_resource::<Events<CreatureStep>>();
         app.init_resource::<Events<RespawnCage>>();
         app.insert_resource(TurnManager {
@@ -66,6 +80,9 @@ pub struct TurnManager {
 #[derive(Resource)]
 pub struct SoulWheel {
     pub souls: [Option<Soul>; 8],
+    /// Slots which only souls of one caste may fill. Spells cast from them cost
+    /// nothing, and their soul goes back to the draw pile.
+    pub locks: [Option<Soul>; 8],
     pub draw_pile: HashMap<Soul, usize>,
     pub discard_pile: HashMap<Soul, usize>,
 }
@@ -74,6 +91,7 @@ impl FromWorld for SoulWheel {
     fn from_world(_world: &mut World) -> Self {
         let mut soul_wheel = Self {
             souls: [None; 8],
+            locks: [None; 8],
             draw_pile: HashMap::new(),
             discard_pile: HashMap::new(),
         };
@@ -104,10 +122,36 @@ impl SoulWheel {
         output
     }
 
-    fn draw_random_caste(&mut self) -> Option<Soul> {
-        let possible_castes = self.castes_with_non_zero_souls();
-        let mut rng = thread_rng();
-        if let Some(drawn_soul) = possible_castes.iter().choose(&mut rng) {
+    /// Whether `soul` may be placed in the slot at `index`.
+    pub fn accepts(&self, index: usize, soul: Soul) -> bool {
+        self.locks[index].is_none_or(|caste| caste == soul)
+    }
+
+    /// The empty slot a newly drawn `soul` goes into. A slot locked to its
+    /// caste is preferred, then the first unlocked one.
+    fn slot_for(&self, soul: Soul) -> Option<usize> {
+        let empty = || (0..self.souls.len()).filter(|index| self.souls[*index].is_none());
+        empty()
+            .find(|index| self.locks[*index] == Some(soul))
+            .or_else(|| empty().find(|index| self.locks[*index].is_none()))
+    }
+
+    /// Lock the last unlocked slot to `caste`, returning its index.
+    pub fn lock_slot(&mut self, caste: Soul) -> Option<usize> {
+        let index = (0..self.locks.len())
+            .rev()
+            .find(|index| self.locks[*index].is_none())?;
+        self.locks[index] = Some(caste);
+        Some(index)
+    }
+
+    fn draw_random_caste(&mut self, rng: &mut impl Rng) -> Option<Soul> {
+        let possible_castes: HashSet<Soul> = self
+            .castes_with_non_zero_souls()
+            .into_iter()
+            .filter(|caste| self.slot_for(*caste).is_some())
+            .collect();
+        if let Some(drawn_soul) = possible_castes.iter().choose(rng) {
             self.draw_pile
                 .entry(*drawn_soul)
                 .and_modify(|count| *count -= 1);
@@ -125,25 +169,23 @@ pub struct DrawSoul {
 pub fn draw_soul(
     mut events: EventReader<DrawSoul>,
     mut soul_wheel: ResMut<SoulWheel>,
+    mut rng: ResMut<GameRng>,
     mut ui_soul_slots: Query<(&mut ImageNode, &SoulSlot)>,
     mut turn_manager: ResMut<TurnManager>,
     mut text: EventWriter<AddMessage>,
+    difficulty: Res<GameDifficulty>,
 ) {
     for event in events.read() {
-        for _i in 0..event.amount {
-            let mut index_to_fill = None;
-
-            // Find an empty slot in the Soul Wheel.
-            for (index, soul_slot) in soul_wheel.souls.iter().enumerate() {
-                if soul_slot.is_none() {
-                    index_to_fill = Some(index);
-                    break;
-                }
-            }
-
-            if let Some(index) = index_to_fill {
-                // Draw a new soul from the deck.
-                if let Some(new_soul) = soul_wheel.draw_random_caste() {
+        for i in 0..event.amount * difficulty.souls_per_draw {
+            // Souls drawn thanks to the difficulty are a bonus, which is skipped
+            // if there is no room for it.
+            let is_bonus = i >= event.amount;
+
+            // Is there an empty slot in the Soul Wheel?
+            if soul_wheel.souls.iter().any(|soul_slot| soul_slot.is_none()) {
+                // Draw a new soul from the deck, among those with room left for them.
+                if let Some(new_soul) = soul_wheel.draw_random_caste(&mut *rng) {
+                    let index = soul_wheel.slot_for(new_soul).unwrap();
                     soul_wheel.souls[index] = Some(new_soul);
                     // Reflect this new soul in the UI wheel.
                     for (mut ui_slot_node, ui_slot_marker) in ui_soul_slots.iter_mut() {
@@ -152,14 +194,14 @@ pub fn draw_soul(
                                 get_soul_sprite(&new_soul);
                         }
                     }
-                } else {
+                } else if !is_bonus {
                     // There is nothing left in the draw pile!
                     text.send(AddMessage {
                         message: Message::InvalidAction(InvalidAction::NoSoulsInPile),
                     });
                     turn_manager.action_this_turn = PlayerAction::Invalid;
                 }
-            } else {
+            } else if !is_bonus {
                 // There is no empty space in the Wheel!
                 text.send(AddMessage {
                     message: Message::InvalidAction(InvalidAction::WheelFull),
@@ -173,6 +215,8 @@ pub fn draw_soul(
 #[derive(Event)]
 pub struct UseWheelSoul {
     pub index: usize,
+    /// The tile picked in ControlState::Targeting, for spells with Axiom::CursorTarget.
+    pub target: Option<Position>,
 }
 
 pub fn use_wheel_soul(
@@ -181,22 +225,56 @@ pub fn use_wheel_soul(
     mut spell: EventWriter<CastSpell>,
     mut ui_soul_slots: Query<(&mut ImageNode, &SoulSlot)>,
     mut turn_manager: ResMut<TurnManager>,
-    player: Query<(Entity, &Spellbook), With<Player>>,
+    player: Query<(Entity, &Spellbook, Option<&Blessed>), With<Player>>,
     mut text: EventWriter<AddMessage>,
 ) {
     for event in events.read() {
         let mut newly_discarded = None;
-        if let Some(soul) = soul_wheel.souls.get(event.index).unwrap() {
+        if let Some(soul) = *soul_wheel.souls.get(event.index).unwrap() {
+            let (player_entity, spellbook, blessed) = player.get_single().unwrap();
+            let cast = spellbook.spells.get(&soul).unwrap().clone();
+            let is_locked = soul_wheel.locks[event.index] == Some(soul);
+            // Long spells burn extra souls of the same caste, spent ones first.
+            // Locked slots cast for free.
+            let extra_cost = if is_locked { 0 } else { cast.soul_cost() - 1 };
+            let spent = soul_wheel.discard_pile.get(&soul).copied().unwrap_or(0);
+            let unspent = soul_wheel.draw_pile.get(&soul).copied().unwrap_or(0);
+            if spent + unspent < extra_cost {
+                text.send(AddMessage {
+                    message: Message::InvalidAction(InvalidAction::NotEnoughSouls(
+                        soul, extra_cost,
+                    )),
+                });
+                turn_manager.action_this_turn = PlayerAction::Invalid;
+                continue;
+            }
+            let from_discard = extra_cost.min(spent);
+            if let Some(amount) = soul_wheel.discard_pile.get_mut(&soul) {
+                *amount -= from_discard;
+            }
+            if let Some(amount) = soul_wheel.draw_pile.get_mut(&soul) {
+                *amount -= extra_cost - from_discard;
+            }
             // Cast the spell corresponding to this soul type.
-            let (player_entity, spellbook) = player.get_single().unwrap();
             spell.send(CastSpell {
                 caster: player_entity,
-                spell: spellbook.spells.get(soul).unwrap().clone(),
+                // Blessings change the spell, but not what it costs.
+                spell: match blessed {
+                    Some(blessed) => bless_spell(&cast, blessed.blessing),
+                    None => cast,
+                },
                 starting_step: 0,
-                soul_caste: *soul,
+                soul_caste: soul,
+                target: event.target,
+                from_wheel: true,
+                from_locked_slot: is_locked,
             });
-            // Discard the soul into the discard pile.
-            newly_discarded = Some(*soul);
+            // Discard the soul into the discard pile, or the draw pile from a locked slot.
+            if is_locked {
+                *soul_wheel.draw_pile.entry(soul).or_insert(0) += 1;
+            } else {
+                newly_discarded = Some(soul);
+            }
             // Empty this soul slot.
             soul_wheel.souls[event.index] = None;
             // Update the UI accordingly.
@@ -227,10 +305,62 @@ pub fn use_wheel_soul(
     }
 }
 
+/// Tell the player their spell did nothing, and give back the soul it was cast
+/// with if the GameDifficulty allows it.
+pub fn refund_fizzled_spells(
+    mut events: EventReader<SpellFizzled>,
+    player: Query<&Position, With<Player>>,
+    difficulty: Res<GameDifficulty>,
+    mut soul_wheel: ResMut<SoulWheel>,
+    mut magic_vfx: EventWriter<PlaceMagicVfx>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        // Spells which were not cast from the Soul Wheel fizzle silently.
+        if !event.from_wheel {
+            continue;
+        }
+        let Ok(position) = player.get(event.caster) else {
+            continue;
+        };
+        // A locked slot already returned its soul to the draw pile.
+        let refunded = difficulty.refund_fizzles
+            && !event.from_locked_slot
+            && soul_wheel
+                .discard_pile
+                .get(&event.soul_caste)
+                .is_some_and(|amount| *amount > 0);
+        if refunded {
+            if let Some(amount) = soul_wheel.discard_pile.get_mut(&event.soul_caste) {
+                *amount -= 1;
+            }
+            *soul_wheel.draw_pile.entry(event.soul_caste).or_insert(0) += 1;
+        }
+        magic_vfx.send(PlaceMagicVfx {
+            targets: vec![*position],
+            sequence: EffectSequence::Simultaneous,
+            effect: EffectType::Deflect,
+            decay: 0.5,
+            appear: 0.,
+            caste: Some(event.soul_caste),
+        });
+        text.send(AddMessage {
+            message: Message::SpellFizzled(event.soul_caste, event.reason, refunded),
+        });
+    }
+}
+
 pub enum PlayerAction {
     Step,
     Spell,
     Draw,
+    Interact,
+    /// Placing a soul into a recipe, see WeaveSoul.
+    Weave,
+    /// Running out of time, see PassTurn.
+    Pass,
+    /// Escaping to a safe tile, see PanicWarp.
+    Warp,
     Invalid,
     Skipped,
 }
@@ -246,11 +376,22 @@ pub struct AddStatusEffect {
 
 pub fn add_status_effects(
     mut events: EventReader<AddStatusEffect>,
-    mut effects: Query<(&mut StatusEffectsList, &CreatureFlags)>,
+    mut effects: Query<(&mut StatusEffectsList, &CreatureFlags, &Species)>,
+    registry: Res<SpeciesRegistry>,
+    mut text: EventWriter<AddMessage>,
     mut commands: Commands,
 ) {
     for event in events.read() {
-        let (mut effects_list, flags) = effects.get_mut(event.entity).unwrap();
+        let (mut effects_list, flags, species) = effects.get_mut(event.entity).unwrap();
+        if registry.get(species).immunities.contains(&event.effect) {
+            // Some effects are inserted ahead of time, like the Dizzy
+            // of creatures waking up in their cage.
+            strip_status_effect(event.effect, flags.effects_flags, &mut commands);
+            text.send(AddMessage {
+                message: Message::EffectResisted(*species, event.effect),
+            });
+            continue;
+        }
         if let Some(effect) = effects_list.effects.get(&event.effect) {
             // Re-applying a status effect which is already possessed does not work
             // if the new effect has a lesser potency.
@@ -285,6 +426,30 @@ pub fn add_status_effects(
                     summoner: event.culprit,
                 });
             }
+            StatusEffect::SpellReflect => {
+                commands.entity(effects_flags).insert(SpellReflect);
+            }
+        }
+    }
+}
+
+/// Remove the components granted by a status effect from an effects flags entity.
+pub fn strip_status_effect(effect: StatusEffect, effects_flags: Entity, commands: &mut Commands) {
+    match effect {
+        StatusEffect::Invincible => {
+            commands.entity(effects_flags).remove::<Invincible>();
+        }
+        StatusEffect::Stab => {
+            commands.entity(effects_flags).remove::<Stab>();
+        }
+        StatusEffect::Dizzy => {
+            commands.entity(effects_flags).remove::<Dizzy>();
+        }
+        StatusEffect::DimensionBond => {
+            commands.entity(effects_flags).remove::<Summoned>();
+        }
+        StatusEffect::SpellReflect => {
+            commands.entity(effects_flags).remove::<SpellReflect>();
         }
     }
 }
@@ -297,6 +462,10 @@ pub struct SummonCreature {
     pub summoner_tile: Position,
     pub summoner: Option<Entity>,
     pub spellbook: Option<Spellbook>,
+    /// Launched projectiles, see Axiom::LaunchProjectile.
+    pub projectile: Option<Projectile>,
+    /// Traps and projectiles are credited to whoever left them behind, see Owner.
+    pub owner: Option<Entity>,
 }
 
 /// Place a new Creature on the map of Species and at Position.
@@ -307,30 +476,24 @@ pub fn summon_creature(
     atlas_layout: Res<SpriteSheetAtlas>,
     map: Res<Map>,
     faiths_end: Res<FaithsEnd>,
+    registry: Res<SpeciesRegistry>,
+    death_effects: Res<DeathEffects>,
+    difficulty: Res<GameDifficulty>,
+    players: Query<(), With<Player>>,
 ) {
     for event in events.read() {
-        // Avoid summoning if the tile is already occupied.
+        let definition = registry.get(&event.species);
+        let footprint = definition.footprint();
+        // Avoid summoning if the tile (or any of the tiles, for large creatures)
+        // is already occupied.
         // Intangible creatures are allowed to spawn.
-        if !map.is_passable(event.position.x, event.position.y)
-            && !is_naturally_intangible(&event.species)
+        if !occupied_tiles(event.position, footprint.as_ref())
+            .iter()
+            .all(|tile| map.is_passable(tile.x, tile.y))
+            && !definition.is_naturally_intangible()
         {
             continue;
         }
-        let max_hp = 6;
-        let hp = match &event.species {
-            Species::Player => 6,
-            Species::Hunter => 1,
-            Species::Spawner => 3,
-            Species::Apiarist => 3,
-            Species::Shrike => 1,
-            Species::Second => 1,
-            Species::Tinker => 1,
-            Species::Oracle => 2,
-            // Wall-type creatures just get full HP to avoid displaying
-            // their healthbar.
-            _ => max_hp,
-        };
-
         let (effects_flags, species_flags) =
             (commands.spawn_empty().id(), commands.spawn_empty().id());
 
@@ -339,6 +502,11 @@ pub fn summon_creature(
             commands.entity(effects_flags).insert(Summoned { summoner });
         }
 
+        let spellbook = event
+            .spellbook
+            .clone()
+            .unwrap_or_else(|| registry.spellbook(&event.species));
+
         let mut new_creature = commands.spawn_empty();
         let parent_creature = new_creature.id();
 
@@ -351,32 +519,31 @@ pub fn summon_creature(
                     custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
                     texture_atlas: Some(TextureAtlas {
                         layout: atlas_layout.handle.clone(),
-                        index: get_species_sprite(&event.species),
+                        index: definition.sprite,
                     }),
                     ..default()
                 },
                 momentum: event.momentum,
-                health: Health { max_hp, hp },
+                health: {
+                    let max_hp = if event.species == Species::Player {
+                        difficulty.player_max_hp(definition.max_hp)
+                    } else if definition.is_hostile() {
+                        difficulty.enemy_max_hp(definition.max_hp)
+                    } else {
+                        definition.max_hp
+                    };
+                    Health {
+                        max_hp,
+                        hp: (definition.hp * max_hp)
+                            .div_ceil(definition.max_hp.max(1))
+                            .min(max_hp),
+                    }
+                },
                 effects: StatusEffectsList {
                     effects: HashMap::new(),
                 },
-                soul: match &event.species {
-                    Species::Player => Soul::Saintly,
-                    Species::Wall | Species::WeakWall => Soul::Ordered,
-                    Species::Hunter => Soul::Saintly,
-                    Species::Shrike => Soul::Feral,
-                    Species::Apiarist => Soul::Ordered,
-                    Species::Tinker => Soul::Artistic,
-                    Species::Second => Soul::Vile,
-                    Species::Oracle => Soul::Unhinged,
-                    Species::EpsilonHead | Species::EpsilonTail => Soul::Ordered,
-                    Species::CageSlot => Soul::Empty,
-                    _ => Soul::Unhinged,
-                },
-                spellbook: event
-                    .spellbook
-                    .clone()
-                    .unwrap_or(get_species_spellbook(&event.species)),
+                soul: definition.soul,
+                spellbook,
                 flags: CreatureFlags {
                     effects_flags,
                     species_flags,
@@ -397,8 +564,29 @@ pub fn summon_creature(
                 ..Default::default()
             },
             SlideAnimation,
+            VisualLayer::Creature,
         ));
 
+        // The player knows where their own traps are.
+        if definition.hidden
+            && !event
+                .summoner
+                .is_some_and(|summoner| players.contains(summoner))
+        {
+            new_creature.insert(Hidden);
+        }
+
+        if let Some(owner) = event.owner {
+            new_creature.insert(Owner { entity: owner });
+        }
+
+        if definition.is_hostile() {
+            if let Some(death_effect) = death_effects.of(&definition.soul) {
+                new_creature.insert(death_effect);
+            }
+        }
+
+        let mut ai_state = AiState::Hunting;
         // If the map is "faith's end", log the cage address # of this creature.
         if let Some(cage_idx) = faiths_end
             .cage_address_position
@@ -407,46 +595,95 @@ pub fn summon_creature(
         {
             // HACK: Walls being marked as Awake prevents the cage clear check,
             // as they must then be cleared as well to open the doors (this is impossible).
-            if cage_idx != 0
-                && [
-                    Species::Shrike,
-                    Species::Tinker,
-                    Species::Oracle,
-                    Species::Second,
-                    Species::Hunter,
-                    Species::Apiarist,
-                    Species::EpsilonHead,
-                ]
-                .contains(&event.species)
-            {
-                new_creature.insert(Sleeping { cage_idx });
-            } else if [
-                Species::Shrike,
-                Species::Tinker,
-                Species::Oracle,
-                Species::Second,
-                Species::Hunter,
-                Species::Apiarist,
-                Species::EpsilonHead,
-            ]
-            .contains(&event.species)
-            {
-                new_creature.insert(Awake);
+            if definition.sleeps_in_cage {
+                if cage_idx != 0 {
+                    new_creature.insert(Sleeping { cage_idx });
+                    ai_state = AiState::Asleep;
+                } else {
+                    new_creature.insert(Awake);
+                }
+            }
+        }
+
+        // Large creatures are stretched over all their tiles, and do not rotate
+        // (their Footprint doesn't either).
+        if let Some(footprint) = footprint {
+            let (width, height) = footprint.size();
+            let (width, height) = (width as f32, height as f32);
+            // Composite creatures are drawn one tile at a time instead. Their own
+            // sprite shrinks away, but still carries their fog and elevation.
+            let body = definition.body_sprites();
+            new_creature.insert((
+                Sprite {
+                    image: asset_server.load("spritesheet.png"),
+                    custom_size: Some(if body.is_some() {
+                        Vec2::ZERO
+                    } else {
+                        Vec2::new(width * TILE_SIZE, height * TILE_SIZE)
+                    }),
+                    texture_atlas: Some(TextureAtlas {
+                        layout: atlas_layout.handle.clone(),
+                        index: definition.sprite,
+                    }),
+                    // The Transform stays on the centre of the bottom-left tile.
+                    anchor: Anchor::Custom(Vec2::new(0.5 / width - 0.5, 0.5 / height - 0.5)),
+                    ..default()
+                },
+                Transform::from_xyz(
+                    event.summoner_tile.x as f32 * TILE_SIZE,
+                    event.summoner_tile.y as f32 * TILE_SIZE,
+                    0.,
+                ),
+                footprint,
+            ));
+            if let Some(body) = body {
+                new_creature.with_children(|parent| {
+                    for (offset, sprite) in body {
+                        parent.spawn((
+                            BodyPart { offset },
+                            Sprite {
+                                image: asset_server.load("spritesheet.png"),
+                                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                                texture_atlas: Some(TextureAtlas {
+                                    layout: atlas_layout.handle.clone(),
+                                    index: sprite,
+                                }),
+                                ..default()
+                            },
+                            Transform::from_xyz(
+                                offset.0 as f32 * TILE_SIZE,
+                                offset.1 as f32 * TILE_SIZE,
+                                0.,
+                            ),
+                        ));
+                    }
+                });
             }
+            if let Some(weak_points) = definition.weak_points() {
+                new_creature.insert(weak_points);
+            }
+        }
+
+        if let Some(projectile) = &event.projectile {
+            new_creature.insert(projectile.clone());
+        }
+
+        if event.species != Species::Player {
+            new_creature.insert(ai_state);
         }
 
         // NOTE: This will have to be removed when creating player clones
         // becomes possible.
         if event.species == Species::Player {
-            new_creature.insert(Player);
+            new_creature.insert((
+                Player,
+                Facing {
+                    direction: event.momentum,
+                },
+                Equipment::default(),
+            ));
         }
 
-        // Creatures which start out damaged show their HP bar in advance.
-        let (visibility, index) = hp_bar_visibility_and_index(hp, max_hp);
-
-        // Free the borrow on Commands.
-        let new_creature_entity = new_creature.id();
-
         // Inform the effects and species flags that this creature
         // is their parent.
         commands
@@ -455,23 +692,6 @@ pub fn summon_creature(
         commands
             .entity(species_flags)
             .insert(FlagEntity { parent_creature });
-
-        let hp_bar = commands
-            .spawn(HealthIndicator {
-                sprite: Sprite {
-                    image: asset_server.load("spritesheet.png"),
-                    custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
-                    texture_atlas: Some(TextureAtlas {
-                        layout: atlas_layout.handle.clone(),
-                        index,
-                    }),
-                    ..default()
-                },
-                visibility,
-                transform: Transform::from_xyz(0., 0., 1.),
-            })
-            .id();
-        commands.entity(new_creature_entity).add_child(hp_bar);
     }
 }
 
@@ -484,6 +704,7 @@ pub struct TransformCreature {
 pub fn transform_creature(
     mut transform: EventReader<TransformCreature>,
     mut creature_query: Query<(&mut Species, &mut Sprite, &CreatureFlags)>,
+    registry: Res<SpeciesRegistry>,
     mut commands: Commands,
 ) {
     for event in transform.read() {
@@ -491,7 +712,8 @@ pub fn transform_creature(
             creature_query.get_mut(event.entity).unwrap();
         // Change the species.
         *species_of_creature = event.new_species;
-        sprite.texture_atlas.as_mut().unwrap().index = get_species_sprite(&event.new_species);
+        // NOTE: The body parts of composite creatures are left as they were.
+        sprite.texture_atlas.as_mut().unwrap().index = registry.get(&event.new_species).sprite;
         // Remove all components except for its knowledge of its parent.
         // The appropriate ones will be readded by assign_species_components.
         commands.entity(flags.species_flags).retain::<FlagEntity>();
@@ -501,89 +723,21 @@ pub fn transform_creature(
 /// Add any species-specific components.
 pub fn assign_species_components(
     changed_species: Query<(&CreatureFlags, &Species), Changed<Species>>,
+    registry: Res<SpeciesRegistry>,
     mut commands: Commands,
 ) {
     for (flags, species) in changed_species.iter() {
         let mut new_creature = commands.entity(flags.species_flags);
-        match species {
-            Species::Trap => {
-                new_creature.insert((
-                    Meleeproof, Spellproof, Intangible, Fragile, Invincible, NoDropSoul,
-                ));
-            }
-            Species::CageBorder | Species::CageSlot => {
-                new_creature.insert((Meleeproof, Spellproof, Intangible, Invincible, NoDropSoul));
-            }
-            Species::Wall => {
-                new_creature.insert((Meleeproof, Spellproof, Wall, Invincible, Dizzy, NoDropSoul));
-            }
-            Species::WeakWall => {
-                new_creature.insert((Meleeproof, Wall, Invincible, Dizzy, NoDropSoul));
-            }
-            Species::Airlock => {
-                new_creature.insert((Meleeproof, Spellproof, Door, Invincible, Dizzy, NoDropSoul));
-            }
-            Species::Hunter | Species::Spawner | Species::Second | Species::Oracle => {
-                new_creature.insert(Hunt);
-            }
-            Species::Tinker => {
-                new_creature.insert(Random);
-            }
-            Species::Abazon => {
-                new_creature.insert((Immobile, Hunt));
-            }
-            Species::EpsilonHead => {
-                new_creature.insert((
-                    Magnetic {
-                        species: Species::EpsilonTail,
-                        conductor: None,
-                    },
-                    Hunt,
-                ));
-            }
-            Species::Apiarist => {
-                new_creature.insert((Speed::Slow { wait_turns: 1 }, Hunt));
-            }
-            Species::Shrike => {
-                new_creature.insert((
-                    Speed::Fast {
-                        actions_per_turn: 2,
-                    },
-                    Hunt,
-                ));
-            }
-            _ => (),
+        let definition = registry.get(species);
+        for component in &definition.components {
+            component.insert(&mut new_creature);
+        }
+        if !definition.tags.is_empty() {
+            new_creature.insert(Tags(definition.tags.clone()));
         }
     }
 }
 
-/// Determine whether to show or not, and at which sprite index, an HP bar.
-fn hp_bar_visibility_and_index(hp: usize, max_hp: usize) -> (Visibility, usize) {
-    (
-        {
-            if max_hp == hp {
-                Visibility::Hidden
-            } else {
-                Visibility::Inherited
-            }
-        },
-        {
-            if max_hp == hp {
-                178
-            } else {
-                match hp {
-                    5 => 238,
-                    4 => 239,
-                    3 => 240,
-                    2 => 241,
-                    1 => 242,
-                    _ => 243,
-                }
-            }
-        },
-    )
-}
-
 #[derive(Event)]
 pub struct CreatureStep {
     pub entity: Entity,
@@ -594,16 +748,28 @@ pub fn creature_step(
     mut events: EventReader<CreatureStep>,
     mut teleporter: EventWriter<TeleportEntity>,
     mut momentum: EventWriter<AlterMomentum>,
-    mut creature: Query<&Position>,
+    mut creature: Query<(&Position, Has<Mired>, Has<Player>)>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
 ) {
     for event in events.read() {
-        let creature_pos = creature.get_mut(event.entity).unwrap();
+        let (creature_pos, is_mired, is_player) = creature.get_mut(event.entity).unwrap();
         let (off_x, off_y) = event.direction.as_offset();
-        teleporter.send(TeleportEntity::new(
-            event.entity,
-            creature_pos.x + off_x,
-            creature_pos.y + off_y,
-        ));
+        // Creatures stuck in mud spend their step pulling free instead.
+        if is_mired {
+            commands.entity(event.entity).remove::<Mired>();
+            if is_player {
+                text.send(AddMessage {
+                    message: Message::PulledFromMud,
+                });
+            }
+        } else {
+            teleporter.send(TeleportEntity::new(
+                event.entity,
+                creature_pos.x + off_x,
+                creature_pos.y + off_y,
+            ));
+        }
         // Update the direction towards which this creature is facing.
         momentum.send(AlterMomentum {
             entity: event.entity,
@@ -629,21 +795,13 @@ pub fn magnetize_tail_segments(
     for (conductor_entity, pos, flags) in conductor_query.iter() {
         // of a creature that is considered Magnetic.
         // species has override on effects.
-        if let Ok((flag_entity, magnet)) = query
-            .get(flags.species_flags)
-            .or(query.get(flags.effects_flags))
-        {
+        if let Ok((flag_entity, magnet)) = query.get_flag_by(flags, FlagPriority::Species) {
             let magnetized_finder = magnetized_set.p0();
             let flags_with_magnetized = if let Some(original_conductor) = magnet.conductor {
                 if let Ok(conductor_flags) = creature_flags.get(original_conductor) {
-                    if let Ok(flags_with_magnetized) = magnetized_finder
-                        .get(conductor_flags.species_flags)
-                        .or(magnetized_finder.get(conductor_flags.effects_flags))
-                    {
-                        Some(flags_with_magnetized)
-                    } else {
-                        None
-                    }
+                    magnetized_finder
+                        .get_flag_by(conductor_flags, FlagPriority::Species)
+                        .ok()
                 } else {
                     // The conductor has been removed, and this segment
                     // loses its magnetism.
@@ -733,7 +891,7 @@ impl TeleportEntity {
 
 pub fn teleport_entity(
     mut events: EventReader<TeleportEntity>,
-    mut creature: Query<(&mut Position, &CreatureFlags)>,
+    mut creature: Query<(&mut Position, &CreatureFlags, Option<&Footprint>)>,
     intangible_query: Query<&Intangible>,
     immobile_query: Query<&Immobile>,
     magnet_query: Query<&Magnetized>,
@@ -744,29 +902,43 @@ pub fn teleport_entity(
     mut contingency: EventWriter<TriggerContingency>,
     mut magnet: EventWriter<MagnetFollow>,
     is_player: Query<Has<Player>>,
+    mut animation_queue: ResMut<AnimationQueue>,
 ) {
     for event in events.read() {
-        let (mut creature_position, creature_flags) = creature
+        let (mut creature_position, creature_flags, footprint) = creature
             // Get the Position of the Entity targeted by TeleportEntity.
             .get_mut(event.entity)
             .expect("A TeleportEntity was given an invalid entity");
         let (is_intangible, is_immobile, is_magnetized) = {
             (
-                intangible_query.contains(creature_flags.species_flags)
-                    || intangible_query.contains(creature_flags.effects_flags),
-                immobile_query.contains(creature_flags.species_flags)
-                    || immobile_query.contains(creature_flags.effects_flags),
-                magnet_query.contains(creature_flags.species_flags)
-                    || magnet_query.contains(creature_flags.effects_flags),
+                intangible_query.flagged(creature_flags),
+                immobile_query.flagged(creature_flags),
+                magnet_query.flagged(creature_flags),
             )
         };
+        // Large creatures need room for their whole body, but they don't block themselves.
+        let (old_tiles, new_tiles) = (
+            occupied_tiles(*creature_position, footprint),
+            occupied_tiles(event.destination, footprint),
+        );
+        let blocker = if footprint.is_some() {
+            map.blocker_for(event.entity, &new_tiles)
+        } else {
+            map.get_entity_at(event.destination.x, event.destination.y)
+                .map(|occupant| (event.destination, *occupant))
+        };
         // If motion is possible...
-        if !is_immobile
-            && (map.is_passable(event.destination.x, event.destination.y) || is_intangible)
-        {
+        if !is_immobile && (blocker.is_none() || is_intangible) {
             if !is_intangible {
                 // ...update the Map to reflect this...
-                map.move_creature(*creature_position, event.destination);
+                if footprint.is_some() {
+                    map.move_tiles(event.entity, &old_tiles, &new_tiles);
+                } else {
+                    map.move_creature(*creature_position, event.destination);
+                }
+            } else {
+                map.remove_intangible(event.entity, &old_tiles);
+                map.add_intangible(event.entity, &new_tiles);
             }
             // Magnetized creatures will have their tail follow them.
             if is_magnetized {
@@ -775,34 +947,42 @@ pub fn teleport_entity(
                     conductor: event.entity,
                 });
             }
+            let origin = *creature_position;
+            // Until its movement is played back, the creature is drawn where it was.
+            commands.entity(event.entity).insert_if_new(HeldAt {
+                position: *creature_position,
+            });
             // ...and move that Entity to TeleportEntity's destination tile.
             creature_position.update(event.destination.x, event.destination.y);
             // Also, animate this creature, making its teleport action visible on the screen.
-            commands.entity(event.entity).insert(SlideAnimation);
+            animation_queue.push(AnimationStep::Slide {
+                entity: event.entity,
+                destination: event.destination,
+            });
             // The creature steps on its destination tile, triggering traps there.
             stepped.send(SteppedOnTile {
                 entity: event.entity,
                 position: event.destination,
+                origin,
             });
             // This triggers the "when moved" contingency.
             contingency.send(TriggerContingency {
                 caster: event.entity,
                 contingency: Axiom::WhenMoved,
             });
-        } else if let Some(collided_with) =
-            map.get_entity_at(event.destination.x, event.destination.y)
-        {
+        } else if let Some((tile, collided_with)) = blocker {
             // A creature collides with another entity.
             let (culprit_is_player, collided_is_player) = (
                 is_player.get(event.entity).unwrap(),
-                is_player.get(*collided_with).unwrap(),
+                is_player.get(collided_with).unwrap(),
             );
             // Only collide if one of the two creature is the player.
             // TODO: This will prevent allied creatures from attacking.
             if culprit_is_player || collided_is_player {
                 collision.send(CreatureCollision {
                     culprit: event.entity,
-                    collided_with: *collided_with,
+                    collided_with,
+                    tile,
                 });
             }
         }
@@ -831,8 +1011,7 @@ pub fn magnet_follow(
         let conductor_flags = flags_query.get(event.conductor).unwrap();
         let magnet_finder = magnet_set.p0();
         let flags_with_magnetized = magnet_finder
-            .get(conductor_flags.species_flags)
-            .or(magnet_finder.get(conductor_flags.effects_flags))
+            .get_flag_by(conductor_flags, FlagPriority::Species)
             .unwrap();
         let mut magnet = magnet_set.p1();
         let mut magnet = magnet.get_mut(flags_with_magnetized).unwrap();
@@ -917,8 +1096,10 @@ pub fn magnet_follow(
 
 #[derive(Event)]
 pub struct SteppedOnTile {
-    entity: Entity,
-    position: Position,
+    pub entity: Entity,
+    pub position: Position,
+    /// Where the creature came from.
+    pub origin: Position,
 }
 
 pub fn stepped_on_tile(
@@ -930,8 +1111,7 @@ pub fn stepped_on_tile(
 ) {
     for event in events.read() {
         for (entity, position, flags) in stepped_on_creatures.iter() {
-            let is_fragile =
-                fragile.contains(flags.species_flags) || fragile.contains(flags.effects_flags);
+            let is_fragile = fragile.flagged(flags);
             // If an entity is at the Position that was stepped on and isn't the creature
             // responsible for stepping...
             if event.position == *position && entity != event.entity {
@@ -942,7 +1122,10 @@ pub fn stepped_on_tile(
                 });
                 // Fragile floor entities are destroyed when stepped on.
                 if is_fragile {
-                    remove.send(RemoveCreature { entity });
+                    remove.send(RemoveCreature {
+                        entity,
+                        culprit: None,
+                    });
                 }
             }
         }
@@ -951,35 +1134,50 @@ pub fn stepped_on_tile(
 
 #[derive(Event)]
 pub struct CreatureCollision {
-    culprit: Entity,
-    collided_with: Entity,
+    pub culprit: Entity,
+    pub collided_with: Entity,
+    /// The tile where the collision happened, which matters for large creatures.
+    pub tile: Position,
+}
+
+impl CreatureCollision {
+    pub fn new(culprit: Entity, collided_with: Entity, tile: Position) -> Self {
+        Self {
+            culprit,
+            collided_with,
+            tile,
+        }
+    }
 }
 
 pub fn creature_collision(
     mut events: EventReader<CreatureCollision>,
     mut harm: EventWriter<DamageOrHealCreature>,
+    mut deflect: EventWriter<Deflect>,
     mut text: EventWriter<AddMessage>,
-    stab_query: Query<&Stab>,
+    (stab_query, melee_bonus_query): (Query<&Stab>, Query<&MeleeBonus>),
     species_query: Query<&Species>,
-    meleeproof_query: Query<&Meleeproof>,
+    (meleeproof_query, pushable_query): (Query<&Meleeproof>, Query<&Pushable>),
     mut turn_manager: ResMut<TurnManager>,
-    mut creature: Query<(&mut Transform, Has<Player>, &CreatureFlags)>,
+    creature: Query<(Has<Player>, &CreatureFlags)>,
     flags_query: Query<&CreatureFlags>,
-    mut commands: Commands,
     mut effects: Query<&mut StatusEffectsList>,
     position: Query<&Position>,
+    mut animation_queue: ResMut<AnimationQueue>,
+    (mut sound, mut noise): (EventWriter<PlaySound>, EventWriter<Noise>),
 ) {
     for event in events.read() {
         if event.culprit == event.collided_with {
             // No colliding with yourself.
             continue;
         }
-        let (mut attacker_transform, is_player, flags) = creature.get_mut(event.culprit).unwrap();
-        let cannot_be_melee_attacked = {
-            let defender_flags = flags_query.get(event.collided_with).unwrap();
-            meleeproof_query.contains(defender_flags.species_flags)
-                || meleeproof_query.contains(defender_flags.effects_flags)
-        };
+        let (is_player, flags) = creature.get(event.culprit).unwrap();
+        let defender_flags = flags_query.get(event.collided_with).unwrap();
+        // Pushable creatures are shoved instead, see push_chains.
+        if pushable_query.flagged(defender_flags) {
+            continue;
+        }
+        let cannot_be_melee_attacked = meleeproof_query.flagged(defender_flags);
         // if is_door {
         // Open doors.
         // NOTE: Disabled as doors are currently automatic.
@@ -990,11 +1188,7 @@ pub fn creature_collision(
         // }
         // else
         if !cannot_be_melee_attacked {
-            let damage = if let Ok(stab) = {
-                stab_query
-                    .get(flags.species_flags)
-                    .or(stab_query.get(flags.effects_flags))
-            } {
+            let damage = if let Ok(stab) = stab_query.get_flag_by(flags, FlagPriority::Species) {
                 // Attacking something with Stab active resets the Stab bonus.
                 let mut status_effects = effects.get_mut(event.culprit).unwrap();
                 status_effects
@@ -1006,28 +1200,113 @@ pub fn creature_collision(
             } else {
                 -1
             };
+            // Equipment bonuses are never used up.
+            let damage = damage
+                - melee_bonus_query
+                    .get(flags.effects_flags)
+                    .map_or(0, |bonus| bonus.bonus_damage);
+            noise.send(Noise {
+                origin: event.tile,
+                radius: MELEE_NOISE,
+            });
             // Melee attack.
             harm.send(DamageOrHealCreature {
                 entity: event.collided_with,
                 culprit: event.culprit,
                 hp_mod: damage,
+                tile: Some(event.tile),
+                kind: DamageKind::Melee,
             });
             // Melee attack animation.
             // This must be calculated and cannot be "momentum", it has not been altered yet.
             let atk_pos = position.get(event.culprit).unwrap();
             let def_pos = position.get(event.collided_with).unwrap();
-            attacker_transform.translation.x += (def_pos.x - atk_pos.x) as f32 * TILE_SIZE / 4.;
-            attacker_transform.translation.y += (def_pos.y - atk_pos.y) as f32 * TILE_SIZE / 4.;
-            commands.entity(event.culprit).insert(SlideAnimation);
-        } else if matches!(turn_manager.action_this_turn, PlayerAction::Step) && is_player {
-            // The player spent their turn walking into a wall, disallow the turn from ending.
-            text.send(AddMessage {
-                message: Message::InvalidAction(InvalidAction::CannotMelee(
-                    *species_query.get(event.collided_with).unwrap(),
-                )),
+            animation_queue.push(AnimationStep::Lunge {
+                entity: event.culprit,
+                offset: (def_pos.x - atk_pos.x, def_pos.y - atk_pos.y),
             });
-            turn_manager.action_this_turn = PlayerAction::Invalid;
+            sound.send(PlaySound {
+                sound: Sound::MeleeHit,
+                position: Some(*def_pos),
+            });
+        } else {
+            deflect.send(Deflect {
+                entity: event.collided_with,
+                culprit: event.culprit,
+                kind: DeflectKind::Melee,
+            });
+            if matches!(turn_manager.action_this_turn, PlayerAction::Step) && is_player {
+                // The player spent their turn walking into a wall, disallow the turn from ending.
+                text.send(AddMessage {
+                    message: Message::InvalidAction(InvalidAction::CannotMelee(
+                        *species_query.get(event.collided_with).unwrap(),
+                    )),
+                });
+                turn_manager.action_this_turn = PlayerAction::Invalid;
+            }
+        }
+    }
+}
+
+/// What was shrugged off in a Deflect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeflectKind {
+    Melee,
+    Spell,
+}
+
+/// A Meleeproof or Spellproof creature shrugged off an attack or spell.
+#[derive(Event)]
+pub struct Deflect {
+    pub entity: Entity,
+    pub culprit: Entity,
+    pub kind: DeflectKind,
+}
+
+/// Show a spark where an attack or spell was deflected. The player is told when
+/// their spells are deflected (unless it is by walls, which deflect everything),
+/// and when they deflect melee attacks.
+// NOTE: The player's own deflected attacks are already reported as CannotMelee.
+pub fn deflect_attack(
+    mut events: EventReader<Deflect>,
+    creatures: Query<(&Position, &Species, &CreatureFlags)>,
+    species_query: Query<&Species>,
+    player: Query<(), With<Player>>,
+    wall_query: Query<&Wall>,
+    mut magic_vfx: EventWriter<PlaceMagicVfx>,
+    mut text: EventWriter<AddMessage>,
+) {
+    let mut already_deflected = HashSet::new();
+    for event in events.read() {
+        // A single spell may bounce off the same creature several times.
+        if !already_deflected.insert((event.entity, event.kind)) {
+            continue;
         }
+        let Ok((position, species, flags)) = creatures.get(event.entity) else {
+            continue;
+        };
+        magic_vfx.send(PlaceMagicVfx {
+            targets: vec![*position],
+            sequence: EffectSequence::Simultaneous,
+            effect: EffectType::Deflect,
+            decay: 0.5,
+            appear: 0.,
+            caste: None,
+        });
+        let is_wall = wall_query.flagged(flags);
+        let message = match event.kind {
+            DeflectKind::Spell if player.contains(event.culprit) && !is_wall => {
+                Message::SpellDeflected(*species)
+            }
+            DeflectKind::Melee if player.contains(event.entity) => {
+                let Ok(attacker) = species_query.get(event.culprit) else {
+                    continue;
+                };
+                Message::MeleeDeflected(*attacker)
+            }
+            _ => continue,
+        };
+        text.send(AddMessage { message });
     }
 }
 
@@ -1037,10 +1316,25 @@ pub struct AlterMomentum {
     pub direction: OrdDir,
 }
 
+#[derive(Event)]
+pub struct TurnFacing {
+    pub entity: Entity,
+    pub direction: OrdDir,
+}
+
+/// Turn a creature to look in another direction. Unlike AlterMomentum,
+/// this does not rotate the creature, nor does it take a turn.
+pub fn turn_facing(mut events: EventReader<TurnFacing>, mut facing: Query<&mut Facing>) {
+    for event in events.read() {
+        if let Ok(mut facing) = facing.get_mut(event.entity) {
+            facing.direction = event.direction;
+        }
+    }
+}
+
 pub fn alter_momentum(
     mut events: EventReader<AlterMomentum>,
-    mut creature: Query<(&mut OrdDir, &mut Transform, &Children)>,
-    mut hp_bar: Query<&mut Transform, Without<OrdDir>>,
+    mut creature: Query<(&mut OrdDir, &mut Transform, Has<Footprint>)>,
     turn_manager: Res<TurnManager>,
 ) {
     for event in events.read() {
@@ -1048,51 +1342,72 @@ pub fn alter_momentum(
         if matches!(turn_manager.action_this_turn, PlayerAction::Invalid) {
             return;
         }
-        let (mut creature_momentum, mut creature_transform, children) =
+        let (mut creature_momentum, mut creature_transform, is_large) =
             creature.get_mut(event.entity).unwrap();
         *creature_momentum = event.direction;
+        // Large creatures would rotate out of their own tiles.
+        if is_large {
+            continue;
+        }
         match event.direction {
             OrdDir::Down => creature_transform.rotation = Quat::from_rotation_z(0.),
             OrdDir::Right => creature_transform.rotation = Quat::from_rotation_z(PI / 2.),
             OrdDir::Up => creature_transform.rotation = Quat::from_rotation_z(PI),
             OrdDir::Left => creature_transform.rotation = Quat::from_rotation_z(3. * PI / 2.),
         }
-        // Keep the HP bar on the bottom.
-        for child in children.iter() {
-            let mut hp_transform = hp_bar.get_mut(*child).unwrap();
-            match event.direction {
-                OrdDir::Down => hp_transform.rotation = Quat::from_rotation_z(0.),
-                OrdDir::Right => hp_transform.rotation = Quat::from_rotation_z(3. * PI / 2.),
-                OrdDir::Up => hp_transform.rotation = Quat::from_rotation_z(PI),
-                OrdDir::Left => hp_transform.rotation = Quat::from_rotation_z(PI / 2.),
-            }
-        }
     }
 }
 
+/// The Health of `entity` changed, and its HP bar needs redrawing, see update_hp_bars.
+#[derive(Event)]
+pub struct HealthChanged {
+    pub entity: Entity,
+}
+
 #[derive(Event)]
 pub struct DamageOrHealCreature {
     pub entity: Entity,
     pub culprit: Entity,
     pub hp_mod: isize,
+    /// The tile which was hit, if any. Only matters for creatures with WeakPoints.
+    pub tile: Option<Position>,
+    pub kind: DamageKind,
 }
 
 pub fn harm_creature(
     mut events: EventReader<DamageOrHealCreature>,
     mut remove: EventWriter<RemoveCreature>,
-    mut creature: Query<(&mut Health, &Children, &CreatureFlags)>,
-    mut hp_bar: Query<(&mut Visibility, &mut Sprite)>,
+    mut creature: Query<(&mut Health, &CreatureFlags)>,
     defender_flags: Query<&Invincible>,
+    shield_query: Query<&RealityShield>,
+    mut buffers: Query<&mut ShieldBuffer>,
     mut contingency: EventWriter<TriggerContingency>,
     mut text: EventWriter<AddMessage>,
-    text_query: Query<(&Species, Has<Player>)>,
+    text_query: Query<(&Species, Has<Player>, &Position)>,
+    mut weak_points: Query<(
+        &Position,
+        &mut WeakPoints,
+        &mut Spellbook,
+        Option<&DeathEffect>,
+    )>,
+    difficulty: Res<GameDifficulty>,
+    owners: Query<&Owner>,
+    tags_query: Query<&Tags>,
+    mut health_changed: EventWriter<HealthChanged>,
+    mut commands: Commands,
 ) {
     for event in events.read() {
-        let (mut health, children, flags) = creature.get_mut(event.entity).unwrap();
-        let is_invincible = defender_flags.contains(flags.effects_flags)
-            || defender_flags.contains(flags.species_flags);
-        let (culprit_species, culprit_is_player) = text_query.get(event.culprit).unwrap();
-        let (victim_species, victim_is_player) = text_query.get(event.entity).unwrap();
+        let (mut health, flags) = creature.get_mut(event.entity).unwrap();
+        let is_invincible = defender_flags.flagged(flags);
+        let (culprit_species, _, _) = text_query.get(event.culprit).unwrap();
+        // Traps and projectiles act on behalf of whoever left them behind.
+        let credited = credited_culprit(event.culprit, &owners);
+        let culprit_is_player = text_query
+            .get(credited)
+            .is_ok_and(|(_, is_player, _)| is_player);
+        let is_proxy = credited != event.culprit;
+        let (victim_species, victim_is_player, victim_position) =
+            text_query.get(event.entity).unwrap();
         // Apply damage or healing.
         match event.hp_mod.signum() {
             -1 => {
@@ -1105,25 +1420,86 @@ pub fn harm_creature(
                     continue;
                 }
 
-                if culprit_is_player {
+                let mut damage = -event.hp_mod;
+                // Hitting a large creature's weak point hurts it more, and
+                // might break off one of its abilities.
+                if let (Some(tile), Ok((position, mut weak_points, mut spellbook, death_effect))) =
+                    (event.tile, weak_points.get_mut(event.entity))
+                {
+                    let offset = (tile.x - position.x, tile.y - position.y);
+                    if let Some(weak_point) = weak_points.points.get_mut(&offset) {
+                        if !weak_point.is_destroyed() {
+                            damage += weak_point.bonus_damage as isize;
+                            weak_point.hp = weak_point.hp.saturating_sub((-event.hp_mod) as usize);
+                            if weak_point.is_destroyed() {
+                                if let Some(soul) = weak_point.disables {
+                                    spellbook.spells.remove(&soul);
+                                    if death_effect.is_some_and(|effect| effect.soul == soul) {
+                                        commands.entity(event.entity).remove::<DeathEffect>();
+                                    }
+                                }
+                                text.send(AddMessage {
+                                    message: Message::WeakPointDestroyed(*victim_species),
+                                });
+                            }
+                        }
+                    }
+                }
+                // Some tags resist certain kinds of blows, like Constructs against spells.
+                if let Ok(tags) = tags_query.get(flags.species_flags) {
+                    let resistance = tags.resistance(event.kind);
+                    if resistance > 0 {
+                        damage = (damage - resistance).max(1);
+                    }
+                }
+                // Shields soften blows, but never stop them entirely.
+                if let Ok(shield) = shield_query.get_flag(flags) {
+                    damage = (damage - shield.amount as isize).max(1);
+                }
+                if victim_is_player {
+                    damage = difficulty.damage_taken(damage);
+                }
+                // Shield buffers soak up what they can before health does.
+                if let Ok(mut buffer) = buffers.get_mut(event.entity) {
+                    let absorbed = buffer.amount.min(damage.max(0) as usize);
+                    buffer.amount -= absorbed;
+                    damage -= absorbed as isize;
+                    if absorbed > 0 {
+                        text.send(AddMessage {
+                            message: Message::ShieldAbsorbed(*victim_species, absorbed),
+                        });
+                    }
+                }
+
+                if culprit_is_player && is_proxy {
+                    text.send(AddMessage {
+                        message: Message::ProxyAttack(*culprit_species, *victim_species, damage),
+                    });
+                } else if culprit_is_player {
                     text.send(AddMessage {
-                        message: Message::PlayerAttack(*victim_species, -event.hp_mod),
+                        message: Message::PlayerAttack(*victim_species, damage),
                     });
                 } else if victim_is_player {
                     text.send(AddMessage {
-                        message: Message::HostileAttack(*culprit_species, -event.hp_mod),
+                        message: Message::HostileAttack(*culprit_species, damage),
                     });
                 } else {
                     text.send(AddMessage {
-                        message: Message::NoPlayerAttack(
-                            *culprit_species,
-                            *victim_species,
-                            -event.hp_mod,
+                        message: Message::At(
+                            Box::new(Message::NoPlayerAttack(
+                                *culprit_species,
+                                *victim_species,
+                                damage,
+                            )),
+                            *victim_position,
                         ),
                     });
                 }
 
-                health.hp = health.hp.saturating_sub((-event.hp_mod) as usize);
+                health.hp = health.hp.saturating_sub(damage as usize);
+                health_changed.send(HealthChanged {
+                    entity: event.entity,
+                });
                 contingency.send(TriggerContingency {
                     caster: event.culprit,
                     contingency: Axiom::WhenDealingDamage,
@@ -1144,6 +1520,9 @@ pub fn harm_creature(
                     health.max_hp,
                 );
                 let health_difference = (health.hp - health_difference) as isize;
+                health_changed.send(HealthChanged {
+                    entity: event.entity,
+                });
                 if victim_is_player {
                     text.send(AddMessage {
                         message: Message::HealSelf(health_difference),
@@ -1154,23 +1533,23 @@ pub fn harm_creature(
                     });
                 } else {
                     text.send(AddMessage {
-                        message: Message::CreatureHealsItself(*victim_species, health_difference),
+                        message: Message::At(
+                            Box::new(Message::CreatureHealsItself(
+                                *victim_species,
+                                health_difference,
+                            )),
+                            *victim_position,
+                        ),
                     });
                 }
             } // Healing
             _ => (), // 0 values do nothing
         }
-        // Update the healthbar.
-        for child in children.iter() {
-            let (mut hp_vis, mut hp_bar) = hp_bar.get_mut(*child).unwrap();
-            // Don't show the healthbar at full hp.
-            (*hp_vis, hp_bar.texture_atlas.as_mut().unwrap().index) =
-                hp_bar_visibility_and_index(health.hp, health.max_hp);
-        }
         // 0 hp creatures are removed.
         if health.hp == 0 {
             remove.send(RemoveCreature {
                 entity: event.entity,
+                culprit: Some(event.culprit),
             });
         }
     }
@@ -1178,8 +1557,8 @@ pub fn harm_creature(
 
 #[derive(Event)]
 pub struct OpenCloseDoor {
-    entity: Entity,
-    open: bool,
+    pub entity: Entity,
+    pub open: bool,
 }
 
 #[derive(Component)]
@@ -1237,12 +1616,15 @@ pub fn open_close_door(
                         }),
                         ..default()
                     },
+                    // The pane needs to hide under actual tiles, such as walls.
+                    layer: VisualLayer::Decal,
                     visibility: Visibility::Inherited,
                     vfx: MagicVfx {
                         appear: Timer::from_seconds(0., TimerMode::Once),
                         // Very slow decay - the alpha shouldn't be reduced too much
                         // while the panes are still visible.
                         decay: Timer::from_seconds(5.0, TimerMode::Once),
+                        pooled: false,
                     },
                 },
                 // Ensure the panes are sliding.
@@ -1252,15 +1634,13 @@ pub fn open_close_door(
                         Vec3 {
                             x: position.x as f32 * TILE_SIZE,
                             y: position.y as f32 * TILE_SIZE,
-                            // The pane needs to hide under actual tiles, such as walls.
-                            z: -1.,
+                            z: 0.,
                         }
                     } else {
                         Vec3 {
                             x: (position.x + offset.0) as f32 * TILE_SIZE,
                             y: (position.y + offset.1) as f32 * TILE_SIZE,
-                            // The pane needs to hide under actual tiles, such as walls.
-                            z: -1.,
+                            z: 0.,
                         }
                     },
                     // Adjust the pane's rotation with its door.
@@ -1298,6 +1678,22 @@ pub fn render_closing_doors(
 #[derive(Event, Debug)]
 pub struct RemoveCreature {
     pub entity: Entity,
+    /// Whoever slew or banished it, if anyone did. See credited_culprit.
+    pub culprit: Option<Entity>,
+}
+
+/// Who gets the credit for what `culprit` did: whoever left behind the trap
+/// or projectile, if it was one, or else the culprit itself.
+pub fn credited_culprit(culprit: Entity, owners: &Query<&Owner>) -> Entity {
+    let mut credited = culprit;
+    // NOTE: Bounded, in case a trap ever ends up owning itself through a chain.
+    for _ in 0..8 {
+        match owners.get(credited) {
+            Ok(owner) => credited = owner.entity,
+            Err(_) => break,
+        }
+    }
+    credited
 }
 
 pub fn remove_creature(
@@ -1324,9 +1720,9 @@ pub fn remove_creature(
                 effect: EffectType::XCross,
                 decay: 0.5,
                 appear: 0.,
+                caste: None,
             });
-            let cannot_drop_soul = dying_flags.contains(flags.effects_flags)
-                || dying_flags.contains(flags.species_flags);
+            let cannot_drop_soul = dying_flags.flagged(flags);
             // For now, avoid removing the player - the game panics without a player.
             if !is_player {
                 // Add Dizzy to prevent this creature from taking any further actions.
@@ -1370,29 +1766,27 @@ pub fn respawn_player(
             Without<DesignatedForRemoval>,
         ),
     >,
-    player: Query<Entity, With<Player>>,
+    mut player: Query<(Entity, &mut Health), With<Player>>,
     mut remove: EventWriter<RemoveCreature>,
-    mut heal: EventWriter<DamageOrHealCreature>,
-    mut teleport: EventWriter<TeleportEntity>,
+    mut health_changed: EventWriter<HealthChanged>,
     mut title: EventWriter<AnnounceGameOver>,
     mut cage: EventWriter<RespawnCage>,
     mut soul_wheel: ResMut<SoulWheel>,
     mut faiths_end: ResMut<FaithsEnd>,
+    mut stats: ResMut<RunStats>,
 ) {
     for event in events.read() {
         for npc in npcs.iter() {
-            remove.send(RemoveCreature { entity: npc });
+            remove.send(RemoveCreature {
+                entity: npc,
+                culprit: None,
+            });
         }
-        let player = player.get_single().unwrap();
-        heal.send(DamageOrHealCreature {
-            entity: player,
-            culprit: player,
-            hp_mod: 6,
-        });
-        teleport.send(TeleportEntity {
-            destination: Position::new(4, 4),
-            entity: player,
-        });
+        // Back to full health, whatever the difficulty and equipment make that.
+        let (player, mut health) = player.get_single_mut().unwrap();
+        health.hp = health.max_hp;
+        health_changed.send(HealthChanged { entity: player });
+        // NOTE: The player is moved to the start of the new floor by spawn_cage.
         soul_wheel.draw_pile.insert(Soul::Saintly, 1);
         soul_wheel.draw_pile.insert(Soul::Ordered, 1);
         soul_wheel.draw_pile.insert(Soul::Artistic, 1);
@@ -1404,6 +1798,7 @@ pub fn respawn_player(
         cage.send(RespawnCage);
         title.send(AnnounceGameOver {
             victorious: event.victorious,
+            stats: std::mem::take(&mut *stats),
         });
     }
 }
@@ -1416,7 +1811,7 @@ pub fn remove_designated_creatures(
     remove: Query<(Entity, &CreatureFlags), With<DesignatedForRemoval>>,
     mut commands: Commands,
     mut map: ResMut<Map>,
-    position: Query<&Position>,
+    position: Query<(&Position, Option<&Footprint>)>,
     awake: Query<&Awake>,
     sleeping: Query<&Sleeping>,
     doors: Query<(Entity, &CreatureFlags)>,
@@ -1425,17 +1820,15 @@ pub fn remove_designated_creatures(
 ) {
     for (designated, designated_flags) in remove.iter() {
         // Remove the creature from Map
-        let position = position.get(designated).unwrap();
-        if let Some(preexisting_entity) = map.creatures.get(position) {
-            // Check that the entity being removed is actually the dead entity.
-            // REASON: Dying intangible creatures which are on top of a tangible
-            // creature will remove the tangible creature from the map instead
-            // of themselves.
-            if *preexisting_entity == designated {
-                map.creatures.remove(position);
-            }
-        }
-        // Remove the creature AND its children (health bar)
+        let (position, footprint) = position.get(designated).unwrap();
+        // NOTE: remove_tiles checks that the entity being removed is actually the dead entity.
+        // REASON: Dying intangible creatures which are on top of a tangible
+        // creature will remove the tangible creature from the map instead
+        // of themselves.
+        let tiles = occupied_tiles(*position, footprint);
+        map.remove_tiles(designated, &tiles);
+        map.remove_intangible(designated, &tiles);
+        // Remove the creature AND its children.
         commands.entity(designated).despawn_recursive();
         commands
             .entity(designated_flags.species_flags)
@@ -1450,9 +1843,7 @@ pub fn remove_designated_creatures(
     // despawned by the victory check.
     if awake.is_empty() && !sleeping.is_empty() {
         for (door, flags) in doors.iter() {
-            if closed_door_query.contains(flags.species_flags)
-                || closed_door_query.contains(flags.effects_flags)
-            {
+            if closed_door_query.flagged(flags) {
                 open.send(OpenCloseDoor {
                     entity: door,
                     open: true,
@@ -1465,6 +1856,10 @@ pub fn remove_designated_creatures(
 #[derive(Event)]
 pub struct EndTurn;
 
+/// The player let the turn timer run out, see TurnTimer.
+#[derive(Event)]
+pub struct PassTurn;
+
 pub fn end_turn(
     mut events: EventReader<EndTurn>,
     mut npc_actions: EventWriter<DistributeNpcActions>,
@@ -1481,6 +1876,12 @@ pub fn end_turn(
     mut respawn: EventWriter<RespawnPlayer>,
     mut status_effect: EventWriter<AddStatusEffect>,
     mut screenshake: ResMut<Screenshake>,
+    // NOTE: Bundled together to stay under the system parameter limit.
+    (speed_query, player_flags, mut free_actions): (
+        Query<&Speed>,
+        Query<&CreatureFlags, With<Player>>,
+        Local<usize>,
+    ),
 ) {
     for _event in events.read() {
         // The player shouldn't be allowed to "wait" turns by stepping into walls.
@@ -1494,6 +1895,19 @@ pub fn end_turn(
             // }
             return;
         }
+        // A fast player acts several times before the rest of the world does.
+        // NOTE: Conveyors, projectiles and the like still move on every action.
+        let player_speed = player_flags
+            .get_single()
+            .ok()
+            .and_then(|flags| speed_query.get_flag(flags).ok());
+        if let Some(Speed::Fast { actions_per_turn }) = player_speed {
+            *free_actions += 1;
+            if *free_actions < *actions_per_turn {
+                return;
+            }
+            *free_actions = 0;
+        }
         // Victory check.
         if sleeping_creatures.is_empty() && awake_creatures.is_empty() {
             respawn.send(RespawnPlayer { victorious: true });
@@ -1514,9 +1928,7 @@ pub fn end_turn(
             {
                 faiths_end.current_cage += 1;
                 for (door, flags) in flags_query.iter() {
-                    if open_door_query.contains(flags.species_flags)
-                        || open_door_query.contains(flags.effects_flags)
-                    {
+                    if open_door_query.flagged(flags) {
                         open.send(OpenCloseDoor {
                             entity: door,
                             open: false,
@@ -1525,7 +1937,9 @@ pub fn end_turn(
                 }
                 for (sleeping_entity, sleeping_component) in sleeping_creatures.iter() {
                     if sleeping_component.cage_idx == faiths_end.current_cage {
-                        commands.entity(sleeping_entity).insert(Awake);
+                        commands
+                            .entity(sleeping_entity)
+                            .insert((Awake, AiState::Hunting));
                         commands.entity(sleeping_entity).remove::<Sleeping>();
                         // Give one turn for the player to act.
                         // This also prevents them from immediately moving
@@ -1556,20 +1970,7 @@ pub fn end_turn(
                         // Disable this effect.
                         potency_and_stacks.potency = 0;
                         let effects_flags = flags_query.get(entity).unwrap().1.effects_flags;
-                        match effect {
-                            StatusEffect::Invincible => {
-                                commands.entity(effects_flags).remove::<Invincible>();
-                            }
-                            StatusEffect::Stab => {
-                                commands.entity(effects_flags).remove::<Stab>();
-                            }
-                            StatusEffect::Dizzy => {
-                                commands.entity(effects_flags).remove::<Dizzy>();
-                            }
-                            StatusEffect::DimensionBond => {
-                                commands.entity(effects_flags).remove::<Summoned>();
-                            }
-                        }
+                        strip_status_effect(*effect, effects_flags, &mut commands);
                     }
                 }
             }
@@ -1590,61 +1991,61 @@ pub fn distribute_npc_actions(
     mut events: EventReader<DistributeNpcActions>,
     turn_manager: Res<TurnManager>,
     player: Query<&Position, With<Player>>,
-    npcs: Query<(Entity, &Position, &Species, &Spellbook, &CreatureFlags), Without<Player>>,
-    species: Query<&Species>,
+    mut npcs: Query<
+        (
+            Entity,
+            &Position,
+            &Spellbook,
+            &CreatureFlags,
+            &Health,
+            Option<&mut AiState>,
+            Option<&mut Patrol>,
+        ),
+        Without<Player>,
+    >,
+    flags_query: Query<&CreatureFlags>,
     map: Res<Map>,
-
-    hunt_query: Query<&Hunt>,
-    random_query: Query<&Random>,
+    mut rng: ResMut<GameRng>,
+    // NOTE: Bundled together to stay under the system parameter limit.
+    (hunt_query, random_query): (Query<&Hunt>, Query<&Random>),
+    (devour_query, tags_query): (Query<&Devours>, Query<&Tags>),
     speed_query: Query<&Speed>,
     stunned_query: Query<Entity, Or<(With<Dizzy>, With<Sleeping>)>>,
+    (style_query, cowardly_query): (Query<&MovementStyle>, Query<&Cowardly>),
+    wall_query: Query<&Wall>,
 ) {
     for event in events.read() {
         let player_pos = player.get_single().unwrap();
         let mut send_echo = false;
-        for (npc_entity, npc_pos, npc_species, npc_spellbook, flags) in npcs.iter() {
+        for (npc_entity, npc_pos, npc_spellbook, flags, health, mut ai_state, mut patrol) in
+            npcs.iter_mut()
+        {
             let (is_hunter, is_random, is_stunned, speed) = {
                 (
-                    hunt_query.contains(flags.species_flags)
-                        || hunt_query.contains(flags.effects_flags),
-                    random_query.contains(flags.species_flags)
-                        || random_query.contains(flags.effects_flags),
-                    stunned_query.contains(flags.species_flags)
-                        || stunned_query.contains(flags.effects_flags)
+                    hunt_query.flagged(flags),
+                    random_query.flagged(flags),
+                    stunned_query.flagged(flags)
                         // HACK: The "Sleeping" component currently appears
                         // on the creature itself and not the effects_flags.
                         || stunned_query.contains(npc_entity),
                     // NOTE: Currently, status effect speed overrides species speed.
                     // Maybe it would be interesting to have them cancel each other out.
-                    speed_query
-                        .get(flags.effects_flags)
-                        .or(speed_query.get(flags.species_flags)),
+                    speed_query.get_flag(flags),
                 )
             };
             if is_stunned {
                 continue;
             }
-            if let Ok(speed) = speed {
-                match speed {
-                    Speed::Slow { wait_turns } => {
-                        if turn_manager.turn_count % (wait_turns + 1) != 0 || event.speed_level > 1
-                        {
-                            continue;
-                        }
-                    }
-                    Speed::Fast { actions_per_turn } => {
-                        if event.speed_level > *actions_per_turn {
-                            continue;
-                        } else {
-                            send_echo = true;
-                        }
-                    }
-                }
-            } else if event.speed_level > 1 {
+            if !acts_at_speed_level(speed.ok(), event.speed_level, turn_manager.turn_count) {
                 continue;
             }
+            if matches!(speed, Ok(Speed::Fast { .. })) {
+                send_echo = true;
+            }
             if is_random {
-                if let Some(move_direction) = map.random_adjacent_passable_direction(*npc_pos) {
+                if let Some(move_direction) =
+                    map.random_adjacent_passable_direction(*npc_pos, &mut *rng)
+                {
                     // If it is found, cause a CreatureStep event.
                     step.send(CreatureStep {
                         direction: move_direction,
@@ -1652,34 +2053,51 @@ pub fn distribute_npc_actions(
                     });
                 }
             } else if is_hunter {
-                // Occasionally cast a spell.
-                if *npc_species == Species::Second {
-                    let mut found_wall = false;
-                    for adj_pos in map.get_adjacent_tiles(*npc_pos) {
-                        if let Some(adjacent_npc) = map.creatures.get(&adj_pos) {
-                            if *species.get(*adjacent_npc).unwrap() == Species::WeakWall {
-                                spell.send(CastSpell {
-                                    caster: npc_entity,
-                                    spell: npc_spellbook.spells.get(&Soul::Vile).unwrap().clone(),
-                                    starting_step: 0,
-                                    soul_caste: Soul::Vile,
-                                });
-                                found_wall = true;
-                                break;
-                            }
-                        }
+                let is_wall = |p: &Position| {
+                    map.get_entity_at(p.x, p.y)
+                        .and_then(|entity| flags_query.get(*entity).ok())
+                        .is_some_and(|flags| wall_query.flagged(flags))
+                };
+                let panicked = cowardly_query
+                    .get_flag(flags)
+                    .ok()
+                    .map(|cowardly| health.hp <= cowardly.threshold);
+                // Occasionally cast a spell, unless a broken WeakPoint took it away.
+                let devour_spell = devour_query
+                    .get(flags.species_flags)
+                    .ok()
+                    .filter(|devours| has_prey(&map, *npc_pos, devours, &flags_query, &tags_query))
+                    .and(npc_spellbook.spells.get(&Soul::Vile));
+                let action = hunter_action(
+                    &map,
+                    *npc_pos,
+                    *player_pos,
+                    ai_state.as_deref_mut(),
+                    patrol.as_deref_mut(),
+                    panicked,
+                    devour_spell.is_some(),
+                    style_query.get_flag(flags).ok(),
+                    is_wall,
+                );
+                match action {
+                    Some(HunterAction::Step(direction) | HunterAction::Flee(direction)) => {
+                        step.send(CreatureStep {
+                            direction,
+                            entity: npc_entity,
+                        });
                     }
-                    if found_wall {
-                        continue;
+                    Some(HunterAction::Devour) => {
+                        spell.send(CastSpell {
+                            caster: npc_entity,
+                            spell: devour_spell.unwrap().clone(),
+                            starting_step: 0,
+                            soul_caste: Soul::Vile,
+                            target: None,
+                            from_wheel: false,
+                            from_locked_slot: false,
+                        });
                     }
-                }
-                // Try to find a tile that gets the hunter closer to the player.
-                if let Some(move_direction) = map.best_manhattan_move(*npc_pos, *player_pos) {
-                    // If it is found, cause a CreatureStep event.
-                    step.send(CreatureStep {
-                        direction: move_direction,
-                        entity: npc_entity,
-                    });
+                    None => (),
                 }
             }
         }
@@ -1691,6 +2109,116 @@ pub fn distribute_npc_actions(
     }
 }
 
+/// What a hunter does with its turn, see hunter_action.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HunterAction {
+    Step(OrdDir),
+    /// Step away from the player, see Cowardly.
+    Flee(OrdDir),
+    /// Cast its Vile spell, to devour a neighbour.
+    Devour,
+}
+
+/// Decide what a hunter does this turn, moving its AiState and Patrol along.
+/// `panicked` is None for creatures which are not Cowardly.
+// NOTE: Shared by distribute_npc_actions and predict_intents, which hands it
+// copies of the state so that predicting changes nothing.
+pub fn hunter_action(
+    map: &Map,
+    npc_pos: Position,
+    player_pos: Position,
+    ai_state: Option<&mut AiState>,
+    patrol: Option<&mut Patrol>,
+    panicked: Option<bool>,
+    can_devour: bool,
+    style: Option<&MovementStyle>,
+    is_wall: impl Fn(&Position) -> bool,
+) -> Option<HunterAction> {
+    let target = match ai_state {
+        Some(state) => {
+            if let Some(panicked) = panicked {
+                match *state {
+                    AiState::Alert { .. } | AiState::Hunting if panicked => {
+                        *state = AiState::Fleeing;
+                    }
+                    AiState::Fleeing if !panicked => *state = AiState::Hunting,
+                    _ => (),
+                }
+            }
+            // Alert creatures go check out what they heard, until they spot the player.
+            match *state {
+                AiState::Asleep => return None,
+                AiState::Alert { toward } => {
+                    if can_see(npc_pos, player_pos, ALERT_SIGHT, &is_wall) {
+                        *state = AiState::Hunting;
+                        player_pos
+                    } else {
+                        toward
+                    }
+                }
+                AiState::Patrolling => match patrol {
+                    Some(patrol) if !can_see(npc_pos, player_pos, PATROL_SIGHT, &is_wall) => {
+                        let destination = patrol.destination(npc_pos);
+                        // Walk around obstacles, instead of bumping into them.
+                        let distances =
+                            map.dijkstra_map(&[destination], |p| map.is_passable(p.x, p.y));
+                        return map
+                            .best_dijkstra_move(npc_pos, &distances, |_| 0)
+                            .map(HunterAction::Step);
+                    }
+                    _ => {
+                        *state = AiState::Hunting;
+                        player_pos
+                    }
+                },
+                AiState::Hunting => player_pos,
+                AiState::Fleeing => {
+                    return map.flee_move(npc_pos, player_pos).map(HunterAction::Flee);
+                }
+            }
+        }
+        None => player_pos,
+    };
+    if can_devour {
+        return Some(HunterAction::Devour);
+    }
+    if let Some(style) = style {
+        map.styled_move(style, npc_pos, target, |p| is_wall(&p))
+    } else {
+        // Try to find a tile that gets the hunter closer to the player.
+        map.best_manhattan_move(npc_pos, target)
+    }
+    .map(HunterAction::Step)
+}
+
+/// Whether a creature with this appetite stands next to something it could devour.
+pub fn has_prey(
+    map: &Map,
+    position: Position,
+    devours: &Devours,
+    flags_query: &Query<&CreatureFlags>,
+    tags_query: &Query<&Tags>,
+) -> bool {
+    map.get_adjacent_tiles(position).into_iter().any(|adj_pos| {
+        map.get_entity_at(adj_pos.x, adj_pos.y)
+            .and_then(|entity| flags_query.get(*entity).ok())
+            .and_then(|flags| tags_query.get(flags.species_flags).ok())
+            .is_some_and(|tags| tags.has(devours.tag))
+    })
+}
+
+/// Whether a creature of this speed acts during this echo of a turn.
+/// Slow creatures skip some turns, Fast creatures act again in later echoes.
+pub fn acts_at_speed_level(speed: Option<&Speed>, speed_level: usize, turn_count: usize) -> bool {
+    match speed {
+        Some(Speed::Slow { wait_turns }) => {
+            turn_count.is_multiple_of(wait_turns + 1) && speed_level == 1
+        }
+        Some(Speed::Fast { actions_per_turn }) => speed_level <= *actions_per_turn,
+        None => speed_level == 1,
+    }
+}
+
 #[derive(Event)]
 pub struct EchoSpeed {
     pub speed_level: usize,
@@ -1707,6 +2235,80 @@ pub fn echo_speed(
     }
 }
 
+/// Once per turn, bring Ephemeral creatures closer to crumbling away.
+pub fn crumble_ephemeral_creatures(
+    turn_manager: Res<TurnManager>,
+    creatures: Query<(Entity, &CreatureFlags)>,
+    mut ephemeral_query: Query<&mut Ephemeral>,
+    mut last_turn: Local<usize>,
+    mut remove: EventWriter<RemoveCreature>,
+) {
+    if turn_manager.turn_count == *last_turn {
+        return;
+    }
+    *last_turn = turn_manager.turn_count;
+    for (entity, flags) in creatures.iter() {
+        if let Ok(mut ephemeral) = ephemeral_query.get_mut(flags.species_flags) {
+            ephemeral.turns = ephemeral.turns.saturating_sub(1);
+            if ephemeral.turns == 0 {
+                remove.send(RemoveCreature {
+                    entity,
+                    culprit: None,
+                });
+            }
+        }
+    }
+}
+
+/// Once per turn, every projectile flies one tile ahead. If a creature other than
+/// its owner is on its tile or the next one, it bursts there instead.
+pub fn advance_projectiles(
+    mut events: EventReader<EndTurn>,
+    projectiles: Query<(Entity, &Position, &Projectile), Without<DesignatedForRemoval>>,
+    owners: Query<(), (With<Position>, Without<DesignatedForRemoval>)>,
+    map: Res<Map>,
+    mut teleport: EventWriter<TeleportEntity>,
+    mut cast: EventWriter<CastSpell>,
+    mut remove: EventWriter<RemoveCreature>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for (entity, position, projectile) in projectiles.iter() {
+        let (off_x, off_y) = projectile.velocity.as_offset();
+        let ahead = Position::new(position.x + off_x, position.y + off_y);
+        let hit = [*position, ahead].into_iter().find(|tile| {
+            map.get_entity_at(tile.x, tile.y)
+                .is_some_and(|occupant| *occupant != projectile.owner)
+        });
+        if let Some(tile) = hit {
+            // The payload starts with CursorTarget, aimed at the tile that was hit.
+            // NOTE: The owner casts it, as the projectile is gone before it resolves.
+            // Projectiles whose owner was slain crumble without bursting.
+            if projectile.owner != entity && owners.contains(projectile.owner) {
+                cast.send(CastSpell {
+                    caster: projectile.owner,
+                    spell: projectile.payload.clone(),
+                    starting_step: 0,
+                    soul_caste: projectile.caste,
+                    target: Some(tile),
+                    from_wheel: false,
+                    from_locked_slot: false,
+                });
+            }
+            remove.send(RemoveCreature {
+                entity,
+                culprit: None,
+            });
+        } else {
+            teleport.send(TeleportEntity {
+                destination: ahead,
+                entity,
+            });
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct RespawnCage;
 
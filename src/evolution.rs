@@ -0,0 +1,189 @@
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{Awake, DeathEffect, Health, Species, Spellbook},
+    difficulty::GameDifficulty,
+    events::{EndTurn, HealthChanged, TransformCreature},
+    graphics::{get_caste_palette, get_effect_sprite, EffectType, SpriteSheetAtlas, VisualLayer},
+    map::Position,
+    sets::Animation,
+    settings::Settings,
+    species::{DeathEffects, SpeciesRegistry},
+    ui::{AddMessage, Message},
+    vision::VisibilityMap,
+    TILE_SIZE,
+};
+
+pub struct EvolutionPlugin;
+
+impl Plugin for EvolutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, render_evolution_glows.in_set(Animation));
+    }
+}
+
+/// How many turns before evolving a creature starts to glow.
+pub const EVOLUTION_CHARGE_TURNS: usize = 3;
+
+/// What a species turns into after surviving long enough, see mature_creatures.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Evolution {
+    pub into: Species,
+    /// How many turns it needs to spend awake first.
+    pub turns: usize,
+}
+
+/// How many turns this creature has spent awake, on its way to evolving.
+#[derive(Component, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Maturing {
+    pub turns: usize,
+}
+
+impl Maturing {
+    fn is_charging(&self, evolution: &Evolution) -> bool {
+        self.turns + EVOLUTION_CHARGE_TURNS >= evolution.turns
+    }
+}
+
+/// Each turn, awake creatures which can evolve grow a bit older. They glow for
+/// their last few turns, then transform into their evolution.
+// NOTE: Evolved creatures learn the spells of their new species, and come back to
+// the health it is summoned with.
+pub fn mature_creatures(
+    mut events: EventReader<EndTurn>,
+    mut creatures: Query<
+        (
+            Entity,
+            &Species,
+            &Position,
+            &mut Health,
+            &mut Spellbook,
+            Option<&mut Maturing>,
+        ),
+        With<Awake>,
+    >,
+    registry: Res<SpeciesRegistry>,
+    death_effects: Res<DeathEffects>,
+    difficulty: Res<GameDifficulty>,
+    mut transform: EventWriter<TransformCreature>,
+    mut health_changed: EventWriter<HealthChanged>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for (entity, species, position, mut health, mut spellbook, maturing) in creatures.iter_mut() {
+        let Some(evolution) = registry.get(species).evolves else {
+            continue;
+        };
+        let Some(mut maturing) = maturing else {
+            commands.entity(entity).insert(Maturing { turns: 1 });
+            continue;
+        };
+        let was_charging = maturing.is_charging(&evolution);
+        maturing.turns += 1;
+        if maturing.turns >= evolution.turns {
+            commands.entity(entity).remove::<Maturing>();
+            transform.send(TransformCreature {
+                entity,
+                new_species: evolution.into,
+            });
+            let definition = registry.get(&evolution.into);
+            *spellbook = registry.spellbook(&evolution.into);
+            match death_effects.of(&definition.soul) {
+                Some(death_effect) if definition.is_hostile() => {
+                    commands.entity(entity).insert(death_effect);
+                }
+                _ => {
+                    commands.entity(entity).remove::<DeathEffect>();
+                }
+            }
+            let max_hp = difficulty.enemy_max_hp(definition.max_hp);
+            *health = Health {
+                max_hp,
+                hp: (definition.hp * max_hp)
+                    .div_ceil(definition.max_hp.max(1))
+                    .min(max_hp),
+            };
+            health_changed.send(HealthChanged { entity });
+            text.send(AddMessage {
+                message: Message::At(
+                    Box::new(Message::Evolved(*species, evolution.into)),
+                    *position,
+                ),
+            });
+        } else if !was_charging && maturing.is_charging(&evolution) {
+            text.send(AddMessage {
+                message: Message::At(Box::new(Message::EvolutionCharging(*species)), *position),
+            });
+        }
+    }
+}
+
+/// The pulsing light over a creature about to evolve.
+#[derive(Component)]
+pub struct EvolutionGlow {
+    creature: Entity,
+}
+
+fn render_evolution_glows(
+    creatures: Query<(Entity, &Transform, &Position, &Species, &Maturing)>,
+    mut glows: Query<(Entity, &EvolutionGlow, &mut Transform, &mut Sprite), Without<Maturing>>,
+    registry: Res<SpeciesRegistry>,
+    vision: Res<VisibilityMap>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut commands: Commands,
+) {
+    let charging = |creature: Entity| {
+        let (_, transform, position, species, maturing) = creatures.get(creature).ok()?;
+        let definition = registry.get(species);
+        let evolution = definition.evolves?;
+        maturing
+            .is_charging(&evolution)
+            .then_some((transform, position, definition.soul))
+    };
+    let pulse = 0.35 + 0.25 * (time.elapsed_secs() * 6.).sin();
+    let mut glowing = HashSet::new();
+    for (glow_entity, glow, mut transform, mut sprite) in glows.iter_mut() {
+        let Some((creature_transform, position, soul)) = charging(glow.creature) else {
+            commands.entity(glow_entity).despawn();
+            continue;
+        };
+        glowing.insert(glow.creature);
+        transform.translation = creature_transform.translation;
+        let alpha = if vision.is_visible(position) {
+            pulse
+        } else {
+            0.
+        };
+        sprite.color = get_caste_palette(&soul, settings.palette)
+            .primary
+            .with_alpha(alpha);
+    }
+    for (creature, ..) in creatures.iter() {
+        if glowing.contains(&creature) || charging(creature).is_none() {
+            continue;
+        }
+        commands.spawn((
+            EvolutionGlow { creature },
+            Sprite {
+                image: asset_server.load("spritesheet.png"),
+                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index: get_effect_sprite(&EffectType::GreenBlast),
+                }),
+                color: Color::NONE,
+                ..default()
+            },
+            // Placed on the creature next frame.
+            Transform::default(),
+            VisualLayer::Overlay,
+        ));
+    }
+}
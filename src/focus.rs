@@ -0,0 +1,56 @@
+use bevy::prelude::*;
+
+use crate::sets::Animation;
+
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_focus_rings.in_set(Animation));
+    }
+}
+
+/// The highlighted line of a modal menu. It gets a focus ring around it.
+#[derive(Component)]
+pub struct Focused;
+
+/// How many lines the keyboard moves the focus of a menu by: W/S and the
+/// arrow keys, or Tab and Shift+Tab.
+pub fn focus_step(input: &ButtonInput<KeyCode>) -> isize {
+    let shift = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let mut step = 0;
+    if input.any_just_pressed([KeyCode::ArrowUp, KeyCode::KeyW])
+        || (shift && input.just_pressed(KeyCode::Tab))
+    {
+        step -= 1;
+    }
+    if input.any_just_pressed([KeyCode::ArrowDown, KeyCode::KeyS])
+        || (!shift && input.just_pressed(KeyCode::Tab))
+    {
+        step += 1;
+    }
+    step
+}
+
+/// Whether the focused line of a menu was activated.
+pub fn focus_confirm(input: &ButtonInput<KeyCode>) -> bool {
+    input.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter])
+}
+
+/// Move the focus of a menu with this many lines, wrapping around.
+pub fn cycle_focus(focus: usize, step: isize, length: usize) -> usize {
+    if length == 0 {
+        return 0;
+    }
+    (focus as isize + step).rem_euclid(length as isize) as usize
+}
+
+fn draw_focus_rings(focused: Query<Entity, Added<Focused>>, mut commands: Commands) {
+    for entity in focused.iter() {
+        commands.entity(entity).insert(Outline {
+            width: Val::Px(0.2),
+            offset: Val::Px(0.1),
+            color: Color::srgb(1., 0.9, 0.2),
+        });
+    }
+}
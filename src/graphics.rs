@@ -1,21 +1,86 @@
-use std::f32::consts::PI;
+use std::{collections::VecDeque, f32::consts::PI};
 
-use bevy::prelude::*;
+use bevy::{prelude::*, sprite::Anchor, utils::HashSet};
 use rand::{thread_rng, Rng};
 
-use crate::{creature::Player, map::Position, TILE_SIZE};
+use crate::{
+    creature::{
+        BodyPart, CreatureFlags, Door, Facing, FlagQuery, Flying, Footprint, Hidden, Intangible,
+        Player, Revealed, ScrollPickup, Soul, Species, Spellbook, Wall, WeakPoints,
+    },
+    events::{DamageOrHealCreature, DoorPanel, RespawnPlayer},
+    input::Targeting,
+    map::{occupied_tiles, Map, Position},
+    scroll::ScrollRarity,
+    settings::{ColorPalette, Settings},
+    spells::{walk_grid, AimMode, CastSpell},
+    vision::VisibilityMap,
+    TILE_SIZE,
+};
 
 pub struct GraphicsPlugin;
 
 impl Plugin for GraphicsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SpriteSheetAtlas>();
+        app.init_resource::<AnimationQueue>();
+        app.init_resource::<EnemyPacing>();
+        app.init_resource::<VfxPool>();
+        app.init_resource::<CastChoreography>();
         app.add_event::<PlaceMagicVfx>();
         app.add_systems(Startup, setup_camera);
+        app.add_systems(
+            PostUpdate,
+            apply_visual_layers.before(TransformSystem::TransformPropagate),
+        );
         app.insert_resource(Screenshake { intensity: 0 });
     }
 }
 
+/// What a sprite is drawn above and below. Each layer is a band of heights:
+/// children can be nudged a bit up or down inside their parent's band,
+/// but nothing else should set its own height.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VisualLayer {
+    /// The colour of the floor, see TileKind.
+    Ground,
+    /// Walls, rails and conveyors, see TerrainMesh.
+    Terrain,
+    /// Marks left on the floor, like scorches and footprints.
+    Decal,
+    Creature,
+    /// Creatures raised off the ground, see render_elevation.
+    Flying,
+    Vfx,
+    /// Cursors, prompts and markers, above everything on the board.
+    Overlay,
+}
+
+impl VisualLayer {
+    pub fn z(&self) -> f32 {
+        match self {
+            VisualLayer::Ground => -3.,
+            VisualLayer::Terrain => -2.,
+            VisualLayer::Decal => -1.,
+            VisualLayer::Creature => 0.,
+            VisualLayer::Flying => 1.,
+            VisualLayer::Vfx => 2.,
+            VisualLayer::Overlay => 3.,
+        }
+    }
+}
+
+/// Put everything at the height of its VisualLayer, once every other system
+/// is done moving things around.
+pub fn apply_visual_layers(mut layered: Query<(&VisualLayer, &mut Transform)>) {
+    for (layer, mut transform) in layered.iter_mut() {
+        // Checked first, to not trigger change detection every frame.
+        if transform.translation.z != layer.z() {
+            transform.translation.z = layer.z();
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct Screenshake {
     pub intensity: usize,
@@ -39,12 +104,136 @@ impl FromWorld for SpriteSheetAtlas {
 }
 
 fn setup_camera(mut commands: Commands) {
-    commands.spawn((Camera2d, Transform::from_xyz(0., 0., 0.), Msaa::Off));
+    commands.spawn((
+        Camera2d,
+        Transform::from_xyz(0., 0., 0.),
+        Msaa::Off,
+        // Positional sounds are heard from the camera.
+        SpatialListener::new(TILE_SIZE * 2.),
+    ));
 }
 
 #[derive(Component)]
 pub struct SlideAnimation;
 
+/// Past this many queued steps, the queue plays everything at once to catch up.
+const ANIMATION_BACKLOG: usize = 12;
+
+/// Movements which happened during a turn, played back one at a time so
+/// that fast creatures don't all blur together.
+#[derive(Resource, Default)]
+pub struct AnimationQueue {
+    steps: VecDeque<AnimationStep>,
+    timer: Timer,
+}
+
+pub enum AnimationStep {
+    /// The creature slides to this tile.
+    Slide {
+        entity: Entity,
+        destination: Position,
+    },
+    /// The creature lunges by this offset, then slides back.
+    Lunge { entity: Entity, offset: (i32, i32) },
+}
+
+impl AnimationQueue {
+    pub fn push(&mut self, step: AnimationStep) {
+        self.steps.push_back(step);
+    }
+
+    /// How long until everything currently queued has played.
+    pub fn delay(&self, pacing: EnemyPacing) -> f32 {
+        self.steps.len() as f32 * pacing.step() + self.timer.remaining_secs()
+    }
+}
+
+/// How long the AnimationQueue waits between each queued action. This is separate
+/// from how fast sprites slide, which does not change.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnemyPacing {
+    /// Everything plays at once.
+    Instant,
+    Fast,
+    /// Slow enough to follow each creature's action.
+    #[default]
+    Readable,
+    Slow,
+}
+
+impl EnemyPacing {
+    /// How long each step of the AnimationQueue takes to play.
+    pub fn step(&self) -> f32 {
+        match self {
+            EnemyPacing::Instant => 0.,
+            EnemyPacing::Fast => 0.04,
+            EnemyPacing::Readable => 0.08,
+            EnemyPacing::Slow => 0.2,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            EnemyPacing::Instant => EnemyPacing::Fast,
+            EnemyPacing::Fast => EnemyPacing::Readable,
+            EnemyPacing::Readable => EnemyPacing::Slow,
+            EnemyPacing::Slow => EnemyPacing::Instant,
+        }
+    }
+}
+
+/// Where a creature is drawn while its queued movements have not played yet.
+#[derive(Component)]
+pub struct HeldAt {
+    pub position: Position,
+}
+
+/// Play the next step of the AnimationQueue once the previous one is done.
+pub fn play_animation_queue(
+    mut queue: ResMut<AnimationQueue>,
+    mut creatures: Query<(&Position, &mut Transform)>,
+    pacing: Res<EnemyPacing>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    queue.timer.tick(time.delta());
+    // If too much is queued, such as after a long Haste spree, play all of it at once.
+    let catch_up = queue.steps.len() > ANIMATION_BACKLOG || *pacing == EnemyPacing::Instant;
+    while catch_up || queue.timer.finished() {
+        let Some(step) = queue.steps.pop_front() else {
+            break;
+        };
+        match step {
+            AnimationStep::Slide {
+                entity,
+                destination,
+            } => {
+                // The creature might have died since.
+                let Ok((position, _)) = creatures.get(entity) else {
+                    continue;
+                };
+                if *position == destination {
+                    commands.entity(entity).remove::<HeldAt>();
+                } else {
+                    commands.entity(entity).insert(HeldAt {
+                        position: destination,
+                    });
+                }
+                commands.entity(entity).insert(SlideAnimation);
+            }
+            AnimationStep::Lunge { entity, offset } => {
+                let Ok((_, mut transform)) = creatures.get_mut(entity) else {
+                    continue;
+                };
+                transform.translation.x += offset.0 as f32 * TILE_SIZE / 4.;
+                transform.translation.y += offset.1 as f32 * TILE_SIZE / 4.;
+                commands.entity(entity).insert(SlideAnimation);
+            }
+        }
+        queue.timer = Timer::from_seconds(pacing.step(), TimerMode::Once);
+    }
+}
+
 /// Each frame, adjust every entity's display location to match
 /// their position on the grid, and make the camera follow the player.
 pub fn adjust_transforms(
@@ -54,13 +243,16 @@ pub fn adjust_transforms(
         &mut Transform,
         Has<SlideAnimation>,
         Has<Player>,
+        Option<&HeldAt>,
     )>,
     mut camera: Query<&mut Transform, (With<Camera>, Without<Position>)>,
     time: Res<Time>,
     mut commands: Commands,
     mut screenshake: ResMut<Screenshake>,
 ) {
-    for (entity, pos, mut trans, is_animated, is_player) in creatures.iter_mut() {
+    for (entity, pos, mut trans, is_animated, is_player, held_at) in creatures.iter_mut() {
+        // Creatures with movements still in the AnimationQueue wait for their turn.
+        let pos = held_at.map_or(pos, |held_at| &held_at.position);
         // If this creature is affected by an animation...
         if is_animated {
             // The sprite approaches its destination.
@@ -103,6 +295,348 @@ pub fn adjust_transforms(
     }
 }
 
+/// How high above its tile a creature is drawn, in tiles.
+#[derive(Component)]
+pub struct Elevation {
+    pub height: f32,
+}
+
+/// How high Flying creatures hover.
+const FLYING_HEIGHT: f32 = 0.3;
+
+/// A dark blot under a creature, showing where it actually stands.
+#[derive(Component)]
+pub struct DropShadow;
+
+/// Each frame, raise Flying creatures above the ground (or let them land),
+/// and keep a shadow under every creature that stands on its own.
+pub fn render_elevation(
+    mut creatures: Query<
+        (
+            Entity,
+            &CreatureFlags,
+            &mut Sprite,
+            Option<&mut Elevation>,
+            Option<&Footprint>,
+            Option<&Children>,
+            Option<&VisualLayer>,
+        ),
+        With<Species>,
+    >,
+    mut shadows: Query<(&mut Sprite, &mut Transform), (With<DropShadow>, Without<Species>)>,
+    flying: Query<(), With<Flying>>,
+    grounded: Query<(), Or<(With<Wall>, With<Door>, With<Intangible>)>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, flags, mut sprite, elevation, footprint, children, layer) in creatures.iter_mut() {
+        // Walls, doors and traps are part of the floor, and cast no shadow.
+        if grounded.contains(flags.species_flags) {
+            continue;
+        }
+        let (target, target_layer) = if flying.flagged(flags) {
+            (FLYING_HEIGHT, VisualLayer::Flying)
+        } else {
+            (0., VisualLayer::Creature)
+        };
+        if layer != Some(&target_layer) {
+            commands.entity(entity).insert(target_layer);
+        }
+        let height = match elevation {
+            Some(mut elevation) => {
+                elevation.height += (target - elevation.height) * (5. * time.delta_secs()).min(1.);
+                elevation.height
+            }
+            None => {
+                commands.entity(entity).insert(Elevation { height: target });
+                target
+            }
+        };
+        // Raise the sprite by shifting its anchor, so the Transform stays on the grid.
+        let (width, tile_height) = footprint.map_or((1, 1), |footprint| footprint.size());
+        let (width, tile_height) = (width as f32, tile_height as f32);
+        sprite.anchor = Anchor::Custom(Vec2::new(
+            0.5 / width - 0.5,
+            0.5 / tile_height - 0.5 - height / tile_height,
+        ));
+        // Shadows shrink as their creature rises, and disappear with it in the fog.
+        let opacity = 0.35 * sprite.color.alpha();
+        let shadow_size = Vec2::new(0.7 * width * TILE_SIZE, 0.25 * TILE_SIZE) * (1. - height);
+        let shadow_translation = Vec3::new((width - 1.) / 2. * TILE_SIZE, -0.4 * TILE_SIZE, -0.1);
+        let shadow = children.and_then(|children| {
+            children
+                .iter()
+                .find(|child| shadows.contains(**child))
+                .copied()
+        });
+        if let Some(shadow) = shadow {
+            let (mut shadow_sprite, mut shadow_transform) = shadows.get_mut(shadow).unwrap();
+            shadow_sprite.color = Color::BLACK.with_alpha(opacity);
+            shadow_sprite.custom_size = Some(shadow_size);
+            shadow_transform.translation = shadow_translation;
+        } else {
+            commands.entity(entity).with_children(|parent| {
+                parent.spawn((
+                    DropShadow,
+                    Sprite {
+                        color: Color::BLACK.with_alpha(opacity),
+                        custom_size: Some(shadow_size),
+                        ..default()
+                    },
+                    Transform::from_translation(shadow_translation),
+                ));
+            });
+        }
+    }
+}
+
+/// Each frame, the tiles of composite creatures take on the fog and elevation
+/// of the invisible sprite they belong to.
+pub fn render_body_parts(
+    creatures: Query<
+        (&Sprite, Option<&Elevation>, &Children),
+        (With<Footprint>, Without<BodyPart>),
+    >,
+    mut parts: Query<(&BodyPart, &mut Sprite, &mut Transform)>,
+) {
+    for (sprite, elevation, children) in creatures.iter() {
+        let height = elevation.map_or(0., |elevation| elevation.height);
+        for child in children.iter() {
+            let Ok((part, mut part_sprite, mut transform)) = parts.get_mut(*child) else {
+                continue;
+            };
+            part_sprite.color = sprite.color;
+            transform.translation.y = (part.offset.1 as f32 + height) * TILE_SIZE;
+        }
+    }
+}
+
+/// Each frame, hide creatures the player cannot see. Walls and doors which were
+/// seen before stay drawn, but dimmed.
+// NOTE: This tints sprites instead of touching their Visibility, which doors
+// already use to show whether they are open.
+pub fn apply_fog_of_war(
+    mut creatures: Query<(
+        &Position,
+        &CreatureFlags,
+        &mut Sprite,
+        Option<&Footprint>,
+        Has<Hidden>,
+        Has<Revealed>,
+        &Spellbook,
+    )>,
+    opaque: Query<(), Or<(With<Wall>, With<Door>)>>,
+    scrolls: Query<(), With<ScrollPickup>>,
+    vision: Res<VisibilityMap>,
+    time: Res<Time>,
+) {
+    for (position, flags, mut sprite, footprint, is_hidden, is_revealed, spellbook) in
+        creatures.iter_mut()
+    {
+        let tiles = occupied_tiles(*position, footprint);
+        let is_scroll = scrolls.contains(flags.species_flags);
+        let color = if is_hidden {
+            Color::NONE
+        } else if tiles.iter().any(|tile| vision.is_visible(tile)) && is_revealed {
+            // Found traps glow a warning red.
+            Color::srgb(1., 0.45, 0.45)
+        } else if tiles.iter().any(|tile| vision.is_visible(tile)) && is_scroll {
+            // Scrolls pulse in the colour of their rarity.
+            let glow = spellbook
+                .spells
+                .values()
+                .next()
+                .map_or(Color::WHITE, |spell| ScrollRarity::of(spell).glow());
+            glow.mix(
+                &Color::WHITE,
+                (time.elapsed_secs() * 3.).sin() * 0.25 + 0.25,
+            )
+        } else if tiles.iter().any(|tile| vision.is_visible(tile)) {
+            Color::WHITE
+        } else if tiles.iter().any(|tile| vision.is_remembered(tile)) && opaque.flagged(flags) {
+            Color::srgb(0.35, 0.35, 0.35)
+        } else {
+            Color::NONE
+        };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+/// Marks the tile the player is facing, when spells are aimed with Facing.
+#[derive(Component)]
+pub struct FacingIndicator;
+
+/// Each frame, place the facing indicator in front of the player.
+pub fn place_facing_indicator(
+    player: Query<(&Transform, &Facing), With<Player>>,
+    mut indicator: Query<
+        (&mut Transform, &mut Visibility),
+        (With<FacingIndicator>, Without<Player>),
+    >,
+    aim_mode: Res<AimMode>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut commands: Commands,
+) {
+    let Ok((mut indicator_transform, mut visibility)) = indicator.get_single_mut() else {
+        commands.spawn((
+            FacingIndicator,
+            Sprite {
+                image: asset_server.load("spritesheet.png"),
+                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index: get_effect_sprite(&EffectType::RedBlast),
+                }),
+                color: Color::srgba(1., 1., 1., 0.4),
+                ..default()
+            },
+            Transform::default(),
+            VisualLayer::Overlay,
+            Visibility::Hidden,
+        ));
+        return;
+    };
+    let Ok((player_transform, facing)) = player.get_single() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = if *aim_mode == AimMode::Facing {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    // Follow the player's sprite, not its Position, to keep up with its sliding.
+    let (off_x, off_y) = facing.direction.as_offset();
+    indicator_transform.translation = player_transform.translation
+        + Vec3::new(off_x as f32 * TILE_SIZE, off_y as f32 * TILE_SIZE, 0.);
+}
+
+/// Marks the tile picked in ControlState::Targeting.
+#[derive(Component)]
+pub struct TargetingCursor;
+
+/// Each frame, glide the targeting cursor towards the picked tile, or hide it
+/// if nothing is being aimed.
+pub fn render_targeting_cursor(
+    targeting: Option<Res<Targeting>>,
+    mut cursor: Query<(&mut Transform, &mut Visibility), With<TargetingCursor>>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let Ok((mut transform, mut visibility)) = cursor.get_single_mut() else {
+        commands.spawn((
+            TargetingCursor,
+            Sprite {
+                image: asset_server.load("spritesheet.png"),
+                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index: 18,
+                }),
+                color: Color::srgb(1., 0.4, 0.4),
+                ..default()
+            },
+            Transform::default(),
+            VisualLayer::Overlay,
+            Visibility::Hidden,
+        ));
+        return;
+    };
+    let Some(targeting) = targeting else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    let destination = Vec3::new(
+        targeting.position.x as f32 * TILE_SIZE,
+        targeting.position.y as f32 * TILE_SIZE,
+        VisualLayer::Overlay.z(),
+    );
+    // Appear right on the tile, then slide along as it moves.
+    if *visibility == Visibility::Hidden {
+        transform.translation = destination;
+        *visibility = Visibility::Inherited;
+    } else {
+        transform.translation = transform
+            .translation
+            .lerp(destination, (20. * time.delta_secs()).min(1.));
+    }
+}
+
+/// Drawn on top of a large creature's weak point.
+#[derive(Component)]
+pub struct WeakPointSprite {
+    creature: Entity,
+    offset: (i32, i32),
+}
+
+/// Each frame, mark the weak points of large creatures, cracking them once destroyed.
+pub fn render_weak_points(
+    creatures: Query<(Entity, &Transform, &WeakPoints)>,
+    mut markers: Query<
+        (Entity, &WeakPointSprite, &mut Transform, &mut Sprite),
+        Without<WeakPoints>,
+    >,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut commands: Commands,
+) {
+    let mut marked = HashSet::new();
+    for (marker_entity, marker, mut transform, mut sprite) in markers.iter_mut() {
+        let Some(weak_point) = creatures
+            .get(marker.creature)
+            .ok()
+            .and_then(|(_, _, weak_points)| weak_points.points.get(&marker.offset))
+        else {
+            commands.entity(marker_entity).despawn();
+            continue;
+        };
+        let (_, creature_transform, _) = creatures.get(marker.creature).unwrap();
+        transform.translation = creature_transform.translation
+            + Vec3::new(
+                marker.offset.0 as f32 * TILE_SIZE,
+                marker.offset.1 as f32 * TILE_SIZE,
+                0.,
+            );
+        sprite.texture_atlas.as_mut().unwrap().index = if weak_point.is_destroyed() {
+            get_effect_sprite(&EffectType::XCross)
+        } else {
+            get_effect_sprite(&EffectType::RedBlast)
+        };
+        marked.insert((marker.creature, marker.offset));
+    }
+    for (creature, _, weak_points) in creatures.iter() {
+        for offset in weak_points.points.keys() {
+            if marked.contains(&(creature, *offset)) {
+                continue;
+            }
+            commands.spawn((
+                WeakPointSprite {
+                    creature,
+                    offset: *offset,
+                },
+                Sprite {
+                    image: asset_server.load("spritesheet.png"),
+                    custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                    texture_atlas: Some(TextureAtlas {
+                        layout: atlas_layout.handle.clone(),
+                        index: get_effect_sprite(&EffectType::RedBlast),
+                    }),
+                    color: Color::srgba(1., 1., 1., 0.6),
+                    ..default()
+                },
+                // Placed on the creature next frame.
+                Transform::default(),
+                VisualLayer::Overlay,
+            ));
+        }
+    }
+}
+
 // graphics.rs
 #[derive(Bundle)]
 pub struct MagicEffect {
@@ -110,6 +644,7 @@ pub struct MagicEffect {
     pub position: Position,
     /// The sprite representing this visual effect.
     pub sprite: Sprite,
+    pub layer: VisualLayer,
     pub visibility: Visibility,
     /// The timers tracking when the effect appears, and how
     /// long it takes to decay.
@@ -129,6 +664,9 @@ pub struct PlaceMagicVfx {
     pub decay: f32,
     /// How long these effects take to appear.
     pub appear: f32,
+    /// The caste of the spell which caused these effects, if any.
+    /// They are drawn in its colours.
+    pub caste: Option<Soul>,
 }
 
 #[derive(Clone, Copy)]
@@ -140,7 +678,7 @@ pub enum EffectSequence {
     Sequential { duration: f32 },
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum EffectType {
     HorizontalBeam,
     VerticalBeam,
@@ -148,6 +686,7 @@ pub enum EffectType {
     GreenBlast,
     XCross,
     Airlock,
+    Deflect,
 }
 
 #[derive(Component)]
@@ -156,6 +695,19 @@ pub struct MagicVfx {
     pub appear: Timer,
     /// How long this effect takes to appear.
     pub decay: Timer,
+    /// Whether this effect is done, and waiting in the VfxPool to be reused.
+    pub pooled: bool,
+}
+
+/// Big fights place hundreds of effects per turn. Instead of despawning
+/// decayed effects, they are hidden and kept here to be reused.
+#[derive(Resource, Default)]
+pub struct VfxPool {
+    free: Vec<Entity>,
+    /// How many effects were placed by reusing a pooled entity.
+    pub hits: usize,
+    /// How many effects needed a new entity.
+    pub misses: usize,
 }
 
 /// Get the appropriate texture from the spritesheet depending on the effect type.
@@ -167,56 +719,329 @@ pub fn get_effect_sprite(effect: &EffectType) -> usize {
         EffectType::GreenBlast => 13,
         EffectType::XCross => 1,
         EffectType::Airlock => 17,
+        EffectType::Deflect => 54,
+    }
+}
+
+/// The colours in which a caste's spells are drawn. Sequential effects
+/// fade from the primary colour to the secondary one, leaving a trail.
+pub struct CastePalette {
+    pub primary: Color,
+    pub secondary: Color,
+}
+
+/// Under the ColorblindSafe palette, the green and orange castes are moved
+/// to colours which do not blend together.
+pub fn get_caste_palette(soul: &Soul, palette: ColorPalette) -> CastePalette {
+    let (primary, secondary) = match (palette, soul) {
+        (ColorPalette::ColorblindSafe, Soul::Artistic) => {
+            (Color::srgb(0., 0.62, 0.45), Color::srgb(0.5, 0.9, 0.8))
+        }
+        (ColorPalette::ColorblindSafe, Soul::Unhinged) => {
+            (Color::srgb(0.84, 0.37, 0.), Color::srgb(0.8, 0.47, 0.65))
+        }
+        (ColorPalette::ColorblindSafe, Soul::Feral) => {
+            (Color::srgb(0.94, 0.89, 0.26), Color::srgb(1., 1., 0.6))
+        }
+        (_, soul) => get_standard_caste_palette(soul),
+    };
+    CastePalette { primary, secondary }
+}
+
+fn get_standard_caste_palette(soul: &Soul) -> (Color, Color) {
+    match soul {
+        Soul::Saintly => (Color::srgb(1., 0.95, 0.6), Color::WHITE),
+        Soul::Ordered => (Color::srgb(0.6, 0.8, 1.), Color::srgb(0.85, 0.85, 0.9)),
+        Soul::Artistic => (Color::srgb(0.55, 0.75, 0.55), Color::srgb(0.8, 1., 0.4)),
+        Soul::Unhinged => (Color::srgb(1., 0.35, 0.1), Color::srgb(0.9, 0.5, 0.95)),
+        Soul::Feral => (Color::srgb(1., 0.6, 0.2), Color::srgb(0.9, 0.9, 0.2)),
+        Soul::Vile => (Color::srgb(0.8, 0.2, 0.9), Color::srgb(0.35, 0.1, 0.4)),
+        Soul::Empty => (Color::WHITE, Color::WHITE),
+    }
+}
+
+/// Blasts come in a warm and a cool variety, each caste uses
+/// the one its palette tints best.
+pub fn get_caste_effect(effect: EffectType, soul: &Soul) -> EffectType {
+    match (effect, soul) {
+        (
+            EffectType::RedBlast | EffectType::GreenBlast,
+            Soul::Saintly | Soul::Ordered | Soul::Artistic,
+        ) => EffectType::GreenBlast,
+        (
+            EffectType::RedBlast | EffectType::GreenBlast,
+            Soul::Unhinged | Soul::Feral | Soul::Vile,
+        ) => EffectType::RedBlast,
+        _ => effect,
+    }
+}
+
+/// How long each phase of a cast takes to play, before its effects resolve.
+#[derive(Clone, Copy)]
+pub struct CastProfile {
+    /// The caster glows, gathering its soul.
+    pub windup: f32,
+    /// A pulse travels from the caster to the first targets.
+    pub release: f32,
+}
+
+/// Patient castes take their time, violent ones lash out at once.
+pub fn get_cast_profile(soul: &Soul, pacing: EnemyPacing) -> CastProfile {
+    let (windup, release) = match soul {
+        Soul::Saintly => (0.15, 0.1),
+        Soul::Ordered => (0.1, 0.06),
+        Soul::Artistic => (0.12, 0.12),
+        Soul::Unhinged => (0.03, 0.05),
+        Soul::Feral => (0.05, 0.04),
+        Soul::Vile => (0.2, 0.08),
+        Soul::Empty => (0., 0.),
+    };
+    // Faster pacings skip the wind-up, then the release too.
+    match pacing {
+        EnemyPacing::Instant => CastProfile {
+            windup: 0.,
+            release: 0.,
+        },
+        EnemyPacing::Fast => CastProfile {
+            windup: 0.,
+            release,
+        },
+        EnemyPacing::Readable => CastProfile { windup, release },
+        EnemyPacing::Slow => CastProfile {
+            windup: windup * 1.5,
+            release: release * 1.5,
+        },
+    }
+}
+
+/// A cast which has wound up, and whose release pulse will be drawn towards
+/// the first effects of its caste.
+struct PendingRelease {
+    origin: Position,
+    caste: Soul,
+    profile: CastProfile,
+}
+
+/// Casts play in three phases: wind-up, release, then the effects of each axiom.
+#[derive(Resource)]
+pub struct CastChoreography {
+    pending: Vec<PendingRelease>,
+    /// Until this runs out, spell effects wait for the casts before them to wind up and release.
+    resolve: Timer,
+}
+
+impl Default for CastChoreography {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            resolve: Timer::from_seconds(0., TimerMode::Once),
+        }
+    }
+}
+
+/// Start the wind-up of every new cast, by making its caster glow.
+pub fn choreograph_casts(
+    mut casts: EventReader<CastSpell>,
+    mut choreography: ResMut<CastChoreography>,
+    position: Query<&Position>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    animation_queue: Res<AnimationQueue>,
+    pacing: Res<EnemyPacing>,
+    mut pool: ResMut<VfxPool>,
+    time: Res<Time>,
+    settings: Res<Settings>,
+) {
+    choreography.resolve.tick(time.delta());
+    let queue_delay = animation_queue.delay(*pacing);
+    let mut started = false;
+    for cast in casts.read() {
+        started = true;
+        // NOTE: Casters which died before their contingency fired have no position.
+        let Ok(origin) = position.get(cast.caster) else {
+            continue;
+        };
+        let profile = get_cast_profile(&cast.soul_caste, *pacing);
+        if profile.windup > 0. {
+            let glow = magic_effect(
+                *origin,
+                get_effect_sprite(&EffectType::XCross),
+                get_caste_palette(&cast.soul_caste, settings.palette).primary,
+                queue_delay,
+                profile.windup,
+                &asset_server,
+                &atlas_layout,
+            );
+            spawn_magic_effect(glow, &mut commands, &mut pool);
+        }
+        // Casts in the same turn wind up together, the slowest one holds the rest.
+        let total = profile.windup + profile.release;
+        if total > choreography.resolve.remaining_secs() {
+            choreography.resolve = Timer::from_seconds(total, TimerMode::Once);
+        }
+        choreography.pending.push(PendingRelease {
+            origin: *origin,
+            caste: cast.soul_caste,
+            profile,
+        });
+    }
+    // Casts which placed no effects at all are never released.
+    if choreography.resolve.finished() && !started {
+        choreography.pending.clear();
+    }
+}
+
+/// A single visual effect, hidden until `appear` seconds have passed.
+fn magic_effect(
+    position: Position,
+    index: usize,
+    color: Color,
+    appear: f32,
+    decay: f32,
+    asset_server: &AssetServer,
+    atlas_layout: &SpriteSheetAtlas,
+) -> MagicEffect {
+    MagicEffect {
+        position,
+        sprite: Sprite {
+            image: asset_server.load("spritesheet.png"),
+            custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+            texture_atlas: Some(TextureAtlas {
+                layout: atlas_layout.handle.clone(),
+                index,
+            }),
+            color,
+            ..default()
+        },
+        layer: VisualLayer::Vfx,
+        visibility: Visibility::Hidden,
+        vfx: MagicVfx {
+            appear: Timer::from_seconds(appear, TimerMode::Once),
+            decay: Timer::from_seconds(decay, TimerMode::Once),
+            pooled: false,
+        },
+    }
+}
+
+fn spawn_magic_effect(magic_effect: MagicEffect, commands: &mut Commands, pool: &mut VfxPool) {
+    // Reused effects get every component of the bundle overwritten,
+    // so nothing from their previous life lingers.
+    if let Some(vfx_entity) = pool.free.pop() {
+        pool.hits += 1;
+        commands.entity(vfx_entity).insert(magic_effect);
+    } else {
+        pool.misses += 1;
+        commands.spawn(magic_effect);
     }
 }
 
-// graphics.rs
 pub fn place_magic_effects(
     mut events: EventReader<PlaceMagicVfx>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     atlas_layout: Res<SpriteSheetAtlas>,
+    animation_queue: Res<AnimationQueue>,
+    pacing: Res<EnemyPacing>,
+    mut pool: ResMut<VfxPool>,
+    mut choreography: ResMut<CastChoreography>,
+    settings: Res<Settings>,
 ) {
+    // Effects wait for the movements queued before them to play out,
+    // then for the casts before them to wind up and release.
+    let queue_delay = animation_queue.delay(*pacing);
+    let resolve_delay = choreography.resolve.remaining_secs();
     for event in events.read() {
+        let (effect, palette) = match &event.caste {
+            Some(caste) => (
+                get_caste_effect(event.effect, caste),
+                get_caste_palette(caste, settings.palette),
+            ),
+            None => (
+                event.effect,
+                CastePalette {
+                    primary: Color::WHITE,
+                    secondary: Color::WHITE,
+                },
+            ),
+        };
+        // The first effects of a cast are preceded by its release pulse,
+        // travelling from the caster to the first target.
+        let release = event.caste.and_then(|caste| {
+            let index = choreography
+                .pending
+                .iter()
+                .position(|pending| pending.caste == caste)?;
+            Some(choreography.pending.remove(index))
+        });
+        if let (Some(release), Some(first)) = (release, event.targets.first()) {
+            // The caster's own tile already glowed during the wind-up.
+            let path = walk_grid(release.origin, *first);
+            let step = release.profile.release / path.len().max(1) as f32;
+            let start = queue_delay + resolve_delay - release.profile.release;
+            for (i, tile) in path.into_iter().skip(1).enumerate() {
+                let pulse = magic_effect(
+                    tile,
+                    get_effect_sprite(&EffectType::XCross),
+                    palette.primary,
+                    start.max(queue_delay) + i as f32 * step,
+                    step * 2.,
+                    &asset_server,
+                    &atlas_layout,
+                );
+                spawn_magic_effect(pulse, &mut commands, &mut pool);
+            }
+        }
         for (i, target) in event.targets.iter().enumerate() {
+            let color = match event.sequence {
+                EffectSequence::Sequential { .. } if event.targets.len() > 1 => {
+                    palette.primary.mix(
+                        &palette.secondary,
+                        i as f32 / (event.targets.len() - 1) as f32,
+                    )
+                }
+                _ => palette.primary,
+            };
+            let appear = match event.sequence {
+                // If simultaneous, everything appears at the same time.
+                EffectSequence::Simultaneous => event.appear,
+                // Otherwise, effects gradually get increased appear timers depending on
+                // how far back they are in their queue.
+                EffectSequence::Sequential { duration } => i as f32 * duration + event.appear,
+            };
             // Place effects on all positions from the event.
-            commands.spawn(MagicEffect {
-                position: *target,
-                sprite: Sprite {
-                    image: asset_server.load("spritesheet.png"),
-                    custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
-                    texture_atlas: Some(TextureAtlas {
-                        layout: atlas_layout.handle.clone(),
-                        index: get_effect_sprite(&event.effect),
-                    }),
-                    ..default()
-                },
-                visibility: Visibility::Hidden,
-                vfx: MagicVfx {
-                    appear: match event.sequence {
-                        // If simultaneous, everything appears at the same time.
-                        EffectSequence::Simultaneous => {
-                            Timer::from_seconds(event.appear, TimerMode::Once)
-                        }
-                        // Otherwise, effects gradually get increased appear timers depending on
-                        // how far back they are in their queue.
-                        EffectSequence::Sequential { duration } => {
-                            Timer::from_seconds(i as f32 * duration + event.appear, TimerMode::Once)
-                        }
-                    },
-                    decay: Timer::from_seconds(event.decay, TimerMode::Once),
-                },
-            });
+            let magic_effect = magic_effect(
+                *target,
+                get_effect_sprite(&effect),
+                color,
+                appear + queue_delay + resolve_delay,
+                event.decay,
+                &asset_server,
+                &atlas_layout,
+            );
+            spawn_magic_effect(magic_effect, &mut commands, &mut pool);
         }
     }
 }
 
 pub fn decay_magic_effects(
     mut commands: Commands,
-    mut magic_vfx: Query<(Entity, &mut Visibility, &mut MagicVfx, &mut Sprite)>,
+    mut magic_vfx: Query<(
+        Entity,
+        &mut Visibility,
+        &mut MagicVfx,
+        &mut Sprite,
+        Has<DoorPanel>,
+    )>,
+    mut pool: ResMut<VfxPool>,
     time: Res<Time>,
 ) {
-    for (vfx_entity, mut vfx_vis, mut vfx_timers, mut vfx_sprite) in magic_vfx.iter_mut() {
+    for (vfx_entity, mut vfx_vis, mut vfx_timers, mut vfx_sprite, is_door_panel) in
+        magic_vfx.iter_mut()
+    {
+        if vfx_timers.pooled {
+            continue;
+        }
         // Effects that have completed their appear timer and are now visible, decay.
         if matches!(*vfx_vis, Visibility::Inherited) {
             vfx_timers.decay.tick(time.delta());
@@ -225,7 +1050,14 @@ pub fn decay_magic_effects(
                 .color
                 .set_alpha(vfx_timers.decay.fraction_remaining());
             if vfx_timers.decay.finished() {
-                commands.entity(vfx_entity).despawn();
+                // NOTE: Door panels are despawned by their door, they can't be pooled.
+                if is_door_panel {
+                    commands.entity(vfx_entity).despawn();
+                } else {
+                    *vfx_vis = Visibility::Hidden;
+                    vfx_timers.pooled = true;
+                    pool.free.push(vfx_entity);
+                }
             }
         // Effects that have not appeared yet progress towards appearing for the first time.
         } else {
@@ -236,3 +1068,158 @@ pub fn decay_magic_effects(
         }
     }
 }
+
+/// What kind of mark a decal leaves on the board.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecalKind {
+    /// Left on the floor by spells and heavy blows.
+    Scorch,
+    /// Left on walls next to where something died.
+    Crack,
+}
+
+/// A lasting mark of battle. It slowly fades, but never entirely,
+/// until the floor is left behind.
+#[derive(Component)]
+pub struct Decal {
+    position: Position,
+    kind: DecalKind,
+    /// How opaque this decal was when it was last refreshed.
+    intensity: f32,
+    fade: Timer,
+}
+
+/// How long decals take to fade down to their resting opacity.
+const DECAL_FADE_TIME: f32 = 60.;
+/// Decals never fade below this fraction of their intensity.
+const DECAL_RESTING_OPACITY: f32 = 0.25;
+
+fn get_decal_sprite(kind: &DecalKind) -> (usize, Color, VisualLayer) {
+    // Sprite, tint, and layer: scorch marks go under creatures, cracks on top of walls,
+    // level with Flying creatures.
+    match kind {
+        DecalKind::Scorch => (
+            get_effect_sprite(&EffectType::RedBlast),
+            Color::srgb(0.15, 0.1, 0.1),
+            VisualLayer::Decal,
+        ),
+        DecalKind::Crack => (
+            get_effect_sprite(&EffectType::XCross),
+            Color::srgb(0.2, 0.2, 0.2),
+            VisualLayer::Flying,
+        ),
+    }
+}
+
+/// Leave decals where damage was dealt, where spells landed, and on walls
+/// next to slain creatures.
+pub fn place_decals(
+    mut damage: EventReader<DamageOrHealCreature>,
+    mut vfx: EventReader<PlaceMagicVfx>,
+    mut respawn: EventReader<RespawnPlayer>,
+    creatures: Query<&Position>,
+    walls: Query<&CreatureFlags>,
+    wall_flags: Query<(), With<Wall>>,
+    mut decals: Query<(Entity, &mut Decal)>,
+    map: Res<Map>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut commands: Commands,
+) {
+    // A new floor starts clean.
+    if respawn.read().count() > 0 {
+        for (entity, _) in decals.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+    let mut marks: Vec<(Position, DecalKind, f32)> = Vec::new();
+    for event in damage.read() {
+        if event.hp_mod >= 0 {
+            continue;
+        }
+        let Some(position) = event
+            .tile
+            .or_else(|| creatures.get(event.entity).ok().copied())
+        else {
+            continue;
+        };
+        // Heavier blows leave darker marks.
+        marks.push((position, DecalKind::Scorch, 0.3 * -event.hp_mod as f32));
+    }
+    for event in vfx.read() {
+        if event.caste.is_some() {
+            for target in &event.targets {
+                marks.push((*target, DecalKind::Scorch, 0.1));
+            }
+        }
+        // NOTE: By now, removed creatures are already gone, but remove_creature
+        // flashes an X where they were.
+        if event.effect != EffectType::XCross {
+            continue;
+        }
+        for (position, (dx, dy)) in event
+            .targets
+            .iter()
+            .flat_map(|target| [(0, 1), (1, 0), (0, -1), (-1, 0)].map(|delta| (target, delta)))
+        {
+            let adjacent = Position::new(position.x + dx, position.y + dy);
+            let is_wall = map
+                .get_entity_at(adjacent.x, adjacent.y)
+                .and_then(|entity| walls.get(*entity).ok())
+                .is_some_and(|flags| wall_flags.contains(flags.species_flags));
+            if is_wall {
+                marks.push((adjacent, DecalKind::Crack, 0.6));
+            }
+        }
+    }
+    for (position, kind, intensity) in marks {
+        let intensity = intensity.min(1.);
+        // Marks on the same tile build up on each other instead of stacking sprites.
+        if let Some((_, mut decal)) = decals
+            .iter_mut()
+            .find(|(_, decal)| decal.position == position && decal.kind == kind)
+        {
+            decal.intensity = (decal.intensity + intensity).min(1.);
+            decal.fade.reset();
+            continue;
+        }
+        let (index, color, layer) = get_decal_sprite(&kind);
+        commands.spawn((
+            Decal {
+                position,
+                kind,
+                intensity,
+                fade: Timer::from_seconds(DECAL_FADE_TIME, TimerMode::Once),
+            },
+            Sprite {
+                image: asset_server.load("spritesheet.png"),
+                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index,
+                }),
+                color: color.with_alpha(intensity),
+                ..default()
+            },
+            Transform::from_xyz(
+                position.x as f32 * TILE_SIZE,
+                position.y as f32 * TILE_SIZE,
+                0.,
+            ),
+            layer,
+        ));
+    }
+}
+
+pub fn fade_decals(mut decals: Query<(&mut Decal, &mut Sprite)>, time: Res<Time>) {
+    for (mut decal, mut sprite) in decals.iter_mut() {
+        if decal.fade.finished() {
+            continue;
+        }
+        decal.fade.tick(time.delta());
+        let opacity =
+            DECAL_RESTING_OPACITY + (1. - DECAL_RESTING_OPACITY) * decal.fade.fraction_remaining();
+        sprite.color.set_alpha(decal.intensity * opacity);
+    }
+}
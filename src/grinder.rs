@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpse::{despawn_corpse, Corpse},
+    creature::Species,
+    events::{RemoveCreature, RespawnPlayer, SummonCreature, TurnManager},
+    graphics::{EffectSequence, EffectType, PlaceMagicVfx},
+    map::{Map, Position},
+    rng::GameRng,
+    ui::{AddMessage, Message},
+    OrdDir,
+};
+
+pub struct GrinderPlugin;
+
+impl Plugin for GrinderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Grinder>();
+    }
+}
+
+/// How many turns can be spent on a floor before the grinder shows up.
+pub const GRINDER_DELAY: usize = 120;
+/// How many turns the grinder waits before devouring another line of the floor.
+pub const GRINDER_INTERVAL: usize = 4;
+
+/// Lingering on a floor for too long wakes up the grinder, which devours
+/// it one line at a time from one of its edges.
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+pub struct Grinder {
+    /// Turns spent on this floor.
+    pub turns: usize,
+    last_turn: usize,
+    /// Where the grinder is, once it has started moving.
+    pub front: Option<GrinderFront>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GrinderFront {
+    /// The edge of the floor the grinder comes from.
+    pub edge: OrdDir,
+    /// How many lines of the floor have been devoured.
+    pub depth: i32,
+    min: Position,
+    max: Position,
+}
+
+impl GrinderFront {
+    /// All tiles of the line `depth` lines away from the edge.
+    pub fn line(&self, depth: i32) -> Vec<Position> {
+        let (min, max) = (self.min, self.max);
+        match self.edge {
+            OrdDir::Up => (min.x..=max.x)
+                .map(|x| Position::new(x, max.y - depth))
+                .collect(),
+            OrdDir::Down => (min.x..=max.x)
+                .map(|x| Position::new(x, min.y + depth))
+                .collect(),
+            OrdDir::Right => (min.y..=max.y)
+                .map(|y| Position::new(max.x - depth, y))
+                .collect(),
+            OrdDir::Left => (min.y..=max.y)
+                .map(|y| Position::new(min.x + depth, y))
+                .collect(),
+        }
+    }
+
+    /// Whether this tile was already devoured.
+    pub fn is_consumed(&self, position: &Position) -> bool {
+        let distance = match self.edge {
+            OrdDir::Up => self.max.y - position.y,
+            OrdDir::Down => position.y - self.min.y,
+            OrdDir::Right => self.max.x - position.x,
+            OrdDir::Left => position.x - self.min.x,
+        };
+        distance < self.depth
+    }
+
+    /// Whether the entire floor was devoured.
+    fn is_done(&self) -> bool {
+        match self.edge {
+            OrdDir::Up | OrdDir::Down => self.depth > self.max.y - self.min.y,
+            OrdDir::Right | OrdDir::Left => self.depth > self.max.x - self.min.x,
+        }
+    }
+}
+
+/// Once per turn, bring the grinder closer. The next line it will devour is
+/// telegraphed with red flashes.
+// TODO: Show the grinder on the minimap, once there is one.
+pub fn advance_grinder(
+    mut respawn: EventReader<RespawnPlayer>,
+    turn_manager: Res<TurnManager>,
+    mut grinder: ResMut<Grinder>,
+    mut map: ResMut<Map>,
+    creatures: Query<(Entity, &Position, &Species)>,
+    corpses: Query<(Entity, &Corpse)>,
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    mut summon: EventWriter<SummonCreature>,
+    mut remove: EventWriter<RemoveCreature>,
+    mut magic_vfx: EventWriter<PlaceMagicVfx>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if respawn.read().count() > 0 {
+        *grinder = Grinder {
+            last_turn: turn_manager.turn_count,
+            ..default()
+        };
+        return;
+    }
+    if turn_manager.turn_count == grinder.last_turn {
+        return;
+    }
+    grinder.last_turn = turn_manager.turn_count;
+    grinder.turns += 1;
+    if grinder.turns < GRINDER_DELAY {
+        return;
+    }
+    if grinder.front.is_none() {
+        let xs = map.creatures.keys().map(|position| position.x);
+        let ys = map.creatures.keys().map(|position| position.y);
+        let (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) =
+            (xs.clone().min(), xs.max(), ys.clone().min(), ys.max())
+        else {
+            return;
+        };
+        let edge = *[OrdDir::Up, OrdDir::Right, OrdDir::Down, OrdDir::Left]
+            .choose(&mut *rng)
+            .unwrap();
+        grinder.front = Some(GrinderFront {
+            edge,
+            depth: 0,
+            min: Position::new(min_x, min_y),
+            max: Position::new(max_x, max_y),
+        });
+        text.send(AddMessage {
+            message: Message::GrinderApproaches,
+        });
+    }
+    let turns = grinder.turns;
+    let front = grinder.front.as_mut().unwrap();
+    if front.is_done() {
+        return;
+    }
+    // Anything which wandered into the void is devoured.
+    for (entity, position, species) in creatures.iter() {
+        if *species != Species::Grinder && front.is_consumed(position) {
+            remove.send(RemoveCreature {
+                entity,
+                culprit: None,
+            });
+        }
+    }
+    if (turns - GRINDER_DELAY).is_multiple_of(GRINDER_INTERVAL) {
+        let line = front.line(front.depth);
+        // Corpses in its way clog the grinder, which spends its advance chewing through them.
+        let clogs: Vec<(Entity, &Corpse)> = corpses
+            .iter()
+            .filter(|(_, corpse)| corpse.tiles.iter().any(|tile| line.contains(tile)))
+            .collect();
+        if !clogs.is_empty() {
+            for (entity, corpse) in clogs {
+                despawn_corpse(entity, corpse, &mut map, &mut commands);
+            }
+            return;
+        }
+        for (entity, position, species) in creatures.iter() {
+            if *species != Species::Grinder && line.contains(position) {
+                remove.send(RemoveCreature {
+                    entity,
+                    culprit: None,
+                });
+            }
+        }
+        for position in line {
+            summon.send(SummonCreature {
+                position,
+                species: Species::Grinder,
+                momentum: front.edge,
+                summoner_tile: position,
+                summoner: None,
+                spellbook: None,
+                projectile: None,
+                owner: None,
+            });
+        }
+        front.depth += 1;
+    }
+    if !front.is_done() {
+        magic_vfx.send(PlaceMagicVfx {
+            targets: front.line(front.depth),
+            sequence: EffectSequence::Simultaneous,
+            effect: EffectType::RedBlast,
+            decay: 0.5,
+            appear: 0.,
+            caste: None,
+        });
+    }
+}
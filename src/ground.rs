@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+
+use crate::{
+    conveyor::{Flung, ResidualMomentum},
+    creature::{CreatureFlags, DamageKind, FlagQuery, Flying, Mired, Player},
+    events::{DamageOrHealCreature, SteppedOnTile, TeleportEntity},
+    map::{Map, Position, TileKind},
+    ui::{AddMessage, Message},
+    OrdDir,
+};
+
+/// The furthest a creature can slide on ice in one go.
+const MAX_SLIDE: usize = 20;
+
+/// Creatures on foot feel the ground they step on: lava burns them, mud holds
+/// them fast, and ice sends them sliding onwards until they hit something.
+/// Flying creatures pass over all of it.
+pub fn tread_terrain(
+    mut events: EventReader<SteppedOnTile>,
+    map: Res<Map>,
+    creatures: Query<(&CreatureFlags, Has<Mired>, Has<Player>)>,
+    flying: Query<(), With<Flying>>,
+    mut residual: ResMut<ResidualMomentum>,
+    mut teleport: EventWriter<TeleportEntity>,
+    mut harm: EventWriter<DamageOrHealCreature>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        // NOTE: Intangible creatures are missing from the Map, and never touch the ground.
+        if map.get_entity_at(event.position.x, event.position.y) != Some(&event.entity) {
+            continue;
+        }
+        let Ok((flags, is_mired, is_player)) = creatures.get(event.entity) else {
+            continue;
+        };
+        if flying.flagged(flags) {
+            continue;
+        }
+        let kind = map.tile_kind(&event.position);
+        // Creatures moved out of the mud by something else are free of it.
+        if is_mired && kind != TileKind::Mud {
+            commands.entity(event.entity).remove::<Mired>();
+        }
+        match kind {
+            TileKind::Lava => {
+                harm.send(DamageOrHealCreature {
+                    entity: event.entity,
+                    culprit: event.entity,
+                    hp_mod: -1,
+                    tile: None,
+                    kind: DamageKind::Terrain,
+                });
+                if is_player {
+                    text.send(AddMessage {
+                        message: Message::Scorched,
+                    });
+                }
+            }
+            TileKind::Mud => {
+                commands.entity(event.entity).try_insert(Mired);
+                if is_player && !is_mired {
+                    text.send(AddMessage {
+                        message: Message::StuckInMud,
+                    });
+                }
+            }
+            TileKind::Ice => {
+                let Some(direction) = OrdDir::direction_towards(event.origin, event.position)
+                else {
+                    continue;
+                };
+                // Just like a dash, but it only ends at an obstacle or at the edge of the ice.
+                let (off_x, off_y) = direction.as_offset();
+                let mut destination = event.position;
+                let mut path = Vec::new();
+                while path.len() < MAX_SLIDE {
+                    let next = Position::new(destination.x + off_x, destination.y + off_y);
+                    if !map.is_passable(next.x, next.y) {
+                        break;
+                    }
+                    destination = next;
+                    path.push(next);
+                    if map.tile_kind(&next) != TileKind::Ice {
+                        break;
+                    }
+                }
+                if path.is_empty() {
+                    continue;
+                }
+                teleport.send(TeleportEntity {
+                    destination,
+                    entity: event.entity,
+                });
+                // Conveyor belts crossed on the way may carry it a little further.
+                residual.flung.push(Flung {
+                    entity: event.entity,
+                    direction,
+                    path,
+                });
+            }
+            TileKind::Floor | TileKind::Water => (),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+
+use crate::{
+    events::TurnManager,
+    graphics::SpriteSheetAtlas,
+    save::thumbnail,
+    sets::{modal_is_open, PlayerInput},
+    ui::{AddMessage, Message},
+};
+
+pub struct HighlightPlugin;
+
+impl Plugin for HighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HighlightReel>();
+        app.add_systems(Update, record_highlight_frame);
+        app.add_systems(
+            Update,
+            export_highlight
+                .run_if(not(modal_is_open))
+                .in_set(PlayerInput),
+        );
+    }
+}
+
+/// How many turns the highlight reel remembers, at HIGHLIGHT_DELAY each.
+const HIGHLIGHT_FRAMES: usize = 40;
+/// How long each turn stays on screen in an exported highlight, in hundredths of a second.
+const HIGHLIGHT_DELAY: u16 = 35;
+/// How many tiles away from the player a highlight shows.
+const HIGHLIGHT_RADIUS: i32 = 8;
+/// The size of a sprite on the spritesheet, in pixels.
+const SPRITE_SIZE: usize = 16;
+pub const HIGHLIGHT_FOLDER: &str = "highlights";
+
+/// The last few turns around the player, as the sprites on each tile. Exported
+/// as an animated GIF with I, to show off a big kill or a death.
+#[derive(Resource, Default)]
+pub struct HighlightReel {
+    frames: VecDeque<Vec<Vec<Option<usize>>>>,
+}
+
+/// Once per turn, remember what surrounds the player.
+// NOTE: Replays and headless worlds have no reel, this only watches the turn count.
+fn record_highlight_frame(world: &mut World, mut last_turn: Local<Option<usize>>) {
+    let turn = world.resource::<TurnManager>().turn_count;
+    if *last_turn == Some(turn) {
+        return;
+    }
+    *last_turn = Some(turn);
+    let frame = thumbnail(world, HIGHLIGHT_RADIUS);
+    if frame.is_empty() {
+        return;
+    }
+    let mut reel = world.resource_mut::<HighlightReel>();
+    if reel.frames.len() == HIGHLIGHT_FRAMES {
+        reel.frames.pop_front();
+    }
+    reel.frames.push_back(frame);
+}
+
+/// I draws the reel with the spritesheet, and saves it in HIGHLIGHT_FOLDER.
+fn export_highlight(
+    input: Res<ButtonInput<KeyCode>>,
+    reel: Res<HighlightReel>,
+    images: Res<Assets<Image>>,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    atlas: Res<SpriteSheetAtlas>,
+    asset_server: Res<AssetServer>,
+    turn_manager: Res<TurnManager>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if !input.just_pressed(KeyCode::KeyI) {
+        return;
+    }
+    let spritesheet = asset_server
+        .get_handle::<Image>("spritesheet.png")
+        .and_then(|handle| images.get(&handle));
+    let (Some(spritesheet), Some(layout)) = (spritesheet, layouts.get(&atlas.handle)) else {
+        return;
+    };
+    let path = format!(
+        "{}/highlight_{}.gif",
+        HIGHLIGHT_FOLDER, turn_manager.turn_count
+    );
+    let result = render_reel(&reel, spritesheet, layout).and_then(|gif| {
+        std::fs::create_dir_all(HIGHLIGHT_FOLDER).map_err(|error| error.to_string())?;
+        std::fs::write(&path, gif).map_err(|error| error.to_string())
+    });
+    text.send(AddMessage {
+        message: match result {
+            Ok(()) => Message::HighlightSaved(path),
+            Err(error) => Message::HighlightFailed(error),
+        },
+    });
+}
+
+/// Every frame of the reel, drawn tile by tile and encoded as a GIF.
+fn render_reel(
+    reel: &HighlightReel,
+    spritesheet: &Image,
+    layout: &TextureAtlasLayout,
+) -> Result<Vec<u8>, String> {
+    if !matches!(
+        spritesheet.texture_descriptor.format,
+        TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm
+    ) {
+        return Err("the spritesheet is not in RGBA".to_owned());
+    }
+    let Some(first) = reel.frames.front() else {
+        return Err("nothing happened yet".to_owned());
+    };
+    let (columns, rows) = (first.first().map_or(0, Vec::len), first.len());
+    let (width, height) = (columns * SPRITE_SIZE, rows * SPRITE_SIZE);
+    let sheet_width = spritesheet.width() as usize;
+    let frames: Vec<Vec<u8>> = reel
+        .frames
+        .iter()
+        .map(|frame| {
+            let mut pixels = vec![0; width * height];
+            for (row, tiles) in frame.iter().enumerate() {
+                for (column, sprite) in tiles.iter().enumerate() {
+                    let Some(rect) = sprite.and_then(|sprite| layout.textures.get(sprite)) else {
+                        continue;
+                    };
+                    for y in 0..SPRITE_SIZE {
+                        for x in 0..SPRITE_SIZE {
+                            let source =
+                                ((rect.min.y as usize + y) * sheet_width + rect.min.x as usize + x)
+                                    * 4;
+                            let Some(&[r, g, b, a]) = spritesheet.data.get(source..source + 4)
+                            else {
+                                continue;
+                            };
+                            if a < 128 {
+                                continue;
+                            }
+                            let destination =
+                                (row * SPRITE_SIZE + y) * width + column * SPRITE_SIZE + x;
+                            pixels[destination] = palette_index(r, g, b);
+                        }
+                    }
+                }
+            }
+            pixels
+        })
+        .collect();
+    Ok(encode_gif(width as u16, height as u16, &frames))
+}
+
+/// Colours are squeezed into 3 bits of red, 3 of green and 2 of blue, which
+/// fits the 256 colours of a GIF palette.
+fn palette_index(r: u8, g: u8, b: u8) -> u8 {
+    (r & 0b1110_0000) | ((g >> 5) << 2) | (b >> 6)
+}
+
+/// An endlessly looping GIF, with every frame on the palette of palette_index.
+fn encode_gif(width: u16, height: u16, frames: &[Vec<u8>]) -> Vec<u8> {
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend(width.to_le_bytes());
+    gif.extend(height.to_le_bytes());
+    // A global palette of 256 colours, a black background, square pixels.
+    gif.extend([0b1111_0111, 0, 0]);
+    for index in 0..=255u8 {
+        let (r, g, b) = (index >> 5, (index >> 2) & 0b111, index & 0b11);
+        gif.extend([
+            (r as u16 * 255 / 7) as u8,
+            (g as u16 * 255 / 7) as u8,
+            (b as u16 * 255 / 3) as u8,
+        ]);
+    }
+    // Loop forever.
+    gif.extend(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+    for frame in frames {
+        // How long this frame stays on screen.
+        gif.extend([0x21, 0xF9, 0x04, 0x00]);
+        gif.extend(HIGHLIGHT_DELAY.to_le_bytes());
+        gif.extend([0x00, 0x00]);
+        // The frame covers the whole image.
+        gif.push(0x2C);
+        gif.extend([0, 0, 0, 0]);
+        gif.extend(width.to_le_bytes());
+        gif.extend(height.to_le_bytes());
+        gif.push(0);
+        gif.push(8);
+        for block in lzw_compress(frame).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3B);
+    gif
+}
+
+/// The variable-length LZW of GIF, for 8 bit colour indices.
+fn lzw_compress(indices: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+    const MAX_CODES: u16 = 4096;
+    let mut output = Vec::new();
+    let (mut buffer, mut buffered) = (0u32, 0);
+    let mut emit = |code: u16, size: u32, output: &mut Vec<u8>| {
+        buffer |= (code as u32) << buffered;
+        buffered += size;
+        while buffered >= 8 {
+            output.push(buffer as u8);
+            buffer >>= 8;
+            buffered -= 8;
+        }
+    };
+    let mut dictionary = bevy::utils::HashMap::new();
+    let (mut next_code, mut size) = (END + 1, 9);
+    emit(CLEAR, size, &mut output);
+    let Some((&first, rest)) = indices.split_first() else {
+        emit(END, size, &mut output);
+        return output;
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = dictionary.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+        emit(prefix, size, &mut output);
+        if next_code < MAX_CODES {
+            dictionary.insert((prefix, index), next_code);
+            next_code += 1;
+            if next_code > 1 << size && size < 12 {
+                size += 1;
+            }
+        } else {
+            emit(CLEAR, size, &mut output);
+            dictionary.clear();
+            (next_code, size) = (END + 1, 9);
+        }
+        prefix = index as u16;
+    }
+    emit(prefix, size, &mut output);
+    emit(END, size, &mut output);
+    if buffered > 0 {
+        output.push(buffer as u8);
+    }
+    output
+}
@@ -0,0 +1,152 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::{
+    creature::{Awake, Footprint, Species},
+    graphics::{AnimationQueue, EnemyPacing, SlideAnimation},
+    map::Position,
+    sets::{Animation, ControlState},
+    species::SpeciesRegistry,
+    spells::spell_stack_is_empty,
+    vision::VisibilityMap,
+};
+
+pub struct IdlePlugin;
+
+impl Plugin for IdlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                start_idle_ticks.run_if(in_state(ControlState::Player).and(spell_stack_is_empty)),
+                play_idle_ticks,
+            )
+                .chain()
+                .in_set(Animation),
+        );
+    }
+}
+
+/// How often, on average, a creature fidgets while the player thinks.
+const IDLE_TICKS_PER_SECOND: f32 = 0.15;
+
+/// Something a creature does while waiting for the player, written in creatures.ron.
+/// Purely for show: these only ever touch the sprite, never the simulation.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleKind {
+    /// A little wobble from side to side.
+    Shuffle,
+    /// A quick squint.
+    Blink,
+    /// A slow tilt to one side, and a look behind.
+    Preen,
+}
+
+impl IdleKind {
+    fn seconds(&self) -> f32 {
+        match self {
+            IdleKind::Shuffle => 0.5,
+            IdleKind::Blink => 0.2,
+            IdleKind::Preen => 1.2,
+        }
+    }
+
+    /// The rotation, vertical scale and flip of the sprite, `progress` from 0 to 1 through.
+    fn pose(&self, progress: f32) -> (f32, f32, bool) {
+        match self {
+            IdleKind::Shuffle => ((progress * PI * 4.).sin() * 0.12, 1., false),
+            IdleKind::Blink => (0., 1. - (progress * PI).sin() * 0.2, false),
+            IdleKind::Preen => (
+                -(progress * PI).sin() * 0.25,
+                1. - (progress * PI).sin() * 0.05,
+                (0.3..0.7).contains(&progress),
+            ),
+        }
+    }
+}
+
+/// A creature in the middle of fidgeting.
+#[derive(Component)]
+pub struct IdleTick {
+    kind: IdleKind,
+    elapsed: f32,
+    /// Creatures are summoned facing the way they came from.
+    rotation: Quat,
+    /// The scale and flip the sprite goes back to once the tick is over.
+    scale: Vec3,
+    flip_x: bool,
+}
+
+/// Once everything has finished moving, creatures in sight now and then pick
+/// one of their species' idle ticks.
+// NOTE: This uses thread_rng, never GameRng, so replays are left untouched.
+fn start_idle_ticks(
+    creatures: Query<
+        (Entity, &Position, &Species, &Transform, &Sprite),
+        (
+            With<Awake>,
+            Without<IdleTick>,
+            Without<SlideAnimation>,
+            Without<Footprint>,
+        ),
+    >,
+    queue: Res<AnimationQueue>,
+    pacing: Res<EnemyPacing>,
+    vision: Res<VisibilityMap>,
+    registry: Res<SpeciesRegistry>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    if queue.delay(*pacing) > 0. {
+        return;
+    }
+    let mut rng = thread_rng();
+    let chance = (IDLE_TICKS_PER_SECOND * time.delta_secs()).min(1.) as f64;
+    for (entity, position, species, transform, sprite) in creatures.iter() {
+        if !vision.is_visible(position) || !rng.gen_bool(chance) {
+            continue;
+        }
+        if let Some(kind) = registry.get(species).idles.choose(&mut rng) {
+            commands.entity(entity).insert(IdleTick {
+                kind: *kind,
+                elapsed: 0.,
+                rotation: transform.rotation,
+                scale: transform.scale,
+                flip_x: sprite.flip_x,
+            });
+        }
+    }
+}
+
+/// Play each idle tick, and put the sprite back as it was once it's over,
+/// or as soon as the creature has somewhere to be.
+fn play_idle_ticks(
+    mut creatures: Query<(
+        Entity,
+        &mut IdleTick,
+        &mut Transform,
+        &mut Sprite,
+        Has<SlideAnimation>,
+    )>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut tick, mut transform, mut sprite, is_animated) in creatures.iter_mut() {
+        tick.elapsed += time.delta_secs();
+        let progress = tick.elapsed / tick.kind.seconds();
+        if progress >= 1. || is_animated {
+            transform.rotation = tick.rotation;
+            transform.scale = tick.scale;
+            sprite.flip_x = tick.flip_x;
+            commands.entity(entity).remove::<IdleTick>();
+            continue;
+        }
+        let (angle, height, flipped) = tick.kind.pose(progress);
+        transform.rotation = tick.rotation * Quat::from_rotation_z(angle);
+        transform.scale.y = tick.scale.y * height;
+        sprite.flip_x = tick.flip_x != flipped;
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{Awake, CreatureFlags, Dizzy, FlagQuery, Player, Speed},
+    events::{acts_at_speed_level, TurnManager},
+    graphics::SpriteSheetAtlas,
+    map::Position,
+    sets::Animation,
+    vision::VisibilityMap,
+};
+
+pub struct InitiativePlugin;
+
+impl Plugin for InitiativePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_initiative_strip);
+        app.add_systems(Update, update_initiative_strip.in_set(Animation));
+    }
+}
+
+/// How many portraits fit in the strip.
+const MAX_PORTRAITS: usize = 12;
+
+/// A row of portraits, in the order creatures will act once the player ends their turn.
+#[derive(Component)]
+pub struct InitiativeStrip;
+
+fn spawn_initiative_strip(mut commands: Commands) {
+    commands.spawn((
+        InitiativeStrip,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(35.),
+            top: Val::Px(4.),
+            flex_direction: FlexDirection::Row,
+            column_gap: Val::Px(0.3),
+            ..default()
+        },
+        PickingBehavior::IGNORE,
+    ));
+}
+
+/// The sprite of each creature in sight, once for each time it will act next turn,
+/// and whether that action is an extra one granted by Speed.
+fn update_initiative_strip(
+    player: Query<&Sprite, With<Player>>,
+    creatures: Query<(&Position, &Sprite, &CreatureFlags), (With<Awake>, Without<Player>)>,
+    player_position: Query<&Position, With<Player>>,
+    speed_query: Query<&Speed>,
+    stunned_query: Query<(), With<Dizzy>>,
+    turn_manager: Res<TurnManager>,
+    vision: Res<VisibilityMap>,
+    strip: Query<Entity, With<InitiativeStrip>>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut shown: Local<Vec<(usize, bool)>>,
+    mut commands: Commands,
+) {
+    let (Ok(strip), Ok(player_sprite), Ok(player_position)) = (
+        strip.get_single(),
+        player.get_single(),
+        player_position.get_single(),
+    ) else {
+        return;
+    };
+    let sprite_index =
+        |sprite: &Sprite| sprite.texture_atlas.as_ref().map_or(0, |atlas| atlas.index);
+    // NOTE: Stunned creatures whose stun runs out this turn will actually act.
+    let mut actors: Vec<(&Position, usize, Option<&Speed>)> = creatures
+        .iter()
+        .filter(|(position, ..)| vision.is_visible(position))
+        .filter(|(.., flags)| !stunned_query.flagged(flags))
+        .map(|(position, sprite, flags)| {
+            let speed = speed_query.get_flag(flags).ok();
+            (position, sprite_index(sprite), speed)
+        })
+        .collect();
+    // Creatures of the same echo act all at once, they are listed nearest first.
+    actors.sort_by_key(|(position, sprite, _)| {
+        (
+            (position.x - player_position.x).abs() + (position.y - player_position.y).abs(),
+            position.x,
+            position.y,
+            *sprite,
+        )
+    });
+    // The turn count goes up before the creatures act.
+    let turn = turn_manager.turn_count + 1;
+    let mut order = vec![(sprite_index(player_sprite), false)];
+    let mut speed_level = 1;
+    while order.len() < MAX_PORTRAITS {
+        let acting: Vec<(usize, bool)> = actors
+            .iter()
+            .filter(|(_, _, speed)| acts_at_speed_level(*speed, speed_level, turn))
+            .map(|(_, sprite, _)| (*sprite, speed_level > 1))
+            .collect();
+        if acting.is_empty() {
+            break;
+        }
+        order.extend(acting);
+        speed_level += 1;
+    }
+    order.truncate(MAX_PORTRAITS);
+    if *shown == order {
+        return;
+    }
+    commands.entity(strip).despawn_descendants();
+    commands.entity(strip).with_children(|parent| {
+        for (index, is_echo) in &order {
+            parent.spawn((
+                ImageNode {
+                    image: asset_server.load("spritesheet.png"),
+                    texture_atlas: Some(TextureAtlas {
+                        layout: atlas_layout.handle.clone(),
+                        index: *index,
+                    }),
+                    ..default()
+                },
+                Node {
+                    width: Val::Px(2.),
+                    height: Val::Px(2.),
+                    ..default()
+                },
+                // Extra actions from Speed are framed in yellow.
+                BackgroundColor(if *is_echo {
+                    Color::srgba(1., 0.9, 0.2, 0.5)
+                } else {
+                    Color::srgba(0., 0., 0., 0.8)
+                }),
+            ));
+        }
+    });
+    *shown = order;
+}
@@ -1,16 +1,38 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, window::PrimaryWindow};
 
 use crate::{
-    creature::{Player, Soul},
+    creature::{Facing, Player, Spellbook},
     cursor::CursorStep,
     events::{
-        CreatureStep, DrawSoul, EndTurn, PlayerAction, RespawnPlayer, TurnManager, UseWheelSoul,
+        CreatureStep, DrawSoul, EndTurn, PlayerAction, RespawnPlayer, SoulWheel, TurnFacing,
+        TurnManager, UseWheelSoul,
     },
-    sets::ControlState,
-    ui::LargeCastePanel,
-    OrdDir,
+    graphics::EnemyPacing,
+    integrity::PrintWorldHash,
+    map::Position,
+    mapgen::LevelGenConfig,
+    replay::ValidateDeterminism,
+    rng::{GameRng, SetSeed},
+    save::{LoadGame, SaveGame, AUTOSAVE_SLOT},
+    sets::{ControlState, DumpSchedule},
+    settings::Settings,
+    spells::{AimMode, Axiom, SetAimMode},
+    ui::{AddMessage, Message},
+    OrdDir, TILE_SIZE,
 };
 
+/// The keys of each Soul Wheel slot, in order.
+const SOUL_KEYS: [KeyCode; 8] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+];
+
 /// Each frame, if a button is pressed, move the player 1 tile.
 pub fn keyboard_input(
     player: Query<Entity, With<Player>>,
@@ -24,43 +46,20 @@ pub fn keyboard_input(
     state: Res<State<ControlState>>,
     mut next_state: ResMut<NextState<ControlState>>,
     mut cursor: EventWriter<CursorStep>,
-    mut caste_menu: Query<&mut LargeCastePanel>,
-    mut scale: ResMut<UiScale>,
+    mut settings: ResMut<Settings>,
+    (aim_mode, mut set_aim_mode): (Res<AimMode>, EventWriter<SetAimMode>),
+    mut pacing: ResMut<EnemyPacing>,
+    mut text: EventWriter<AddMessage>,
 ) {
-    let soul_keys = [
-        KeyCode::Digit1,
-        KeyCode::Digit2,
-        KeyCode::Digit3,
-        KeyCode::Digit4,
-        KeyCode::Digit5,
-        KeyCode::Digit6,
-        KeyCode::Digit7,
-        KeyCode::Digit8,
-    ];
-    if input.any_just_pressed(soul_keys) {
-        for (i, key) in soul_keys.iter().enumerate() {
-            if input.just_pressed(*key) {
-                match state.get() {
-                    ControlState::Player => {
-                        use_wheel_soul.send(UseWheelSoul { index: i });
-                        turn_manager.action_this_turn = PlayerAction::Spell;
-                        turn_end.send(EndTurn);
-                    }
-                    ControlState::CasteMenu => {
-                        let mut caste_menu = caste_menu.single_mut();
-                        let current_soul = caste_menu.0;
-                        caste_menu.0 = match i {
-                            0 => Soul::Saintly,
-                            1 => Soul::Ordered,
-                            2 => Soul::Artistic,
-                            3 => Soul::Unhinged,
-                            4 => Soul::Feral,
-                            5 => Soul::Vile,
-                            _ => current_soul,
-                        }
-                    }
-                    _ => (),
-                }
+    if input.any_just_pressed(SOUL_KEYS) {
+        for (i, key) in SOUL_KEYS.iter().enumerate() {
+            if input.just_pressed(*key) && *state.get() == ControlState::Player {
+                use_wheel_soul.send(UseWheelSoul {
+                    index: i,
+                    target: None,
+                });
+                turn_manager.action_this_turn = PlayerAction::Spell;
+                turn_end.send(EndTurn);
             }
         }
     }
@@ -69,98 +68,306 @@ pub fn keyboard_input(
         turn_manager.action_this_turn = PlayerAction::Draw;
         turn_end.send(EndTurn);
     }
+    // Modal states have their own input systems, see ControlState::is_modal,
+    // so this is either the Cursor or the Player state.
     if input.just_pressed(KeyCode::ArrowUp) || input.just_pressed(KeyCode::KeyW) {
-        match state.get() {
-            ControlState::Cursor => {
-                cursor.send(CursorStep {
-                    direction: OrdDir::Up,
-                });
-            }
-            ControlState::Player => {
-                events.send(CreatureStep {
-                    direction: OrdDir::Up,
-                    entity: player.get_single().unwrap(),
-                });
-                turn_manager.action_this_turn = PlayerAction::Step;
-                turn_end.send(EndTurn);
-            }
-            ControlState::CasteMenu => todo!(),
+        if *state.get() == ControlState::Cursor {
+            cursor.send(CursorStep {
+                direction: OrdDir::Up,
+            });
+        } else {
+            events.send(CreatureStep {
+                direction: OrdDir::Up,
+                entity: player.get_single().unwrap(),
+            });
+            turn_manager.action_this_turn = PlayerAction::Step;
+            turn_end.send(EndTurn);
         }
     }
     if input.just_pressed(KeyCode::ArrowRight) || input.just_pressed(KeyCode::KeyD) {
-        match state.get() {
-            ControlState::Cursor => {
-                cursor.send(CursorStep {
-                    direction: OrdDir::Right,
-                });
-            }
-            ControlState::Player => {
-                events.send(CreatureStep {
-                    direction: OrdDir::Right,
-                    entity: player.get_single().unwrap(),
-                });
-                turn_manager.action_this_turn = PlayerAction::Step;
-                turn_end.send(EndTurn);
-            }
-            ControlState::CasteMenu => todo!(),
+        if *state.get() == ControlState::Cursor {
+            cursor.send(CursorStep {
+                direction: OrdDir::Right,
+            });
+        } else {
+            events.send(CreatureStep {
+                direction: OrdDir::Right,
+                entity: player.get_single().unwrap(),
+            });
+            turn_manager.action_this_turn = PlayerAction::Step;
+            turn_end.send(EndTurn);
         }
     }
     if input.just_pressed(KeyCode::ArrowLeft) || input.just_pressed(KeyCode::KeyA) {
-        match state.get() {
-            ControlState::Cursor => {
-                cursor.send(CursorStep {
-                    direction: OrdDir::Left,
-                });
-            }
-            ControlState::Player => {
-                events.send(CreatureStep {
-                    direction: OrdDir::Left,
-                    entity: player.get_single().unwrap(),
-                });
-                turn_manager.action_this_turn = PlayerAction::Step;
-                turn_end.send(EndTurn);
-            }
-            ControlState::CasteMenu => todo!(),
+        if *state.get() == ControlState::Cursor {
+            cursor.send(CursorStep {
+                direction: OrdDir::Left,
+            });
+        } else {
+            events.send(CreatureStep {
+                direction: OrdDir::Left,
+                entity: player.get_single().unwrap(),
+            });
+            turn_manager.action_this_turn = PlayerAction::Step;
+            turn_end.send(EndTurn);
         }
     }
     if input.just_pressed(KeyCode::ArrowDown) || input.just_pressed(KeyCode::KeyS) {
-        match state.get() {
-            ControlState::Cursor => {
-                cursor.send(CursorStep {
-                    direction: OrdDir::Down,
-                });
-            }
-            ControlState::Player => {
-                events.send(CreatureStep {
-                    direction: OrdDir::Down,
-                    entity: player.get_single().unwrap(),
-                });
-                turn_manager.action_this_turn = PlayerAction::Step;
-                turn_end.send(EndTurn);
-            }
-            ControlState::CasteMenu => todo!(),
+        if *state.get() == ControlState::Cursor {
+            cursor.send(CursorStep {
+                direction: OrdDir::Down,
+            });
+        } else {
+            events.send(CreatureStep {
+                direction: OrdDir::Down,
+                entity: player.get_single().unwrap(),
+            });
+            turn_manager.action_this_turn = PlayerAction::Step;
+            turn_end.send(EndTurn);
         }
     }
-    if input.just_pressed(KeyCode::KeyZ) || input.just_pressed(KeyCode::KeyX) {
+    if input.just_pressed(KeyCode::KeyZ) {
         respawn.send(RespawnPlayer { victorious: false });
     }
 
-    if input.just_pressed(KeyCode::KeyC) {
+    if input.just_pressed(KeyCode::KeyC) || input.just_pressed(KeyCode::KeyX) {
         match state.get() {
             ControlState::Cursor => next_state.set(ControlState::Player),
             _ => next_state.set(ControlState::Cursor),
         }
     }
+    if input.just_pressed(KeyCode::F6) {
+        next_state.set(ControlState::SaveMenu);
+    }
+    if input.just_pressed(KeyCode::KeyR) {
+        next_state.set(ControlState::RecipeBook);
+    }
+    if input.just_pressed(KeyCode::KeyB) {
+        next_state.set(ControlState::SpellEditor);
+    }
+    // Closed by caste_menu_input.
     if input.just_pressed(KeyCode::KeyE) {
-        match state.get() {
-            ControlState::CasteMenu => next_state.set(ControlState::Player),
-            _ => next_state.set(ControlState::CasteMenu),
-        }
+        next_state.set(ControlState::CasteMenu);
     }
     if input.pressed(KeyCode::KeyO) {
-        scale.0 += 0.02;
+        settings.ui_scale += 0.02;
     }
     if input.pressed(KeyCode::KeyP) {
-        scale.0 -= 0.02;
+        settings.ui_scale = (settings.ui_scale - 0.02).max(0.1);
+    }
+    if input.just_pressed(KeyCode::KeyF) {
+        set_aim_mode.send(SetAimMode {
+            mode: match *aim_mode {
+                AimMode::Momentum => AimMode::Facing,
+                AimMode::Facing => AimMode::Momentum,
+            },
+        });
+    }
+    if input.just_pressed(KeyCode::KeyT) {
+        *pacing = pacing.next();
+        text.send(AddMessage {
+            message: Message::EnemyPacing(*pacing),
+        });
+    }
+}
+
+/// Which soul is being aimed, and at which tile, while in ControlState::Targeting.
+#[derive(Resource)]
+pub struct Targeting {
+    pub index: usize,
+    pub position: Position,
+}
+
+/// Casting a soul whose spell has Axiom::CursorTarget first asks the player
+/// to pick a tile, instead of casting it right away.
+pub fn begin_targeting(
+    mut input: ResMut<ButtonInput<KeyCode>>,
+    soul_wheel: Res<SoulWheel>,
+    player: Query<(&Position, &Spellbook), With<Player>>,
+    mut next_state: ResMut<NextState<ControlState>>,
+    mut commands: Commands,
+) {
+    let Ok((position, spellbook)) = player.get_single() else {
+        return;
+    };
+    for (index, key) in SOUL_KEYS.iter().enumerate() {
+        if !input.just_pressed(*key) {
+            continue;
+        }
+        let is_aimed = soul_wheel.souls[index]
+            .and_then(|soul| spellbook.spells.get(&soul))
+            .is_some_and(|spell| spell.axioms.contains(&Axiom::CursorTarget));
+        if is_aimed {
+            commands.insert_resource(Targeting {
+                index,
+                position: *position,
+            });
+            next_state.set(ControlState::Targeting);
+            // NOTE: Otherwise, keyboard_input would cast it this very frame.
+            input.clear_just_pressed(*key);
+            return;
+        }
+    }
+}
+
+/// Move the targeting cursor with the movement keys or the mouse. Enter, left click
+/// or the same soul key again casts the spell. Escape or right click cancels.
+pub fn targeting_input(
+    input: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut mouse_moved: EventReader<CursorMoved>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    mut targeting: ResMut<Targeting>,
+    mut use_wheel_soul: EventWriter<UseWheelSoul>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut turn_end: EventWriter<EndTurn>,
+    mut next_state: ResMut<NextState<ControlState>>,
+    mut commands: Commands,
+) {
+    for (keys, direction) in [
+        ([KeyCode::ArrowUp, KeyCode::KeyW], OrdDir::Up),
+        ([KeyCode::ArrowRight, KeyCode::KeyD], OrdDir::Right),
+        ([KeyCode::ArrowDown, KeyCode::KeyS], OrdDir::Down),
+        ([KeyCode::ArrowLeft, KeyCode::KeyA], OrdDir::Left),
+    ] {
+        if input.any_just_pressed(keys) {
+            let (dx, dy) = direction.as_offset();
+            targeting.position =
+                Position::new(targeting.position.x + dx, targeting.position.y + dy);
+        }
+    }
+    // The mouse only takes over once it moves, so it does not fight with the keys.
+    if mouse_moved.read().count() > 0 {
+        if let Some(hovered) = hovered_tile(&window, &camera) {
+            targeting.position = hovered;
+        }
+    }
+    let confirmed = input.just_pressed(KeyCode::Enter)
+        || input.just_pressed(SOUL_KEYS[targeting.index])
+        || mouse.just_pressed(MouseButton::Left);
+    if confirmed {
+        use_wheel_soul.send(UseWheelSoul {
+            index: targeting.index,
+            target: Some(targeting.position),
+        });
+        turn_manager.action_this_turn = PlayerAction::Spell;
+        turn_end.send(EndTurn);
+    }
+    if confirmed || input.just_pressed(KeyCode::Escape) || mouse.just_pressed(MouseButton::Right) {
+        commands.remove_resource::<Targeting>();
+        next_state.set(ControlState::Player);
+    }
+}
+
+/// The tile under the mouse, if it is inside the window.
+pub fn hovered_tile(
+    window: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Position> {
+    let hovered = window
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(camera.get_single().ok())
+        .and_then(|(cursor, (camera, camera_transform))| {
+            camera.viewport_to_world_2d(camera_transform, cursor).ok()
+        })?;
+    Some(Position::new(
+        (hovered.x / TILE_SIZE).round() as i32,
+        (hovered.y / TILE_SIZE).round() as i32,
+    ))
+}
+
+/// Keys which are only there to help with development.
+pub fn debug_input(
+    input: Res<ButtonInput<KeyCode>>,
+    mut dump_schedule: EventWriter<DumpSchedule>,
+    mut validate: EventWriter<ValidateDeterminism>,
+    mut print_hash: EventWriter<PrintWorldHash>,
+    mut save: EventWriter<SaveGame>,
+    mut load: EventWriter<LoadGame>,
+    mut respawn: EventWriter<RespawnPlayer>,
+    mut level_gen: ResMut<LevelGenConfig>,
+    mut set_seed: EventWriter<SetSeed>,
+    rng: Res<GameRng>,
+) {
+    // Debug: print the system execution order in the console.
+    if input.just_pressed(KeyCode::F12) {
+        dump_schedule.send(DumpSchedule);
+    }
+    // Debug: replay this run twice in the background, and check that both agree.
+    if input.just_pressed(KeyCode::F11) {
+        validate.send(ValidateDeterminism);
+    }
+    // Debug: print the world hash in the console.
+    if input.just_pressed(KeyCode::F10) {
+        print_hash.send(PrintWorldHash);
+    }
+    if input.just_pressed(KeyCode::F5) {
+        save.send(SaveGame {
+            slot: AUTOSAVE_SLOT,
+        });
+    }
+    if input.just_pressed(KeyCode::F9) {
+        load.send(LoadGame {
+            slot: AUTOSAVE_SLOT,
+        });
+    }
+    // Debug: restart this run from the beginning, or with Shift, start a new one.
+    if input.just_pressed(KeyCode::F8) {
+        set_seed.send(SetSeed {
+            seed: if input.pressed(KeyCode::ShiftLeft) {
+                None
+            } else {
+                Some(rng.seed())
+            },
+        });
+    }
+    // Debug: switch to the next level layout, and restart on it.
+    if input.just_pressed(KeyCode::F7) {
+        level_gen.next_layout();
+        respawn.send(RespawnPlayer { victorious: false });
+    }
+}
+
+/// Each frame, make the player face the mouse cursor.
+pub fn face_cursor(
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    player: Query<(Entity, &Transform, &Facing), With<Player>>,
+    aim_mode: Res<AimMode>,
+    mut turn: EventWriter<TurnFacing>,
+) {
+    if *aim_mode != AimMode::Facing {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform)), Ok((player, transform, facing))) = (
+        window.get_single(),
+        camera.get_single(),
+        player.get_single(),
+    ) else {
+        return;
+    };
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+    // Whichever axis the cursor is furthest along decides the direction.
+    let offset = cursor - transform.translation.truncate();
+    let direction = if offset.x.abs() > offset.y.abs() {
+        OrdDir::as_variant(offset.x.signum() as i32, 0)
+    } else {
+        OrdDir::as_variant(0, offset.y.signum() as i32)
+    };
+    if let Some(direction) = direction {
+        if direction != facing.direction {
+            turn.send(TurnFacing {
+                entity: player,
+                direction,
+            });
+        }
     }
 }
@@ -0,0 +1,118 @@
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+
+use crate::{
+    creature::{Health, Soul, Species, WeakPoints},
+    events::{SoulWheel, TurnManager},
+    map::Position,
+    rng::GameRng,
+    stats::RunStats,
+    OrdDir,
+};
+
+pub struct IntegrityPlugin;
+
+impl Plugin for IntegrityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PrintWorldHash>();
+        app.add_systems(Last, print_world_hash.run_if(on_event::<PrintWorldHash>));
+    }
+}
+
+/// Debug: print the current world hash in the console.
+#[derive(Event)]
+pub struct PrintWorldHash;
+
+/// FNV-1a. Unlike DefaultHasher, its output will never change between
+/// Rust versions, so hashes can be written to disk and checked later.
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // NOTE: usize and isize differ in size between platforms,
+    // always hash them as 64 bits so a score submitted from a 32-bit
+    // machine still matches.
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+/// A canonical hash of everything which matters to the rules of the game:
+/// creatures (by position, not by Entity), their health and weak points, the Soul Wheel,
+/// the turn count and the state of the GameRng. Two worlds in the same state
+/// always hash the same, no matter in which order their entities were spawned.
+pub fn world_hash(world: &mut World) -> u64 {
+    let mut creatures: Vec<_> = world
+        .query::<(&Position, &Species, &Health, &OrdDir, Option<&WeakPoints>)>()
+        .iter(world)
+        .map(|(position, species, health, momentum, weak_points)| {
+            let mut weak_points: Vec<((i32, i32), usize)> = weak_points
+                .iter()
+                .flat_map(|weak_points| weak_points.points.iter())
+                .map(|(offset, point)| (*offset, point.hp))
+                .collect();
+            weak_points.sort();
+            (
+                position.x,
+                position.y,
+                *species as usize,
+                health.hp,
+                health.max_hp,
+                momentum.as_offset(),
+                weak_points,
+            )
+        })
+        .collect();
+    // Sorted on everything, so that creatures sharing a tile (intangible ones)
+    // do not depend on the order of the query either.
+    creatures.sort();
+    let mut hasher = StableHasher::default();
+    creatures.hash(&mut hasher);
+    let wheel = world.resource::<SoulWheel>();
+    wheel.souls.hash(&mut hasher);
+    wheel.locks.hash(&mut hasher);
+    // HashMaps have no stable iteration order, sort them first.
+    for pile in [&wheel.draw_pile, &wheel.discard_pile] {
+        let mut pile: Vec<(Soul, usize)> = pile.iter().map(|(s, n)| (*s, *n)).collect();
+        pile.sort_by_key(|(soul, _)| *soul as usize);
+        pile.hash(&mut hasher);
+    }
+    world.resource::<TurnManager>().turn_count.hash(&mut hasher);
+    let rng = world.resource::<GameRng>().state();
+    rng.seed.hash(&mut hasher);
+    rng.words.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Write down the world hash in the RunStats as the run ends, so the run
+/// summary can show it next to the seed.
+pub fn hash_final_state(world: &mut World) {
+    let hash = world_hash(world);
+    world.resource_mut::<RunStats>().world_hash = Some(hash);
+}
+
+fn print_world_hash(world: &mut World) {
+    let hash = world_hash(world);
+    info!("World hash: {:016x}", hash);
+}
@@ -0,0 +1,159 @@
+use bevy::prelude::*;
+
+use crate::{
+    ai::AiState,
+    creature::{
+        Cowardly, CreatureFlags, Devours, Dizzy, FlagQuery, Health, Hunt, MovementStyle, Player,
+        Sleeping, Soul, Speed, Spellbook, Tags, Wall,
+    },
+    events::{has_prey, hunter_action, HunterAction, TurnManager},
+    map::{Map, Position},
+    overlay::update_creature_overlays,
+    sets::Animation,
+    OrdDir,
+};
+
+pub struct IntentPlugin;
+
+impl Plugin for IntentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            predict_intents
+                .run_if(resource_changed::<Map>.or(resource_changed::<TurnManager>))
+                .before(update_creature_overlays)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// What a creature plans to do next turn, shown above it as an icon.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Intent {
+    /// Strike the player, who is in this direction.
+    Attack(OrdDir),
+    Move(OrdDir),
+    Cast,
+}
+
+impl Intent {
+    pub fn sprite(&self) -> usize {
+        match self {
+            Intent::Attack(direction) => match direction {
+                OrdDir::Down => 86,
+                OrdDir::Left => 87,
+                OrdDir::Right => 88,
+                OrdDir::Up => 89,
+            },
+            Intent::Move(direction) => match direction {
+                OrdDir::Down => 91,
+                OrdDir::Left => 92,
+                OrdDir::Right => 93,
+                OrdDir::Up => 94,
+            },
+            Intent::Cast => 177,
+        }
+    }
+}
+
+/// Guess what each hunter will do next turn, the same way distribute_npc_actions
+/// will decide it. Erratic creatures are unpredictable, and get no intent.
+pub fn predict_intents(
+    turn_manager: Res<TurnManager>,
+    map: Res<Map>,
+    player: Query<&Position, With<Player>>,
+    npcs: Query<
+        (
+            Entity,
+            &Position,
+            &CreatureFlags,
+            &Health,
+            &Spellbook,
+            Has<Intent>,
+            Option<&AiState>,
+        ),
+        Without<Player>,
+    >,
+    flags_query: Query<&CreatureFlags>,
+    hunt_query: Query<&Hunt>,
+    speed_query: Query<&Speed>,
+    stunned_query: Query<Entity, Or<(With<Dizzy>, With<Sleeping>)>>,
+    style_query: Query<&MovementStyle>,
+    wall_query: Query<&Wall>,
+    (devour_query, tags_query, cowardly_query): (Query<&Devours>, Query<&Tags>, Query<&Cowardly>),
+    mut commands: Commands,
+) {
+    let Ok(player_pos) = player.get_single() else {
+        return;
+    };
+    for (npc_entity, npc_pos, flags, health, npc_spellbook, has_intent, ai_state) in npcs.iter() {
+        let is_hunter = hunt_query.flagged(flags);
+        let is_stunned = stunned_query.flagged(flags) || stunned_query.contains(npc_entity);
+        let speed = speed_query.get_flag(flags);
+        // Slow creatures spend most turns waiting.
+        let waits = matches!(speed, Ok(Speed::Slow { wait_turns })
+            if !(turn_manager.turn_count + 1).is_multiple_of(wait_turns + 1));
+        // Cowards start or stop fleeing at the start of their turn, not when wounded.
+        let cowardly = cowardly_query
+            .get_flag(flags)
+            .ok()
+            .map(|cowardly| health.hp <= cowardly.threshold);
+        let panicked = cowardly == Some(true);
+        let is_fleeing = panicked
+            && ai_state.is_some_and(|state| {
+                matches!(
+                    state,
+                    AiState::Alert { .. } | AiState::Hunting | AiState::Fleeing
+                )
+            });
+        // Only creatures which are after the player, or running from them,
+        // are predictable enough.
+        let is_hunting = ai_state.is_none_or(|state| match state {
+            AiState::Hunting => true,
+            AiState::Fleeing => !panicked,
+            _ => false,
+        });
+        if !is_hunter || is_stunned || waits || !(is_hunting || is_fleeing) {
+            if has_intent {
+                commands.entity(npc_entity).remove::<Intent>();
+            }
+            continue;
+        }
+        let can_devour = npc_spellbook.spells.contains_key(&Soul::Vile)
+            && devour_query
+                .get(flags.species_flags)
+                .is_ok_and(|devours| has_prey(&map, *npc_pos, devours, &flags_query, &tags_query));
+        // Decide on copies, so that predicting moves no state along.
+        let action = hunter_action(
+            &map,
+            *npc_pos,
+            *player_pos,
+            ai_state.copied().as_mut(),
+            None,
+            cowardly,
+            can_devour,
+            style_query.get_flag(flags).ok(),
+            |p| {
+                map.get_entity_at(p.x, p.y)
+                    .and_then(|entity| flags_query.get(*entity).ok())
+                    .is_some_and(|flags| wall_query.flagged(flags))
+            },
+        );
+        let intent = action.map(|action| match action {
+            HunterAction::Step(direction) => {
+                let (dx, dy) = direction.as_offset();
+                if Position::new(npc_pos.x + dx, npc_pos.y + dy) == *player_pos {
+                    Intent::Attack(direction)
+                } else {
+                    Intent::Move(direction)
+                }
+            }
+            HunterAction::Flee(direction) => Intent::Move(direction),
+            HunterAction::Devour => Intent::Cast,
+        });
+        match intent {
+            Some(intent) => commands.entity(npc_entity).insert(intent),
+            None => commands.entity(npc_entity).remove::<Intent>(),
+        };
+    }
+}
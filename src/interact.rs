@@ -0,0 +1,237 @@
+use bevy::prelude::*;
+
+use crate::{
+    circuit::Circuits,
+    creature::{
+        Awake, CreatureFlags, Door, Facing, FlagQuery, Intangible, Interactable, Lever, Lock,
+        Player,
+    },
+    events::{EndTurn, OpenCloseDoor, PlayerAction, TurnManager},
+    graphics::VisualLayer,
+    input::keyboard_input,
+    key_items::KeyItems,
+    map::{Map, Position},
+    sets::{Animation, ControlState, PlayerInput},
+    spells::spell_stack_is_empty,
+    ui::{AddMessage, InvalidAction, Message},
+    OrdDir, TILE_SIZE,
+};
+
+pub struct InteractPlugin;
+
+impl Plugin for InteractPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Interact>();
+        app.add_systems(
+            Update,
+            interact_input
+                .run_if(spell_stack_is_empty.and(in_state(ControlState::Player)))
+                .before(keyboard_input)
+                .in_set(PlayerInput),
+        );
+        app.add_systems(Update, update_interaction_prompt.in_set(Animation));
+    }
+}
+
+/// `entity` uses whatever Interactable creature is next to it, in `direction`.
+#[derive(Event)]
+pub struct Interact {
+    pub entity: Entity,
+    pub direction: OrdDir,
+}
+
+/// The floating key shown above what the player would interact with.
+#[derive(Component)]
+pub struct InteractionPrompt;
+
+/// The Interactable creature next to `position` which would be used by pressing E,
+/// and in which direction it is. The one being faced is preferred.
+// NOTE: Open doors are intangible, and missing from the Map, hence the query.
+pub fn find_interaction(
+    position: &Position,
+    facing: &OrdDir,
+    creatures: &Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: &Query<(), With<Interactable>>,
+) -> Option<(Entity, OrdDir)> {
+    let mut directions = vec![*facing];
+    directions.extend(
+        [OrdDir::Up, OrdDir::Right, OrdDir::Down, OrdDir::Left]
+            .into_iter()
+            .filter(|direction| direction != facing),
+    );
+    directions.into_iter().find_map(|direction| {
+        let (dx, dy) = direction.as_offset();
+        let target = Position::new(position.x + dx, position.y + dy);
+        creatures
+            .iter()
+            .find(|(_, creature_position, flags)| {
+                **creature_position == target && interactable.flagged(flags)
+            })
+            .map(|(entity, ..)| (entity, direction))
+    })
+}
+
+/// The Interactable creature an Interact event is aimed at, if it is still there.
+pub fn interaction_target(
+    event: &Interact,
+    position: &Query<&Position>,
+    creatures: &Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: &Query<(), With<Interactable>>,
+) -> Option<Entity> {
+    let interactor_position = position.get(event.entity).ok()?;
+    let (dx, dy) = event.direction.as_offset();
+    let target = Position::new(interactor_position.x + dx, interactor_position.y + dy);
+    creatures
+        .iter()
+        .find(|(_, creature_position, flags)| {
+            **creature_position == target && interactable.flagged(flags)
+        })
+        .map(|(entity, ..)| entity)
+}
+
+/// E uses the nearby Interactable, if there is one. Otherwise, it is left
+/// for keyboard_input to open the caste menu.
+pub fn interact_input(
+    mut input: ResMut<ButtonInput<KeyCode>>,
+    player: Query<(Entity, &Position, &Facing), With<Player>>,
+    creatures: Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: Query<(), With<Interactable>>,
+    mut interact: EventWriter<Interact>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut turn_end: EventWriter<EndTurn>,
+) {
+    if !input.just_pressed(KeyCode::KeyE) {
+        return;
+    }
+    let Ok((player, position, facing)) = player.get_single() else {
+        return;
+    };
+    if let Some((_, direction)) =
+        find_interaction(position, &facing.direction, &creatures, &interactable)
+    {
+        interact.send(Interact {
+            entity: player,
+            direction,
+        });
+        turn_manager.action_this_turn = PlayerAction::Interact;
+        turn_end.send(EndTurn);
+        input.clear_just_pressed(KeyCode::KeyE);
+    }
+}
+
+/// Use an Interactable creature. Doors open and close at will once their room
+/// is safe, unless they are wired to a circuit or locked. Levers are pulled.
+/// Chests are opened in open_chest.
+pub fn interact(
+    mut events: EventReader<Interact>,
+    position: Query<&Position>,
+    creatures: Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: Query<(), With<Interactable>>,
+    doors: Query<(), With<Door>>,
+    mut levers: Query<&mut Lever>,
+    mut sprites: Query<&mut Sprite>,
+    circuits: Res<Circuits>,
+    locks: Query<&Lock>,
+    key_items: Res<KeyItems>,
+    open_doors: Query<(), With<Intangible>>,
+    awake: Query<(), (With<Awake>, Without<Player>)>,
+    map: Res<Map>,
+    mut open: EventWriter<OpenCloseDoor>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        let Some((entity, &target, flags)) =
+            interaction_target(event, &position, &creatures, &interactable)
+                .and_then(|entity| creatures.get(entity).ok())
+        else {
+            turn_manager.action_this_turn = PlayerAction::Invalid;
+            continue;
+        };
+        if let Ok(mut lever) = levers.get_mut(flags.species_flags) {
+            lever.on = !lever.on;
+            if let Ok(mut sprite) = sprites.get_mut(entity) {
+                sprite.flip_x = lever.on;
+            }
+        } else if doors.contains(flags.species_flags) {
+            if circuits.is_receiver(&target) {
+                text.send(AddMessage {
+                    message: Message::InvalidAction(InvalidAction::DoorWired),
+                });
+                turn_manager.action_this_turn = PlayerAction::Invalid;
+                continue;
+            }
+            if let Ok(lock) = locks.get(flags.species_flags) {
+                if !key_items.has(lock.key) {
+                    text.send(AddMessage {
+                        message: Message::InvalidAction(InvalidAction::MissingKey(lock.key)),
+                    });
+                    turn_manager.action_this_turn = PlayerAction::Invalid;
+                    continue;
+                }
+            }
+            // Doors stay shut while there is still fighting to be done.
+            if !awake.is_empty() {
+                text.send(AddMessage {
+                    message: Message::InvalidAction(InvalidAction::DoorLocked),
+                });
+                turn_manager.action_this_turn = PlayerAction::Invalid;
+                continue;
+            }
+            let is_open = open_doors.contains(flags.species_flags);
+            // Something is standing in the doorway.
+            if is_open && map.get_entity_at(target.x, target.y).is_some() {
+                text.send(AddMessage {
+                    message: Message::InvalidAction(InvalidAction::DoorBlocked),
+                });
+                turn_manager.action_this_turn = PlayerAction::Invalid;
+                continue;
+            }
+            open.send(OpenCloseDoor {
+                entity,
+                open: !is_open,
+            });
+        }
+    }
+}
+
+/// Show an E above the Interactable the player is standing next to.
+pub fn update_interaction_prompt(
+    player: Query<(&Position, &Facing), With<Player>>,
+    creatures: Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: Query<(), With<Interactable>>,
+    mut prompt: Query<(&mut Transform, &mut Visibility), With<InteractionPrompt>>,
+    state: Res<State<ControlState>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let target = player.get_single().ok().and_then(|(position, facing)| {
+        find_interaction(position, &facing.direction, &creatures, &interactable)
+    });
+    let target_position = target
+        .filter(|_| *state.get() == ControlState::Player)
+        .and_then(|(entity, _)| creatures.get(entity).ok())
+        .map(|(_, position, _)| *position);
+    let Ok((mut transform, mut visibility)) = prompt.get_single_mut() else {
+        commands.spawn((
+            InteractionPrompt,
+            Text2d::new("E"),
+            TextFont {
+                font: asset_server.load("fonts/Play-Regular.ttf"),
+                font_size: 1.5,
+                ..default()
+            },
+            Transform::default(),
+            VisualLayer::Overlay,
+            Visibility::Hidden,
+        ));
+        return;
+    };
+    if let Some(position) = target_position {
+        transform.translation.x = position.x as f32 * TILE_SIZE;
+        transform.translation.y = (position.y as f32 + 0.7) * TILE_SIZE;
+        *visibility = Visibility::Visible;
+    } else {
+        *visibility = Visibility::Hidden;
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{CreatureFlags, KeyPickup, Player},
+    events::{EndTurn, RemoveCreature, RespawnPlayer},
+    graphics::SpriteSheetAtlas,
+    map::{Map, Position},
+    sets::Animation,
+    ui::{AddMessage, Message},
+};
+
+pub struct KeyItemPlugin;
+
+impl Plugin for KeyItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeyItems>();
+        app.add_systems(Startup, spawn_key_item_strip);
+        app.add_systems(
+            Update,
+            update_key_item_strip
+                .run_if(resource_changed::<KeyItems>)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// Opens the way forwards, and nothing else. Key items are not souls or
+/// creatures, so no spell can take them away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyItem {
+    Keycard,
+    Sigil,
+}
+
+impl KeyItem {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyItem::Keycard => "[a]Nacre Keycard[w]",
+            KeyItem::Sigil => "[p]Sigil of Passage[w]",
+        }
+    }
+
+    pub fn sprite(&self) -> usize {
+        match self {
+            KeyItem::Keycard => 36,
+            KeyItem::Sigil => 46,
+        }
+    }
+}
+
+/// The key items the player is carrying on this floor.
+#[derive(Resource, Default)]
+pub struct KeyItems {
+    pub items: Vec<KeyItem>,
+}
+
+impl KeyItems {
+    pub fn has(&self, item: KeyItem) -> bool {
+        self.items.contains(&item)
+    }
+}
+
+/// Once per turn, pick up the key items the player is standing on.
+/// They are left behind when the floor changes.
+// NOTE: Pickups are intangible, and only found in Map::intangible_creatures.
+pub fn pick_up_key_items(
+    mut events: EventReader<EndTurn>,
+    mut respawn: EventReader<RespawnPlayer>,
+    player: Query<&Position, With<Player>>,
+    creatures: Query<&CreatureFlags>,
+    pickups: Query<&KeyPickup>,
+    map: Res<Map>,
+    mut key_items: ResMut<KeyItems>,
+    mut remove: EventWriter<RemoveCreature>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if respawn.read().count() > 0 {
+        key_items.items.clear();
+    }
+    if events.read().count() == 0 {
+        return;
+    }
+    let Ok(player_position) = player.get_single() else {
+        return;
+    };
+    for entity in map.intangible_at(player_position) {
+        let Ok(flags) = creatures.get(*entity) else {
+            continue;
+        };
+        if let Ok(pickup) = pickups.get(flags.species_flags) {
+            key_items.items.push(pickup.item);
+            remove.send(RemoveCreature {
+                entity: *entity,
+                culprit: None,
+            });
+            text.send(AddMessage {
+                message: Message::KeyItemFound(pickup.item),
+            });
+        }
+    }
+}
+
+/// The row of key item icons in the bottom left corner.
+#[derive(Component)]
+pub struct KeyItemStrip;
+
+fn spawn_key_item_strip(mut commands: Commands) {
+    commands.spawn((
+        KeyItemStrip,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(0.5),
+            bottom: Val::Px(0.5),
+            column_gap: Val::Px(0.5),
+            ..default()
+        },
+        PickingBehavior::IGNORE,
+    ));
+}
+
+fn update_key_item_strip(
+    key_items: Res<KeyItems>,
+    strip: Query<Entity, With<KeyItemStrip>>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut commands: Commands,
+) {
+    let Ok(strip) = strip.get_single() else {
+        return;
+    };
+    commands.entity(strip).despawn_descendants();
+    commands.entity(strip).with_children(|parent| {
+        for item in &key_items.items {
+            parent.spawn((
+                ImageNode {
+                    image: asset_server.load("spritesheet.png"),
+                    texture_atlas: Some(TextureAtlas {
+                        layout: atlas_layout.handle.clone(),
+                        index: item.sprite(),
+                    }),
+                    ..default()
+                },
+                Node {
+                    width: Val::Px(2.),
+                    height: Val::Px(2.),
+                    ..default()
+                },
+            ));
+        }
+    });
+}
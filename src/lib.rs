@@ -0,0 +1,241 @@
+//! The Games Foxes Play, as a library.
+//!
+//! The game binary is a thin layer over this crate. Tools like balance simulators
+//! or map editors can embed the rules of the game without any window or UI:
+//!
+//! - Add TgfpCorePlugin (or call headless_app) to get a world running the simulation.
+//! - Drive it with apply_player_command and settle_turn, or send events directly:
+//!   SummonCreature, CastSpell, DamageOrHealCreature, TeleportEntity...
+//! - Hook extra systems into the PlayerInput, SpellResolution, Cleanup and NpcTurn
+//!   sets, which run in that order every frame. Anything reading events sent by the
+//!   simulation should go after the set which resolves them.
+//! - Species live in assets/creatures.ron, their spells in assets/spellbooks.ron.
+//!   Spells are lists of Axioms.
+//!
+//! - Spellbooks can be pitted against enemy compositions with `cargo run --bin balance_sim`.
+//! - Floors can be painted with `cargo run --bin editor`, and played in game by
+//!   cycling to the Authored layout with F7.
+//! - Themed content packs in assets/packs switch on by date, or from settings.ron.
+//!
+//! Everything not re-exported here is internal, and may change at any time.
+
+mod ai;
+mod ascii;
+mod audio;
+#[cfg(feature = "audit")]
+mod audit;
+mod caste;
+mod chest;
+mod circuit;
+mod content;
+mod conveyor;
+mod corpse;
+mod crafting;
+mod creature;
+mod cursor;
+mod difficulty;
+mod director;
+mod equipment;
+mod events;
+mod evolution;
+mod focus;
+mod graphics;
+mod grinder;
+mod ground;
+mod highlight;
+mod idle;
+mod initiative;
+mod input;
+mod integrity;
+mod intent;
+mod interact;
+mod key_items;
+mod loading;
+mod map;
+mod mapgen;
+mod objective;
+mod overlay;
+mod preview;
+mod push;
+mod rail;
+mod replay;
+mod review;
+mod rng;
+mod rumble;
+mod save;
+mod script;
+mod scroll;
+mod sets;
+mod settings;
+mod shield;
+mod shrine;
+mod simulation;
+mod species;
+mod spectate;
+mod spells;
+mod stats;
+mod terrain;
+mod text;
+mod threat;
+mod trail;
+mod trap;
+mod turn_timer;
+mod ui;
+mod vision;
+mod warp;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// The presentation plugins, which the game binary adds on top of TgfpCorePlugin.
+pub use ascii::AsciiPlugin;
+pub use audio::AudioPlugin;
+#[cfg(feature = "audit")]
+pub use audit::AuditPlugin;
+pub use chest::ChestPlugin;
+pub use content::ContentPlugin;
+pub use conveyor::ConveyorPlugin;
+pub use corpse::CorpsePlugin;
+pub use crafting::CraftingPlugin;
+pub use cursor::CursorPlugin;
+pub use difficulty::DifficultyPlugin;
+pub use director::DirectorPlugin;
+pub use evolution::EvolutionPlugin;
+pub use focus::FocusPlugin;
+pub use graphics::GraphicsPlugin;
+pub use grinder::GrinderPlugin;
+pub use highlight::HighlightPlugin;
+pub use idle::IdlePlugin;
+pub use initiative::InitiativePlugin;
+pub use integrity::IntegrityPlugin;
+pub use intent::IntentPlugin;
+pub use interact::InteractPlugin;
+pub use key_items::KeyItemPlugin;
+pub use loading::LoadingPlugin;
+pub use objective::ObjectivePlugin;
+pub use preview::PreviewPlugin;
+pub use replay::ReplayPlugin;
+pub use review::ReviewPlugin;
+pub use rng::RngPlugin;
+pub use rumble::RumblePlugin;
+pub use save::SaveGamePlugin;
+pub use scroll::ScrollPlugin;
+pub use sets::SetsPlugin;
+pub use settings::SettingsPlugin;
+pub use shrine::ShrinePlugin;
+pub use species::SpeciesPlugin;
+pub use spectate::SpectatePlugin;
+pub use stats::RunStatsPlugin;
+pub use terrain::TerrainPlugin;
+pub use trail::TrailPlugin;
+pub use turn_timer::TurnTimerPlugin;
+pub use ui::UIPlugin;
+pub use vision::VisionPlugin;
+pub use warp::WarpPlugin;
+
+// The simulation, and how to drive it.
+pub use events::{SoulWheel, TurnManager};
+pub use integrity::world_hash;
+pub use mapgen::LevelGenConfig;
+pub use replay::{apply_player_command, headless_app, settle_turn, ActionLog, PlayerCommand};
+pub use rng::{GameRng, RngState};
+pub use save::{finish_restore, restore_save, write_save, SaveFile};
+pub use sets::{Animation, Cleanup, NpcTurn, PlayerInput, SpellResolution};
+pub use simulation::TgfpCorePlugin;
+pub use ui::AnnounceGameOver;
+
+// Extension points: events which can be sent to, or read from, the simulation.
+pub use ai::Noise;
+pub use events::{
+    AddStatusEffect, DamageOrHealCreature, EndTurn, RemoveCreature, SummonCreature, TeleportEntity,
+    TransformCreature,
+};
+pub use spells::{CastSpell, TriggerContingency};
+
+// The data those events are made of.
+pub use ai::AiState;
+pub use creature::{
+    Awake, CreatureFlags, DamageKind, FlagEntity, Health, Player, Soul, Species, Spellbook,
+};
+pub use difficulty::{DifficultyPreset, GameDifficulty};
+pub use director::DirectorIntensity;
+pub use map::{Map, Position, TileKind};
+pub use script::{parse_script, ScriptCommand, ScriptError};
+pub use species::SpeciesRegistry;
+pub use spells::{validate_spell, Axiom, Spell, SpellWarning};
+
+// The systems timed by the spell_bench binary.
+pub use events::teleport_entity;
+pub use spells::{cast_new_spell, cleanup_synapses, process_axiom, SpellStack};
+
+// Map authoring, see the editor binary.
+pub use graphics::SpriteSheetAtlas;
+pub use mapgen::{species_from_tile, tile_kind_from_char, MapFile, MapRegion, AUTHORED_MAP};
+pub use text::strip_color_tags;
+
+pub const TILE_SIZE: f32 = 3.;
+
+#[derive(Component, PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum OrdDir {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl OrdDir {
+    pub fn as_offset(self) -> (i32, i32) {
+        let (x, y) = match self {
+            OrdDir::Up => (0, 1),
+            OrdDir::Right => (1, 0),
+            OrdDir::Down => (0, -1),
+            OrdDir::Left => (-1, 0),
+        };
+        (x, y)
+    }
+
+    pub fn rotate_clockwise(self) -> Self {
+        match self {
+            OrdDir::Up => OrdDir::Right,
+            OrdDir::Right => OrdDir::Down,
+            OrdDir::Down => OrdDir::Left,
+            OrdDir::Left => OrdDir::Up,
+        }
+    }
+
+    pub fn rotate_counterclockwise(self) -> Self {
+        self.rotate_clockwise()
+            .rotate_clockwise()
+            .rotate_clockwise()
+    }
+
+    pub fn as_variant(dx: i32, dy: i32) -> Option<Self> {
+        match (dx, dy) {
+            (0, 1) => Some(OrdDir::Up),
+            (0, -1) => Some(OrdDir::Down),
+            (1, 0) => Some(OrdDir::Right),
+            (-1, 0) => Some(OrdDir::Left),
+            _ => None,
+        }
+    }
+
+    pub fn direction_towards_adjacent_tile(
+        source: Position,
+        destination: Position,
+    ) -> Option<Self> {
+        OrdDir::as_variant(destination.x - source.x, destination.y - source.y)
+    }
+
+    /// The general direction of `destination` as seen from `source`, along whichever
+    /// axis is furthest. Exact diagonals lean horizontal.
+    pub fn direction_towards(source: Position, destination: Position) -> Option<Self> {
+        let (dx, dy) = (destination.x - source.x, destination.y - source.y);
+        if dx == 0 && dy == 0 {
+            None
+        } else if dx.abs() >= dy.abs() {
+            OrdDir::as_variant(dx.signum(), 0)
+        } else {
+            OrdDir::as_variant(0, dy.signum())
+        }
+    }
+}
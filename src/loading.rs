@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, thread_rng};
+
+use crate::{
+    map::FloorLoading,
+    sets::Animation,
+    text::{split_text, LOADING_TIPS},
+};
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_loading_screen);
+        app.add_systems(Update, update_loading_screen.in_set(Animation));
+    }
+}
+
+/// The loading screen stays up at least this long, so it doesn't just flicker.
+const MIN_SECONDS: f32 = 0.8;
+
+/// Covers everything while a floor is generated and its creatures summoned.
+#[derive(Component)]
+pub struct LoadingScreen;
+
+#[derive(Component)]
+pub struct LoadingTip;
+
+fn spawn_loading_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 2.,
+        ..default()
+    };
+    commands
+        .spawn((
+            LoadingScreen,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(2.),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK),
+            GlobalZIndex(10),
+            Visibility::Hidden,
+        ))
+        .with_children(|parent| {
+            parent.spawn((Text::new("Descending into Faith's End..."), font.clone()));
+            parent.spawn((
+                LoadingTip,
+                Text::default(),
+                Node {
+                    max_width: Val::Percent(60.),
+                    ..default()
+                },
+                TextLayout::new_with_justify(JustifyText::Center),
+                font,
+            ));
+        });
+}
+
+fn update_loading_screen(
+    loading: Res<FloorLoading>,
+    mut screen: Query<&mut Visibility, With<LoadingScreen>>,
+    tip: Query<(Entity, &TextFont), With<LoadingTip>>,
+    time: Res<Time>,
+    mut shown_for: Local<Option<f32>>,
+    mut commands: Commands,
+) {
+    let (Ok(mut visibility), Ok((tip, font))) = (screen.get_single_mut(), tip.get_single()) else {
+        return;
+    };
+    match (loading.is_loading(), *shown_for) {
+        (true, None) => {
+            // A new tip each time a floor starts loading.
+            let text = LOADING_TIPS.choose(&mut thread_rng()).unwrap();
+            commands.entity(tip).despawn_descendants();
+            commands.entity(tip).with_children(|parent| {
+                for (section, color) in split_text(text) {
+                    parent.spawn((TextSpan::new(section), font.clone(), color));
+                }
+            });
+            *visibility = Visibility::Inherited;
+            *shown_for = Some(0.);
+        }
+        (loading, Some(seconds)) => {
+            let seconds = seconds + time.delta_secs();
+            if !loading && seconds >= MIN_SECONDS {
+                *visibility = Visibility::Hidden;
+                *shown_for = None;
+            } else {
+                *shown_for = Some(seconds);
+            }
+        }
+        (false, None) => (),
+    }
+}
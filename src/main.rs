@@ -1,26 +1,5 @@
-mod caste;
-mod crafting;
-mod creature;
-mod cursor;
-mod events;
-mod graphics;
-mod input;
-mod map;
-mod sets;
-mod spells;
-mod text;
-mod ui;
-
 use bevy::{asset::AssetMetaCheck, prelude::*, window::WindowResolution};
-use cursor::CursorPlugin;
-use events::EventPlugin;
-use graphics::GraphicsPlugin;
-use map::{MapPlugin, Position};
-use sets::SetsPlugin;
-use spells::SpellPlugin;
-use ui::UIPlugin;
-
-pub const TILE_SIZE: f32 = 3.;
+use redesign_tgfp::*;
 
 fn main() {
     let app_window = Some(Window {
@@ -30,70 +9,75 @@ fn main() {
         // mode: bevy::window::WindowMode::Windowed,
         ..default()
     });
-    App::new()
-        .add_plugins(
-            DefaultPlugins
-                .set(AssetPlugin {
-                    meta_check: AssetMetaCheck::Never,
-                    ..Default::default()
-                })
-                .set(ImagePlugin::default_nearest())
-                .set(WindowPlugin {
-                    primary_window: app_window,
-                    ..default()
-                }),
-        )
-        .add_plugins((
-            SetsPlugin,
-            SpellPlugin,
-            EventPlugin,
-            GraphicsPlugin,
-            MapPlugin,
-            UIPlugin,
-            CursorPlugin,
-        ))
-        // .edit_schedule(Update, |schedule| {
-        //     schedule.set_build_settings(ScheduleBuildSettings {
-        //         ambiguity_detection: LogLevel::Warn,
-        //         ..default()
-        //     });
-        // })
-        .run();
-}
-
-#[derive(Component, PartialEq, Eq, Copy, Clone, Debug)]
-pub enum OrdDir {
-    Up,
-    Right,
-    Down,
-    Left,
-}
-
-impl OrdDir {
-    pub fn as_offset(self) -> (i32, i32) {
-        let (x, y) = match self {
-            OrdDir::Up => (0, 1),
-            OrdDir::Right => (1, 0),
-            OrdDir::Down => (0, -1),
-            OrdDir::Left => (-1, 0),
-        };
-        (x, y)
-    }
-
-    pub fn as_variant(dx: i32, dy: i32) -> Option<Self> {
-        match (dx, dy) {
-            (0, 1) => Some(OrdDir::Up),
-            (0, -1) => Some(OrdDir::Down),
-            (1, 0) => Some(OrdDir::Right),
-            (-1, 0) => Some(OrdDir::Left),
-            _ => None,
-        }
-    }
-
-    pub fn direction_towards_adjacent_tile(
-        source: Position,
-        destination: Position,
-    ) -> Option<Self> {
-        OrdDir::as_variant(destination.x - source.x, destination.y - source.y)
-    }
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(AssetPlugin {
+                meta_check: AssetMetaCheck::Never,
+                ..Default::default()
+            })
+            .set(ImagePlugin::default_nearest())
+            .set(WindowPlugin {
+                primary_window: app_window,
+                ..default()
+            }),
+    )
+    .add_plugins(TgfpCorePlugin)
+    .add_plugins((
+        SetsPlugin,
+        GraphicsPlugin,
+        UIPlugin,
+        CursorPlugin,
+        ReplayPlugin,
+        IntegrityPlugin,
+        SaveGamePlugin,
+        SpeciesPlugin,
+        ReviewPlugin,
+        RngPlugin,
+        InteractPlugin,
+        ContentPlugin,
+    ))
+    .add_plugins((
+        ChestPlugin,
+        KeyItemPlugin,
+        GrinderPlugin,
+        IntentPlugin,
+        CraftingPlugin,
+        TerrainPlugin,
+        DirectorPlugin,
+        AudioPlugin,
+        PreviewPlugin,
+        ConveyorPlugin,
+        TrailPlugin,
+        ObjectivePlugin,
+        RumblePlugin,
+        InitiativePlugin,
+        DifficultyPlugin,
+    ))
+    .add_plugins((
+        LoadingPlugin,
+        FocusPlugin,
+        RunStatsPlugin,
+        TurnTimerPlugin,
+        IdlePlugin,
+        ScrollPlugin,
+        CorpsePlugin,
+        ShrinePlugin,
+        SpectatePlugin,
+        HighlightPlugin,
+        AsciiPlugin,
+        SettingsPlugin,
+        WarpPlugin,
+        EvolutionPlugin,
+    ));
+    // app.edit_schedule(Update, |schedule| {
+    //     schedule.set_build_settings(ScheduleBuildSettings {
+    //         ambiguity_detection: LogLevel::Warn,
+    //         ..default()
+    //     });
+    // });
+    // Debug: periodically check for leaked flag entities.
+    #[cfg(feature = "audit")]
+    app.add_plugins(AuditPlugin);
+    app.run();
 }
@@ -1,16 +1,28 @@
+use std::collections::VecDeque;
+
 use bevy::{
     prelude::*,
+    tasks::{block_on, AsyncComputeTaskPool, Task},
     utils::{HashMap, HashSet},
 };
-use rand::{
-    seq::{IteratorRandom, SliceRandom},
-    thread_rng, Rng,
-};
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    creature::{CreatureFlags, FlagEntity, Intangible, Player, Species},
-    events::{RemoveCreature, SummonCreature},
-    ui::AddMessage,
+    ai::PendingPatrols,
+    circuit::{Circuit, Circuits},
+    corpse::Corpse,
+    creature::{
+        CreatureFlags, FlagEntity, FlagQuery, Footprint, Intangible, MovementStyle, Player, Species,
+    },
+    events::{RemoveCreature, SteppedOnTile, SummonCreature, TeleportEntity},
+    mapgen::{
+        generate_cage, generate_level, species_from_tile, tile_kind_from_char, Blueprint,
+        LevelGenConfig, LevelLayout,
+    },
+    rng::GameRng,
+    ui::{AddMessage, Message},
+    vision::symmetric_shadowcasting,
     OrdDir,
 };
 
@@ -20,18 +32,29 @@ impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Map {
             creatures: HashMap::new(),
+            terrain: HashMap::new(),
+            corpses: HashMap::new(),
+            cover: HashSet::new(),
+            intangible_creatures: HashMap::new(),
         });
         app.insert_resource(FaithsEnd {
             cage_address_position: HashMap::new(),
             cage_dimensions: HashMap::new(),
             current_cage: 0,
         });
+        app.init_resource::<LevelGenConfig>();
+        app.init_resource::<Circuits>();
+        app.init_resource::<PendingPatrols>();
+        app.init_resource::<FloorLoading>();
+        app.init_resource::<Regions>();
+        app.add_event::<RegionEntered>();
+        app.add_event::<RegionExited>();
         app.add_systems(Startup, spawn_cage);
     }
 }
 
 /// A position on the map.
-#[derive(Component, PartialEq, Eq, Hash, Copy, Clone, Debug)]
+#[derive(Component, PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -67,13 +90,62 @@ fn manhattan_distance(a: Position, b: Position) -> i32 {
     (a.x - b.x).abs() + (a.y - b.y).abs()
 }
 
+fn chebyshev_distance(a: Position, b: Position) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+/// How far a Dijkstra map floods out before giving up.
+const DIJKSTRA_MAX_DISTANCE: usize = 30;
+
 /// The position of every creature, updated automatically.
 #[derive(Resource)]
 pub struct Map {
     pub creatures: HashMap<Position, Entity>,
+    /// The ground of every tile which is not plain floor, set when a floor is generated.
+    pub terrain: HashMap<Position, TileKind>,
+    /// Corpses never block movement, so they are kept apart from the creatures.
+    pub corpses: HashMap<Position, Entity>,
+    /// The tiles of large corpses, which beams struggle to get through.
+    pub cover: HashSet<Position>,
+    /// Intangible creatures are missing from `creatures`, and kept here instead.
+    /// Several of them can share a tile, like a pickup lying on a belt.
+    pub intangible_creatures: HashMap<Position, Vec<Entity>>,
+}
+
+/// What the ground of a tile is made of, see tread_terrain.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum TileKind {
+    #[default]
+    Floor,
+    /// Does nothing on its own, but stops creatures sliding on ice.
+    Water,
+    /// Burns creatures stepping on it.
+    Lava,
+    /// Creatures stepping on it slide onwards until they hit something.
+    Ice,
+    /// Creatures stepping on it need an extra turn to pull free.
+    Mud,
+}
+
+impl TileKind {
+    /// The colour of the ground, drawn under any creature standing on it.
+    pub fn color(&self) -> Color {
+        match self {
+            TileKind::Floor => Color::NONE,
+            TileKind::Water => Color::srgb(0.15, 0.3, 0.8),
+            TileKind::Lava => Color::srgb(0.9, 0.3, 0.05),
+            TileKind::Ice => Color::srgb(0.7, 0.9, 1.),
+            TileKind::Mud => Color::srgb(0.4, 0.27, 0.12),
+        }
+    }
 }
 
 impl Map {
+    /// What is the ground of this tile made of?
+    pub fn tile_kind(&self, position: &Position) -> TileKind {
+        self.terrain.get(position).copied().unwrap_or_default()
+    }
+
     /// Which creature stands on a certain tile?
     pub fn get_entity_at(&self, x: i32, y: i32) -> Option<&Entity> {
         self.creatures.get(&Position::new(x, y))
@@ -84,6 +156,21 @@ impl Map {
         self.get_entity_at(x, y).is_none()
     }
 
+    /// Every tile which can be seen from `origin`, up to `range` tiles away.
+    /// Creatures for which `blocks_sight` is true can be seen, but hide
+    /// everything behind them.
+    pub fn visible_tiles(
+        &self,
+        origin: Position,
+        range: i32,
+        blocks_sight: impl Fn(&Entity) -> bool,
+    ) -> HashSet<Position> {
+        symmetric_shadowcasting(origin, range, |tile| {
+            self.get_entity_at(tile.x, tile.y)
+                .is_some_and(&blocks_sight)
+        })
+    }
+
     /// Get all tile coordinates of adjacent tiles from a point.
     pub fn get_adjacent_tiles(&self, centre: Position) -> Vec<Position> {
         vec![
@@ -94,6 +181,29 @@ impl Map {
         ]
     }
 
+    /// The closest creature to `origin`, up to `range` tiles away, for which `eligible` is true.
+    /// Ties go to the lowest position, so that the HashMap order does not matter.
+    pub fn nearest_creature(
+        &self,
+        origin: Position,
+        range: i32,
+        eligible: impl Fn(&Position, &Entity) -> bool,
+    ) -> Option<(Position, Entity)> {
+        self.creatures
+            .iter()
+            .filter(|(position, entity)| {
+                manhattan_distance(**position, origin) <= range && eligible(position, entity)
+            })
+            .min_by_key(|(position, _)| {
+                (
+                    manhattan_distance(**position, origin),
+                    position.x,
+                    position.y,
+                )
+            })
+            .map(|(position, entity)| (*position, *entity))
+    }
+
     /// Filter tiles from closest to further to another tile.
     pub fn sort_by_manhattan(
         &self,
@@ -106,9 +216,12 @@ impl Map {
         tiles
     }
 
-    pub fn random_adjacent_passable_direction(&self, start: Position) -> Option<OrdDir> {
+    pub fn random_adjacent_passable_direction(
+        &self,
+        start: Position,
+        rng: &mut impl Rng,
+    ) -> Option<OrdDir> {
         let adjacent = self.get_adjacent_tiles(start);
-        let mut rng = thread_rng();
         let final_choice = adjacent
             .iter()
             // Only keep unblocked tiles.
@@ -116,7 +229,7 @@ impl Map {
             // Remove the borrow.
             // Get the tile that manages to close the most distance to the destination.
             // If it exists, that is. Otherwise, this is just a None.
-            .choose(&mut rng);
+            .choose(rng);
         if let Some(final_choice) = final_choice {
             OrdDir::direction_towards_adjacent_tile(start, *final_choice)
         } else {
@@ -146,6 +259,227 @@ impl Map {
         }
     }
 
+    /// Flood outwards from `sources`, writing down how many steps it takes
+    /// to reach each tile. Only tiles for which `passable` is true are explored.
+    pub fn dijkstra_map(
+        &self,
+        sources: &[Position],
+        passable: impl Fn(Position) -> bool,
+    ) -> HashMap<Position, usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        for source in sources {
+            distances.insert(*source, 0);
+            queue.push_back(*source);
+        }
+        // All steps cost the same, so a simple breadth-first search
+        // gives the same result as Dijkstra's algorithm.
+        while let Some(tile) = queue.pop_front() {
+            let distance = distances[&tile];
+            if distance >= DIJKSTRA_MAX_DISTANCE {
+                continue;
+            }
+            for adjacent in self.get_adjacent_tiles(tile) {
+                if distances.contains_key(&adjacent) || !passable(adjacent) {
+                    continue;
+                }
+                distances.insert(adjacent, distance + 1);
+                queue.push_back(adjacent);
+            }
+        }
+        distances
+    }
+
+    /// Roll downhill on a Dijkstra map: pick the adjacent tile with the lowest distance.
+    /// `cost` adds an extra penalty to some tiles, to make them less attractive.
+    pub fn best_dijkstra_move(
+        &self,
+        start: Position,
+        distances: &HashMap<Position, usize>,
+        cost: impl Fn(Position) -> usize,
+    ) -> Option<OrdDir> {
+        let final_choice = self
+            .get_adjacent_tiles(start)
+            .into_iter()
+            .filter_map(|p| distances.get(&p).map(|distance| (p, distance + cost(p))))
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(p, _)| p)?;
+        OrdDir::direction_towards_adjacent_tile(start, final_choice)
+    }
+
+    /// Like best_dijkstra_move, but creatures too far away to be on the Dijkstra map
+    /// (see DIJKSTRA_MAX_DISTANCE) walk straight towards `target` instead.
+    fn dijkstra_or_manhattan_move(
+        &self,
+        start: Position,
+        target: Position,
+        distances: &HashMap<Position, usize>,
+        cost: impl Fn(Position) -> usize,
+    ) -> Option<OrdDir> {
+        let on_map = self
+            .get_adjacent_tiles(start)
+            .iter()
+            .any(|p| distances.contains_key(p));
+        if on_map {
+            self.best_dijkstra_move(start, distances, cost)
+        } else {
+            self.best_manhattan_move(start, target)
+        }
+    }
+
+    /// Pick a move according to a creature's movement style, relative to its `target`.
+    pub fn styled_move(
+        &self,
+        style: &MovementStyle,
+        start: Position,
+        target: Position,
+        is_wall: impl Fn(Position) -> bool,
+    ) -> Option<OrdDir> {
+        let passable = |p: Position| self.is_passable(p.x, p.y);
+        match style {
+            MovementStyle::WallHugger => {
+                let distances = self.dijkstra_map(&[target], passable);
+                let hugs_wall = |p: Position| self.get_adjacent_tiles(p).into_iter().any(&is_wall);
+                // Straying away from the walls costs an extra step.
+                self.dijkstra_or_manhattan_move(start, target, &distances, |p| {
+                    if p == target || hugs_wall(p) {
+                        0
+                    } else {
+                        1
+                    }
+                })
+            }
+            MovementStyle::Skirmisher => {
+                match manhattan_distance(start, target) {
+                    // Already adjacent, attack.
+                    1 => OrdDir::direction_towards_adjacent_tile(start, target),
+                    // Just out of reach, wait for the target to come closer.
+                    2 => None,
+                    _ => {
+                        let keeps_distance =
+                            |p: Position| passable(p) && manhattan_distance(p, target) >= 2;
+                        let sources: Vec<Position> = (-2..=2)
+                            .flat_map(|dx| (-2..=2).map(move |dy| (dx, dy)))
+                            .map(|(dx, dy)| Position::new(target.x + dx, target.y + dy))
+                            .filter(|p| manhattan_distance(*p, target) == 2 && passable(*p))
+                            .collect();
+                        let distances = self.dijkstra_map(&sources, keeps_distance);
+                        self.dijkstra_or_manhattan_move(start, target, &distances, |_| 0)
+                    }
+                }
+            }
+            MovementStyle::Circler { range } => {
+                let range = *range;
+                if chebyshev_distance(start, target) == range {
+                    // Orbit clockwise around the target.
+                    let (dx, dy) = (start.x - target.x, start.y - target.y);
+                    let final_choice = self
+                        .get_adjacent_tiles(start)
+                        .into_iter()
+                        .filter(|p| passable(*p) && chebyshev_distance(*p, target) == range)
+                        .max_by_key(|p| (p.x - start.x) * dy - (p.y - start.y) * dx)
+                        .filter(|p| (p.x - start.x) * dy - (p.y - start.y) * dx > 0)?;
+                    return OrdDir::direction_towards_adjacent_tile(start, final_choice);
+                }
+                let sources: Vec<Position> = (-range..=range)
+                    .flat_map(|dx| (-range..=range).map(move |dy| (dx, dy)))
+                    .map(|(dx, dy)| Position::new(target.x + dx, target.y + dy))
+                    .filter(|p| chebyshev_distance(*p, target) == range && passable(*p))
+                    .collect();
+                let distances = self.dijkstra_map(&sources, passable);
+                self.dijkstra_or_manhattan_move(start, target, &distances, |_| 0)
+            }
+        }
+    }
+
+    /// Roll uphill on a Dijkstra map centered on `threat`: pick the adjacent tile
+    /// which is the most steps away from it, if that gets further than `start` is.
+    /// Tiles the threat cannot reach at all count as the furthest.
+    pub fn flee_move(&self, start: Position, threat: Position) -> Option<OrdDir> {
+        let passable = |p: Position| self.is_passable(p.x, p.y);
+        // The threat may path through the fleeing creature, so tiles behind it
+        // in a corridor are measured as further than where it stands.
+        let distances = self.dijkstra_map(&[threat], |p| p == start || passable(p));
+        let distance = |p: Position| {
+            distances
+                .get(&p)
+                .copied()
+                .unwrap_or(DIJKSTRA_MAX_DISTANCE + 1)
+        };
+        let final_choice = self
+            .get_adjacent_tiles(start)
+            .into_iter()
+            .filter(|p| passable(*p))
+            // Ties go to the first direction, to stay deterministic.
+            .fold(None, |best: Option<(Position, usize)>, p| match best {
+                Some((_, best_distance)) if best_distance >= distance(p) => best,
+                _ => Some((p, distance(p))),
+            })
+            .filter(|(_, far)| *far > distance(start))
+            .map(|(p, _)| p)?;
+        OrdDir::direction_towards_adjacent_tile(start, final_choice)
+    }
+
+    /// Is every one of these tiles free for `entity`? Tiles it already covers do not block it.
+    pub fn is_passable_for(&self, entity: Entity, tiles: &[Position]) -> bool {
+        self.blocker_for(entity, tiles).is_none()
+    }
+
+    /// The first creature other than `entity` standing on one of these tiles, and where.
+    pub fn blocker_for(&self, entity: Entity, tiles: &[Position]) -> Option<(Position, Entity)> {
+        tiles
+            .iter()
+            .filter_map(|p| self.get_entity_at(p.x, p.y).map(|occupant| (*p, *occupant)))
+            .find(|(_, occupant)| *occupant != entity)
+    }
+
+    /// Move a creature covering multiple tiles around the Map.
+    pub fn move_tiles(&mut self, entity: Entity, old_tiles: &[Position], new_tiles: &[Position]) {
+        self.remove_tiles(entity, old_tiles);
+        for tile in new_tiles {
+            self.creatures.insert(*tile, entity);
+        }
+    }
+
+    /// Forget that `entity` was standing on these tiles.
+    pub fn remove_tiles(&mut self, entity: Entity, tiles: &[Position]) {
+        for tile in tiles {
+            // Only remove the tile if it actually belongs to this creature.
+            if self.creatures.get(tile) == Some(&entity) {
+                self.creatures.remove(tile);
+            }
+        }
+    }
+
+    /// Every intangible creature on this tile.
+    pub fn intangible_at(&self, position: &Position) -> &[Entity] {
+        self.intangible_creatures
+            .get(position)
+            .map_or(&[], |entities| entities.as_slice())
+    }
+
+    /// Remember that intangible `entity` now stands on these tiles.
+    pub fn add_intangible(&mut self, entity: Entity, tiles: &[Position]) {
+        for tile in tiles {
+            let entities = self.intangible_creatures.entry(*tile).or_default();
+            if !entities.contains(&entity) {
+                entities.push(entity);
+            }
+        }
+    }
+
+    /// Forget that intangible `entity` was standing on these tiles.
+    pub fn remove_intangible(&mut self, entity: Entity, tiles: &[Position]) {
+        for tile in tiles {
+            if let Some(entities) = self.intangible_creatures.get_mut(tile) {
+                entities.retain(|other| *other != entity);
+                if entities.is_empty() {
+                    self.intangible_creatures.remove(tile);
+                }
+            }
+        }
+    }
+
     /// Move a pre-existing entity around the Map.
     pub fn move_creature(&mut self, old_pos: Position, new_pos: Position) {
         // As the entity already existed in the Map's records, remove it.
@@ -155,66 +489,179 @@ impl Map {
     }
 }
 
+/// Every tile covered by a creature standing on `position`.
+pub fn occupied_tiles(position: Position, footprint: Option<&Footprint>) -> Vec<Position> {
+    match footprint {
+        Some(footprint) => footprint.tiles(position),
+        None => vec![position],
+    }
+}
+
 /// Newly spawned creatures earn their place in the HashMap.
 pub fn register_creatures(
     mut map: ResMut<Map>,
     // Any entity that has a Position that just got added to it -
     // currently only possible as a result of having just been spawned in.
     // Naturally intangible creatures skip this.
-    newly_positioned_creatures: Query<(&Position, Entity, &CreatureFlags), Added<Position>>,
+    newly_positioned_creatures: Query<
+        (&Position, Entity, &CreatureFlags, Option<&Footprint>),
+        Added<Position>,
+    >,
     intangible_query: Query<&FlagEntity, Added<Intangible>>,
 
-    intangible_creature: Query<&Position>,
-    tangible_creatures: Query<&Position, With<Species>>,
+    intangible_creature: Query<(&Position, Option<&Footprint>)>,
+    tangible_creatures: Query<(&Position, Option<&Footprint>), With<Species>>,
     flag_query: Query<&FlagEntity>,
     mut tangible_entities: RemovedComponents<Intangible>,
     mut remove: EventWriter<RemoveCreature>,
 ) {
-    for (position, entity, flags) in newly_positioned_creatures.iter() {
+    for (position, entity, flags, footprint) in newly_positioned_creatures.iter() {
         // Intangible creatures are not added to the map.
-        if !intangible_query.contains(flags.effects_flags)
-            && !intangible_query.contains(flags.species_flags)
-        {
+        if !intangible_query.flagged(flags) {
             // Insert the new creature in the Map. Position implements Copy,
             // so it can be dereferenced (*), but `.clone()` would have been
             // fine too.
-            map.creatures.insert(*position, entity);
+            // Large creatures take up all the tiles they cover.
+            for tile in occupied_tiles(*position, footprint) {
+                map.creatures.insert(tile, entity);
+            }
         }
     }
 
     // A creature recovering its tangibility is added to the map.
     for flag_entity in tangible_entities.read() {
-        let entity = flag_query.get(flag_entity).unwrap().parent_creature;
-        if let Ok(tangible_position) = tangible_creatures.get(entity) {
-            if map.creatures.get(tangible_position).is_some() {
+        // Despawned creatures, such as those cleared out by restore_save, lose it too.
+        let Ok(flag) = flag_query.get(flag_entity) else {
+            continue;
+        };
+        let entity = flag.parent_creature;
+        if let Ok((tangible_position, footprint)) = tangible_creatures.get(entity) {
+            let tiles = occupied_tiles(*tangible_position, footprint);
+            if !map.is_passable_for(entity, &tiles) {
                 // NOTE: This is kind of like Caves of Qud's death by phasing
                 // ("the pauli principle"). Creatures recovering tangibility
                 // on top of another die. I am mostly adding this so I can
                 // debug the occasional door issue.
-                remove.send(RemoveCreature { entity });
+                remove.send(RemoveCreature {
+                    entity,
+                    culprit: None,
+                });
                 dbg!(tangible_position);
                 dbg!("A creature recovered its tangibility while on top of another creature!");
             } else {
-                map.creatures.insert(*tangible_position, entity);
+                map.remove_intangible(entity, &tiles);
+                map.move_tiles(entity, &[], &tiles);
             }
         }
     }
 
-    // Newly intangible creatures are removed from the map.
+    // Newly intangible creatures are moved from the map to its intangible index.
     for flag_entity in intangible_query.iter() {
-        let intangible_position = intangible_creature
+        let (intangible_position, footprint) = intangible_creature
             .get(flag_entity.parent_creature)
             .unwrap();
-        if let Some(preexisting_entity) = map.creatures.get(intangible_position) {
-            // Check that the entity being removed is actually the intangible entity.
-            // REASON: If a creature spawns in already intangible on top of a
-            // tangible creature, without this check, it would remove
-            // the tangible creature from the map.
-            if *preexisting_entity != flag_entity.parent_creature {
-                continue;
+        let tiles = occupied_tiles(*intangible_position, footprint);
+        // NOTE: remove_tiles checks that the entity being removed is actually the
+        // intangible entity.
+        // REASON: If a creature spawns in already intangible on top of a
+        // tangible creature, without this check, it would remove
+        // the tangible creature from the map.
+        map.remove_tiles(flag_entity.parent_creature, &tiles);
+        map.add_intangible(flag_entity.parent_creature, &tiles);
+    }
+}
+
+/// A named rectangle of the floor, from its bottom left tile to its top right one.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Region {
+    pub name: String,
+    pub min: Position,
+    pub max: Position,
+}
+
+impl Region {
+    pub fn contains(&self, position: &Position) -> bool {
+        (self.min.x..=self.max.x).contains(&position.x)
+            && (self.min.y..=self.max.y).contains(&position.y)
+    }
+}
+
+/// Every named room of the current floors, as authored in MapFile::regions.
+#[derive(Resource, Default)]
+pub struct Regions {
+    pub regions: Vec<Region>,
+}
+
+impl Regions {
+    /// Every region this tile is part of. Regions may overlap.
+    pub fn containing<'a>(&'a self, position: &'a Position) -> impl Iterator<Item = &'a Region> {
+        self.regions
+            .iter()
+            .filter(move |region| region.contains(position))
+    }
+}
+
+/// A creature walked into a region.
+#[derive(Event)]
+pub struct RegionEntered {
+    pub entity: Entity,
+    pub region: String,
+}
+
+/// A creature walked out of a region.
+#[derive(Event)]
+pub struct RegionExited {
+    pub entity: Entity,
+    pub region: String,
+}
+
+/// Compare the regions of the tiles creatures step from and onto, to tell
+/// which ones they enter or leave.
+pub fn track_regions(
+    mut events: EventReader<SteppedOnTile>,
+    regions: Res<Regions>,
+    mut entered: EventWriter<RegionEntered>,
+    mut exited: EventWriter<RegionExited>,
+) {
+    if regions.regions.is_empty() {
+        return;
+    }
+    for event in events.read() {
+        for region in regions.containing(&event.origin) {
+            if !region.contains(&event.position) {
+                exited.send(RegionExited {
+                    entity: event.entity,
+                    region: region.name.clone(),
+                });
+            }
+        }
+        for region in regions.containing(&event.position) {
+            if !region.contains(&event.origin) {
+                entered.send(RegionEntered {
+                    entity: event.entity,
+                    region: region.name.clone(),
+                });
             }
         }
-        map.creatures.remove(intangible_position);
+    }
+}
+
+/// Name each region the player walks into or out of.
+pub fn announce_regions(
+    mut entered: EventReader<RegionEntered>,
+    mut exited: EventReader<RegionExited>,
+    player: Query<(), With<Player>>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in exited.read().filter(|event| player.contains(event.entity)) {
+        text.send(AddMessage {
+            message: Message::RegionExited(event.region.clone()),
+        });
+    }
+    for event in entered.read().filter(|event| player.contains(event.entity)) {
+        text.send(AddMessage {
+            message: Message::RegionEntered(event.region.clone()),
+        });
     }
 }
 
@@ -225,255 +672,251 @@ pub struct FaithsEnd {
     pub current_cage: usize,
 }
 
+/// How many creatures of a new floor are summoned each frame.
+const SPAWNS_PER_FRAME: usize = 16;
+
+/// Every floor of the tower, and the corner each one is placed at.
+type Floors = Vec<(Blueprint, Position)>;
+
+/// A floor being generated in the background, then summoned a few creatures at a time.
+#[derive(Resource, Default)]
+pub struct FloorLoading {
+    task: Option<Task<Floors>>,
+    pending: VecDeque<SummonCreature>,
+    /// Where the returning player goes, once everything else is summoned.
+    player_start: Option<(Entity, Position)>,
+    /// Wait for generation to finish instead of polling it. Headless worlds
+    /// need every run to take the same amount of frames.
+    pub blocking: bool,
+}
+
+impl FloorLoading {
+    pub fn is_loading(&self) -> bool {
+        self.task.is_some() || !self.pending.is_empty() || self.player_start.is_some()
+    }
+
+    /// Drop the floor being generated, and whatever was left to summon.
+    pub fn cancel(&mut self) {
+        self.task = None;
+        self.pending.clear();
+        self.player_start = None;
+    }
+}
+
+pub fn floor_is_loading(loading: Res<FloorLoading>) -> bool {
+    loading.is_loading()
+}
+
+/// Start generating the next floor, away from the main thread.
 pub fn spawn_cage(
-    mut summon: EventWriter<SummonCreature>,
-    mut faiths_end: ResMut<FaithsEnd>,
-    player: Query<&Player>,
+    player: Query<Entity, With<Player>>,
+    config: Res<LevelGenConfig>,
+    mut loading: ResMut<FloorLoading>,
+    mut game_rng: ResMut<GameRng>,
     mut text: EventWriter<AddMessage>,
 ) {
     text.send(AddMessage {
         message: crate::ui::Message::Tutorial,
     });
-    let tower_height = 1;
-    let mut tower_height_tiles = 0;
-    let mut last_room_size = 9;
-    for tower_floor in 0..tower_height {
-        let size = if tower_floor == tower_height - 1 {
-            17
-        } else {
-            9
-        };
-        let mut cage = generate_cage(
-            tower_floor,
-            // Spawn the player in the first room
-            // (the player must not already exist).
-            tower_floor == 0 && player.is_empty(),
-            tower_floor != tower_height - 1,
-            size,
-            if tower_floor == 0 {
-                &[OrdDir::Up]
-            } else if tower_floor == tower_height - 1 {
-                &[OrdDir::Down]
-            } else {
-                &[OrdDir::Up, OrdDir::Down]
-            },
-        );
-        add_creatures(&mut cage, 2 + tower_floor, tower_floor == tower_height - 1);
+    // NOTE: The seed is drawn right away, so the GameRng does not depend
+    // on how long the task takes.
+    let rng = config.rng(&mut game_rng);
+    let config = config.clone();
+    let spawn_player = player.is_empty();
+    loading.pending.clear();
+    loading.player_start = None;
+    loading.task = Some(
+        AsyncComputeTaskPool::get()
+            .spawn(async move { generate_floors(&config, spawn_player, rng) }),
+    );
+}
 
-        for (idx, tile_char) in cage.iter().enumerate() {
+fn generate_floors(config: &LevelGenConfig, spawn_player: bool, mut rng: StdRng) -> Floors {
+    let mut floors = Vec::new();
+    if config.layout == LevelLayout::Cage {
+        let tower_height = 1;
+        let mut tower_height_tiles = 0;
+        let mut last_room_size = 9;
+        for tower_floor in 0..tower_height {
+            let size = if tower_floor == tower_height - 1 {
+                17
+            } else {
+                9
+            };
+            let cage = generate_cage(
+                tower_floor,
+                // Spawn the player in the first room
+                // (the player must not already exist).
+                tower_floor == 0 && spawn_player,
+                tower_floor != tower_height - 1,
+                size,
+                if tower_floor == 0 {
+                    &[OrdDir::Up]
+                } else if tower_floor == tower_height - 1 {
+                    &[OrdDir::Down]
+                } else {
+                    &[OrdDir::Up, OrdDir::Down]
+                },
+                config,
+                &mut rng,
+            );
             let cage_corner = Position::new(
                 (last_room_size as i32 - size as i32) / 2,
                 tower_height_tiles as i32,
             );
-            let position = Position::new(
-                cage_corner.x + idx as i32 % size as i32,
-                cage_corner.y + size as i32 - 1 - idx as i32 / size as i32,
+            floors.push((cage, cage_corner));
+            tower_height_tiles += size;
+            last_room_size = size;
+        }
+    } else {
+        floors.push((
+            generate_level(config, spawn_player, &mut rng),
+            Position::new(0, 0),
+        ));
+    }
+    floors
+}
+
+/// Once the floor is generated, queue up its creatures and lay out its circuits.
+pub fn finish_floor_generation(
+    mut loading: ResMut<FloorLoading>,
+    mut faiths_end: ResMut<FaithsEnd>,
+    player: Query<Entity, With<Player>>,
+    mut circuits: ResMut<Circuits>,
+    mut patrols: ResMut<PendingPatrols>,
+    mut map: ResMut<Map>,
+    mut regions: ResMut<Regions>,
+    corpses: Query<Entity, With<Corpse>>,
+    mut commands: Commands,
+) {
+    let blocking = loading.blocking;
+    let Some(task) = loading.task.take_if(|task| blocking || task.is_finished()) else {
+        return;
+    };
+    let floors = block_on(task);
+    circuits.circuits.clear();
+    patrols.routes.clear();
+    map.terrain.clear();
+    map.corpses.clear();
+    map.cover.clear();
+    regions.regions.clear();
+    // The new floor may not have the same cages, or cages of the same size.
+    faiths_end.cage_address_position.clear();
+    faiths_end.cage_dimensions.clear();
+    for corpse in corpses.iter() {
+        commands.entity(corpse).despawn();
+    }
+    for (tower_floor, (blueprint, corner)) in floors.iter().enumerate() {
+        let position_of = |idx: usize| {
+            let (x, y) = blueprint.xy(idx);
+            Position::new(
+                corner.x + x as i32,
+                corner.y + blueprint.height as i32 - 1 - y as i32,
+            )
+        };
+        for (idx, tile_char) in blueprint.tiles.iter().enumerate() {
+            if let Some(kind) = tile_kind_from_char(*tile_char) {
+                map.terrain.insert(position_of(idx), kind);
+            }
+        }
+        let creatures = blueprint
+            .tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, tile_char)| {
+                species_from_tile(*tile_char).map(|(species, momentum)| {
+                    let species = blueprint
+                        .overrides
+                        .iter()
+                        .find(|(overridden, _)| *overridden == idx)
+                        .map_or(species, |(_, species)| *species);
+                    (idx, species, momentum)
+                })
+            })
+            .chain(
+                blueprint
+                    .creatures
+                    .iter()
+                    .map(|(idx, species)| (*idx, *species, OrdDir::Down)),
             );
-            let species = match tile_char {
-                '#' => Species::Wall,
-                'H' => Species::Hunter,
-                'S' => Species::Spawner,
-                'T' => Species::Tinker,
-                '@' => Species::Player,
-                'W' => Species::WeakWall,
-                '2' => Species::Second,
-                'A' => Species::Apiarist,
-                'F' => Species::Shrike,
-                'O' => Species::Oracle,
-                'E' => Species::EpsilonHead,
-                't' => Species::EpsilonTail,
-                'x' => Species::CageSlot,
-                '^' | '>' | '<' | 'V' => Species::Airlock,
-                'w' | 'n' | 'e' | 's' => Species::CageBorder,
-                _ => continue,
-            };
-            let momentum = match tile_char {
-                '^' => OrdDir::Up,
-                '>' => OrdDir::Right,
-                '<' => OrdDir::Left,
-                'n' => OrdDir::Up,
-                'e' => OrdDir::Right,
-                'w' => OrdDir::Left,
-                's' => OrdDir::Down,
-                'V' | _ => OrdDir::Down,
-            };
-            summon.send(SummonCreature {
+        for (idx, species, momentum) in creatures {
+            let position = position_of(idx);
+            loading.pending.push_back(SummonCreature {
                 species,
                 position,
                 momentum,
                 summoner_tile: Position::new(0, 0),
                 summoner: None,
                 spellbook: None,
+                projectile: None,
+                owner: None,
             });
             faiths_end
                 .cage_address_position
                 .insert(position, tower_floor);
-            // If there is no player yet (first run),
-            // set the boundaries.
-            if player.is_empty() {
-                faiths_end.cage_dimensions.insert(
-                    tower_floor,
-                    (
-                        cage_corner,
-                        Position::new(
-                            cage_corner.x + size as i32 - 1,
-                            cage_corner.y + size as i32 - 1,
-                        ),
-                    ),
-                );
-            }
         }
-        tower_height_tiles += size;
-        last_room_size = size;
-    }
-}
-
-fn add_creatures(cage: &mut [char], creatures_amount: usize, spawn_snake: bool) {
-    if spawn_snake {
-        cage[20] = 'E';
-        cage[21] = 't';
-        // cage[22] = 't';
-        // cage[23] = 't';
-        // cage[24] = 't';
-        cage[90] = 'E';
-        cage[91] = 't';
-        // cage[92] = 't';
-        // cage[93] = 't';
-        // cage[94] = 't';
-        cage[112] = 's';
-        cage[113] = 's';
-        cage[114] = 's';
-        cage[112 + 17 * 4] = 'n';
-        cage[113 + 17 * 4] = 'n';
-        cage[114 + 17 * 4] = 'n';
-        cage[112 + 17 * 1 - 1] = 'e';
-        cage[113 + 17 * 2 - 2] = 'e';
-        cage[114 + 17 * 3 - 3] = 'e';
-        cage[112 + 17 * 1 + 3] = 'w';
-        cage[113 + 17 * 2 + 2] = 'w';
-        cage[114 + 17 * 3 + 1] = 'w';
-        cage[112 + 17 * 1 - 0] = 'x';
-        cage[113 + 17 * 2 - 1] = 'x';
-        cage[114 + 17 * 3 - 2] = 'x';
-        cage[112 + 17 * 1 + 2] = 'x';
-        cage[113 + 17 * 2 + 1] = 'x';
-        cage[114 + 17 * 3 + 0] = 'x';
-        cage[112 + 17 * 1 + 1] = 'x';
-        cage[113 + 17 * 2 + 0] = 'x';
-        cage[114 + 17 * 3 - 1] = 'x';
-        return;
-    }
-
-    let creature_chars = ['A', 'T', 'F', '2', 'H', 'O'];
-
-    let floor_positions: Vec<usize> = cage
-        .iter()
-        .enumerate()
-        .filter(|&(_, c)| *c == '.')
-        .map(|(i, _)| i)
-        .collect();
-
-    let mut rng = thread_rng();
-    let creature_spawn_points = floor_positions.choose_multiple(&mut rng, creatures_amount);
-
-    for pos in creature_spawn_points {
-        let new_creature = *creature_chars.choose(&mut rng).unwrap();
-        cage[*pos] = new_creature;
-    }
-}
-
-pub fn generate_cage(
-    floor: usize,
-    spawn_player: bool,
-    spawn_walls: bool,
-    size: usize,
-    connections: &[OrdDir],
-) -> Vec<char> {
-    let mut cage = Vec::new();
-
-    for _i in 0..100 {
-        let mut passable_tiles = 0;
-        let mut idx_start = 0;
-        let mut rng = thread_rng();
-        for i in 0..size.pow(2) {
-            // If the player is here, it spawns in the middle.
-            if spawn_player && xy_idx(i, size) == ((size - 1) / 2, (size - 1) / 2) {
-                cage.push('@');
-                passable_tiles += 1;
-            // If the player is already spawned, the bottom cage should still
-            // not place anything in its centre so the player can be teleported
-            // there.
-            } else if !spawn_player
-                && floor == 0
-                && xy_idx(i, size) == ((size - 1) / 2, (size - 1) / 2)
-            {
-                cage.push('.');
-                passable_tiles += 1;
-            // Edges get walls 100% of the time, other tiles, 30% of the time.
-            } else if is_edge(i, size) {
-                cage.push('#');
-            } else if rng.gen::<f32>() < 0.3 && spawn_walls {
-                cage.push('W');
-            // Everything else is a floor.
-            } else {
-                cage.push('.');
-                passable_tiles += 1;
-                idx_start = i;
-            }
+        for wiring in &blueprint.wiring {
+            circuits.circuits.push(Circuit {
+                emitters: wiring
+                    .emitters
+                    .iter()
+                    .map(|idx| position_of(*idx))
+                    .collect(),
+                receivers: wiring
+                    .receivers
+                    .iter()
+                    .map(|idx| position_of(*idx))
+                    .collect(),
+                powered: false,
+            });
         }
-        for airlock in connections {
-            match airlock {
-                OrdDir::Up => {
-                    cage[size / 2] = '^';
-                }
-                OrdDir::Left => {
-                    cage[size * (size / 2)] = '<';
-                }
-                OrdDir::Right => {
-                    cage[size * (size / 2 + 1) - 1] = '>';
-                }
-                OrdDir::Down => {
-                    cage[size * size - size / 2 - 1] = 'V';
-                }
-            }
-            passable_tiles += 1;
+        for region in &blueprint.regions {
+            let (first, second) = (position_of(region.corners.0), position_of(region.corners.1));
+            regions.regions.push(Region {
+                name: region.name.clone(),
+                min: Position::new(first.x.min(second.x), first.y.min(second.y)),
+                max: Position::new(first.x.max(second.x), first.y.max(second.y)),
+            });
         }
-        // Every passable tile must be connected to all other passable tiles, no "islands".
-        if passable_tiles == get_connected_tiles(idx_start, size, &cage) {
-            return cage;
-        } else {
-            cage.clear();
+        for route in &blueprint.patrols {
+            patrols
+                .routes
+                .push(route.iter().map(|idx| position_of(*idx)).collect());
+        }
+        faiths_end.cage_dimensions.insert(
+            tower_floor,
+            (
+                *corner,
+                Position::new(
+                    corner.x + blueprint.width as i32 - 1,
+                    corner.y + blueprint.height as i32 - 1,
+                ),
+            ),
+        );
+        // Returning players start at the bottom of the new floor.
+        if tower_floor == 0 {
+            if let Ok(player) = player.get_single() {
+                loading.player_start = Some((player, position_of(blueprint.start)));
+            }
         }
     }
-    panic!("Cage generation timeout achieved.");
-}
-
-fn xy_idx(idx: usize, size: usize) -> (usize, usize) {
-    (idx % size, idx / size)
-}
-
-fn is_edge(idx: usize, size: usize) -> bool {
-    idx % size == 0 || idx % size == size - 1 || idx / size == 0 || idx / size == size - 1
 }
 
-fn get_connected_tiles(idx_start: usize, size: usize, cage: &[char]) -> usize {
-    // All previously found floor tiles.
-    let mut connected_indices = HashSet::new();
-    connected_indices.insert(idx_start);
-    // The new neighbours to inspect.
-    let mut frontier_indices = vec![idx_start];
-    while let Some(frontier) = frontier_indices.pop() {
-        // Get each frontier's 4 adjacent neighbours.
-        for neighbour in [frontier + 1, frontier - 1, frontier + size, frontier - size] {
-            // Add all floors that are not already known.
-            if !['W', '#'].contains(&cage[neighbour]) && !connected_indices.contains(&neighbour) {
-                // Airlocks are on the edge, and not worth expanding from.
-                if !['V', '^', '<', '>'].contains(&cage[neighbour]) {
-                    frontier_indices.push(neighbour);
-                }
-                connected_indices.insert(neighbour);
-            }
+/// Summon the creatures of a freshly generated floor over a few frames,
+/// instead of hitching on a single one.
+pub fn stream_floor_spawns(
+    mut loading: ResMut<FloorLoading>,
+    mut summon: EventWriter<SummonCreature>,
+    mut teleport: EventWriter<TeleportEntity>,
+) {
+    let amount = loading.pending.len().min(SPAWNS_PER_FRAME);
+    summon.send_batch(loading.pending.drain(..amount));
+    if loading.pending.is_empty() && loading.task.is_none() {
+        if let Some((entity, destination)) = loading.player_start.take() {
+            teleport.send(TeleportEntity {
+                destination,
+                entity,
+            });
         }
     }
-    connected_indices.len()
 }
@@ -0,0 +1,869 @@
+use bevy::{prelude::*, utils::HashSet};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{creature::Species, map::TileKind, rng::GameRng, OrdDir};
+
+/// Creatures are never placed this close to where the player starts.
+const SAFE_RADIUS: usize = 3;
+/// At most this many chests are hidden in the dead ends of a floor.
+const DEAD_END_CHESTS: usize = 2;
+/// How many patches of water, lava, ice or mud are spread on a floor.
+const TERRAIN_PATCHES: usize = 3;
+/// The most tiles a single patch of terrain can cover.
+const TERRAIN_PATCH_SIZE: usize = 6;
+/// How far away creatures make a tile dangerous enough for a shrine.
+const SHRINE_DANGER_RADIUS: usize = 3;
+/// How many spots are tried before giving up on placing a prefab.
+const PREFAB_ATTEMPTS: usize = 50;
+
+/// How the next floor will be generated.
+#[derive(Resource, Clone)]
+pub struct LevelGenConfig {
+    /// The same seed always generates the same floor.
+    /// If None, every floor is a fresh one, drawn from the GameRng.
+    pub seed: Option<u64>,
+    pub layout: LevelLayout,
+    pub width: usize,
+    pub height: usize,
+    /// Cages: the chance of each tile being a wall.
+    /// Rooms and corridors: how much of the floor is covered by rooms.
+    /// Caves: how much of the floor is rock, before it gets smoothed out.
+    pub density: f32,
+    /// How many creatures are placed on the floor.
+    pub creatures: usize,
+    /// Which species can be placed, and how likely each one is to be picked.
+    pub species: Vec<(Species, u32)>,
+    /// Hand-drawn rooms, one of which may be stamped onto each floor. See add_prefab.
+    pub prefabs: Vec<Vec<String>>,
+}
+
+impl Default for LevelGenConfig {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            layout: LevelLayout::Cage,
+            width: 17,
+            height: 17,
+            density: 0.3,
+            creatures: 2,
+            species: vec![
+                (Species::Apiarist, 1),
+                (Species::Tinker, 1),
+                (Species::Shrike, 1),
+                (Species::Second, 1),
+                (Species::Hunter, 1),
+                (Species::Oracle, 1),
+            ],
+            prefabs: Vec::new(),
+        }
+    }
+}
+
+impl LevelGenConfig {
+    /// The random number generator for the next floor.
+    pub fn rng(&self, game_rng: &mut GameRng) -> StdRng {
+        let seed = self.seed.unwrap_or_else(|| game_rng.gen());
+        info!("Generating a {:?} floor with seed {}.", self.layout, seed);
+        StdRng::seed_from_u64(seed)
+    }
+
+    /// Cycle to the next layout, for debugging.
+    pub fn next_layout(&mut self) {
+        self.layout = match self.layout {
+            LevelLayout::Cage => LevelLayout::RoomsAndCorridors,
+            LevelLayout::RoomsAndCorridors => LevelLayout::Caves,
+            LevelLayout::Caves => LevelLayout::Authored,
+            LevelLayout::Authored => LevelLayout::Cage,
+        };
+        self.density = match self.layout {
+            LevelLayout::Cage => 0.3,
+            LevelLayout::RoomsAndCorridors => 0.4,
+            LevelLayout::Caves | LevelLayout::Authored => 0.45,
+        };
+        (self.width, self.height) = match self.layout {
+            LevelLayout::Cage => (17, 17),
+            _ => (40, 30),
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LevelLayout {
+    /// A square cage of scattered walls, ending in the Epsilon fight.
+    Cage,
+    /// Rectangular rooms, joined together by corridors.
+    RoomsAndCorridors,
+    /// Winding caves, carved out by a cellular automaton.
+    Caves,
+    /// A floor painted in the editor, read from AUTHORED_MAP.
+    Authored,
+}
+
+/// Where the editor saves by default, and where LevelLayout::Authored floors come from.
+pub const AUTHORED_MAP: &str = "assets/maps/authored.ron";
+
+/// A freshly generated floor, before anything is summoned on it.
+#[derive(Clone)]
+pub struct Blueprint {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row, starting from the top. See species_from_tile for what each character means.
+    pub tiles: Vec<char>,
+    /// Creatures from the species table, by tile index.
+    pub creatures: Vec<(usize, Species)>,
+    /// Where the player starts, as a tile index.
+    pub start: usize,
+    /// Levers and pressure plates, and what they control, by tile index.
+    pub wiring: Vec<Wiring>,
+    /// Tiles which summon another species than their character would, while
+    /// still facing the same way. Used for locked doors.
+    pub overrides: Vec<(usize, Species)>,
+    /// Routes of waypoints, by tile index. The creature on the first waypoint walks them.
+    pub patrols: Vec<Vec<usize>>,
+    /// Named rooms, see Regions.
+    pub regions: Vec<MapRegion>,
+}
+
+/// One circuit of a Blueprint, see Circuit.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Wiring {
+    pub emitters: Vec<usize>,
+    pub receivers: Vec<usize>,
+}
+
+impl Blueprint {
+    fn filled(width: usize, height: usize, tile: char) -> Self {
+        Self {
+            width,
+            height,
+            tiles: vec![tile; width * height],
+            creatures: Vec::new(),
+            start: 0,
+            wiring: Vec::new(),
+            overrides: Vec::new(),
+            patrols: Vec::new(),
+            regions: Vec::new(),
+        }
+    }
+
+    /// The column and row of a tile, from the top left.
+    pub fn xy(&self, idx: usize) -> (usize, usize) {
+        (idx % self.width, idx / self.width)
+    }
+
+    fn idx(&self, x: usize, y: usize) -> usize {
+        x + y * self.width
+    }
+
+    fn is_edge(&self, idx: usize) -> bool {
+        let (x, y) = self.xy(idx);
+        x == 0 || x == self.width - 1 || y == 0 || y == self.height - 1
+    }
+
+    fn is_floor(&self, idx: usize) -> bool {
+        !['W', '#'].contains(&self.tiles[idx])
+    }
+
+    /// Everything connected to `idx_start` without crossing a wall.
+    fn connected_tiles(&self, idx_start: usize) -> HashSet<usize> {
+        // All previously found floor tiles.
+        let mut connected_indices = HashSet::new();
+        connected_indices.insert(idx_start);
+        // The new neighbours to inspect.
+        let mut frontier_indices = vec![idx_start];
+        while let Some(frontier) = frontier_indices.pop() {
+            // Edges are always walls or airlocks, which are not worth expanding from.
+            if self.is_edge(frontier) {
+                continue;
+            }
+            // Get each frontier's 4 adjacent neighbours.
+            for neighbour in [
+                frontier + 1,
+                frontier - 1,
+                frontier + self.width,
+                frontier - self.width,
+            ] {
+                // Add all floors that are not already known.
+                if self.is_floor(neighbour) && connected_indices.insert(neighbour) {
+                    frontier_indices.push(neighbour);
+                }
+            }
+        }
+        connected_indices
+    }
+
+    /// Scatter creatures from the species table on the floor, away from the player.
+    fn populate(&mut self, amount: usize, species: &[(Species, u32)], rng: &mut StdRng) {
+        let (start_x, start_y) = self.xy(self.start);
+        let floor_positions: Vec<usize> = (0..self.tiles.len())
+            .filter(|idx| self.tiles[*idx] == '.')
+            .filter(|idx| {
+                let (x, y) = self.xy(*idx);
+                x.abs_diff(start_x) + y.abs_diff(start_y) > SAFE_RADIUS
+            })
+            .collect();
+        let spawn_points: Vec<usize> = floor_positions
+            .choose_multiple(rng, amount)
+            .copied()
+            .collect();
+        for idx in spawn_points {
+            if let Ok((new_creature, _)) = species.choose_weighted(rng, |(_, weight)| *weight) {
+                self.creatures.push((idx, *new_creature));
+            }
+        }
+    }
+}
+
+/// What each character of a Blueprint summons, and which way it faces.
+pub fn species_from_tile(tile_char: char) -> Option<(Species, OrdDir)> {
+    let species = match tile_char {
+        '#' => Species::Wall,
+        'H' => Species::Hunter,
+        'S' => Species::Spawner,
+        'T' => Species::Tinker,
+        '@' => Species::Player,
+        'W' => Species::WeakWall,
+        '2' => Species::Second,
+        'A' => Species::Apiarist,
+        'F' => Species::Shrike,
+        'O' => Species::Oracle,
+        'E' => Species::EpsilonHead,
+        't' => Species::EpsilonTail,
+        'x' => Species::CageSlot,
+        'C' => Species::Colossus,
+        'Z' => Species::Warden,
+        'c' => Species::CleansingSalts,
+        'L' => Species::Lever,
+        'P' => Species::PressurePlate,
+        'u' | 'r' | 'l' | 'd' => Species::ConveyorBelt,
+        '7' | '9' | '3' | '1' => Species::ConveyorCorner,
+        '8' | '6' | '5' | '4' => Species::ConveyorSplitter,
+        '$' => Species::Chest,
+        'k' => Species::Keycard,
+        '*' => Species::Sigil,
+        '(' => Species::AegisShard,
+        'B' => Species::ExpanseShrine,
+        'b' => Species::RenewalShrine,
+        '=' => Species::Cart,
+        '+' => Species::Rail,
+        'j' => Species::Switch,
+        '%' => Species::TrappedChest,
+        '&' => Species::MimicChest,
+        '^' | '>' | '<' | 'V' => Species::Airlock,
+        'w' | 'n' | 'e' | 's' => Species::CageBorder,
+        _ => return None,
+    };
+    let momentum = match tile_char {
+        '^' => OrdDir::Up,
+        '>' => OrdDir::Right,
+        '<' => OrdDir::Left,
+        'n' => OrdDir::Up,
+        'e' => OrdDir::Right,
+        'w' => OrdDir::Left,
+        's' => OrdDir::Down,
+        'u' => OrdDir::Up,
+        'r' => OrdDir::Right,
+        'l' => OrdDir::Left,
+        'd' => OrdDir::Down,
+        // Bends, like the corners of a numpad, leading on clockwise.
+        '7' => OrdDir::Up,
+        '9' => OrdDir::Right,
+        '3' => OrdDir::Down,
+        '1' => OrdDir::Left,
+        // Splitters, like the arrows of a numpad.
+        '8' => OrdDir::Up,
+        '6' => OrdDir::Right,
+        '5' => OrdDir::Down,
+        '4' => OrdDir::Left,
+        // 'V', and everything else.
+        _ => OrdDir::Down,
+    };
+    Some((species, momentum))
+}
+
+/// What ground each character of a Blueprint lays down, if it is not plain floor.
+pub fn tile_kind_from_char(tile_char: char) -> Option<TileKind> {
+    match tile_char {
+        '~' => Some(TileKind::Water),
+        '!' => Some(TileKind::Lava),
+        '_' => Some(TileKind::Ice),
+        ':' => Some(TileKind::Mud),
+        _ => None,
+    }
+}
+
+/// A named rectangle of a MapFile, from one corner tile index to the other.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapRegion {
+    pub name: String,
+    pub corners: (usize, usize),
+}
+
+/// A hand-made floor, as painted in the editor binary and saved as RON.
+/// Everything is by tile index, like Blueprint.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapFile {
+    /// Row by row, starting from the top. See species_from_tile for what each character means.
+    pub rows: Vec<String>,
+    #[serde(default)]
+    pub wiring: Vec<Wiring>,
+    #[serde(default)]
+    pub overrides: Vec<(usize, Species)>,
+    #[serde(default)]
+    pub regions: Vec<MapRegion>,
+    /// Paths of waypoints for wandering creatures, see Patrol.
+    #[serde(default)]
+    pub patrols: Vec<Vec<usize>>,
+}
+
+impl MapFile {
+    /// An empty floor of this size, walled in.
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut blueprint = Blueprint::filled(width, height, '.');
+        add_outer_walls(&mut blueprint);
+        Self::from_blueprint(&blueprint)
+    }
+
+    pub fn from_blueprint(blueprint: &Blueprint) -> Self {
+        Self {
+            rows: blueprint
+                .tiles
+                .chunks(blueprint.width)
+                .map(|row| row.iter().collect())
+                .collect(),
+            wiring: blueprint.wiring.clone(),
+            overrides: blueprint.overrides.clone(),
+            regions: blueprint.regions.clone(),
+            patrols: blueprint.patrols.clone(),
+        }
+    }
+
+    /// Rows shorter than the first one are padded with floor.
+    pub fn to_blueprint(&self) -> Blueprint {
+        let width = self.rows.first().map_or(0, |row| row.chars().count());
+        let mut blueprint = Blueprint::filled(width, self.rows.len(), '.');
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, tile) in row.chars().take(width).enumerate() {
+                let idx = blueprint.idx(x, y);
+                blueprint.tiles[idx] = tile;
+            }
+        }
+        blueprint.start = blueprint
+            .tiles
+            .iter()
+            .position(|tile| *tile == '@')
+            .unwrap_or_default();
+        blueprint.wiring = self.wiring.clone();
+        blueprint.overrides = self.overrides.clone();
+        blueprint.patrols = self.patrols.clone();
+        blueprint.regions = self.regions.clone();
+        blueprint
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        ron::from_str(&contents).map_err(|error| error.to_string())
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        if let Some(folder) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(folder).map_err(|error| error.to_string())?;
+        }
+        std::fs::write(path, contents).map_err(|error| error.to_string())
+    }
+}
+
+/// The floor saved at AUTHORED_MAP. Without one, caves are generated instead.
+fn load_authored_level(config: &LevelGenConfig, rng: &mut StdRng) -> Blueprint {
+    match MapFile::load(AUTHORED_MAP) {
+        Ok(map_file) => map_file.to_blueprint(),
+        Err(error) => {
+            warn!("Could not read {}: {}", AUTHORED_MAP, error);
+            generate_caves(config, rng)
+        }
+    }
+}
+
+/// Generate a single floor which is not a cage.
+pub fn generate_level(config: &LevelGenConfig, spawn_player: bool, rng: &mut StdRng) -> Blueprint {
+    if config.layout == LevelLayout::Authored {
+        let mut blueprint = load_authored_level(config, rng);
+        // Hand-made floors say where the player starts themselves.
+        if !spawn_player {
+            for tile in blueprint.tiles.iter_mut().filter(|tile| **tile == '@') {
+                *tile = '.';
+            }
+        }
+        return blueprint;
+    }
+    let mut blueprint = match config.layout {
+        LevelLayout::RoomsAndCorridors => generate_rooms_and_corridors(config, rng),
+        LevelLayout::Caves => generate_caves(config, rng),
+        LevelLayout::Cage | LevelLayout::Authored => {
+            panic!("Cages are generated floor by floor, see spawn_cage.")
+        }
+    };
+    if spawn_player {
+        blueprint.tiles[blueprint.start] = '@';
+    }
+    add_prefab(&mut blueprint, &config.prefabs, rng);
+    add_dead_end_chests(&mut blueprint, rng);
+    add_terrain_patches(&mut blueprint, rng);
+    blueprint.populate(config.creatures, &config.species, rng);
+    add_shrine(&mut blueprint, rng);
+    blueprint
+}
+
+fn generate_rooms_and_corridors(config: &LevelGenConfig, rng: &mut StdRng) -> Blueprint {
+    let mut blueprint = Blueprint::filled(config.width, config.height, 'W');
+    let target_floor = (config.density * (config.width * config.height) as f32) as usize;
+    let mut rooms: Vec<(usize, usize, usize, usize)> = Vec::new();
+    let mut floor = 0;
+    for _i in 0..200 {
+        if floor >= target_floor {
+            break;
+        }
+        let (width, height) = (rng.gen_range(3..=8), rng.gen_range(3..=6));
+        if width + 2 >= config.width || height + 2 >= config.height {
+            continue;
+        }
+        let x = rng.gen_range(1..config.width - width - 1);
+        let y = rng.gen_range(1..config.height - height - 1);
+        // Rooms keep at least one wall between each other.
+        let overlaps = rooms.iter().any(|&(other_x, other_y, other_w, other_h)| {
+            x <= other_x + other_w
+                && other_x <= x + width
+                && y <= other_y + other_h
+                && other_y <= y + height
+        });
+        if overlaps {
+            continue;
+        }
+        for room_x in x..x + width {
+            for room_y in y..y + height {
+                let idx = blueprint.idx(room_x, room_y);
+                blueprint.tiles[idx] = '.';
+            }
+        }
+        floor += width * height;
+        // Join this room to the previous one with an L-shaped corridor.
+        if let Some(&(other_x, other_y, other_w, other_h)) = rooms.last() {
+            let (from_x, from_y) = (x + width / 2, y + height / 2);
+            let (to_x, to_y) = (other_x + other_w / 2, other_y + other_h / 2);
+            for corridor_x in from_x.min(to_x)..=from_x.max(to_x) {
+                let idx = blueprint.idx(corridor_x, from_y);
+                blueprint.tiles[idx] = '.';
+            }
+            for corridor_y in from_y.min(to_y)..=from_y.max(to_y) {
+                let idx = blueprint.idx(to_x, corridor_y);
+                blueprint.tiles[idx] = '.';
+            }
+        }
+        rooms.push((x, y, width, height));
+    }
+    // Floors too small for any room become a single one, wall to wall.
+    if rooms.is_empty() {
+        let (x, y) = (
+            usize::from(config.width > 2),
+            usize::from(config.height > 2),
+        );
+        let (width, height) = (config.width - 2 * x, config.height - 2 * y);
+        for room_x in x..x + width {
+            for room_y in y..y + height {
+                let idx = blueprint.idx(room_x, room_y);
+                blueprint.tiles[idx] = '.';
+            }
+        }
+        rooms.push((x, y, width, height));
+    }
+    let (x, y, width, height) = rooms[0];
+    blueprint.start = blueprint.idx(x + width / 2, y + height / 2);
+    if rooms.len() >= 3 {
+        add_vault(&mut blueprint, &rooms, rng);
+    }
+    add_outer_walls(&mut blueprint);
+    blueprint
+}
+
+/// Lock the last room behind a door, opened by a lever in the first room,
+/// or by a key item found there.
+// NOTE: Corridors dug before the last room was placed may run through it,
+// leaving it open anyway. This is fine, not every vault is well built.
+fn add_vault(blueprint: &mut Blueprint, rooms: &[(usize, usize, usize, usize)], rng: &mut StdRng) {
+    let (x, y, width, height) = rooms[rooms.len() - 1];
+    let (other_x, other_y, other_w, other_h) = rooms[rooms.len() - 2];
+    // Follow the corridor to the previous room, to find where it leaves the last room.
+    let from_y = y + height / 2;
+    let (to_x, to_y) = (other_x + other_w / 2, other_y + other_h / 2);
+    let (door, door_char) = if to_x >= x + width {
+        (blueprint.idx(x + width, from_y), '>')
+    } else if to_x < x {
+        (blueprint.idx(x - 1, from_y), '<')
+    } else if to_y >= y + height {
+        (blueprint.idx(to_x, y + height), 'V')
+    } else {
+        (blueprint.idx(to_x, y - 1), '^')
+    };
+    let (first_x, first_y, ..) = rooms[0];
+    let opener = blueprint.idx(first_x, first_y);
+    if blueprint.tiles[door] != '.' || blueprint.tiles[opener] != '.' || opener == blueprint.start {
+        return;
+    }
+    blueprint.tiles[door] = door_char;
+    match rng.gen_range(0..3) {
+        0 => {
+            blueprint.tiles[opener] = 'L';
+            blueprint.wiring.push(Wiring {
+                emitters: vec![opener],
+                receivers: vec![door],
+            });
+        }
+        1 => {
+            blueprint.tiles[opener] = 'k';
+            blueprint.overrides.push((door, Species::LockedAirlock));
+        }
+        _ => {
+            blueprint.tiles[opener] = '*';
+            blueprint.overrides.push((door, Species::SealedAirlock));
+        }
+    }
+    // The reward for getting in, in the corner furthest from the door.
+    let chest = match door_char {
+        '<' => blueprint.idx(x + width - 1, y),
+        '^' => blueprint.idx(x + width - 1, y + height - 1),
+        _ => blueprint.idx(x, y),
+    };
+    if blueprint.tiles[chest] == '.' {
+        blueprint.tiles[chest] = '$';
+    }
+    // And a guard, walking from corner to corner.
+    let route: Vec<usize> = [
+        (x, y),
+        (x + width - 1, y),
+        (x + width - 1, y + height - 1),
+        (x, y + height - 1),
+    ]
+    .into_iter()
+    .map(|(corner_x, corner_y)| blueprint.idx(corner_x, corner_y))
+    .filter(|corner| blueprint.tiles[*corner] == '.')
+    .collect();
+    if route.len() > 1 {
+        blueprint.tiles[route[0]] = 'H';
+        blueprint.patrols.push(route);
+    }
+}
+
+fn generate_caves(config: &LevelGenConfig, rng: &mut StdRng) -> Blueprint {
+    let mut blueprint = Blueprint::filled(config.width, config.height, 'W');
+    let inside: Vec<usize> = (0..blueprint.tiles.len())
+        .filter(|idx| !blueprint.is_edge(*idx))
+        .collect();
+    for idx in inside {
+        if rng.gen::<f32>() >= config.density {
+            blueprint.tiles[idx] = '.';
+        }
+    }
+    // Each pass, rock surrounded by floor crumbles, and floor surrounded by rock fills up.
+    for _pass in 0..4 {
+        let mut smoothed = blueprint.tiles.clone();
+        for (idx, tile) in smoothed.iter_mut().enumerate() {
+            if blueprint.is_edge(idx) {
+                continue;
+            }
+            let (x, y) = blueprint.xy(idx);
+            let walls = (x - 1..=x + 1)
+                .flat_map(|nx| (y - 1..=y + 1).map(move |ny| (nx, ny)))
+                .filter(|(nx, ny)| !blueprint.is_floor(blueprint.idx(*nx, *ny)))
+                .count();
+            *tile = if walls >= 5 { 'W' } else { '.' };
+        }
+        blueprint.tiles = smoothed;
+    }
+    // Only keep the largest cave, and fill in the others.
+    let mut unexplored: HashSet<usize> = (0..blueprint.tiles.len())
+        .filter(|idx| blueprint.is_floor(*idx))
+        .collect();
+    let mut largest = HashSet::new();
+    while let Some(idx) = unexplored.iter().next().copied() {
+        let cave = blueprint.connected_tiles(idx);
+        unexplored.retain(|idx| !cave.contains(idx));
+        if cave.len() > largest.len() {
+            largest = cave;
+        }
+    }
+    for idx in 0..blueprint.tiles.len() {
+        if !largest.contains(&idx) {
+            blueprint.tiles[idx] = 'W';
+        }
+    }
+    // Start as close as possible to the centre.
+    let centre = (config.width / 2, config.height / 2);
+    blueprint.start = largest
+        .iter()
+        .copied()
+        .min_by_key(|idx| {
+            let (x, y) = blueprint.xy(*idx);
+            (x.abs_diff(centre.0) + y.abs_diff(centre.1), *idx)
+        })
+        .expect("The cave generator carved out nothing.");
+    add_outer_walls(&mut blueprint);
+    blueprint
+}
+
+/// Tuck chests away in corridors which lead nowhere. Some of them are not
+/// what they seem.
+fn add_dead_end_chests(blueprint: &mut Blueprint, rng: &mut StdRng) {
+    let dead_ends: Vec<usize> = (0..blueprint.tiles.len())
+        .filter(|idx| blueprint.tiles[*idx] == '.' && !blueprint.is_edge(*idx))
+        .filter(|idx| {
+            let neighbours = [
+                idx + 1,
+                idx - 1,
+                idx + blueprint.width,
+                idx - blueprint.width,
+            ];
+            neighbours
+                .iter()
+                .filter(|neighbour| blueprint.is_floor(**neighbour))
+                .count()
+                == 1
+        })
+        .collect();
+    for idx in dead_ends
+        .choose_multiple(rng, DEAD_END_CHESTS)
+        .copied()
+        .collect::<Vec<usize>>()
+    {
+        // NOTE: Not always a chest, shields are tucked away in dead ends too.
+        let chest = [('$', 6), ('%', 3), ('&', 2), ('(', 2)]
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .map(|(chest, _)| *chest)
+            .unwrap_or('$');
+        blueprint.tiles[idx] = chest;
+    }
+}
+
+/// Raise a shrine on the free tile with the most creatures around it, so
+/// claiming its blessing means wading into a fight.
+fn add_shrine(blueprint: &mut Blueprint, rng: &mut StdRng) {
+    let danger = |idx: usize| {
+        let (x, y) = blueprint.xy(idx);
+        blueprint
+            .creatures
+            .iter()
+            .filter(|(other, _)| {
+                let (other_x, other_y) = blueprint.xy(*other);
+                x.abs_diff(other_x).max(y.abs_diff(other_y)) <= SHRINE_DANGER_RADIUS
+            })
+            .count()
+    };
+    let Some(idx) = (0..blueprint.tiles.len())
+        .filter(|idx| blueprint.tiles[*idx] == '.' && *idx != blueprint.start)
+        .filter(|idx| blueprint.creatures.iter().all(|(other, _)| other != idx))
+        .max_by_key(|idx| danger(*idx))
+        .filter(|idx| danger(*idx) > 0)
+    else {
+        return;
+    };
+    blueprint.tiles[idx] = if rng.gen_bool(0.5) { 'B' } else { 'b' };
+}
+
+/// Stamp one of the prefabs onto open floor, away from where the player starts.
+/// Spaces in a prefab leave the tile below as it was.
+// NOTE: Prefabs which would cut the floor in two are not placed there.
+fn add_prefab(blueprint: &mut Blueprint, prefabs: &[Vec<String>], rng: &mut StdRng) {
+    let Some(prefab) = prefabs.choose(rng) else {
+        return;
+    };
+    let rows: Vec<Vec<char>> = prefab.iter().map(|row| row.chars().collect()).collect();
+    let height = rows.len();
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 || width > blueprint.width || height > blueprint.height {
+        return;
+    }
+    let reachable = blueprint.connected_tiles(blueprint.start);
+    for _ in 0..PREFAB_ATTEMPTS {
+        let x = rng.gen_range(0..=blueprint.width - width);
+        let y = rng.gen_range(0..=blueprint.height - height);
+        let footprint: Vec<(usize, char)> = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(dy, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, tile)| **tile != ' ')
+                    .map(move |(dx, tile)| (dx, dy, *tile))
+            })
+            .map(|(dx, dy, tile)| (blueprint.idx(x + dx, y + dy), tile))
+            .collect();
+        if footprint
+            .iter()
+            .any(|(idx, _)| blueprint.tiles[*idx] != '.' || *idx == blueprint.start)
+        {
+            continue;
+        }
+        let mut stamped = blueprint.clone();
+        for (idx, tile) in footprint {
+            stamped.tiles[idx] = tile;
+        }
+        let still_reachable = reachable
+            .iter()
+            .filter(|idx| stamped.is_floor(**idx))
+            .count();
+        if still_reachable == stamped.connected_tiles(stamped.start).len() {
+            *blueprint = stamped;
+            return;
+        }
+    }
+}
+
+/// Spread a few small patches of special ground on the floor, away from the player.
+fn add_terrain_patches(blueprint: &mut Blueprint, rng: &mut StdRng) {
+    let (start_x, start_y) = blueprint.xy(blueprint.start);
+    let is_free = |blueprint: &Blueprint, idx: usize| {
+        let (x, y) = blueprint.xy(idx);
+        blueprint.tiles[idx] == '.' && x.abs_diff(start_x) + y.abs_diff(start_y) > SAFE_RADIUS
+    };
+    for _ in 0..TERRAIN_PATCHES {
+        let candidates: Vec<usize> = (0..blueprint.tiles.len())
+            .filter(|idx| is_free(blueprint, *idx))
+            .collect();
+        let Some(&seed) = candidates.choose(rng) else {
+            return;
+        };
+        let tile = *['~', '!', '_', ':'].choose(rng).unwrap();
+        let mut patch = vec![seed];
+        blueprint.tiles[seed] = tile;
+        while patch.len() < TERRAIN_PATCH_SIZE {
+            let idx = *patch.choose(rng).unwrap();
+            let (x, y) = blueprint.xy(idx);
+            let neighbours: Vec<usize> = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .iter()
+                .filter_map(|(dx, dy)| {
+                    let (x, y) = (x.checked_add_signed(*dx)?, y.checked_add_signed(*dy)?);
+                    (x < blueprint.width && y < blueprint.height).then(|| blueprint.idx(x, y))
+                })
+                .filter(|neighbour| is_free(blueprint, *neighbour))
+                .collect();
+            // NOTE: Patches stop growing if they get boxed in, rather than looping forever.
+            let Some(&next) = neighbours.choose(rng) else {
+                break;
+            };
+            blueprint.tiles[next] = tile;
+            patch.push(next);
+        }
+    }
+}
+
+/// The edges of a floor are made of indestructible walls.
+fn add_outer_walls(blueprint: &mut Blueprint) {
+    for idx in 0..blueprint.tiles.len() {
+        if blueprint.is_edge(idx) {
+            blueprint.tiles[idx] = '#';
+        }
+    }
+}
+
+/// Generate one floor of the cage tower.
+pub fn generate_cage(
+    floor: usize,
+    spawn_player: bool,
+    spawn_walls: bool,
+    size: usize,
+    connections: &[OrdDir],
+    config: &LevelGenConfig,
+    rng: &mut StdRng,
+) -> Blueprint {
+    let mut cage = Blueprint::filled(size, size, '.');
+    let centre = cage.idx((size - 1) / 2, (size - 1) / 2);
+    cage.start = centre;
+
+    for _i in 0..100 {
+        let mut idx_start = centre;
+        for i in 0..size.pow(2) {
+            cage.tiles[i] = if i == centre && floor == 0 {
+                // If the player is here, it spawns in the middle.
+                // If the player is already spawned, the bottom cage should still
+                // not place anything in its centre so the player can be teleported
+                // there.
+                if spawn_player {
+                    '@'
+                } else {
+                    '.'
+                }
+            // Edges get walls 100% of the time, other tiles, 30% of the time.
+            } else if cage.is_edge(i) {
+                '#'
+            } else if rng.gen::<f32>() < config.density && spawn_walls {
+                'W'
+            // Everything else is a floor.
+            } else {
+                idx_start = i;
+                '.'
+            };
+        }
+        for airlock in connections {
+            match airlock {
+                OrdDir::Up => {
+                    cage.tiles[size / 2] = '^';
+                }
+                OrdDir::Left => {
+                    cage.tiles[size * (size / 2)] = '<';
+                }
+                OrdDir::Right => {
+                    cage.tiles[size * (size / 2 + 1) - 1] = '>';
+                }
+                OrdDir::Down => {
+                    cage.tiles[size * size - size / 2 - 1] = 'V';
+                }
+            }
+        }
+        // Every passable tile must be connected to all other passable tiles, no "islands".
+        let passable_tiles = (0..cage.tiles.len())
+            .filter(|idx| cage.is_floor(*idx))
+            .count();
+        if passable_tiles == cage.connected_tiles(idx_start).len() {
+            let spawn_snake = !spawn_walls;
+            if spawn_snake {
+                add_snake(&mut cage);
+            } else {
+                add_prefab(&mut cage, &config.prefabs, rng);
+                add_terrain_patches(&mut cage, rng);
+                cage.populate(config.creatures + floor, &config.species, rng);
+            }
+            return cage;
+        }
+    }
+    panic!("Cage generation timeout achieved.");
+}
+
+/// The final cage holds Epsilon, and the soul cage it guards.
+fn add_snake(cage: &mut Blueprint) {
+    let centre = (cage.width - 1) / 2;
+    // Epsilon's two heads, each with a tail segment behind.
+    for (x, y) in [(centre - 5, centre - 7), (centre - 3, centre - 3)] {
+        let head = cage.idx(x, y);
+        cage.tiles[head] = 'E';
+        cage.tiles[head + 1] = 't';
+    }
+    // The soul cage, a 3x3 block of slots framed by its borders on each side.
+    let (left, top) = (centre + 2, centre - 1);
+    for i in 0..3 {
+        for (x, y, tile) in [
+            (left + i, top - 1, 's'),
+            (left + i, top + 3, 'n'),
+            (left - 1, top + i, 'e'),
+            (left + 3, top + i, 'w'),
+        ] {
+            let idx = cage.idx(x, y);
+            cage.tiles[idx] = tile;
+        }
+        for j in 0..3 {
+            let idx = cage.idx(left + i, top + j);
+            cage.tiles[idx] = 'x';
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{Awake, Sleeping},
+    map::FaithsEnd,
+    sets::Animation,
+    text::split_text,
+};
+
+pub struct ObjectivePlugin;
+
+impl Plugin for ObjectivePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FloorObjective>();
+        app.add_systems(Startup, spawn_objective_hud);
+        app.add_systems(
+            Update,
+            update_objective_hud
+                .run_if(resource_changed::<FloorObjective>)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// What the player must do to leave the current floor.
+// NOTE: Every floor is cleared the same way for now. Stairs or survival
+// floors would go here, with their own check in end_turn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Objective {
+    /// Defeat every creature, cage after cage.
+    #[default]
+    ClearArena,
+}
+
+/// The objective of the current floor, and how far along it the player is.
+#[derive(Resource, Default, PartialEq, Eq)]
+pub struct FloorObjective {
+    pub objective: Objective,
+    /// Creatures left standing, asleep or not.
+    pub remaining: usize,
+    /// The cage the player is in, counting from 1.
+    pub cage: usize,
+    pub cages: usize,
+}
+
+impl FloorObjective {
+    pub fn describe(&self) -> String {
+        match self.objective {
+            Objective::ClearArena => format!(
+                "[y]Clear the arena[w] - [r]{}[w] left, cage {}/{}",
+                self.remaining,
+                self.cage,
+                self.cages.max(self.cage),
+            ),
+        }
+    }
+}
+
+pub fn track_objective(
+    awake: Query<(), With<Awake>>,
+    sleeping: Query<(), With<Sleeping>>,
+    faiths_end: Res<FaithsEnd>,
+    mut objective: ResMut<FloorObjective>,
+) {
+    let progress = FloorObjective {
+        objective: objective.objective,
+        remaining: awake.iter().count() + sleeping.iter().count(),
+        cage: faiths_end.current_cage + 1,
+        cages: faiths_end.cage_dimensions.len(),
+    };
+    objective.set_if_neq(progress);
+}
+
+#[derive(Component)]
+pub struct ObjectiveHud;
+
+fn spawn_objective_hud(mut commands: Commands) {
+    commands.spawn((
+        ObjectiveHud,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(35.),
+            top: Val::Px(1.),
+            padding: UiRect::all(Val::Px(0.5)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+    ));
+}
+
+fn update_objective_hud(
+    objective: Res<FloorObjective>,
+    hud: Query<Entity, With<ObjectiveHud>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let Ok(hud) = hud.get_single() else {
+        return;
+    };
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    commands.entity(hud).despawn_descendants();
+    commands.entity(hud).with_children(|parent| {
+        for (section, color) in split_text(&objective.describe()) {
+            parent.spawn((TextSpan::new(section), font.clone(), color));
+        }
+    });
+}
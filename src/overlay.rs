@@ -0,0 +1,382 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    creature::{Health, ShieldBuffer, Species, StatusEffect, StatusEffectsList},
+    events::HealthChanged,
+    graphics::SpriteSheetAtlas,
+    intent::Intent,
+    map::Position,
+    preview::PreviewChip,
+    species::SpeciesRegistry,
+    terrain::Batched,
+    text::split_text,
+    vision::VisibilityMap,
+    TILE_SIZE,
+};
+
+/// The screen-space overlay drawn on top of a creature:
+/// its HP bar and shield bar, its status effects, its name, its intent and the preview of
+/// the hovered spell.
+#[derive(Component)]
+pub struct CreatureOverlay {
+    pub creature: Entity,
+    hp_bar: Entity,
+    shield_bar: Entity,
+    status: Entity,
+    name: Entity,
+    intent: Entity,
+    preview: Entity,
+}
+
+#[derive(Component)]
+pub struct OverlayText;
+
+/// A second segment under the HP bar, as long as the ShieldBuffer is
+/// compared to max HP.
+#[derive(Component)]
+pub struct ShieldBar;
+
+/// How many pips the HP bar sprites have, below the full one.
+const HP_BAR_PIPS: usize = 5;
+
+/// Determine at which sprite index to draw an HP bar. Creatures with more
+/// HP than the bar has pips have it drawn in proportion to their max HP.
+pub fn hp_bar_index(hp: usize, max_hp: usize) -> usize {
+    if max_hp == hp {
+        178
+    } else {
+        let pips = if max_hp > HP_BAR_PIPS + 1 && hp > 0 {
+            // Only slain creatures look empty, and only unharmed ones look full.
+            (hp * (HP_BAR_PIPS + 1))
+                .div_ceil(max_hp)
+                .clamp(1, HP_BAR_PIPS)
+        } else {
+            hp
+        };
+        match pips {
+            5 => 238,
+            4 => 239,
+            3 => 240,
+            2 => 241,
+            1 => 242,
+            _ => 243,
+        }
+    }
+}
+
+// NOTE: Letters until status effects get sprites of their own.
+fn status_effect_icon(effect: &StatusEffect) -> &'static str {
+    match effect {
+        StatusEffect::Invincible => "I",
+        StatusEffect::Stab => "S",
+        StatusEffect::Dizzy => "D",
+        StatusEffect::DimensionBond => "B",
+        StatusEffect::SpellReflect => "R",
+    }
+}
+
+/// The species' name, without its colour codes, and the colour it is usually written in.
+fn species_name(registry: &SpeciesRegistry, species: &Species) -> (String, TextColor) {
+    split_text(&registry.get(species).name)
+        .into_iter()
+        .find(|(section, _)| !section.is_empty())
+        .unwrap_or((String::new(), TextColor::WHITE))
+}
+
+/// Each frame, project every creature onto the screen, and draw its overlay there.
+/// Creatures without an overlay get one, and overlays without a creature are despawned.
+/// Walls and other creatures drawn as terrain have none, see Batched.
+pub fn update_creature_overlays(
+    creatures: Query<
+        (
+            Entity,
+            &Transform,
+            &Position,
+            &Health,
+            &Species,
+            &StatusEffectsList,
+            Option<&Intent>,
+            Option<&PreviewChip>,
+            Option<&ShieldBuffer>,
+        ),
+        Without<Batched>,
+    >,
+    mut overlays: Query<(Entity, &CreatureOverlay, &mut Node, &mut Visibility)>,
+    mut shield_bars: Query<&mut Node, (With<ShieldBar>, Without<CreatureOverlay>)>,
+    mut icons: Query<(&mut ImageNode, &mut Visibility), Without<CreatureOverlay>>,
+    mut texts: Query<(&mut Text, &mut TextColor), With<OverlayText>>,
+    camera: Query<(&Camera, &Transform), Without<Health>>,
+    ui_scale: Res<UiScale>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    registry: Res<SpeciesRegistry>,
+    vision: Res<VisibilityMap>,
+    mut commands: Commands,
+) {
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+    // NOTE: The GlobalTransform of the camera is only updated in PostUpdate,
+    // using it here would make the overlay lag one frame behind the sprites.
+    let camera_transform = GlobalTransform::from(*camera_transform);
+    let mut overlay_of: HashMap<Entity, Entity> = HashMap::new();
+    for (overlay_entity, overlay, ..) in overlays.iter() {
+        if creatures.contains(overlay.creature) {
+            overlay_of.insert(overlay.creature, overlay_entity);
+        } else {
+            commands.entity(overlay_entity).despawn_recursive();
+        }
+    }
+    for (creature, transform, position, health, species, effects, intent, preview, shield) in
+        creatures.iter()
+    {
+        let Some(overlay_entity) = overlay_of.get(&creature) else {
+            spawn_creature_overlay(
+                creature,
+                health,
+                &mut commands,
+                &asset_server,
+                &atlas_layout,
+            );
+            continue;
+        };
+        let (_, overlay, mut node, mut visibility) = overlays.get_mut(*overlay_entity).unwrap();
+
+        // Creatures at full health, with no effects and no plans need no overlay.
+        let mut active_effects: Vec<_> = effects
+            .effects
+            .iter()
+            .filter(|(_, effect)| effect.is_active())
+            .map(|(effect, _)| status_effect_icon(effect))
+            .collect();
+        // Neither do creatures hidden in the fog of war.
+        let shield = shield.map_or(0, |shield| shield.amount);
+        if (health.hp == health.max_hp
+            && shield == 0
+            && active_effects.is_empty()
+            && intent.is_none()
+            && preview.is_none())
+            || !vision.is_visible(position)
+        {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        // Find where the corners of this creature's tile are on the screen.
+        let half_tile = Vec3::new(TILE_SIZE / 2., TILE_SIZE / 2., 0.);
+        let (Ok(top_left), Ok(bottom_right)) = (
+            camera.world_to_viewport(
+                &camera_transform,
+                transform.translation + half_tile * Vec3::new(-1., 1., 0.),
+            ),
+            camera.world_to_viewport(
+                &camera_transform,
+                transform.translation + half_tile * Vec3::new(1., -1., 0.),
+            ),
+        ) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = Visibility::Inherited;
+        // The UI is scaled, but the viewport is not.
+        let (top_left, size) = (
+            top_left / ui_scale.0,
+            (bottom_right - top_left) / ui_scale.0,
+        );
+        node.left = Val::Px(top_left.x);
+        node.top = Val::Px(top_left.y);
+        node.width = Val::Px(size.x);
+        node.height = Val::Px(size.y);
+
+        if let Ok(mut shield_bar) = shield_bars.get_mut(overlay.shield_bar) {
+            shield_bar.width =
+                Val::Percent((shield as f32 / health.max_hp.max(1) as f32).min(1.) * 100.);
+        }
+        if let Ok((mut icon, mut icon_visibility)) = icons.get_mut(overlay.intent) {
+            if let Some(intent) = intent {
+                icon.texture_atlas.as_mut().unwrap().index = intent.sprite();
+                *icon_visibility = Visibility::Inherited;
+            } else {
+                *icon_visibility = Visibility::Hidden;
+            }
+        }
+        // HashMaps have no stable order, sort the icons to stop them from flickering.
+        active_effects.sort();
+        if let Ok((mut text, _)) = texts.get_mut(overlay.status) {
+            text.0 = active_effects.concat();
+        }
+        if let Ok((mut text, mut color)) = texts.get_mut(overlay.preview) {
+            text.0 = preview.map_or(String::new(), |preview| preview.text.clone());
+            *color = if preview.is_some_and(|preview| preview.blocked) {
+                TextColor(Color::srgb(0.5, 0.5, 0.5))
+            } else {
+                TextColor(Color::srgb(1., 0.5, 0.5))
+            };
+        }
+        // Species can change, through transformation.
+        if let Ok((mut text, mut color)) = texts.get_mut(overlay.name) {
+            (text.0, *color) = species_name(&registry, species);
+        }
+    }
+}
+
+/// Redraw the HP bars of creatures whose Health changed. New overlays are
+/// spawned with the right one, so nothing else touches them.
+pub fn update_hp_bars(
+    mut events: EventReader<HealthChanged>,
+    creatures: Query<&Health>,
+    overlays: Query<&CreatureOverlay>,
+    mut icons: Query<&mut ImageNode>,
+) {
+    let changed: HashSet<Entity> = events.read().map(|event| event.entity).collect();
+    if changed.is_empty() {
+        return;
+    }
+    for overlay in overlays.iter() {
+        if !changed.contains(&overlay.creature) {
+            continue;
+        }
+        let (Ok(health), Ok(mut hp_bar)) = (
+            creatures.get(overlay.creature),
+            icons.get_mut(overlay.hp_bar),
+        ) else {
+            continue;
+        };
+        hp_bar.texture_atlas.as_mut().unwrap().index = hp_bar_index(health.hp, health.max_hp);
+    }
+}
+
+fn spawn_creature_overlay(
+    creature: Entity,
+    health: &Health,
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    atlas_layout: &Res<SpriteSheetAtlas>,
+) {
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 0.8,
+        ..default()
+    };
+    let hp_bar = commands
+        .spawn((
+            ImageNode {
+                image: asset_server.load("spritesheet.png"),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index: hp_bar_index(health.hp, health.max_hp),
+                }),
+                ..default()
+            },
+            Node {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+        ))
+        .id();
+    let shield_bar = commands
+        .spawn((
+            ShieldBar,
+            Node {
+                width: Val::Percent(0.),
+                height: Val::Percent(8.),
+                position_type: PositionType::Absolute,
+                bottom: Val::Percent(0.),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.4, 0.85, 1.)),
+        ))
+        .id();
+    let status = commands
+        .spawn((
+            OverlayText,
+            Text::new(""),
+            font.clone(),
+            TextColor(Color::srgb(1., 1., 0.)),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(100.),
+                ..default()
+            },
+        ))
+        .id();
+    let name = commands
+        .spawn((
+            OverlayText,
+            Text::new(""),
+            font.clone(),
+            TextColor::WHITE,
+            TextLayout::new_with_no_wrap(),
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Percent(100.),
+                ..default()
+            },
+        ))
+        .id();
+    // Right next to the creature, where it's hard to miss.
+    let preview = commands
+        .spawn((
+            OverlayText,
+            Text::new(""),
+            font.clone(),
+            TextColor(Color::srgb(1., 0.5, 0.5)),
+            TextLayout::new_with_no_wrap(),
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(100.),
+                top: Val::Percent(25.),
+                ..default()
+            },
+        ))
+        .id();
+    // A small icon in the top right corner.
+    let intent = commands
+        .spawn((
+            ImageNode {
+                image: asset_server.load("spritesheet.png"),
+                texture_atlas: Some(TextureAtlas {
+                    layout: atlas_layout.handle.clone(),
+                    index: 0,
+                }),
+                ..default()
+            },
+            Node {
+                width: Val::Percent(40.),
+                height: Val::Percent(40.),
+                position_type: PositionType::Absolute,
+                right: Val::Percent(-20.),
+                top: Val::Percent(-20.),
+                ..default()
+            },
+            Visibility::Hidden,
+        ))
+        .id();
+    commands
+        .spawn((
+            CreatureOverlay {
+                creature,
+                hp_bar,
+                shield_bar,
+                status,
+                name,
+                intent,
+                preview,
+            },
+            Node {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            // Only shown once it has been placed on its creature.
+            Visibility::Hidden,
+            // Drawn below the rest of the UI.
+            GlobalZIndex(-1),
+            PickingBehavior::IGNORE,
+        ))
+        .add_children(&[hp_bar, shield_bar, status, name, intent, preview]);
+}
@@ -0,0 +1,159 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    creature::{
+        CreatureFlags, EffectDuration, Facing, FlagQuery, Player, Spellbook, Spellproof,
+        StatusEffect, Wall,
+    },
+    events::SoulWheel,
+    graphics::VisualLayer,
+    map::{Map, Position},
+    overlay::update_creature_overlays,
+    sets::{Animation, ControlState},
+    spells::{predict_spell, AimMode, PredictedOutcome},
+    ui::SoulSlot,
+    OrdDir, TILE_SIZE,
+};
+
+pub struct PreviewPlugin;
+
+impl Plugin for PreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            preview_hovered_soul
+                .before(update_creature_overlays)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// What the spell in the hovered Soul Wheel slot would do to this creature,
+/// shown next to it, such as "-3" or "Stun 2".
+#[derive(Component, PartialEq)]
+pub struct PreviewChip {
+    pub text: String,
+    /// Walls are in the way, the chip is greyed out.
+    pub blocked: bool,
+}
+
+/// A translucent square over a tile the spell in the hovered Soul Wheel slot would land on.
+#[derive(Component)]
+pub struct PreviewTile;
+
+fn chip_text(outcome: &PredictedOutcome) -> String {
+    let mut parts = Vec::new();
+    if outcome.hp_mod != 0 {
+        parts.push(format!("{:+}", outcome.hp_mod));
+    }
+    for (effect, stacks) in &outcome.effects {
+        let name = match effect {
+            StatusEffect::Invincible => "Invincible",
+            StatusEffect::Stab => "Stab",
+            StatusEffect::Dizzy => "Stun",
+            StatusEffect::DimensionBond => "Bond",
+            StatusEffect::SpellReflect => "Reflect",
+        };
+        parts.push(match stacks {
+            EffectDuration::Finite { stacks } => format!("{} {}", name, stacks),
+            EffectDuration::Infinite => name.to_owned(),
+        });
+    }
+    parts.join(" ")
+}
+
+/// Each frame, if the mouse is over a filled Soul Wheel slot, guess what casting it
+/// would do, give a PreviewChip to every creature it would affect, and cover
+/// the tiles it would land on with PreviewTiles.
+pub fn preview_hovered_soul(
+    slots: Query<(&SoulSlot, &Interaction)>,
+    state: Res<State<ControlState>>,
+    soul_wheel: Res<SoulWheel>,
+    player: Query<(&Position, &OrdDir, Option<&Facing>, &Spellbook), With<Player>>,
+    aim_mode: Res<AimMode>,
+    map: Res<Map>,
+    flags: Query<&CreatureFlags>,
+    spellproof_query: Query<&Spellproof>,
+    wall_query: Query<&Wall>,
+    chips: Query<(Entity, &PreviewChip)>,
+    preview_tiles: Query<Entity, With<PreviewTile>>,
+    mut shown_tiles: Local<HashSet<Position>>,
+    mut commands: Commands,
+) {
+    let hovered_soul = slots
+        .iter()
+        .find(|(_, interaction)| **interaction == Interaction::Hovered)
+        .and_then(|(slot, _)| soul_wheel.souls[slot.index]);
+    let mut previews = HashMap::new();
+    let mut tiles = HashSet::new();
+    if let (Some(soul), Ok((position, momentum, facing, spellbook)), ControlState::Player) =
+        (hovered_soul, player.get_single(), state.get())
+    {
+        if let Some(spell) = spellbook.spells.get(&soul) {
+            let prediction = predict_spell(
+                spell,
+                *position,
+                aim_mode.direction(momentum, facing),
+                Some(*position),
+                &map,
+                (&flags, &spellproof_query),
+                |tile| {
+                    map.get_entity_at(tile.x, tile.y)
+                        .and_then(|entity| flags.get(*entity).ok())
+                        .is_some_and(|flags| wall_query.flagged(flags))
+                },
+            );
+            tiles = prediction.tiles;
+            previews = prediction
+                .outcomes
+                .iter()
+                .map(|(entity, outcome)| {
+                    (
+                        *entity,
+                        PreviewChip {
+                            text: chip_text(outcome),
+                            blocked: outcome.blocked,
+                        },
+                    )
+                })
+                .filter(|(_, chip)| !chip.text.is_empty())
+                .collect();
+        }
+    }
+    for (entity, chip) in chips.iter() {
+        if !previews.contains_key(&entity) {
+            commands.entity(entity).remove::<PreviewChip>();
+        } else if previews[&entity] == *chip {
+            // Unchanged, don't trigger change detection.
+            previews.remove(&entity);
+        }
+    }
+    for (entity, chip) in previews {
+        commands.entity(entity).insert(chip);
+    }
+    if tiles != *shown_tiles {
+        for tile in preview_tiles.iter() {
+            commands.entity(tile).despawn();
+        }
+        for position in tiles.iter() {
+            commands.spawn((
+                PreviewTile,
+                Sprite {
+                    color: Color::srgba(1., 0.9, 0.4, 0.2),
+                    custom_size: Some(Vec2::splat(TILE_SIZE)),
+                    ..default()
+                },
+                Transform::from_xyz(
+                    position.x as f32 * TILE_SIZE,
+                    position.y as f32 * TILE_SIZE,
+                    0.,
+                ),
+                VisualLayer::Decal,
+            ));
+        }
+        *shown_tiles = tiles;
+    }
+}
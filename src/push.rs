@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{CreatureFlags, FlagQuery, Footprint, Player, Pushable, Railbound, Species},
+    events::{CreatureCollision, PlayerAction, TeleportEntity, TurnManager},
+    map::{Map, Position},
+    rail::{is_railbound, Rolling},
+    ui::{AddMessage, InvalidAction, Message},
+    OrdDir,
+};
+
+/// Walking into a Pushable creature shoves it one tile further, Sokoban-style,
+/// and the pusher takes its place. Pushable creatures lined up behind it are
+/// shoved along too, unless the far end of the line is blocked. Railbound
+/// creatures keep rolling afterwards, see roll_carts.
+pub fn push_chains(
+    mut events: EventReader<CreatureCollision>,
+    map: Res<Map>,
+    creatures: Query<(&Position, &CreatureFlags, Has<Footprint>)>,
+    pushable: Query<(), With<Pushable>>,
+    railbound: Query<(), With<Railbound>>,
+    species: Query<&Species>,
+    player: Query<(), With<Player>>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut teleport: EventWriter<TeleportEntity>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    let is_pushable = |entity: Entity| {
+        creatures
+            .get(entity)
+            .is_ok_and(|(_, flags, is_large)| !is_large && pushable.flagged(flags))
+    };
+    for event in events.read() {
+        if event.culprit == event.collided_with || !is_pushable(event.collided_with) {
+            continue;
+        }
+        let Ok((pusher_position, _, _)) = creatures.get(event.culprit) else {
+            continue;
+        };
+        // Only straight pushes, from right next to it.
+        let Some(direction) = OrdDir::direction_towards_adjacent_tile(*pusher_position, event.tile)
+        else {
+            continue;
+        };
+        let (off_x, off_y) = direction.as_offset();
+        let mut chain = vec![event.collided_with];
+        let mut end = Position::new(event.tile.x + off_x, event.tile.y + off_y);
+        while let Some(next) = map
+            .get_entity_at(end.x, end.y)
+            .copied()
+            .filter(|next| is_pushable(*next))
+        {
+            chain.push(next);
+            end.shift(off_x, off_y);
+        }
+        if !map.is_passable(end.x, end.y) {
+            if player.contains(event.culprit)
+                && matches!(turn_manager.action_this_turn, PlayerAction::Step)
+            {
+                text.send(AddMessage {
+                    message: Message::InvalidAction(InvalidAction::PushBlocked(
+                        *species.get(event.collided_with).unwrap(),
+                    )),
+                });
+                turn_manager.action_this_turn = PlayerAction::Invalid;
+            }
+            continue;
+        }
+        // The far end moves first, making room for the rest of the chain.
+        for entity in chain.iter().rev() {
+            let (position, flags, _) = creatures.get(*entity).unwrap();
+            teleport.send(TeleportEntity::new(
+                *entity,
+                position.x + off_x,
+                position.y + off_y,
+            ));
+            if is_railbound(flags, &railbound) {
+                commands.entity(*entity).insert(Rolling { direction });
+            }
+        }
+        teleport.send(TeleportEntity {
+            destination: event.tile,
+            entity: event.culprit,
+        });
+    }
+}
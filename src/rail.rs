@@ -0,0 +1,198 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    creature::{CreatureFlags, FlagQuery, Interactable, Railbound, Railway, Switch},
+    events::{AlterMomentum, EndTurn, TeleportEntity},
+    interact::{interaction_target, Interact},
+    map::{Map, Position},
+    ui::{AddMessage, Message},
+    OrdDir,
+};
+
+/// A Railbound creature rolling along the tracks, one tile per turn, until it
+/// is blocked or derails.
+#[derive(Component)]
+pub struct Rolling {
+    pub direction: OrdDir,
+}
+
+/// A rolling creature reached the end of its track, and is thrown off it.
+#[derive(Event)]
+pub struct DerailCart {
+    pub entity: Entity,
+    pub direction: OrdDir,
+}
+
+const DIRECTIONS: [OrdDir; 4] = [OrdDir::Up, OrdDir::Right, OrdDir::Down, OrdDir::Left];
+
+fn reverse(direction: OrdDir) -> OrdDir {
+    match direction {
+        OrdDir::Up => OrdDir::Down,
+        OrdDir::Down => OrdDir::Up,
+        OrdDir::Left => OrdDir::Right,
+        OrdDir::Right => OrdDir::Left,
+    }
+}
+
+fn step(position: Position, direction: OrdDir) -> Position {
+    let (dx, dy) = direction.as_offset();
+    Position::new(position.x + dx, position.y + dy)
+}
+
+/// Every railway tile of the floor, and the way each switch is set.
+// NOTE: Railways and switches are intangible, and missing from the Map.
+fn tracks(
+    creatures: &Query<(Entity, &Position, &OrdDir, &CreatureFlags)>,
+    railways: &Query<(), With<Railway>>,
+    switches: &Query<(), With<Switch>>,
+) -> (HashSet<Position>, HashMap<Position, OrdDir>) {
+    let (mut rails, mut routes) = (HashSet::new(), HashMap::new());
+    for (_, position, direction, flags) in creatures.iter() {
+        if railways.contains(flags.species_flags) {
+            rails.insert(*position);
+        }
+        if switches.contains(flags.species_flags) {
+            routes.insert(*position, *direction);
+        }
+    }
+    (rails, routes)
+}
+
+/// The tracks leaving `position`, other than the one leading back where the
+/// rolling creature came from, in clockwise order starting from straight ahead.
+fn exits(rails: &HashSet<Position>, position: Position, heading: OrdDir) -> Vec<OrdDir> {
+    let start = DIRECTIONS.iter().position(|d| *d == heading).unwrap();
+    (0..4)
+        .map(|turn| DIRECTIONS[(start + turn) % 4])
+        .filter(|direction| *direction != reverse(heading))
+        .filter(|direction| rails.contains(&step(position, *direction)))
+        .collect()
+}
+
+/// Once per turn, every rolling creature follows its track by one tile. Straight
+/// ahead is preferred, then wherever the switch of a junction points, then
+/// the only way onwards. Tracks with no way onwards derail it.
+pub fn roll_carts(
+    mut events: EventReader<EndTurn>,
+    rolling: Query<(Entity, &Position, &Rolling)>,
+    creatures: Query<(Entity, &Position, &OrdDir, &CreatureFlags)>,
+    railways: Query<(), With<Railway>>,
+    switches: Query<(), With<Switch>>,
+    map: Res<Map>,
+    mut teleport: EventWriter<TeleportEntity>,
+    mut derail: EventWriter<DerailCart>,
+    mut commands: Commands,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let (rails, routes) = tracks(&creatures, &railways, &switches);
+    // Sorted, front of each train first, so replays roll creatures in the same order.
+    let mut carts: Vec<(Entity, Position, OrdDir)> = rolling
+        .iter()
+        .map(|(entity, position, rolling)| (entity, *position, rolling.direction))
+        .collect();
+    carts.sort_by_key(|(_, position, direction)| {
+        let (dx, dy) = direction.as_offset();
+        (-(position.x * dx + position.y * dy), position.x, position.y)
+    });
+    let (mut claimed, mut vacated) = (HashSet::new(), HashSet::new());
+    for (entity, position, heading) in carts {
+        if !rails.contains(&position) {
+            commands.entity(entity).remove::<Rolling>();
+            continue;
+        }
+        let exits = exits(&rails, position, heading);
+        let direction = match routes.get(&position) {
+            Some(route) if exits.contains(route) => *route,
+            _ => match exits.first() {
+                Some(direction) => *direction,
+                None => {
+                    derail.send(DerailCart {
+                        entity,
+                        direction: heading,
+                    });
+                    continue;
+                }
+            },
+        };
+        let next = step(position, direction);
+        // A creature on the track stops it in its tracks, unless it is rolling away too.
+        let is_free = map.is_passable(next.x, next.y) || vacated.contains(&next);
+        if !is_free || !claimed.insert(next) {
+            commands.entity(entity).remove::<Rolling>();
+            continue;
+        }
+        teleport.send(TeleportEntity::new(entity, next.x, next.y));
+        commands.entity(entity).insert(Rolling { direction });
+        vacated.insert(position);
+    }
+}
+
+/// Derailed creatures are thrown one tile past the end of their track, if
+/// there is room, and stop rolling.
+pub fn derail_carts(
+    mut events: EventReader<DerailCart>,
+    position: Query<&Position>,
+    map: Res<Map>,
+    mut teleport: EventWriter<TeleportEntity>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Ok(position) = position.get(event.entity) else {
+            continue;
+        };
+        commands.entity(event.entity).remove::<Rolling>();
+        let next = step(*position, event.direction);
+        if map.is_passable(next.x, next.y) {
+            teleport.send(TeleportEntity::new(event.entity, next.x, next.y));
+        }
+        text.send(AddMessage {
+            message: Message::CartDerailed,
+        });
+    }
+}
+
+/// Interacting with a switch turns it clockwise, to the next track leaving its junction.
+pub fn interact_with_switches(
+    mut events: EventReader<Interact>,
+    position: Query<&Position>,
+    interactables: Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: Query<(), With<Interactable>>,
+    creatures: Query<(Entity, &Position, &OrdDir, &CreatureFlags)>,
+    railways: Query<(), With<Railway>>,
+    switches: Query<(), With<Switch>>,
+    mut momentum: EventWriter<AlterMomentum>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        let Some((entity, switch_position, route, _)) =
+            interaction_target(event, &position, &interactables, &interactable)
+                .and_then(|entity| creatures.get(entity).ok())
+                .filter(|(_, _, _, flags)| switches.contains(flags.species_flags))
+        else {
+            continue;
+        };
+        let (rails, _) = tracks(&creatures, &railways, &switches);
+        let start = DIRECTIONS.iter().position(|d| d == route).unwrap();
+        let Some(direction) = (1..=4)
+            .map(|turn| DIRECTIONS[(start + turn) % 4])
+            .find(|direction| rails.contains(&step(*switch_position, *direction)))
+        else {
+            continue;
+        };
+        momentum.send(AlterMomentum { entity, direction });
+        text.send(AddMessage {
+            message: Message::SwitchThrown,
+        });
+    }
+}
+
+/// Railbound creatures pushed along a track start rolling, see push_chains.
+pub fn is_railbound(flags: &CreatureFlags, railbound: &Query<(), With<Railbound>>) -> bool {
+    railbound.flagged(flags)
+}
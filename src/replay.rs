@@ -0,0 +1,421 @@
+use bevy::{asset::AssetPlugin, ecs::schedule::InternedSystemSet, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    caste::EquipSuggestedSpell,
+    content::apply_content_packs,
+    corpse::GrabCorpse,
+    crafting::{EditSpell, SpellEdit, WeaveSoul},
+    creature::{Player, Soul},
+    difficulty::{DifficultyPreset, SetDifficulty},
+    director::{DirectorIntensity, SetDirectorIntensity},
+    events::{
+        CreatureStep, DrawSoul, EndTurn, PassTurn, PlayerAction, TurnFacing, TurnManager,
+        UseWheelSoul,
+    },
+    integrity::world_hash,
+    interact::Interact,
+    map::{FloorLoading, Position},
+    rng::GameRng,
+    scroll::ReadScroll,
+    simulation::TgfpCorePlugin,
+    spells::{AimMode, SetAimMode, SpellStack},
+    warp::PanicWarp,
+    OrdDir,
+};
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActionLog>();
+        app.add_event::<ValidateDeterminism>();
+        app.add_systems(
+            Last,
+            validate_determinism.run_if(on_event::<ValidateDeterminism>),
+        );
+    }
+}
+
+/// Something the player did on their turn, in a form which can be replayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerCommand {
+    Step(OrdDir),
+    Face(OrdDir),
+    CastSoul(usize, Option<Position>),
+    DrawSoul,
+    Interact(OrdDir),
+    /// Place a soul into this recipe of the recipe book.
+    WeaveSoul(usize),
+    /// Equip the oldest spell suggestion, see SpellSuggestions.
+    EquipSuggestion,
+    /// Change a spell of the spellbook, see EditSpell.
+    EditSpell(Soul, SpellEdit),
+    /// Switch to another difficulty, see SetDifficulty.
+    SetDifficulty(DifficultyPreset),
+    /// Make wanderers come back more or less often, see SetDirectorIntensity.
+    SetDirectorIntensity(DirectorIntensity),
+    /// Aim momentum-based forms another way, see SetAimMode.
+    SetAimMode(AimMode),
+    /// Let the turn timer run out, see PassTurn.
+    PassTurn,
+    /// Learn or unravel the oldest held scroll, see ReadScroll.
+    ReadScroll(bool),
+    /// Grab or let go of a corpse, see GrabCorpse.
+    GrabCorpse,
+    /// Escape to a safe tile, see PanicWarp.
+    PanicWarp,
+}
+
+/// Every action the player has taken since the game was launched.
+#[derive(Resource, Default)]
+pub struct ActionLog {
+    pub actions: Vec<(usize, PlayerCommand)>,
+    /// The content packs the run was played with, see ActiveContentPacks.
+    pub content_packs: Vec<String>,
+}
+
+/// Watch what the player is doing and write it down in the ActionLog.
+pub fn record_player_actions(
+    mut steps: EventReader<CreatureStep>,
+    mut casts: EventReader<UseWheelSoul>,
+    mut draws: EventReader<DrawSoul>,
+    mut turns: EventReader<TurnFacing>,
+    mut interactions: EventReader<Interact>,
+    mut weaves: EventReader<WeaveSoul>,
+    mut equips: EventReader<EquipSuggestedSpell>,
+    mut edits: EventReader<EditSpell>,
+    (mut difficulties, mut intensities, mut aim_modes): (
+        EventReader<SetDifficulty>,
+        EventReader<SetDirectorIntensity>,
+        EventReader<SetAimMode>,
+    ),
+    mut passes: EventReader<PassTurn>,
+    mut scrolls: EventReader<ReadScroll>,
+    mut grabs: EventReader<GrabCorpse>,
+    mut warps: EventReader<PanicWarp>,
+    player: Query<Entity, With<Player>>,
+    turn_manager: Res<TurnManager>,
+    mut log: ResMut<ActionLog>,
+) {
+    let Ok(player) = player.get_single() else {
+        return;
+    };
+    let turn = turn_manager.turn_count;
+    for step in steps.read() {
+        // NPCs also take steps, only the player's are of interest.
+        if step.entity == player {
+            log.actions
+                .push((turn, PlayerCommand::Step(step.direction)));
+        }
+    }
+    for turn_facing in turns.read() {
+        log.actions
+            .push((turn, PlayerCommand::Face(turn_facing.direction)));
+    }
+    for cast in casts.read() {
+        log.actions
+            .push((turn, PlayerCommand::CastSoul(cast.index, cast.target)));
+    }
+    for _draw in draws.read() {
+        log.actions.push((turn, PlayerCommand::DrawSoul));
+    }
+    for interaction in interactions.read() {
+        log.actions
+            .push((turn, PlayerCommand::Interact(interaction.direction)));
+    }
+    for weave in weaves.read() {
+        log.actions
+            .push((turn, PlayerCommand::WeaveSoul(weave.index)));
+    }
+    for _equip in equips.read() {
+        log.actions.push((turn, PlayerCommand::EquipSuggestion));
+    }
+    for edit in edits.read() {
+        log.actions
+            .push((turn, PlayerCommand::EditSpell(edit.caste, edit.edit)));
+    }
+    for difficulty in difficulties.read() {
+        log.actions
+            .push((turn, PlayerCommand::SetDifficulty(difficulty.preset)));
+    }
+    for intensity in intensities.read() {
+        log.actions.push((
+            turn,
+            PlayerCommand::SetDirectorIntensity(intensity.intensity),
+        ));
+    }
+    for aim_mode in aim_modes.read() {
+        log.actions
+            .push((turn, PlayerCommand::SetAimMode(aim_mode.mode)));
+    }
+    for _pass in passes.read() {
+        log.actions.push((turn, PlayerCommand::PassTurn));
+    }
+    for scroll in scrolls.read() {
+        log.actions
+            .push((turn, PlayerCommand::ReadScroll(scroll.learn)));
+    }
+    for _grab in grabs.read() {
+        log.actions.push((turn, PlayerCommand::GrabCorpse));
+    }
+    for _warp in warps.read() {
+        log.actions.push((turn, PlayerCommand::PanicWarp));
+    }
+}
+
+/// Debug: replay the ActionLog in two separate worlds, and report the first
+/// moment where they stop agreeing with each other: the action, the frame and
+/// the system of that frame after which the worlds differ.
+#[derive(Event)]
+pub struct ValidateDeterminism;
+
+/// The world state hash after each system of each frame of a headless simulation.
+#[derive(Resource, Default)]
+struct Checkpoints {
+    hashes: Vec<(String, u64)>,
+    /// How many checkpoints each frame goes through.
+    per_frame: usize,
+}
+
+/// Build a world running the rules of the game, with no window, input, UI or animation.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()));
+    app.init_asset::<Image>();
+    app.init_asset::<TextureAtlasLayout>();
+    app.insert_resource(GameRng::new(0));
+    app.add_plugins(TgfpCorePlugin);
+    app.world_mut().resource_mut::<FloorLoading>().blocking = true;
+    app.finish();
+    app.cleanup();
+    app
+}
+
+/// Have the player of a headless world perform an action,
+/// exactly like keyboard_input would.
+pub fn apply_player_command(world: &mut World, command: PlayerCommand) {
+    let player = world
+        .query_filtered::<Entity, With<Player>>()
+        .get_single(world)
+        .expect("The headless world has no player");
+    let action = match command {
+        // Looking around is free, and does not end the turn.
+        PlayerCommand::Face(direction) => {
+            world.send_event(TurnFacing {
+                entity: player,
+                direction,
+            });
+            return;
+        }
+        // So is changing spells.
+        PlayerCommand::EquipSuggestion => {
+            world.send_event(EquipSuggestedSpell);
+            return;
+        }
+        PlayerCommand::EditSpell(caste, edit) => {
+            world.send_event(EditSpell { caste, edit });
+            return;
+        }
+        PlayerCommand::SetDifficulty(preset) => {
+            world.send_event(SetDifficulty { preset });
+            return;
+        }
+        PlayerCommand::SetDirectorIntensity(intensity) => {
+            world.send_event(SetDirectorIntensity { intensity });
+            return;
+        }
+        PlayerCommand::SetAimMode(mode) => {
+            world.send_event(SetAimMode { mode });
+            return;
+        }
+        PlayerCommand::ReadScroll(learn) => {
+            world.send_event(ReadScroll { learn });
+            return;
+        }
+        PlayerCommand::GrabCorpse => {
+            world.send_event(GrabCorpse { entity: player });
+            return;
+        }
+        PlayerCommand::Step(direction) => {
+            world.send_event(CreatureStep {
+                entity: player,
+                direction,
+            });
+            PlayerAction::Step
+        }
+        PlayerCommand::CastSoul(index, target) => {
+            world.send_event(UseWheelSoul { index, target });
+            PlayerAction::Spell
+        }
+        PlayerCommand::DrawSoul => {
+            world.send_event(DrawSoul { amount: 1 });
+            PlayerAction::Draw
+        }
+        PlayerCommand::Interact(direction) => {
+            world.send_event(Interact {
+                entity: player,
+                direction,
+            });
+            PlayerAction::Interact
+        }
+        PlayerCommand::WeaveSoul(index) => {
+            world.send_event(WeaveSoul { index });
+            PlayerAction::Weave
+        }
+        PlayerCommand::PassTurn => {
+            world.send_event(PassTurn);
+            PlayerAction::Pass
+        }
+        PlayerCommand::PanicWarp => {
+            world.send_event(PanicWarp { entity: player });
+            PlayerAction::Warp
+        }
+    };
+    world.resource_mut::<TurnManager>().action_this_turn = action;
+    world.send_event(EndTurn);
+}
+
+/// Run a headless world until the turn has fully resolved.
+pub fn settle_turn(app: &mut App) {
+    // A turn needs a few frames to go through the player's action,
+    // then the NPCs' actions. Spells need one frame per axiom.
+    let mut quiet_frames = 0;
+    for frame in 0..500 {
+        app.update();
+        if app.world().resource::<SpellStack>().spells.is_empty()
+            && !app.world().resource::<FloorLoading>().is_loading()
+        {
+            quiet_frames += 1;
+        } else {
+            quiet_frames = 0;
+        }
+        if frame >= 4 && quiet_frames >= 2 {
+            return;
+        }
+    }
+    warn!("A headless turn failed to settle after 500 frames.");
+}
+
+fn checkpoint(label: String) -> impl FnMut(&mut World) + Send + Sync + 'static {
+    move |world: &mut World| {
+        let hash = world_hash(world);
+        world
+            .resource_mut::<Checkpoints>()
+            .hashes
+            .push((label.clone(), hash));
+    }
+}
+
+/// The systems of `Update`, in the order they are executed, with the set
+/// which can be used to order other systems against them.
+fn update_order(world: &mut World) -> Vec<(String, Option<InternedSystemSet>)> {
+    world.schedule_scope(Update, |world, update| {
+        update
+            .initialize(world)
+            .expect("The Update schedule failed to build");
+        update
+            .systems()
+            .expect("The Update schedule was just initialized")
+            .map(|(_node, system)| {
+                (
+                    system.name().to_string(),
+                    system.default_system_sets().first().copied(),
+                )
+            })
+            .collect()
+    })
+}
+
+fn validation_app(seed: u64, content_packs: &[String]) -> App {
+    let mut app = headless_app();
+    app.insert_resource(GameRng::new(seed));
+    apply_content_packs(app.world_mut(), content_packs);
+    app.init_resource::<Checkpoints>();
+    let order = update_order(app.world_mut());
+    // Systems which were added more than once cannot be ordered against.
+    // Whatever they change is caught by the checkpoint of a neighbour.
+    let anchors: Vec<(String, InternedSystemSet)> = order
+        .iter()
+        .filter_map(|(name, set)| {
+            let set = (*set)?;
+            let instances = order.iter().filter(|(_, other)| *other == Some(set));
+            (instances.count() == 1).then(|| (name.clone(), set))
+        })
+        .collect();
+    let (Some((_, first)), Some((last_name, last))) = (anchors.first(), anchors.last()) else {
+        return app;
+    };
+    // Anything done outside of Update, such as event buffers being swapped.
+    app.add_systems(
+        Update,
+        checkpoint("the start of the frame".to_owned()).before(*first),
+    );
+    for pair in anchors.windows(2) {
+        let ((name, system), (_, next)) = (&pair[0], &pair[1]);
+        app.add_systems(
+            Update,
+            checkpoint(name.clone()).after(*system).before(*next),
+        );
+    }
+    app.add_systems(Update, checkpoint(last_name.clone()).after(*last));
+    app.world_mut().resource_mut::<Checkpoints>().per_frame = anchors.len() + 1;
+    app
+}
+
+fn validate_determinism(log: Res<ActionLog>, rng: Res<GameRng>) {
+    info!(
+        "Validating determinism over {} logged actions...",
+        log.actions.len()
+    );
+    let (mut world_a, mut world_b) = (
+        validation_app(rng.seed(), &log.content_packs),
+        validation_app(rng.seed(), &log.content_packs),
+    );
+    let per_frame = world_a.world().resource::<Checkpoints>().per_frame.max(1);
+    // Spawn the cage.
+    settle_turn(&mut world_a);
+    settle_turn(&mut world_b);
+    // `None` stands for the initial setup, before any action was taken.
+    let mut actions = vec![None];
+    actions.extend(log.actions.iter().map(|action| Some(*action)));
+    for (i, action) in actions.iter().enumerate() {
+        if let Some((_turn, command)) = action {
+            for app in [&mut world_a, &mut world_b] {
+                apply_player_command(app.world_mut(), *command);
+                settle_turn(app);
+            }
+        }
+        let checkpoints_a =
+            std::mem::take(&mut world_a.world_mut().resource_mut::<Checkpoints>().hashes);
+        let checkpoints_b =
+            std::mem::take(&mut world_b.world_mut().resource_mut::<Checkpoints>().hashes);
+        let divergence = checkpoints_a
+            .iter()
+            .zip(checkpoints_b.iter())
+            .enumerate()
+            .find(|(_, (a, b))| a != b);
+        if let Some((checkpoint, ((system, _), _))) = divergence {
+            let frame = checkpoint / per_frame;
+            match action {
+                Some((turn, command)) => warn!(
+                    "Determinism broken on action #{} ({:?}, turn {}), frame {}, after {}.",
+                    i, command, turn, frame, system
+                ),
+                None => warn!(
+                    "Determinism broken before any action was taken, frame {}, after {}.",
+                    frame, system
+                ),
+            }
+            return;
+        }
+        if checkpoints_a.len() != checkpoints_b.len() {
+            warn!(
+                "Determinism broken on action #{}: the worlds took a different amount of frames to settle.",
+                i
+            );
+            return;
+        }
+    }
+    info!("Both worlds agreed on every turn.");
+}
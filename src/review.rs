@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, sprite::Anchor};
+
+use crate::{
+    creature::{Health, Player, Species},
+    events::{remove_creature, RespawnPlayer, TurnManager},
+    graphics::SpriteSheetAtlas,
+    map::Position,
+    overlay::hp_bar_index,
+    replay::{ActionLog, PlayerCommand},
+    sets::{Animation, ControlState, NpcTurn, SpellResolution},
+    species::SpeciesRegistry,
+    stats::RunSummary,
+    TILE_SIZE,
+};
+
+/// How many turns can be scrubbed through after dying.
+const REVIEW_LENGTH: usize = 50;
+
+pub struct ReviewPlugin;
+
+impl Plugin for ReviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TurnHistory>();
+        app.init_resource::<ReviewCursor>();
+        app.add_systems(
+            Update,
+            (
+                record_turn_snapshot
+                    .after(NpcTurn)
+                    .run_if(not(in_state(ControlState::Review))),
+                start_review.after(remove_creature).in_set(SpellResolution),
+                (review_input, render_review)
+                    .chain()
+                    .after(Animation)
+                    .run_if(in_state(ControlState::Review)),
+            ),
+        );
+        app.add_systems(OnExit(ControlState::Review), end_review);
+    }
+}
+
+/// What the board looked like at the end of a turn.
+pub struct TurnSnapshot {
+    pub turn: usize,
+    /// What the player did to get there.
+    pub commands: Vec<PlayerCommand>,
+    /// Position, species, HP and max HP of every creature.
+    pub creatures: Vec<(Position, Species, usize, usize)>,
+}
+
+/// The last REVIEW_LENGTH turns of the run, oldest first.
+#[derive(Resource, Default)]
+pub struct TurnHistory {
+    pub snapshots: VecDeque<TurnSnapshot>,
+}
+
+/// Which snapshot of the TurnHistory is being looked at.
+#[derive(Resource, Default)]
+struct ReviewCursor {
+    index: usize,
+}
+
+/// A creature drawn on top of the board, as it was during the reviewed turn.
+/// The review screen is drawn above every VisualLayer, hiding the board.
+const REVIEW_Z: f32 = 8.;
+
+#[derive(Component)]
+struct ReviewGhost;
+
+#[derive(Component)]
+struct ReviewBackdrop;
+
+#[derive(Component)]
+struct ReviewHeader;
+
+fn take_snapshot(
+    creatures: &Query<(&Position, &Species, &Health)>,
+    log: &ActionLog,
+    turn: usize,
+) -> TurnSnapshot {
+    TurnSnapshot {
+        turn,
+        commands: log
+            .actions
+            .iter()
+            .filter(|(action_turn, _)| action_turn + 1 == turn)
+            .map(|(_, command)| *command)
+            .collect(),
+        creatures: creatures
+            .iter()
+            .map(|(position, species, health)| (*position, *species, health.hp, health.max_hp))
+            .collect(),
+    }
+}
+
+fn push_snapshot(history: &mut TurnHistory, snapshot: TurnSnapshot) {
+    history.snapshots.push_back(snapshot);
+    if history.snapshots.len() > REVIEW_LENGTH {
+        history.snapshots.pop_front();
+    }
+}
+
+/// Once each turn is over, write down where everything is.
+// NOTE: This is recorded live instead of being rebuilt from the ActionLog,
+// as rebuilding any turn would mean replaying the whole run from its seed.
+fn record_turn_snapshot(
+    creatures: Query<(&Position, &Species, &Health)>,
+    turn_manager: Res<TurnManager>,
+    log: Res<ActionLog>,
+    mut history: ResMut<TurnHistory>,
+) {
+    let turn = turn_manager.turn_count;
+    if history
+        .snapshots
+        .back()
+        .is_some_and(|snapshot| snapshot.turn == turn)
+    {
+        return;
+    }
+    push_snapshot(&mut history, take_snapshot(&creatures, &log, turn));
+}
+
+/// When the player dies, freeze the final moment and open the review screen.
+fn start_review(
+    mut events: EventReader<RespawnPlayer>,
+    player: Query<&Health, With<Player>>,
+    creatures: Query<(&Position, &Species, &Health)>,
+    turn_manager: Res<TurnManager>,
+    log: Res<ActionLog>,
+    mut history: ResMut<TurnHistory>,
+    mut cursor: ResMut<ReviewCursor>,
+    mut next_state: ResMut<NextState<ControlState>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    // Resetting the game manually also respawns the player, but does not kill them.
+    let died = events.read().any(|event| !event.victorious)
+        && player.get_single().is_ok_and(|health| health.hp == 0);
+    if !died {
+        return;
+    }
+    push_snapshot(
+        &mut history,
+        take_snapshot(&creatures, &log, turn_manager.turn_count + 1),
+    );
+    cursor.index = history.snapshots.len() - 1;
+    next_state.set(ControlState::Review);
+
+    // Hide the board underneath.
+    commands.spawn((
+        ReviewBackdrop,
+        Sprite {
+            color: Color::BLACK,
+            custom_size: Some(Vec2::splat(1000. * TILE_SIZE)),
+            ..default()
+        },
+        Transform::from_xyz(0., 0., REVIEW_Z),
+    ));
+    commands.spawn((
+        ReviewHeader,
+        Text::new(""),
+        TextFont {
+            font: asset_server.load("fonts/Play-Regular.ttf"),
+            font_size: 1.5,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(1.),
+            left: Val::Px(1.),
+            ..default()
+        },
+    ));
+}
+
+/// Left and right scrub through the turns, Escape or Enter go on to the RunSummary.
+fn review_input(
+    input: Res<ButtonInput<KeyCode>>,
+    history: Res<TurnHistory>,
+    summary: Res<RunSummary>,
+    mut cursor: ResMut<ReviewCursor>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    if input.just_pressed(KeyCode::ArrowLeft) || input.just_pressed(KeyCode::KeyA) {
+        cursor.index = cursor.index.saturating_sub(1);
+    }
+    if input.just_pressed(KeyCode::ArrowRight) || input.just_pressed(KeyCode::KeyD) {
+        cursor.index = (cursor.index + 1).min(history.snapshots.len().saturating_sub(1));
+    }
+    if input.just_pressed(KeyCode::Escape) || input.just_pressed(KeyCode::Enter) {
+        next_state.set(if summary.pending.is_some() {
+            ControlState::RunSummary
+        } else {
+            ControlState::Player
+        });
+    }
+}
+
+/// Draw the creatures of the reviewed turn, and describe what happened during it.
+fn render_review(
+    history: Res<TurnHistory>,
+    cursor: Res<ReviewCursor>,
+    ghosts: Query<Entity, With<ReviewGhost>>,
+    mut header: Query<&mut Text, With<ReviewHeader>>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+    registry: Res<SpeciesRegistry>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut commands: Commands,
+) {
+    let Some(snapshot) = history.snapshots.get(cursor.index) else {
+        return;
+    };
+    // The camera is normally moved by adjust_transforms, this runs after it.
+    if let Some((position, ..)) = snapshot
+        .creatures
+        .iter()
+        .find(|(_, species, ..)| *species == Species::Player)
+    {
+        if let Ok(mut camera) = camera.get_single_mut() {
+            camera.translation.x = position.x as f32 * TILE_SIZE + 10.;
+            camera.translation.y = position.y as f32 * TILE_SIZE;
+        }
+    }
+    if !cursor.is_changed() {
+        return;
+    }
+    for ghost in ghosts.iter() {
+        commands.entity(ghost).despawn_recursive();
+    }
+    for (position, species, hp, max_hp) in &snapshot.creatures {
+        let definition = registry.get(species);
+        let (width, height) = definition
+            .footprint()
+            .map_or((1, 1), |footprint| footprint.size());
+        let (width, height) = (width as f32, height as f32);
+        commands
+            .spawn((
+                ReviewGhost,
+                Sprite {
+                    image: asset_server.load("spritesheet.png"),
+                    custom_size: Some(Vec2::new(width * TILE_SIZE, height * TILE_SIZE)),
+                    texture_atlas: Some(TextureAtlas {
+                        layout: atlas_layout.handle.clone(),
+                        index: definition.sprite,
+                    }),
+                    anchor: Anchor::Custom(Vec2::new(0.5 / width - 0.5, 0.5 / height - 0.5)),
+                    ..default()
+                },
+                Transform::from_xyz(
+                    position.x as f32 * TILE_SIZE,
+                    position.y as f32 * TILE_SIZE,
+                    REVIEW_Z + 1.,
+                ),
+            ))
+            .with_children(|parent| {
+                if hp != max_hp {
+                    parent.spawn((
+                        Sprite {
+                            image: asset_server.load("spritesheet.png"),
+                            custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                            texture_atlas: Some(TextureAtlas {
+                                layout: atlas_layout.handle.clone(),
+                                index: hp_bar_index(*hp, *max_hp),
+                            }),
+                            ..default()
+                        },
+                        Transform::from_xyz(0., 0., 0.1),
+                    ));
+                }
+            });
+    }
+    if let Ok(mut header) = header.get_single_mut() {
+        let player_hp = snapshot
+            .creatures
+            .iter()
+            .find(|(_, species, ..)| *species == Species::Player)
+            .map_or(0, |(_, _, hp, _)| *hp);
+        let commands: Vec<String> = snapshot
+            .commands
+            .iter()
+            .map(|command| format!("{:?}", command))
+            .collect();
+        header.0 = format!(
+            "Turn {} ({}/{}) - HP {} - {}\nLeft/Right: Scrub through turns. Escape: Leave.",
+            snapshot.turn,
+            cursor.index + 1,
+            history.snapshots.len(),
+            player_hp,
+            if commands.is_empty() {
+                "Waited.".to_owned()
+            } else {
+                commands.join(", ")
+            },
+        );
+    }
+}
+
+fn end_review(
+    ghosts: Query<Entity, Or<(With<ReviewGhost>, With<ReviewBackdrop>, With<ReviewHeader>)>>,
+    mut history: ResMut<TurnHistory>,
+    mut commands: Commands,
+) {
+    for entity in ghosts.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // The next run starts with a clean slate.
+    history.snapshots.clear();
+}
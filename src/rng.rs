@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, thread_rng, Rng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    difficulty::GameDifficulty,
+    director::SpawnDirector,
+    events::RespawnPlayer,
+    replay::{ActionLog, PlayerCommand},
+    sets::{PlayerInput, SpellResolution},
+    spells::AimMode,
+    ui::{AddMessage, Message},
+};
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameRng::new(startup_seed()));
+        app.add_event::<SetSeed>();
+        app.add_systems(
+            Update,
+            // After PlayerInput, so a difficulty picked along with the seed is
+            // already applied, and recorded as the one the run starts with.
+            set_seed
+                .run_if(on_event::<SetSeed>)
+                .after(PlayerInput)
+                .before(SpellResolution),
+        );
+    }
+}
+
+/// The only source of randomness for anything which affects the game state.
+/// The same seed, followed by the same player actions, always plays out the same run.
+// NOTE: Purely visual randomness, like screenshake, does not need to use this.
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+    /// How many 32-bit words were drawn since the seed, see RngState.
+    words: u64,
+}
+
+/// Where a GameRng is at, in a form which can be saved and hashed.
+// NOTE: StdRng cannot be serialized. It hands out one stream of 32-bit words
+// (a u64 takes two, bytes are drawn a word at a time), so the seed and the
+// amount of words drawn are enough to bring it back.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RngState {
+    pub seed: u64,
+    pub words: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            words: 0,
+        }
+    }
+
+    /// The seed this run was started with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> RngState {
+        RngState {
+            seed: self.seed,
+            words: self.words,
+        }
+    }
+
+    /// Pick up exactly where a saved GameRng left off.
+    pub fn from_state(state: RngState) -> Self {
+        let mut rng = Self::new(state.seed);
+        for _ in 0..state.words {
+            rng.next_u32();
+        }
+        rng
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.words += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.words += 2;
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.words += dest.len().div_ceil(4) as u64;
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.words += dest.len().div_ceil(4) as u64;
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// `--seed 1234` on the command line, or the TGFP_SEED environment variable.
+/// Otherwise, a random one.
+fn startup_seed() -> u64 {
+    let mut args = std::env::args();
+    let from_args = args
+        .position(|arg| arg == "--seed")
+        .and_then(|_| args.next());
+    let seed = from_args
+        .or_else(|| std::env::var("TGFP_SEED").ok())
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(|| thread_rng().gen());
+    info!("Starting with seed {}.", seed);
+    seed
+}
+
+/// Restart the run with this seed, or with a random one if None.
+#[derive(Event)]
+pub struct SetSeed {
+    pub seed: Option<u64>,
+}
+
+fn set_seed(
+    mut events: EventReader<SetSeed>,
+    mut rng: ResMut<GameRng>,
+    mut log: ResMut<ActionLog>,
+    difficulty: Res<GameDifficulty>,
+    director: Res<SpawnDirector>,
+    aim_mode: Res<AimMode>,
+    mut respawn: EventWriter<RespawnPlayer>,
+) {
+    if let Some(event) = events.read().last() {
+        let seed = event.seed.unwrap_or_else(|| thread_rng().gen());
+        info!("Restarting with seed {}.", seed);
+        *rng = GameRng::new(seed);
+        // The run starts over, and so does what is worth replaying.
+        log.actions.clear();
+        // Except for the difficulty, the director and the aim mode, which carry over.
+        log.actions
+            .push((0, PlayerCommand::SetDifficulty(difficulty.preset)));
+        log.actions
+            .push((0, PlayerCommand::SetDirectorIntensity(director.intensity)));
+        log.actions.push((0, PlayerCommand::SetAimMode(*aim_mode)));
+        respawn.send(RespawnPlayer { victorious: false });
+    }
+}
+
+/// Put the seed in the message log and the console, where it can be copied from.
+// NOTE: Bevy has no clipboard access of its own, so this is only a reminder.
+pub fn share_seed(rng: &GameRng, text: &mut EventWriter<AddMessage>) {
+    info!("This run's seed is {}.", rng.seed());
+    text.send(AddMessage {
+        message: Message::RunSeed(rng.seed()),
+    });
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bevy::{
+    input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest},
+    prelude::*,
+};
+
+use crate::{
+    creature::Player,
+    events::DamageOrHealCreature,
+    graphics::PlaceMagicVfx,
+    sets::{Animation, ControlState, PlayerInput},
+    ui::{AddMessage, Message},
+};
+
+pub struct RumblePlugin;
+
+impl Plugin for RumblePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RumbleIntensity>();
+        app.add_event::<Rumble>();
+        app.add_systems(
+            Update,
+            cycle_rumble_intensity
+                .run_if(in_state(ControlState::Player))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(Update, rumble_gamepads.in_set(Animation));
+    }
+}
+
+/// Spells covering at least this many tiles shake the controller.
+const BIG_SPELL: usize = 6;
+
+/// How strongly connected gamepads rumble, if at all.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RumbleIntensity {
+    Off,
+    Low,
+    #[default]
+    High,
+}
+
+impl RumbleIntensity {
+    fn scale(&self) -> f32 {
+        match self {
+            RumbleIntensity::Off => 0.,
+            RumbleIntensity::Low => 0.4,
+            RumbleIntensity::High => 1.,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            RumbleIntensity::Off => RumbleIntensity::Low,
+            RumbleIntensity::Low => RumbleIntensity::High,
+            RumbleIntensity::High => RumbleIntensity::Off,
+        }
+    }
+}
+
+/// Shake every connected gamepad. Damage and big spells are felt through their
+/// own events, so they don't need to send a Rumble.
+// NOTE: There are no bosses yet. Their phase changes should send one of these.
+#[derive(Event)]
+pub struct Rumble {
+    /// From 0 to 1, before RumbleIntensity is applied.
+    pub strength: f32,
+    pub seconds: f32,
+}
+
+/// H cycles how strongly the controller rumbles.
+fn cycle_rumble_intensity(
+    input: Res<ButtonInput<KeyCode>>,
+    mut intensity: ResMut<RumbleIntensity>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if input.just_pressed(KeyCode::KeyH) {
+        *intensity = intensity.next();
+        text.send(AddMessage {
+            message: Message::RumbleIntensity(*intensity),
+        });
+    }
+}
+
+pub fn rumble_gamepads(
+    mut events: EventReader<Rumble>,
+    mut damage: EventReader<DamageOrHealCreature>,
+    mut magic_vfx: EventReader<PlaceMagicVfx>,
+    player: Query<(), With<Player>>,
+    gamepads: Query<Entity, With<Gamepad>>,
+    intensity: Res<RumbleIntensity>,
+    mut requests: EventWriter<GamepadRumbleRequest>,
+) {
+    let rumbles: Vec<(f32, f32)> = events
+        .read()
+        .map(|event| (event.strength, event.seconds))
+        .chain(
+            damage
+                .read()
+                .filter(|event| event.hp_mod < 0 && player.contains(event.entity))
+                // Heavier blows are felt harder.
+                .map(|event| ((event.hp_mod.unsigned_abs() as f32 / 4.).min(1.), 0.2)),
+        )
+        .chain(
+            magic_vfx
+                .read()
+                .filter(|event| event.targets.len() >= BIG_SPELL)
+                .map(|_| (0.3, 0.15)),
+        )
+        .collect();
+    if *intensity == RumbleIntensity::Off {
+        return;
+    }
+    for (strength, seconds) in rumbles {
+        let strength = (strength * intensity.scale()).clamp(0., 1.);
+        for gamepad in gamepads.iter() {
+            requests.send(GamepadRumbleRequest::Add {
+                duration: Duration::from_secs_f32(seconds),
+                intensity: GamepadRumbleIntensity {
+                    strong_motor: strength,
+                    weak_motor: strength,
+                },
+                gamepad,
+            });
+        }
+    }
+}
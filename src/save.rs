@@ -0,0 +1,948 @@
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::AiState,
+    caste::SpellSuggestions,
+    circuit::{Circuit, Circuits},
+    content::{apply_content_packs, ActiveContentPacks},
+    corpse::{Corpse, SpawnCorpse},
+    crafting::{LooseAxioms, Weaving},
+    creature::{
+        Awake, Blessed, CreatureFlags, DeathEffect, EffectDuration, Health, Hidden, Interactable,
+        Player, Projectile, Revealed, ShieldBuffer, Sleeping, Soul, Species, Spellbook,
+        StatusEffect, StatusEffectsList, WeakPoints,
+    },
+    difficulty::GameDifficulty,
+    director::SpawnDirector,
+    equipment::Equipment,
+    events::{
+        AddStatusEffect, HealthChanged, RespawnPlayer, SoulWheel, SummonCreature, TurnManager,
+    },
+    evolution::Maturing,
+    focus::{cycle_focus, focus_confirm, focus_step, Focused},
+    graphics::SpriteSheetAtlas,
+    grinder::Grinder,
+    integrity::world_hash,
+    key_items::{KeyItem, KeyItems},
+    map::{FaithsEnd, FloorLoading, Map, Position, Region, Regions, TileKind},
+    replay::{ActionLog, PlayerCommand},
+    rng::{share_seed, GameRng, RngState},
+    scroll::HeldScrolls,
+    sets::{ControlState, PlayerInput, SpellResolution},
+    species::SpeciesRegistry,
+    spells::{AimMode, Axiom, Spell, SpellStack},
+    stats::RunStats,
+    text::split_text,
+    ui::AddMessage,
+    warp::WarpCharges,
+    OrdDir,
+};
+
+/// Slot 0 is written to automatically when the game is closed, and by quicksaves (F5).
+pub const AUTOSAVE_SLOT: usize = 0;
+/// How many tiles away from the player the save thumbnail shows.
+const THUMBNAIL_RADIUS: i32 = 3;
+
+pub struct SaveGamePlugin;
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveGame>();
+        app.add_event::<LoadGame>();
+        app.add_event::<DeleteSave>();
+        app.add_event::<CopySave>();
+        app.init_resource::<RunMode>();
+        app.init_resource::<SaveMenu>();
+        app.add_systems(
+            Last,
+            (
+                save_game.run_if(on_event::<SaveGame>.or(on_event::<AppExit>)),
+                load_game.run_if(on_event::<LoadGame>),
+                (delete_save, copy_save, delete_save_on_death),
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Update,
+            finish_restore
+                .run_if(resource_exists::<PendingRestore>)
+                .after(SpellResolution),
+        );
+        app.add_systems(OnEnter(ControlState::SaveMenu), open_save_menu);
+        app.add_systems(OnExit(ControlState::SaveMenu), close_save_menu);
+        app.add_systems(
+            Update,
+            (save_menu_input, update_save_menu)
+                .chain()
+                .run_if(in_state(ControlState::SaveMenu))
+                .in_set(PlayerInput),
+        );
+    }
+}
+
+/// Write the whole run to disk, in this slot.
+#[derive(Event)]
+pub struct SaveGame {
+    pub slot: usize,
+}
+
+/// Replace the whole run with the one written in this slot.
+#[derive(Event)]
+pub struct LoadGame {
+    pub slot: usize,
+}
+
+#[derive(Event)]
+pub struct DeleteSave {
+    pub slot: usize,
+}
+
+#[derive(Event)]
+pub struct CopySave {
+    pub from: usize,
+    pub to: usize,
+}
+
+/// How forgiving the current run is, picked in the difficulty menu.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RunMode {
+    /// Any number of manual saves, which survive death.
+    #[default]
+    Standard,
+    /// Only the autosave, deleted on death.
+    Permadeath,
+}
+
+impl RunMode {
+    /// How many slots can be saved to manually, on top of the autosave.
+    pub fn manual_slots(&self) -> usize {
+        match self {
+            RunMode::Standard => 5,
+            RunMode::Permadeath => 0,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            RunMode::Standard => "Standard [d](manual saves survive death)[w]",
+            RunMode::Permadeath => "[r]Permadeath[w] [d](only the autosave, deleted on death)[w]",
+        }
+    }
+}
+
+fn slot_path(slot: usize) -> String {
+    format!("save_{}.ron", slot)
+}
+
+/// Shown in the save menu, without having to restore the run.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SaveMetadata {
+    /// The current cage of Faith's End.
+    pub floor: usize,
+    pub turns: usize,
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub run_mode: RunMode,
+    /// The sprite of the creature on each tile around the player, top row first.
+    pub thumbnail: Vec<Vec<Option<usize>>>,
+}
+
+/// Everything needed to bring a run back to life.
+#[derive(Serialize, Deserialize)]
+pub struct SaveFile {
+    pub metadata: SaveMetadata,
+    pub turn_count: usize,
+    pub wheel: [Option<Soul>; 8],
+    pub draw_pile: Vec<(Soul, usize)>,
+    pub discard_pile: Vec<(Soul, usize)>,
+    pub current_cage: usize,
+    pub cage_address_position: Vec<(Position, usize)>,
+    pub cage_dimensions: Vec<(usize, (Position, Position))>,
+    pub creatures: Vec<SavedCreature>,
+    // NOTE: Levers are restored in their default position, only
+    // the circuits remember if they were powered.
+    #[serde(default)]
+    pub circuits: Vec<Circuit>,
+    #[serde(default)]
+    pub key_items: Vec<KeyItem>,
+    #[serde(default)]
+    pub grinder: Grinder,
+    #[serde(default)]
+    pub director: SpawnDirector,
+    #[serde(default)]
+    pub weaving: Weaving,
+    #[serde(default)]
+    pub suggestions: Vec<(Soul, Spell)>,
+    #[serde(default)]
+    pub loose_axioms: Vec<Axiom>,
+    #[serde(default)]
+    pub scrolls: Vec<(Soul, Spell)>,
+    #[serde(default)]
+    pub terrain: Vec<(Position, TileKind)>,
+    #[serde(default)]
+    pub corpses: Vec<Corpse>,
+    #[serde(default)]
+    pub wheel_locks: [Option<Soul>; 8],
+    #[serde(default)]
+    pub regions: Vec<Region>,
+    #[serde(default)]
+    pub difficulty: GameDifficulty,
+    #[serde(default)]
+    pub stats: RunStats,
+    #[serde(default)]
+    pub warp_charges: WarpCharges,
+    #[serde(default)]
+    pub content_packs: Vec<String>,
+    #[serde(default)]
+    pub aim_mode: AimMode,
+    /// Older saves keep going with the current GameRng.
+    #[serde(default)]
+    pub rng: Option<RngState>,
+    /// The ActionLog since the seed was set, so the run can still be replayed
+    /// from it once loaded, see validate_determinism.
+    #[serde(default)]
+    pub actions: Vec<(usize, PlayerCommand)>,
+    /// The world hash at the time of saving. If the loaded world does not hash
+    /// to the same value, the save was corrupted (or tampered with).
+    pub world_hash: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedCreature {
+    pub position: Position,
+    pub species: Species,
+    pub momentum: OrdDir,
+    pub hp: usize,
+    pub max_hp: usize,
+    pub spellbook: Vec<(Soul, Spell)>,
+    pub effects: Vec<(StatusEffect, usize, EffectDuration)>,
+    pub asleep: bool,
+    #[serde(default)]
+    pub equipment: Option<Equipment>,
+    /// Still waiting to be found, see Hidden.
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub shield: Option<ShieldBuffer>,
+    #[serde(default)]
+    pub blessing: Option<Blessed>,
+    #[serde(default)]
+    pub maturing: Option<Maturing>,
+    #[serde(default)]
+    pub projectile: Option<SavedProjectile>,
+    /// How much HP each WeakPoint has left, by offset. Destroyed ones are at 0.
+    #[serde(default)]
+    pub weak_points: Vec<((i32, i32), usize)>,
+}
+
+/// A Projectile, with its owner found again by position once loaded.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedProjectile {
+    pub velocity: OrdDir,
+    pub owner: Option<Position>,
+    pub payload: Spell,
+    pub caste: Soul,
+}
+
+/// Creatures which were just summoned back from a save file,
+/// and which still need their health and status effects restored.
+#[derive(Resource)]
+struct PendingRestore {
+    creatures: Vec<SavedCreature>,
+    world_hash: u64,
+}
+
+pub fn read_save(slot: usize) -> Result<SaveFile, String> {
+    fs::read_to_string(slot_path(slot))
+        .map_err(|error| error.to_string())
+        .and_then(|contents| ron::from_str(&contents).map_err(|error| error.to_string()))
+}
+
+/// The sprites of every creature up to `radius` tiles away from the player,
+/// row by row from the top.
+pub fn thumbnail(world: &mut World, radius: i32) -> Vec<Vec<Option<usize>>> {
+    let Ok(center) = world
+        .query_filtered::<&Position, With<Player>>()
+        .get_single(world)
+        .copied()
+    else {
+        return Vec::new();
+    };
+    let species: HashMap<Position, Species> = world
+        .query::<(&Position, &Species)>()
+        .iter(world)
+        .map(|(position, species)| (*position, *species))
+        .collect();
+    let registry = world.resource::<SpeciesRegistry>();
+    (-radius..=radius)
+        .rev()
+        .map(|dy| {
+            (-radius..=radius)
+                .map(|dx| {
+                    species
+                        .get(&Position::new(center.x + dx, center.y + dy))
+                        .map(|species| registry.get(species).sprite)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Everything needed to bring the run of this world back, see restore_save.
+pub fn write_save(world: &mut World) -> SaveFile {
+    let hash = world_hash(world);
+    let thumbnail = thumbnail(world, THUMBNAIL_RADIUS);
+    let positions: HashMap<Entity, Position> = world
+        .query::<(Entity, &Position)>()
+        .iter(world)
+        .map(|(entity, position)| (entity, *position))
+        .collect();
+    let creatures = world
+        .query::<(
+            &Position,
+            &Species,
+            &OrdDir,
+            &Health,
+            &Spellbook,
+            &StatusEffectsList,
+            Has<Sleeping>,
+            Option<&Equipment>,
+            Has<Hidden>,
+            Option<&ShieldBuffer>,
+            Option<&Blessed>,
+            Option<&Maturing>,
+            Option<&Projectile>,
+            Option<&WeakPoints>,
+        )>()
+        .iter(world)
+        .map(
+            |(
+                position,
+                species,
+                momentum,
+                health,
+                spellbook,
+                effects,
+                asleep,
+                equipment,
+                hidden,
+                shield,
+                blessing,
+                maturing,
+                projectile,
+                weak_points,
+            )| {
+                SavedCreature {
+                    position: *position,
+                    species: *species,
+                    momentum: *momentum,
+                    hp: health.hp,
+                    max_hp: health.max_hp,
+                    spellbook: spellbook
+                        .spells
+                        .iter()
+                        .map(|(soul, spell)| (*soul, spell.clone()))
+                        .collect(),
+                    effects: effects
+                        .effects
+                        .iter()
+                        .filter(|(_, effect)| effect.is_active())
+                        .map(|(status, effect)| (*status, effect.potency, effect.stacks))
+                        .collect(),
+                    asleep,
+                    equipment: equipment.cloned(),
+                    hidden,
+                    shield: shield.copied(),
+                    blessing: blessing.copied(),
+                    maturing: maturing.copied(),
+                    projectile: projectile.map(|projectile| SavedProjectile {
+                        velocity: projectile.velocity,
+                        owner: positions.get(&projectile.owner).copied(),
+                        payload: projectile.payload.clone(),
+                        caste: projectile.caste,
+                    }),
+                    weak_points: weak_points.map_or(Vec::new(), |weak_points| {
+                        weak_points
+                            .points
+                            .iter()
+                            .map(|(offset, point)| (*offset, point.hp))
+                            .collect()
+                    }),
+                }
+            },
+        )
+        .collect();
+    let wheel = world.resource::<SoulWheel>();
+    let faiths_end = world.resource::<FaithsEnd>();
+    let turn_count = world.resource::<TurnManager>().turn_count;
+    SaveFile {
+        metadata: SaveMetadata {
+            floor: faiths_end.current_cage,
+            turns: turn_count,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+            // Headless worlds have no save menu, and no RunMode.
+            run_mode: world.get_resource::<RunMode>().copied().unwrap_or_default(),
+            thumbnail,
+        },
+        turn_count,
+        wheel: wheel.souls,
+        wheel_locks: wheel.locks,
+        regions: world.resource::<Regions>().regions.clone(),
+        draw_pile: wheel.draw_pile.iter().map(|(s, n)| (*s, *n)).collect(),
+        discard_pile: wheel.discard_pile.iter().map(|(s, n)| (*s, *n)).collect(),
+        current_cage: faiths_end.current_cage,
+        cage_address_position: faiths_end
+            .cage_address_position
+            .iter()
+            .map(|(p, i)| (*p, *i))
+            .collect(),
+        cage_dimensions: faiths_end
+            .cage_dimensions
+            .iter()
+            .map(|(i, d)| (*i, *d))
+            .collect(),
+        creatures,
+        circuits: world.resource::<Circuits>().circuits.clone(),
+        key_items: world.resource::<KeyItems>().items.clone(),
+        grinder: world.resource::<Grinder>().clone(),
+        director: world.resource::<SpawnDirector>().clone(),
+        weaving: world.resource::<Weaving>().clone(),
+        suggestions: world.resource::<SpellSuggestions>().pending.clone(),
+        loose_axioms: world.resource::<LooseAxioms>().axioms.clone(),
+        scrolls: world.resource::<HeldScrolls>().pending.clone(),
+        terrain: world
+            .resource::<Map>()
+            .terrain
+            .iter()
+            .map(|(p, k)| (*p, *k))
+            .collect(),
+        corpses: world.query::<&Corpse>().iter(world).cloned().collect(),
+        difficulty: *world.resource::<GameDifficulty>(),
+        stats: world.resource::<RunStats>().clone(),
+        warp_charges: world.resource::<WarpCharges>().clone(),
+        content_packs: world.resource::<ActiveContentPacks>().names.clone(),
+        aim_mode: *world.resource::<AimMode>(),
+        rng: Some(world.resource::<GameRng>().state()),
+        // Headless worlds do not record their actions.
+        actions: world
+            .get_resource::<ActionLog>()
+            .map_or(Vec::new(), |log| log.actions.clone()),
+        world_hash: hash,
+    }
+}
+
+fn save_game(world: &mut World) {
+    let mut slots: Vec<usize> = world
+        .resource_mut::<Events<SaveGame>>()
+        .drain()
+        .map(|event| event.slot)
+        .collect();
+    if !world.resource::<Events<AppExit>>().is_empty() {
+        slots.push(AUTOSAVE_SLOT);
+    }
+    let save = write_save(world);
+    let contents = match ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default()) {
+        Ok(contents) => contents,
+        Err(error) => {
+            warn!("Could not serialize the save file: {}", error);
+            return;
+        }
+    };
+    for slot in slots {
+        if let Err(error) = fs::write(slot_path(slot), &contents) {
+            warn!("Could not write the save file: {}", error);
+        }
+    }
+    world.resource_mut::<SaveMenu>().refresh = true;
+}
+
+fn load_game(world: &mut World) {
+    let Some(slot) = world
+        .resource_mut::<Events<LoadGame>>()
+        .drain()
+        .last()
+        .map(|event| event.slot)
+    else {
+        return;
+    };
+    match read_save(slot) {
+        Ok(save) => restore_save(world, save),
+        Err(error) => warn!("Could not load the save file: {}", error),
+    }
+}
+
+/// Replace the run of this world with the saved one. The creatures are only
+/// summoned in the next frame, after which finish_restore must run.
+pub fn restore_save(world: &mut World, save: SaveFile) {
+    // Clear out the current run, including whatever was still resolving.
+    world.resource_mut::<SpellStack>().spells.clear();
+    world.resource_mut::<FloorLoading>().cancel();
+    let creatures: Vec<(Entity, CreatureFlags)> = world
+        .query::<(Entity, &CreatureFlags)>()
+        .iter(world)
+        .map(|(entity, flags)| (entity, flags.clone()))
+        .collect();
+    for (entity, flags) in creatures {
+        for entity in [entity, flags.species_flags, flags.effects_flags] {
+            world.entity_mut(entity).despawn_recursive();
+        }
+    }
+    let mut map = world.resource_mut::<Map>();
+    map.creatures.clear();
+    map.intangible_creatures.clear();
+    let corpses: Vec<Entity> = world
+        .query_filtered::<Entity, With<Corpse>>()
+        .iter(world)
+        .collect();
+    for corpse in corpses {
+        world.entity_mut(corpse).despawn();
+    }
+    let mut map = world.resource_mut::<Map>();
+    map.corpses.clear();
+    map.cover.clear();
+    for corpse in &save.corpses {
+        world.send_event(SpawnCorpse {
+            corpse: corpse.clone(),
+        });
+    }
+    world.resource_mut::<Map>().terrain = HashMap::from_iter(save.terrain.iter().copied());
+    world.resource_mut::<Regions>().regions = save.regions.clone();
+
+    world.insert_resource(save.metadata.run_mode);
+    world.resource_mut::<TurnManager>().turn_count = save.turn_count;
+    let mut wheel = world.resource_mut::<SoulWheel>();
+    wheel.souls = save.wheel;
+    wheel.locks = save.wheel_locks;
+    wheel.draw_pile = HashMap::from_iter(save.draw_pile);
+    wheel.discard_pile = HashMap::from_iter(save.discard_pile);
+    let mut faiths_end = world.resource_mut::<FaithsEnd>();
+    faiths_end.current_cage = save.current_cage;
+    faiths_end.cage_address_position = HashMap::from_iter(save.cage_address_position);
+    faiths_end.cage_dimensions = HashMap::from_iter(save.cage_dimensions);
+    world.resource_mut::<Circuits>().circuits = save.circuits.clone();
+    world.resource_mut::<KeyItems>().items = save.key_items.clone();
+    *world.resource_mut::<Grinder>() = save.grinder.clone();
+    *world.resource_mut::<SpawnDirector>() = save.director.clone();
+    *world.resource_mut::<Weaving>() = save.weaving.clone();
+    world.resource_mut::<SpellSuggestions>().pending = save.suggestions.clone();
+    world.resource_mut::<LooseAxioms>().axioms = save.loose_axioms.clone();
+    world.resource_mut::<HeldScrolls>().pending = save.scrolls.clone();
+    // Health was saved with the difficulty already applied, it is not rescaled.
+    *world.resource_mut::<GameDifficulty>() = save.difficulty;
+    *world.resource_mut::<RunStats>() = save.stats.clone();
+    *world.resource_mut::<WarpCharges>() = save.warp_charges.clone();
+    *world.resource_mut::<AimMode>() = save.aim_mode;
+    // Before any creature is summoned, so they get the definitions they were saved with.
+    apply_content_packs(world, &save.content_packs);
+    if let Some(state) = save.rng {
+        world.insert_resource(GameRng::from_state(state));
+    }
+    // Whatever was done since the save was written never happened.
+    if let Some(mut log) = world.get_resource_mut::<ActionLog>() {
+        log.actions = save.actions;
+    }
+
+    // Bring back every creature. Their health and effects are restored
+    // by finish_restore, once they exist.
+    for creature in &save.creatures {
+        world.send_event(SummonCreature {
+            species: creature.species,
+            position: creature.position,
+            momentum: creature.momentum,
+            summoner_tile: creature.position,
+            summoner: None,
+            spellbook: Some(Spellbook {
+                spells: HashMap::from_iter(creature.spellbook.iter().cloned()),
+            }),
+            // Projectiles get their payload back in finish_restore, once their
+            // owner exists.
+            projectile: None,
+            owner: None,
+        });
+    }
+    world.insert_resource(PendingRestore {
+        creatures: save.creatures,
+        world_hash: save.world_hash,
+    });
+}
+
+/// Give the creatures summoned by restore_save back what they had, then check
+/// the world hash against the saved one.
+// NOTE: Summoners are not saved, so DimensionBond and summoned creatures
+// will not remember who summoned them after a reload.
+pub fn finish_restore(world: &mut World) {
+    let pending = world.remove_resource::<PendingRestore>().unwrap();
+    let mut by_position: HashMap<Position, SavedCreature> = pending
+        .creatures
+        .into_iter()
+        .map(|creature| (creature.position, creature))
+        .collect();
+    let mut restored = Vec::new();
+    let entities: HashMap<Position, Entity> = world
+        .query_filtered::<(Entity, &Position), Without<Projectile>>()
+        .iter(world)
+        .map(|(entity, position)| (*position, entity))
+        .collect();
+    let mut query = world.query::<(Entity, &Position, &Species, &mut Health)>();
+    for (entity, position, species, mut health) in query.iter_mut(world) {
+        let Some(saved) = by_position.remove(position) else {
+            continue;
+        };
+        if saved.species != *species {
+            continue;
+        }
+        health.hp = saved.hp;
+        health.max_hp = saved.max_hp;
+        restored.push((entity, saved));
+    }
+    for (entity, saved) in restored {
+        world.send_event(HealthChanged { entity });
+        for (effect, potency, stacks) in saved.effects {
+            world.send_event(AddStatusEffect {
+                entity,
+                effect,
+                potency,
+                stacks,
+                culprit: entity,
+            });
+        }
+        // NOTE: Patrols are not saved, guards come back hunting.
+        if !saved.asleep {
+            world
+                .entity_mut(entity)
+                .remove::<Sleeping>()
+                .insert((Awake, AiState::Hunting));
+        }
+        // The saved max HP already includes what the items grant.
+        if let Some(equipment) = saved.equipment {
+            world.entity_mut(entity).insert(equipment);
+        }
+        if let Some(shield) = saved.shield {
+            world.entity_mut(entity).insert(shield);
+        }
+        if let Some(blessing) = saved.blessing {
+            world.entity_mut(entity).insert(blessing);
+        }
+        if let Some(maturing) = saved.maturing {
+            world.entity_mut(entity).insert(maturing);
+        }
+        // The saved spellbook already lacks what broken weak points took away,
+        // but the death effect came back with the species.
+        if let Some(mut weak_points) = world.get_mut::<WeakPoints>(entity) {
+            let mut lost = Vec::new();
+            for (offset, hp) in &saved.weak_points {
+                if let Some(point) = weak_points.points.get_mut(offset) {
+                    point.hp = *hp;
+                    if point.is_destroyed() {
+                        lost.extend(point.disables);
+                    }
+                }
+            }
+            if world
+                .get::<DeathEffect>(entity)
+                .is_some_and(|effect| lost.contains(&effect.soul))
+            {
+                world.entity_mut(entity).remove::<DeathEffect>();
+            }
+        }
+        // NOTE: If the owner was not saved, the projectile crumbles when it hits.
+        if let Some(projectile) = saved.projectile {
+            world.entity_mut(entity).insert(Projectile {
+                velocity: projectile.velocity,
+                owner: projectile
+                    .owner
+                    .and_then(|owner| entities.get(&owner).copied())
+                    .unwrap_or(entity),
+                payload: projectile.payload,
+                caste: projectile.caste,
+            });
+        }
+        // Traps come back hidden, as nobody remembers who laid them.
+        // The ones which were not are treated as found.
+        if !saved.hidden && world.entity(entity).contains::<Hidden>() {
+            let effects_flags = world.get::<CreatureFlags>(entity).unwrap().effects_flags;
+            world.entity_mut(entity).remove::<Hidden>().insert(Revealed);
+            world.entity_mut(effects_flags).insert(Interactable);
+        }
+    }
+    if !by_position.is_empty() {
+        warn!(
+            "{} creatures could not be restored from the save file.",
+            by_position.len()
+        );
+    }
+    if world_hash(world) != pending.world_hash {
+        warn!("The save file is corrupted: the restored world does not match its hash.");
+    }
+}
+
+fn delete_save(mut events: EventReader<DeleteSave>, mut menu: ResMut<SaveMenu>) {
+    for event in events.read() {
+        let _ = fs::remove_file(slot_path(event.slot));
+        menu.refresh = true;
+    }
+}
+
+fn copy_save(mut events: EventReader<CopySave>, mut menu: ResMut<SaveMenu>) {
+    for event in events.read() {
+        if let Err(error) = fs::copy(slot_path(event.from), slot_path(event.to)) {
+            warn!("Could not copy the save file: {}", error);
+        }
+        menu.refresh = true;
+    }
+}
+
+/// The run is over, no going back to its autosave.
+/// Permadeath runs lose their manual saves as well.
+fn delete_save_on_death(mut events: EventReader<RespawnPlayer>, run_mode: Res<RunMode>) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let _ = fs::remove_file(slot_path(AUTOSAVE_SLOT));
+    if *run_mode == RunMode::Permadeath {
+        for slot in 1..=RunMode::Standard.manual_slots() {
+            let _ = fs::remove_file(slot_path(slot));
+        }
+    }
+}
+
+/// The save management screen, opened with F6.
+#[derive(Resource, Default)]
+pub struct SaveMenu {
+    pub selected: usize,
+    /// The metadata of each slot, or None if it is empty.
+    slots: Vec<Option<SaveMetadata>>,
+    /// Set when the slots changed on disk, and must be read again.
+    refresh: bool,
+    /// Enter was pressed once on a filled slot, and must be pressed again to load it.
+    confirming_load: bool,
+}
+
+#[derive(Component)]
+struct SaveMenuPanel;
+
+fn open_save_menu(mut menu: ResMut<SaveMenu>, mut commands: Commands) {
+    menu.selected = 0;
+    menu.refresh = true;
+    menu.confirming_load = false;
+    commands.spawn((
+        SaveMenuPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(25.),
+            top: Val::Percent(10.),
+            width: Val::Percent(50.),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(0.5)),
+            row_gap: Val::Px(0.5),
+            ..default()
+        },
+        BackgroundColor(Color::srgb(0., 0., 0.)),
+    ));
+}
+
+fn close_save_menu(panel: Query<Entity, With<SaveMenuPanel>>, mut commands: Commands) {
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+/// Up and down or Tab pick a slot. V saves, L loads, Enter does either depending on
+/// whether the slot is empty, asking again before loading over the current run.
+/// Delete deletes, K copies to the next empty slot.
+/// C copies the seed of the run to the message log. F6 or Escape closes the menu.
+fn save_menu_input(
+    input: Res<ButtonInput<KeyCode>>,
+    mut menu: ResMut<SaveMenu>,
+    rng: Res<GameRng>,
+    mut text: EventWriter<AddMessage>,
+    run_mode: Res<RunMode>,
+    mut save: EventWriter<SaveGame>,
+    mut load: EventWriter<LoadGame>,
+    mut delete: EventWriter<DeleteSave>,
+    mut copy: EventWriter<CopySave>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    let slot_count = run_mode.manual_slots() + 1;
+    let step = focus_step(&input);
+    if step != 0 {
+        menu.selected = cycle_focus(menu.selected, step, slot_count);
+    }
+    let slot = menu.selected;
+    let is_filled = menu.slots.get(slot).is_some_and(|slot| slot.is_some());
+    let confirm = focus_confirm(&input);
+    // The autosave slot is only written to by quicksaves and by closing the game.
+    if (input.just_pressed(KeyCode::KeyV) || (confirm && !is_filled)) && slot != AUTOSAVE_SLOT {
+        save.send(SaveGame { slot });
+    }
+    if confirm && is_filled && !menu.confirming_load {
+        menu.confirming_load = true;
+    } else if (input.just_pressed(KeyCode::KeyL) || confirm) && is_filled {
+        load.send(LoadGame { slot });
+        next_state.set(ControlState::Player);
+    } else if menu.confirming_load && input.get_just_pressed().next().is_some() {
+        menu.confirming_load = false;
+    }
+    if input.just_pressed(KeyCode::Delete) && is_filled {
+        delete.send(DeleteSave { slot });
+    }
+    if input.just_pressed(KeyCode::KeyK) && is_filled {
+        let empty = (1..slot_count).find(|slot| menu.slots.get(*slot).is_some_and(Option::is_none));
+        if let Some(to) = empty {
+            copy.send(CopySave { from: slot, to });
+        }
+    }
+    if input.just_pressed(KeyCode::KeyC) {
+        share_seed(&rng, &mut text);
+    }
+    if input.just_pressed(KeyCode::F6) || input.just_pressed(KeyCode::Escape) {
+        next_state.set(ControlState::Player);
+    }
+}
+
+fn describe_age(timestamp: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let minutes = now.saturating_sub(timestamp) / 60;
+    match minutes {
+        0 => "just now".to_owned(),
+        1..60 => format!("{} minutes ago", minutes),
+        60..1440 => format!("{} hours ago", minutes / 60),
+        _ => format!("{} days ago", minutes / 1440),
+    }
+}
+
+/// Read the slots from disk when they changed, and redraw the menu.
+fn update_save_menu(
+    mut menu: ResMut<SaveMenu>,
+    run_mode: Res<RunMode>,
+    rng: Res<GameRng>,
+    panel: Query<Entity, With<SaveMenuPanel>>,
+    asset_server: Res<AssetServer>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    mut commands: Commands,
+) {
+    if menu.refresh {
+        menu.refresh = false;
+        menu.slots = (0..=run_mode.manual_slots())
+            .map(|slot| read_save(slot).ok().map(|save| save.metadata))
+            .collect();
+    }
+    if !menu.is_changed() {
+        return;
+    }
+    let Ok(panel) = panel.get_single() else {
+        return;
+    };
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.,
+        ..default()
+    };
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|parent| {
+        parent
+            .spawn((Text::default(), font.clone()))
+            .with_children(|line| {
+                let seed = format!(
+                    "Seed: [y]{}[w] [d](C: show in the message log)[w]",
+                    rng.seed()
+                );
+                for (section, color) in split_text(&seed) {
+                    line.spawn((TextSpan::new(section), font.clone(), color));
+                }
+            });
+        for (slot, metadata) in menu.slots.iter().enumerate() {
+            let name = if slot == AUTOSAVE_SLOT {
+                "Autosave".to_owned()
+            } else {
+                format!("Slot {}", slot)
+            };
+            let description = match metadata {
+                Some(metadata) => format!(
+                    "{} - Cage {}, turn {}, {}{}",
+                    name,
+                    metadata.floor,
+                    metadata.turns,
+                    describe_age(metadata.timestamp),
+                    if metadata.run_mode == RunMode::Permadeath {
+                        " (Permadeath)"
+                    } else {
+                        ""
+                    }
+                ),
+                None => format!("{} - Empty", name),
+            };
+            let mut row = parent.spawn((
+                Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(0.5),
+                    height: Val::Px(5.6),
+                    ..default()
+                },
+                BackgroundColor(if slot == menu.selected {
+                    Color::srgb(0.25, 0.25, 0.25)
+                } else {
+                    Color::srgb(0., 0., 0.)
+                }),
+            ));
+            row.with_children(|row| {
+                let thumbnail = metadata
+                    .as_ref()
+                    .map_or(&[][..], |metadata| &metadata.thumbnail[..]);
+                row.spawn(Node {
+                    display: Display::Grid,
+                    width: Val::Px(5.6),
+                    height: Val::Px(5.6),
+                    grid_template_columns: RepeatedGridTrack::flex(
+                        THUMBNAIL_RADIUS as u16 * 2 + 1,
+                        1.,
+                    ),
+                    ..default()
+                })
+                .with_children(|grid| {
+                    for sprite in thumbnail.iter().flatten() {
+                        let mut cell = grid.spawn(Node::default());
+                        if let Some(sprite) = sprite {
+                            cell.insert(ImageNode {
+                                image: asset_server.load("spritesheet.png"),
+                                texture_atlas: Some(TextureAtlas {
+                                    layout: atlas_layout.handle.clone(),
+                                    index: *sprite,
+                                }),
+                                ..default()
+                            });
+                        }
+                    }
+                });
+                row.spawn((Text::new(description), font.clone()));
+            });
+            if slot == menu.selected {
+                row.insert(Focused);
+            }
+        }
+        let help = if menu.confirming_load {
+            "[y]Enter again to load over the current run.[w] Any other key: Cancel."
+        } else {
+            "W/S: Pick a slot. V: Save. L: Load. Enter: Save or load. \
+            Delete: Delete. K: Copy. Escape: Close."
+        };
+        parent
+            .spawn((Text::default(), font.clone()))
+            .with_children(|line| {
+                for (section, color) in split_text(help) {
+                    line.spawn((TextSpan::new(section), font.clone(), color));
+                }
+            });
+    });
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use crate::creature::Species;
+
+/// One line of an Axiom::Script. Offsets are in tiles, relative to the caster
+/// for `target` and `warp`, relative to each creature for `push`.
+///
+/// ```text
+/// target 1 0; target -1 0   // Add tiles to the targets.
+/// damage 2                  // Harm every targeted creature.
+/// heal 1                    // Heal every targeted creature.
+/// push 0 1                  // Move every targeted creature, if there is room.
+/// summon Shrike             // Summon a creature on every targeted tile.
+/// warp 0 -2                 // Move the caster.
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptCommand {
+    Target(i32, i32),
+    Damage(isize),
+    Heal(isize),
+    Push(i32, i32),
+    Summon(Species),
+    Warp(i32, i32),
+}
+
+/// Why a script could not be read, and on which of its commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    pub command: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Script command {}: {}", self.command + 1, self.message)
+    }
+}
+
+/// Read a script, where commands are separated by newlines or semicolons
+/// and anything after `//` is ignored.
+pub fn parse_script(source: &str) -> Result<Vec<ScriptCommand>, ScriptError> {
+    source
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default())
+        .flat_map(|line| line.split(';'))
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .enumerate()
+        .map(|(i, command)| {
+            parse_command(command).map_err(|message| ScriptError {
+                command: i,
+                message,
+            })
+        })
+        .collect()
+}
+
+fn parse_command(command: &str) -> Result<ScriptCommand, String> {
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let arguments = &words[1..];
+    let expect = |count: usize| {
+        if arguments.len() == count {
+            Ok(())
+        } else {
+            Err(format!(
+                "\"{}\" takes {} argument(s), not {}",
+                words[0],
+                count,
+                arguments.len()
+            ))
+        }
+    };
+    let number = |word: &str| {
+        word.parse::<i32>()
+            .map_err(|_| format!("\"{}\" is not a number", word))
+    };
+    match words[0] {
+        "target" | "push" | "warp" => {
+            expect(2)?;
+            let (x, y) = (number(arguments[0])?, number(arguments[1])?);
+            Ok(match words[0] {
+                "target" => ScriptCommand::Target(x, y),
+                "push" => ScriptCommand::Push(x, y),
+                _ => ScriptCommand::Warp(x, y),
+            })
+        }
+        "damage" | "heal" => {
+            expect(1)?;
+            let amount = number(arguments[0])?.unsigned_abs() as isize;
+            Ok(if words[0] == "damage" {
+                ScriptCommand::Damage(amount)
+            } else {
+                ScriptCommand::Heal(amount)
+            })
+        }
+        "summon" => {
+            expect(1)?;
+            ron::from_str::<Species>(arguments[0])
+                .map(ScriptCommand::Summon)
+                .map_err(|_| format!("\"{}\" is not a species", arguments[0]))
+        }
+        unknown => Err(format!("\"{}\" is not a command", unknown)),
+    }
+}
@@ -0,0 +1,316 @@
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    caste::{match_soul_with_string, score_spell, SpellSuggestions},
+    crafting::EDITABLE_CASTES,
+    creature::{CreatureFlags, Health, Player, ScrollPickup, Soul, Species, Spellbook},
+    events::{EndTurn, RemoveCreature, RespawnPlayer, SoulWheel, SummonCreature},
+    map::{Map, Position},
+    rng::GameRng,
+    sets::{ControlState, PlayerInput},
+    species::SpeciesRegistry,
+    spells::{spell_stack_is_empty, Spell},
+    text::split_text,
+    ui::{AddMessage, Message},
+    OrdDir,
+};
+
+pub struct ScrollPlugin;
+
+impl Plugin for ScrollPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HeldScrolls>();
+        app.add_event::<ReadScroll>();
+        app.add_systems(OnEnter(ControlState::ScrollPrompt), spawn_scroll_prompt);
+        app.add_systems(OnExit(ControlState::ScrollPrompt), despawn_scroll_prompt);
+        app.add_systems(
+            Update,
+            open_scroll_prompt
+                .run_if(in_state(ControlState::Player).and(spell_stack_is_empty))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            (
+                scroll_prompt_input.before(read_scroll),
+                update_scroll_prompt.run_if(resource_changed::<HeldScrolls>),
+            )
+                .chain()
+                .run_if(in_state(ControlState::ScrollPrompt))
+                .in_set(PlayerInput),
+        );
+    }
+}
+
+/// How prized a spell scroll is, from how strong its spell is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrollRarity {
+    Common,
+    Uncommon,
+    Rare,
+}
+
+impl ScrollRarity {
+    pub fn of(spell: &Spell) -> Self {
+        match score_spell(spell).total() {
+            ..=2 => ScrollRarity::Common,
+            3..=5 => ScrollRarity::Uncommon,
+            _ => ScrollRarity::Rare,
+        }
+    }
+
+    /// How likely a spell of this rarity is to be the one written down,
+    /// out of all those the dying creature knew.
+    fn weight(&self) -> u32 {
+        match self {
+            ScrollRarity::Common => 6,
+            ScrollRarity::Uncommon => 3,
+            ScrollRarity::Rare => 1,
+        }
+    }
+
+    /// How many souls the scroll unravels into.
+    pub fn souls(&self) -> usize {
+        match self {
+            ScrollRarity::Common => 2,
+            ScrollRarity::Uncommon => 3,
+            ScrollRarity::Rare => 5,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScrollRarity::Common => "[w]common[w]",
+            ScrollRarity::Uncommon => "[c]uncommon[w]",
+            ScrollRarity::Rare => "[m]rare[w]",
+        }
+    }
+
+    /// The glow of scrolls lying on the ground, see apply_fog_of_war.
+    pub fn glow(&self) -> Color {
+        match self {
+            ScrollRarity::Common => Color::srgb(1., 0.95, 0.8),
+            ScrollRarity::Uncommon => Color::srgb(0.5, 0.9, 1.),
+            ScrollRarity::Rare => Color::srgb(1., 0.5, 1.),
+        }
+    }
+}
+
+/// Scrolls the player picked up, and has not yet learned or unravelled, oldest first.
+#[derive(Resource, Default)]
+pub struct HeldScrolls {
+    pub pending: Vec<(Soul, Spell)>,
+}
+
+/// Decide what to do with the oldest held scroll: learn its spell, offered like
+/// any other SpellSuggestion, or unravel it into souls. This takes no time.
+#[derive(Event)]
+pub struct ReadScroll {
+    pub learn: bool,
+}
+
+/// Slain creatures of some species leave one of their spells behind, written
+/// on a scroll. Weaker spells are the likeliest to be written down.
+pub fn drop_scrolls(
+    mut events: EventReader<RemoveCreature>,
+    creatures: Query<(&Position, &Species, &Spellbook, &Health), Without<Player>>,
+    registry: Res<SpeciesRegistry>,
+    mut rng: ResMut<GameRng>,
+    mut summon: EventWriter<SummonCreature>,
+) {
+    for event in events.read() {
+        let Ok((position, species, spellbook, health)) = creatures.get(event.entity) else {
+            continue;
+        };
+        // NOTE: Creatures cleared away at the end of a run are not slain.
+        let chance = registry.get(species).drops_scroll;
+        if health.hp > 0 || chance == 0 || rng.gen_range(0..100) >= chance {
+            continue;
+        }
+        let spells: Vec<_> = EDITABLE_CASTES
+            .iter()
+            .filter_map(|caste| spellbook.spells.get(caste).map(|spell| (*caste, spell)))
+            .collect();
+        let Ok((caste, spell)) =
+            spells.choose_weighted(&mut *rng, |(_, spell)| ScrollRarity::of(spell).weight())
+        else {
+            continue;
+        };
+        let scroll = Spellbook {
+            spells: [(*caste, (*spell).clone())].into_iter().collect(),
+        };
+        summon.send(SummonCreature {
+            position: *position,
+            species: Species::Scroll,
+            momentum: OrdDir::Down,
+            summoner_tile: *position,
+            summoner: None,
+            spellbook: Some(scroll),
+            projectile: None,
+            owner: None,
+        });
+    }
+}
+
+/// Once per turn, pick up the scrolls the player is standing on.
+/// Those not yet read are lost on death.
+// NOTE: Scrolls are intangible, and only found in Map::intangible_creatures.
+pub fn pick_up_scrolls(
+    mut events: EventReader<EndTurn>,
+    mut respawn: EventReader<RespawnPlayer>,
+    player: Query<&Position, With<Player>>,
+    creatures: Query<(&Spellbook, &CreatureFlags)>,
+    pickups: Query<(), With<ScrollPickup>>,
+    map: Res<Map>,
+    mut held: ResMut<HeldScrolls>,
+    mut remove: EventWriter<RemoveCreature>,
+) {
+    if respawn.read().count() > 0 {
+        held.pending.clear();
+    }
+    if events.read().count() == 0 {
+        return;
+    }
+    let Ok(player_position) = player.get_single() else {
+        return;
+    };
+    for entity in map.intangible_at(player_position) {
+        let Ok((spellbook, flags)) = creatures.get(*entity) else {
+            continue;
+        };
+        if !pickups.contains(flags.species_flags) {
+            continue;
+        }
+        held.pending
+            .extend(EDITABLE_CASTES.iter().filter_map(|caste| {
+                spellbook
+                    .spells
+                    .get(caste)
+                    .map(|spell| (*caste, spell.clone()))
+            }));
+        remove.send(RemoveCreature {
+            entity: *entity,
+            culprit: None,
+        });
+    }
+}
+
+pub fn read_scroll(
+    mut events: EventReader<ReadScroll>,
+    mut held: ResMut<HeldScrolls>,
+    mut suggestions: ResMut<SpellSuggestions>,
+    mut soul_wheel: ResMut<SoulWheel>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        if held.pending.is_empty() {
+            continue;
+        }
+        let (caste, spell) = held.pending.remove(0);
+        let rarity = ScrollRarity::of(&spell);
+        if event.learn {
+            suggestions.pending.push((caste, spell));
+            text.send(AddMessage {
+                message: Message::ScrollLearned(caste),
+            });
+        } else {
+            *soul_wheel.draw_pile.entry(caste).or_insert(0) += rarity.souls();
+            text.send(AddMessage {
+                message: Message::ScrollUnravelled(caste, rarity.souls()),
+            });
+        }
+    }
+}
+
+/// The prompt listing the oldest held scroll's spell.
+#[derive(Component)]
+pub struct ScrollPromptPanel;
+
+/// Picking up a scroll asks what to do with it, once the turn is over.
+fn open_scroll_prompt(held: Res<HeldScrolls>, mut next_state: ResMut<NextState<ControlState>>) {
+    if !held.pending.is_empty() {
+        next_state.set(ControlState::ScrollPrompt);
+    }
+}
+
+fn spawn_scroll_prompt(mut commands: Commands, mut held: ResMut<HeldScrolls>) {
+    commands.spawn((
+        ScrollPromptPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(20.),
+            top: Val::Percent(15.),
+            padding: UiRect::all(Val::Px(1.)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.9)),
+    ));
+    // Force the first draw.
+    held.set_changed();
+}
+
+fn despawn_scroll_prompt(mut commands: Commands, panel: Query<Entity, With<ScrollPromptPanel>>) {
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+}
+
+/// Y learns the scroll, N unravels it. The prompt stays open while scrolls are held.
+fn scroll_prompt_input(
+    input: Res<ButtonInput<KeyCode>>,
+    held: Res<HeldScrolls>,
+    mut read: EventWriter<ReadScroll>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    let learn = if input.just_pressed(KeyCode::KeyY) {
+        true
+    } else if input.just_pressed(KeyCode::KeyN) {
+        false
+    } else {
+        return;
+    };
+    read.send(ReadScroll { learn });
+    if held.pending.len() <= 1 {
+        next_state.set(ControlState::Player);
+    }
+}
+
+fn update_scroll_prompt(
+    held: Res<HeldScrolls>,
+    panel: Query<Entity, With<ScrollPromptPanel>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let (Ok(panel), Some((caste, spell))) = (panel.get_single(), held.pending.first()) else {
+        return;
+    };
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    let rarity = ScrollRarity::of(spell);
+    let lines = std::iter::once(format!(
+        "A {} scroll, for your {}:",
+        rarity.name(),
+        match_soul_with_string(caste)
+    ))
+    .chain(spell.axioms.iter().map(|axiom| format!("  {:?}", axiom)))
+    .chain(std::iter::once(format!(
+        "[y]Y: learn it, N: unravel it into {} souls.[w]",
+        rarity.souls()
+    )));
+    commands.entity(panel).despawn_descendants();
+    commands.entity(panel).with_children(|parent| {
+        for line in lines {
+            let mut line_entity = parent.spawn((Text::default(), font.clone()));
+            line_entity.with_children(|line_parent| {
+                for (section, color) in split_text(&line) {
+                    line_parent.spawn((TextSpan::new(section), font.clone(), color));
+                }
+            });
+        }
+    });
+}
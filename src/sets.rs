@@ -1,26 +1,66 @@
 use bevy::prelude::*;
 
 use crate::{
-    caste::{hide_caste_menu, show_caste_menu, update_caste_box},
-    crafting::CraftingRecipes,
-    cursor::{cursor_step, despawn_cursor, spawn_cursor, teleport_cursor, update_cursor_box},
+    ai::{assign_patrols, propagate_noise},
+    caste::{
+        caste_menu_input, equip_suggested_spell, hide_caste_menu, show_caste_menu,
+        suggestion_input, update_caste_box, update_suggestion_panel,
+    },
+    chest::open_chest,
+    circuit::evaluate_circuits,
+    conveyor::{convey_creatures, resolve_momentum},
+    corpse::{drag_corpses, grab_corpse, leave_corpses, spawn_corpses},
+    crafting::{continue_weaving, edit_spell, weave_soul},
+    cursor::{
+        cursor_step, despawn_cursor, hover_cursor, spawn_cursor, teleport_cursor, update_cursor_box,
+    },
+    difficulty::set_difficulty,
+    director::{direct_spawns, set_director_intensity},
+    equipment::{apply_equipment, pick_up_items},
     events::{
-        add_status_effects, alter_momentum, assign_species_components, creature_collision,
-        creature_step, distribute_npc_actions, draw_soul, echo_speed, end_turn, harm_creature,
-        magnet_follow, magnetize_tail_segments, open_close_door, remove_creature,
+        add_status_effects, advance_projectiles, alter_momentum, assign_species_components,
+        creature_collision, creature_step, crumble_ephemeral_creatures, deflect_attack,
+        distribute_npc_actions, draw_soul, echo_speed, end_turn, harm_creature, magnet_follow,
+        magnetize_tail_segments, open_close_door, refund_fizzled_spells, remove_creature,
         remove_designated_creatures, render_closing_doors, respawn_cage, respawn_player,
-        stepped_on_tile, summon_creature, teleport_entity, transform_creature, use_wheel_soul,
+        stepped_on_tile, summon_creature, teleport_entity, transform_creature, turn_facing,
+        use_wheel_soul, PassTurn, RespawnPlayer,
+    },
+    evolution::mature_creatures,
+    graphics::{
+        adjust_transforms, apply_fog_of_war, choreograph_casts, decay_magic_effects, fade_decals,
+        place_decals, place_facing_indicator, place_magic_effects, play_animation_queue,
+        render_body_parts, render_elevation, render_targeting_cursor, render_weak_points,
     },
-    graphics::{adjust_transforms, decay_magic_effects, place_magic_effects},
-    input::keyboard_input,
-    map::register_creatures,
+    grinder::advance_grinder,
+    ground::tread_terrain,
+    input::{begin_targeting, debug_input, face_cursor, keyboard_input, targeting_input},
+    integrity::hash_final_state,
+    interact::interact,
+    key_items::pick_up_key_items,
+    map::{
+        announce_regions, finish_floor_generation, floor_is_loading, register_creatures,
+        stream_floor_spawns, track_regions,
+    },
+    objective::track_objective,
+    overlay::{update_creature_overlays, update_hp_bars},
+    push::push_chains,
+    rail::{derail_carts, interact_with_switches, roll_carts},
+    replay::record_player_actions,
+    scroll::{drop_scrolls, pick_up_scrolls, read_scroll},
+    shield::{decay_shields, pick_up_shields},
+    shrine::{decay_blessings, interact_with_shrines},
     spells::{
-        cast_new_spell, cleanup_synapses, process_axiom, spell_stack_is_empty, trigger_contingency,
+        cast_new_spell, check_for_fizzles, cleanup_synapses, enforce_line_of_effect, process_axiom,
+        reflect_spells, set_aim_mode, spell_stack_is_empty, trigger_contingency,
     },
+    stats::track_run_stats,
+    trap::{disarm_trap, interact_with_traps, reveal_traps},
     ui::{
         decay_fading_title, despawn_fading_title, dispense_sliding_components,
         print_message_in_log, slide_message_log, spawn_fading_title,
     },
+    warp::{panic_warp, restore_warp_charges},
 };
 
 pub struct SetsPlugin;
@@ -28,75 +68,91 @@ pub struct SetsPlugin;
 impl Plugin for SetsPlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<ControlState>();
+        app.add_event::<DumpSchedule>();
         app.add_systems(OnEnter(ControlState::Cursor), spawn_cursor);
         app.add_systems(OnExit(ControlState::Cursor), despawn_cursor);
         app.add_systems(OnEnter(ControlState::CasteMenu), show_caste_menu);
         app.add_systems(OnExit(ControlState::CasteMenu), hide_caste_menu);
-        app.add_systems(Update, magnetize_tail_segments.before(teleport_entity));
-        app.add_systems(Update, magnet_follow.after(teleport_entity));
-        app.init_resource::<CraftingRecipes>();
         app.add_systems(
             Update,
-            (cursor_step, teleport_cursor, update_cursor_box)
-                .run_if(in_state(ControlState::Cursor)),
+            (
+                cursor_step,
+                hover_cursor,
+                teleport_cursor,
+                update_cursor_box,
+            )
+                .chain()
+                .run_if(in_state(ControlState::Cursor))
+                .in_set(PlayerInput),
         );
         app.add_systems(
             Update,
-            update_caste_box.run_if(in_state(ControlState::CasteMenu)),
+            (caste_menu_input, update_caste_box)
+                .chain()
+                .run_if(in_state(ControlState::CasteMenu))
+                .in_set(PlayerInput),
         );
+        // Modal menus own the keyboard, nothing should leak through to the game.
         app.add_systems(
             Update,
-            ((
-                // When a creature loses a status effect,
-                // it might lose a component (such as Spellproof)
-                // which is innate to its species.
-                // This will ensure entities keep their species-specific
-                // components when a turn begins.
-                assign_species_components,
-                keyboard_input.run_if(spell_stack_is_empty),
-                creature_step,
-                use_wheel_soul,
-                process_axiom,
-                cleanup_synapses,
-                draw_soul,
-            )
-                .chain())
-            .in_set(ActionPhase),
+            debug_input.run_if(not(modal_is_open)).in_set(PlayerInput),
         );
         app.add_systems(
             Update,
-            ((
-                summon_creature,
-                transform_creature,
-                assign_species_components,
-                register_creatures,
-                add_status_effects,
-                teleport_entity,
-                stepped_on_tile,
-                creature_collision,
-                alter_momentum,
-                harm_creature,
-                open_close_door,
-                respawn_player,
-                remove_creature,
-                // Last chance to add spells to the spell stack before the end-of-turn check.
-                trigger_contingency,
-                cast_new_spell,
-                remove_designated_creatures.run_if(spell_stack_is_empty),
-                end_turn.run_if(spell_stack_is_empty),
-                distribute_npc_actions,
-                echo_speed,
-                respawn_cage.run_if(spell_stack_is_empty),
+            suggestion_input
+                .run_if(in_state(ControlState::Player))
+                .before(equip_suggested_spell)
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            (
+                begin_targeting.run_if(
+                    spell_stack_is_empty
+                        .and(not(floor_is_loading))
+                        .and(in_state(ControlState::Player)),
+                ),
+                continue_weaving.run_if(
+                    spell_stack_is_empty
+                        .and(not(floor_is_loading))
+                        .and(in_state(ControlState::Player)),
+                ),
+                // The player may only act once every spell has finished resolving,
+                // and the floor is done loading.
+                keyboard_input.run_if(
+                    spell_stack_is_empty
+                        .and(not(floor_is_loading))
+                        .and(not(modal_is_open))
+                        // The turn timer just passed the turn.
+                        .and(not(on_event::<PassTurn>)),
+                ),
+                targeting_input.run_if(spell_stack_is_empty.and(in_state(ControlState::Targeting))),
+                face_cursor.run_if(in_state(ControlState::Player)),
+                record_player_actions,
             )
-                .chain())
-            .in_set(ResolutionPhase),
+                .chain()
+                .before(creature_step)
+                .in_set(PlayerInput),
         );
         app.add_systems(
             Update,
             ((
                 render_closing_doors,
+                choreograph_casts,
                 place_magic_effects,
+                place_decals,
+                fade_decals,
+                play_animation_queue,
                 adjust_transforms,
+                place_facing_indicator,
+                render_targeting_cursor,
+                render_weak_points,
+                apply_fog_of_war,
+                (render_elevation, render_body_parts).chain(),
+                (
+                    (update_creature_overlays, update_hp_bars).chain(),
+                    update_suggestion_panel,
+                ),
                 decay_magic_effects,
                 spawn_fading_title,
                 decay_fading_title,
@@ -108,28 +164,328 @@ impl Plugin for SetsPlugin {
                 slide_message_log,
             )
                 .chain())
-            .in_set(AnimationPhase),
-        );
-        app.configure_sets(
-            Update,
-            (ActionPhase, AnimationPhase, ResolutionPhase).chain(),
+            .in_set(Animation),
         );
+        app.configure_sets(Update, Animation.after(NpcTurn));
+        // The schedule can only be inspected once it is done running,
+        // hence this lives in `Last` and not in `Update`.
+        app.add_systems(Last, dump_schedule_order.run_if(on_event::<DumpSchedule>));
     }
 }
 
+/// Register every system which drives the rules of the game, without any
+/// input, UI or animation. See TgfpCorePlugin.
+pub fn add_simulation_systems(app: &mut App) {
+    app.add_systems(
+        Update,
+        (finish_floor_generation, stream_floor_spawns)
+            .chain()
+            .in_set(PlayerInput),
+    );
+    app.add_systems(
+        Update,
+        ((
+            // When a creature loses a status effect,
+            // it might lose a component (such as Spellproof)
+            // which is innate to its species.
+            // This will ensure entities keep their species-specific
+            // components when a turn begins.
+            assign_species_components,
+            turn_facing,
+            creature_step,
+            use_wheel_soul,
+            draw_soul,
+            weave_soul,
+            equip_suggested_spell,
+            edit_spell,
+            read_scroll,
+            grab_corpse,
+            restore_warp_charges,
+            panic_warp,
+            set_difficulty,
+            set_director_intensity,
+            set_aim_mode,
+        )
+            .chain())
+        .in_set(PlayerInput),
+    );
+    app.add_systems(
+        Update,
+        ((
+            process_axiom,
+            cleanup_synapses,
+            summon_creature,
+            transform_creature,
+            assign_species_components,
+            register_creatures,
+            add_status_effects,
+            magnetize_tail_segments,
+            teleport_entity,
+            magnet_follow,
+            stepped_on_tile,
+            creature_collision,
+            alter_momentum,
+            harm_creature,
+            deflect_attack,
+            (
+                interact,
+                open_chest,
+                interact_with_traps,
+                disarm_trap,
+                interact_with_switches,
+                interact_with_shrines,
+                apply_equipment,
+                open_close_door,
+            )
+                .chain(),
+            (
+                hash_final_state.run_if(on_event::<RespawnPlayer>),
+                respawn_player,
+            )
+                .chain(),
+            remove_creature,
+            // Last chance to add spells to the spell stack before the end-of-turn check.
+            trigger_contingency,
+            cast_new_spell,
+        )
+            .chain())
+        .in_set(SpellResolution),
+    );
+    // NOTE: Like magnet_follow, this sends more teleports, resolved in the next step.
+    app.add_systems(
+        Update,
+        resolve_momentum
+            .after(teleport_entity)
+            .before(magnet_follow)
+            .in_set(SpellResolution),
+    );
+    // NOTE: Targets must be weeded out and reflected before the axiom strikes,
+    // and a creature behind a wall has nothing to reflect.
+    app.add_systems(
+        Update,
+        (enforce_line_of_effect, reflect_spells, check_for_fizzles)
+            .chain()
+            .before(process_axiom)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        assign_patrols
+            .after(register_creatures)
+            .before(add_status_effects)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        refund_fizzled_spells
+            .after(cleanup_synapses)
+            .before(summon_creature)
+            .in_set(SpellResolution),
+    );
+    // NOTE: Traps stepped on are still there to be found, until remove_creature.
+    app.add_systems(
+        Update,
+        reveal_traps
+            .after(stepped_on_tile)
+            .before(creature_collision)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        push_chains
+            .after(teleport_entity)
+            .before(creature_collision)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        drag_corpses
+            .after(stepped_on_tile)
+            .before(creature_collision)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        (leave_corpses, spawn_corpses)
+            .chain()
+            .after(remove_creature)
+            .before(trigger_contingency)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        (track_regions, announce_regions)
+            .chain()
+            .after(stepped_on_tile)
+            .before(creature_collision)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        tread_terrain
+            .after(stepped_on_tile)
+            .before(creature_collision)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        drop_scrolls
+            .after(remove_creature)
+            .before(trigger_contingency)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        track_run_stats
+            .after(remove_creature)
+            .before(trigger_contingency)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        propagate_noise
+            .after(harm_creature)
+            .before(respawn_player)
+            .in_set(SpellResolution),
+    );
+    app.add_systems(
+        Update,
+        (
+            remove_designated_creatures,
+            // This is necessary to come after the removal of dead creatures,
+            // as to ensure everything has despawned before spawning the next
+            // batch of creatures.
+            respawn_cage,
+        )
+            .chain()
+            .in_set(Cleanup),
+    );
+    app.add_systems(
+        Update,
+        (
+            end_turn.run_if(spell_stack_is_empty),
+            evaluate_circuits,
+            convey_creatures,
+            (roll_carts, derail_carts).chain(),
+            advance_projectiles,
+            pick_up_key_items,
+            pick_up_items,
+            pick_up_scrolls,
+            // NOTE: Before pickups, or fresh shields would lose a turn at once.
+            (decay_shields, pick_up_shields).chain(),
+            decay_blessings,
+            mature_creatures,
+            crumble_ephemeral_creatures,
+            advance_grinder,
+            direct_spawns,
+            distribute_npc_actions,
+            echo_speed,
+            track_objective,
+        )
+            .chain()
+            .in_set(NpcTurn),
+    );
+    app.configure_sets(
+        Update,
+        (
+            PlayerInput,
+            SpellResolution,
+            Cleanup.run_if(spell_stack_is_empty),
+            NpcTurn,
+        )
+            .chain(),
+    );
+}
+
+/// Player keyboard input, and any control state menus (cursor, caste menu)
+/// it may open. Steps, casts and draws are dispatched here.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlayerInput;
+
+/// Advance every spell on the spell stack by one axiom, then resolve all
+/// the events they (and the player's actions) sent out: summons, teleports,
+/// collisions, damage, removals, contingencies.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SpellResolution;
+
+/// Despawn dead creatures and rebuild the cage if the game was reset.
+/// Only runs once the spell stack is empty, to avoid crashes where the game
+/// tries to fetch a spellcaster's position that stopped existing.
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
-struct ActionPhase;
+pub struct Cleanup;
 
+/// End the player's turn, tick down status effects, and hand out actions
+/// to every NPC, echoing for Fast creatures.
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
-struct ResolutionPhase;
+pub struct NpcTurn;
 
+/// Purely visual systems: sliding sprites, magic effects, titles and the message log.
+/// Nothing in here should affect the game state.
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
-struct AnimationPhase;
+pub struct Animation;
 
 #[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ControlState {
     #[default]
     Player,
+    /// Examining tiles, with the keys or the mouse. See update_cursor_box.
     Cursor,
     CasteMenu,
+    /// Scrubbing through the last turns of a run, after dying.
+    Review,
+    SaveMenu,
+    /// Picking a tile for a spell with Axiom::CursorTarget.
+    Targeting,
+    /// Browsing the recipe book, see CraftingRecipes.
+    RecipeBook,
+    /// Rearranging the axioms of the player's spells.
+    SpellEditor,
+    /// Picking a GameDifficulty, when the game starts.
+    DifficultyMenu,
+    /// Looking back on a run which just ended, see RunStats.
+    RunSummary,
+    /// Deciding what to do with a scroll which was picked up, see HeldScrolls.
+    ScrollPrompt,
+}
+
+impl ControlState {
+    /// Whether this state has its own input system, which keyboard_input must not
+    /// be run alongside.
+    pub fn is_modal(&self) -> bool {
+        matches!(
+            self,
+            ControlState::CasteMenu
+                | ControlState::Review
+                | ControlState::SaveMenu
+                | ControlState::Targeting
+                | ControlState::RecipeBook
+                | ControlState::SpellEditor
+                | ControlState::DifficultyMenu
+                | ControlState::RunSummary
+                | ControlState::ScrollPrompt
+        )
+    }
+}
+
+pub fn modal_is_open(state: Res<State<ControlState>>) -> bool {
+    state.is_modal()
+}
+
+/// Print the order in which the systems of `Update` are executed.
+#[derive(Event)]
+pub struct DumpSchedule;
+
+fn dump_schedule_order(schedules: Res<Schedules>) {
+    let Some(update) = schedules.get(Update) else {
+        return;
+    };
+    match update.systems() {
+        Ok(systems) => {
+            info!("Effective order of the Update schedule:");
+            for (i, (_node, system)) in systems.enumerate() {
+                info!("{:>3}. {}", i, system.name());
+            }
+        }
+        Err(_) => info!("The Update schedule has not been initialized yet."),
+    }
 }
@@ -0,0 +1,213 @@
+use std::{collections::HashMap, fs};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    director::DirectorIntensity,
+    sets::{modal_is_open, PlayerInput},
+    text::recolor_tag,
+    ui::{AddMessage, Message},
+};
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>();
+        app.add_systems(Startup, load_settings);
+        app.add_systems(
+            Update,
+            (
+                (cycle_palette, cycle_director_intensity)
+                    .run_if(not(modal_is_open))
+                    .in_set(PlayerInput),
+                save_settings.run_if(resource_changed::<Settings>),
+            )
+                .chain(),
+        );
+        app.add_systems(PostUpdate, recolor_text);
+    }
+}
+
+const SETTINGS_PATH: &str = "settings.ron";
+
+/// Options which outlive runs, kept in SETTINGS_PATH.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Settings {
+    #[serde(default)]
+    pub palette: ColorPalette,
+    /// Multiplies the UI size, on top of the scaling to the window height.
+    #[serde(default = "default_ui_scale")]
+    pub ui_scale: f32,
+    /// Which messages make it to the log, see print_message_in_log.
+    #[serde(default)]
+    pub log: LogFilter,
+    /// The colour tag each kind of message is written in, instead of white.
+    #[serde(default)]
+    pub message_colors: HashMap<MessageCategory, char>,
+    /// Content packs forced on or off by name, whatever the date. See ContentPack.
+    #[serde(default)]
+    pub content_packs: HashMap<String, bool>,
+    /// How often wanderers come back to explored places, see SpawnDirector.
+    #[serde(default)]
+    pub director: DirectorIntensity,
+}
+
+fn default_ui_scale() -> f32 {
+    1.
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            palette: ColorPalette::default(),
+            ui_scale: default_ui_scale(),
+            log: LogFilter::default(),
+            message_colors: HashMap::new(),
+            content_packs: HashMap::new(),
+            director: DirectorIntensity::default(),
+        }
+    }
+}
+
+/// Kinds of messages which can be hidden from the log, or given their own colour.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MessageCategory {
+    /// Blows traded with the player.
+    Combat,
+    /// Creatures other than the player fighting each other.
+    NpcCombat,
+    Healing,
+    /// Actions which could not be taken.
+    Warning,
+    General,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct LogFilter {
+    pub heals: bool,
+    pub npc_combat: bool,
+    /// Messages about creatures the player cannot see.
+    pub off_screen: bool,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            heals: true,
+            npc_combat: true,
+            off_screen: true,
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn shows(&self, category: MessageCategory) -> bool {
+        match category {
+            MessageCategory::Healing => self.heals,
+            MessageCategory::NpcCombat => self.npc_combat,
+            _ => true,
+        }
+    }
+}
+
+/// The colours of text tags and caste tints, see tag_color and get_caste_palette.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ColorPalette {
+    #[default]
+    Standard,
+    /// Damage and healing, and the castes, told apart without red and green.
+    ColorblindSafe,
+}
+
+impl ColorPalette {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorPalette::Standard => "standard",
+            ColorPalette::ColorblindSafe => "colourblind-safe",
+        }
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            ColorPalette::Standard => ColorPalette::ColorblindSafe,
+            ColorPalette::ColorblindSafe => ColorPalette::Standard,
+        }
+    }
+}
+
+pub fn load_settings(mut settings: ResMut<Settings>) {
+    let Ok(contents) = fs::read_to_string(SETTINGS_PATH) else {
+        return;
+    };
+    match ron::from_str(&contents) {
+        Ok(loaded) => *settings = loaded,
+        Err(error) => warn!("Could not read the settings: {}", error),
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    let contents =
+        match ron::ser::to_string_pretty(settings.as_ref(), ron::ser::PrettyConfig::default()) {
+            Ok(contents) => contents,
+            Err(error) => {
+                warn!("Could not serialize the settings: {}", error);
+                return;
+            }
+        };
+    if let Err(error) = fs::write(SETTINGS_PATH, contents) {
+        warn!("Could not write the settings: {}", error);
+    }
+}
+
+/// J switches to the next colour palette.
+fn cycle_palette(
+    input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if !input.just_pressed(KeyCode::KeyJ) {
+        return;
+    }
+    settings.palette = settings.palette.next();
+    text.send(AddMessage {
+        message: Message::PaletteChanged(settings.palette),
+    });
+}
+
+/// F4 makes wanderers come back more or less often, see SpawnDirector.
+fn cycle_director_intensity(input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<Settings>) {
+    if input.just_pressed(KeyCode::F4) {
+        settings.director = settings.director.next();
+    }
+}
+
+/// Text coloured by split_text is always in the Standard palette. New text
+/// is brought to the current one, and all text when the palette changes.
+fn recolor_text(
+    mut text: Query<&mut TextColor>,
+    settings: Res<Settings>,
+    mut current: Local<ColorPalette>,
+) {
+    let palette_changed = *current != settings.palette;
+    if !palette_changed && settings.palette == ColorPalette::Standard {
+        return;
+    }
+    for mut color in text.iter_mut() {
+        let from = if color.is_added() {
+            ColorPalette::Standard
+        } else if palette_changed {
+            *current
+        } else {
+            continue;
+        };
+        if let Some(recolored) = recolor_tag(color.0, from, settings.palette) {
+            if color.0 != recolored {
+                color.0 = recolored;
+            }
+        }
+    }
+    *current = settings.palette;
+}
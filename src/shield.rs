@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{CreatureFlags, Player, ShieldBuffer, ShieldPickup},
+    events::{EndTurn, RemoveCreature},
+    map::{Map, Position},
+    ui::{AddMessage, Message},
+};
+
+/// Once per turn, pick up the shields the player is standing on.
+/// A new shield replaces a weaker one, and lasts its full duration again.
+// NOTE: Pickups are intangible, and only found in Map::intangible_creatures.
+pub fn pick_up_shields(
+    mut events: EventReader<EndTurn>,
+    mut player: Query<(Entity, &Position, Option<&mut ShieldBuffer>), With<Player>>,
+    creatures: Query<&CreatureFlags>,
+    pickups: Query<&ShieldPickup>,
+    map: Res<Map>,
+    mut remove: EventWriter<RemoveCreature>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Ok((player, player_position, mut buffer)) = player.get_single_mut() else {
+        return;
+    };
+    for entity in map.intangible_at(player_position) {
+        let Ok(flags) = creatures.get(*entity) else {
+            continue;
+        };
+        let Ok(pickup) = pickups.get(flags.species_flags) else {
+            continue;
+        };
+        let amount = buffer
+            .as_ref()
+            .map_or(pickup.amount, |buffer| buffer.amount.max(pickup.amount));
+        let new_buffer = ShieldBuffer {
+            amount,
+            turns: pickup.turns,
+        };
+        if let Some(buffer) = buffer.as_mut() {
+            **buffer = new_buffer;
+        } else {
+            commands.entity(player).insert(new_buffer);
+        }
+        remove.send(RemoveCreature {
+            entity: *entity,
+            culprit: None,
+        });
+        text.send(AddMessage {
+            message: Message::ShieldRaised(amount, pickup.turns),
+        });
+    }
+}
+
+/// Shields fade away once their turns run out, or once they are broken.
+pub fn decay_shields(
+    mut events: EventReader<EndTurn>,
+    mut buffers: Query<(Entity, &mut ShieldBuffer, Has<Player>)>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for (entity, mut buffer, is_player) in buffers.iter_mut() {
+        buffer.turns = buffer.turns.saturating_sub(1);
+        if buffer.turns > 0 && buffer.amount > 0 {
+            continue;
+        }
+        commands.entity(entity).remove::<ShieldBuffer>();
+        if is_player {
+            text.send(AddMessage {
+                message: Message::ShieldFaded,
+            });
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{Blessed, Blessing, CreatureFlags, DamageKind, Interactable, Player, Shrine},
+    events::{DamageOrHealCreature, EndTurn, PlayerAction, RemoveCreature, TurnManager},
+    interact::{interaction_target, Interact},
+    map::Position,
+    sets::Animation,
+    spells::{Axiom, Spell},
+    text::split_text,
+    ui::{AddMessage, InvalidAction, Message},
+};
+
+pub struct ShrinePlugin;
+
+impl Plugin for ShrinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_blessing_hud);
+        app.add_systems(Update, update_blessing_hud.in_set(Animation));
+    }
+}
+
+impl Blessing {
+    pub fn name(&self) -> &str {
+        match self {
+            Blessing::Expanse => "[c]Blessing of Expanse[w]",
+            Blessing::Renewal => "[l]Blessing of Renewal[w]",
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        match self {
+            Blessing::Expanse => "every spell you cast spreads to the tiles around its targets",
+            Blessing::Renewal => "you heal 1 HP every turn",
+        }
+    }
+}
+
+/// A spell as cast under `blessing`. Expanse follows every Form with Spread.
+pub fn bless_spell(spell: &Spell, blessing: Blessing) -> Spell {
+    match blessing {
+        Blessing::Expanse => Spell {
+            axioms: spell
+                .axioms
+                .iter()
+                .flat_map(|axiom| {
+                    let spread = axiom.is_form().then_some(Axiom::Spread);
+                    std::iter::once(axiom.clone()).chain(spread)
+                })
+                .collect(),
+        },
+        Blessing::Renewal => spell.clone(),
+    }
+}
+
+/// Activating a shrine grants its blessing, then the shrine crumbles. Only one
+/// blessing can be held at a time.
+pub fn interact_with_shrines(
+    mut events: EventReader<Interact>,
+    position: Query<&Position>,
+    creatures: Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: Query<(), With<Interactable>>,
+    shrines: Query<&Shrine>,
+    blessed: Query<&Blessed>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut remove: EventWriter<RemoveCreature>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    for event in events.read() {
+        let Some((entity, shrine)) =
+            interaction_target(event, &position, &creatures, &interactable).and_then(|entity| {
+                let (_, _, flags) = creatures.get(entity).ok()?;
+                Some((entity, shrines.get(flags.species_flags).ok()?))
+            })
+        else {
+            continue;
+        };
+        if let Ok(current) = blessed.get(event.entity) {
+            text.send(AddMessage {
+                message: Message::InvalidAction(InvalidAction::AlreadyBlessed(current.blessing)),
+            });
+            turn_manager.action_this_turn = PlayerAction::Invalid;
+            continue;
+        }
+        commands.entity(event.entity).insert(Blessed {
+            blessing: shrine.blessing,
+            turns: shrine.turns,
+        });
+        remove.send(RemoveCreature {
+            entity,
+            culprit: None,
+        });
+        text.send(AddMessage {
+            message: Message::Blessed(shrine.blessing, shrine.turns),
+        });
+    }
+}
+
+/// Blessings wear off once their turns run out. Renewal heals every turn until then.
+pub fn decay_blessings(
+    mut events: EventReader<EndTurn>,
+    mut blessed: Query<(Entity, &mut Blessed, Has<Player>)>,
+    mut heal: EventWriter<DamageOrHealCreature>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    for (entity, mut blessed, is_player) in blessed.iter_mut() {
+        if blessed.blessing == Blessing::Renewal {
+            heal.send(DamageOrHealCreature {
+                entity,
+                culprit: entity,
+                hp_mod: 1,
+                tile: None,
+                kind: DamageKind::Other,
+            });
+        }
+        blessed.turns = blessed.turns.saturating_sub(1);
+        if blessed.turns > 0 {
+            continue;
+        }
+        commands.entity(entity).remove::<Blessed>();
+        if is_player {
+            text.send(AddMessage {
+                message: Message::BlessingFaded(blessed.blessing),
+            });
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct BlessingHud;
+
+fn spawn_blessing_hud(mut commands: Commands) {
+    commands.spawn((
+        BlessingHud,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(35.),
+            top: Val::Px(4.),
+            padding: UiRect::all(Val::Px(0.5)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+        Visibility::Hidden,
+    ));
+}
+
+/// Count down the turns left on the player's blessing, under the objective.
+fn update_blessing_hud(
+    player: Query<Option<&Blessed>, With<Player>>,
+    mut hud: Query<(Entity, &mut Visibility), With<BlessingHud>>,
+    asset_server: Res<AssetServer>,
+    mut shown: Local<Option<(Blessing, usize)>>,
+    mut commands: Commands,
+) {
+    let Ok((hud, mut visibility)) = hud.get_single_mut() else {
+        return;
+    };
+    let current = player
+        .get_single()
+        .ok()
+        .flatten()
+        .map(|blessed| (blessed.blessing, blessed.turns));
+    if *shown == current {
+        return;
+    }
+    *shown = current;
+    commands.entity(hud).despawn_descendants();
+    let Some((blessing, turns)) = current else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    let description = format!("{} - [y]{}[w] turns left", blessing.name(), turns);
+    commands.entity(hud).with_children(|parent| {
+        for (section, color) in split_text(&description) {
+            parent.spawn((TextSpan::new(section), font.clone(), color));
+        }
+    });
+}
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use crate::{
+    ai::Noise,
+    audio::PlaySound,
+    caste::{EquipSuggestedSpell, SpellSuggestions},
+    chest::LootTable,
+    content::ActiveContentPacks,
+    conveyor::ResidualMomentum,
+    corpse::{GrabCorpse, SpawnCorpse},
+    crafting::{CraftingRecipes, EditSpell, LooseAxioms, WeaveSoul, Weaving},
+    difficulty::{GameDifficulty, SetDifficulty},
+    director::{SetDirectorIntensity, SpawnDirector},
+    events::{EventPlugin, HealthChanged},
+    graphics::{AnimationQueue, PlaceMagicVfx, Screenshake, SpriteSheetAtlas},
+    grinder::Grinder,
+    interact::Interact,
+    key_items::KeyItems,
+    map::MapPlugin,
+    objective::FloorObjective,
+    rail::DerailCart,
+    scroll::{HeldScrolls, ReadScroll},
+    sets::add_simulation_systems,
+    species::{DeathEffects, SpeciesRegistry},
+    spells::SpellPlugin,
+    stats::RunStats,
+    trap::DisarmTrap,
+    ui::{AddMessage, AnnounceGameOver},
+    vision::VisionPlugin,
+    warp::{PanicWarp, WarpCharges},
+};
+
+/// The rules of the game, with no window, input, UI, sound or animation.
+/// The full game adds its presentation plugins on top of this one.
+///
+/// This still needs a GameRng, and the assets and atlases used to give sprites
+/// to creatures: see headless_app for a working minimal setup.
+pub struct TgfpCorePlugin;
+
+impl Plugin for TgfpCorePlugin {
+    fn build(&self, app: &mut App) {
+        // The simulation still gives sprites to its creatures, even if nobody
+        // is there to see them.
+        app.init_resource::<SpriteSheetAtlas>();
+        app.insert_resource(Screenshake { intensity: 0 });
+        app.init_resource::<AnimationQueue>();
+        app.init_resource::<SpeciesRegistry>();
+        app.init_resource::<DeathEffects>();
+        app.init_resource::<LootTable>();
+        app.init_resource::<KeyItems>();
+        app.init_resource::<Grinder>();
+        app.init_resource::<SpawnDirector>();
+        app.init_resource::<CraftingRecipes>();
+        app.init_resource::<Weaving>();
+        app.init_resource::<SpellSuggestions>();
+        app.init_resource::<LooseAxioms>();
+        app.init_resource::<FloorObjective>();
+        app.init_resource::<GameDifficulty>();
+        app.init_resource::<RunStats>();
+        app.init_resource::<ResidualMomentum>();
+        app.init_resource::<HeldScrolls>();
+        app.init_resource::<WarpCharges>();
+        app.init_resource::<ActiveContentPacks>();
+        // Events normally registered by the graphical plugins.
+        app.add_event::<PlaceMagicVfx>();
+        app.add_event::<AddMessage>();
+        app.add_event::<AnnounceGameOver>();
+        app.add_event::<Interact>();
+        app.add_event::<WeaveSoul>();
+        app.add_event::<EquipSuggestedSpell>();
+        app.add_event::<EditSpell>();
+        app.add_event::<SetDifficulty>();
+        app.add_event::<SetDirectorIntensity>();
+        app.add_event::<DisarmTrap>();
+        app.add_event::<ReadScroll>();
+        app.add_event::<SpawnCorpse>();
+        app.add_event::<GrabCorpse>();
+        app.add_event::<PanicWarp>();
+        app.add_event::<HealthChanged>();
+        app.add_event::<DerailCart>();
+        app.add_event::<PlaySound>();
+        app.add_event::<Noise>();
+        app.add_plugins((SpellPlugin, EventPlugin, MapPlugin, VisionPlugin));
+        add_simulation_systems(app);
+    }
+}
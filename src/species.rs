@@ -0,0 +1,532 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    ecs::system::EntityCommands,
+    prelude::*,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    creature::{
+        Blessing, Chest, Conveyor, Cowardly, DeathEffect, Devours, Dizzy, Door, Ephemeral, Flying,
+        Footprint, Fragile, Hunt, Immobile, Intangible, Interactable, Invincible, ItemPickup,
+        KeyPickup, Lever, Lock, Magnetic, Meleeproof, Mimic, MovementStyle, NoDropSoul, Perceptive,
+        Player, PressurePlate, Pushable, Railbound, Railway, Random, ScrollPickup, ShieldPickup,
+        Shrine, Soul, Species, Speed, Spellbook, Spellproof, Splitter, StatusEffect, Switch, Tag,
+        Wall, WeakPoint, WeakPoints,
+    },
+    equipment::Item,
+    evolution::Evolution,
+    idle::IdleKind,
+    key_items::KeyItem,
+    spells::Spell,
+    text::split_text,
+};
+
+pub struct SpeciesPlugin;
+
+impl Plugin for SpeciesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<CreatureDefinitions>();
+        app.init_asset::<SpellbookDefinitions>();
+        app.init_asset_loader::<RonLoader<CreatureDefinitions>>();
+        app.init_asset_loader::<RonLoader<SpellbookDefinitions>>();
+        app.init_resource::<SpeciesRegistry>();
+        app.init_resource::<DeathEffects>();
+        app.add_systems(Startup, load_creature_definitions);
+        app.add_systems(Update, (update_species_registry, update_spellbooks));
+    }
+}
+
+/// Everything which makes a species what it is, as written in creatures.ron.
+#[derive(Deserialize, Clone)]
+pub struct SpeciesDefinition {
+    /// With colour codes, see split_text.
+    pub name: String,
+    #[serde(default = "unknown_description")]
+    pub description: String,
+    pub sprite: usize,
+    #[serde(default = "default_hp")]
+    pub hp: usize,
+    #[serde(default = "default_hp")]
+    pub max_hp: usize,
+    #[serde(default = "default_soul")]
+    pub soul: Soul,
+    /// Added to the species flags entity.
+    #[serde(default)]
+    pub components: Vec<SpeciesComponent>,
+    /// Status effects which never take hold on this species.
+    #[serde(default)]
+    pub immunities: Vec<StatusEffect>,
+    /// Added to the species flags entity as Tags.
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    /// The width and height of creatures larger than one tile.
+    #[serde(default)]
+    pub footprint: Option<(i32, i32)>,
+    /// Only for creatures with a footprint: a sprite for each tile, by its offset,
+    /// drawn instead of stretching `sprite` over the whole body. Tiles left out use `sprite`.
+    #[serde(default)]
+    pub body: Vec<((i32, i32), usize)>,
+    /// Only for creatures with a footprint, by the offset of the tile they are on.
+    #[serde(default)]
+    pub weak_points: Vec<((i32, i32), WeakPoint)>,
+    /// Falls asleep when placed in a cage other than the first one,
+    /// and must be slain for the cage to open.
+    #[serde(default)]
+    pub sleeps_in_cage: bool,
+    #[serde(default)]
+    pub bulk: Bulk,
+    /// Laid in hiding when summoned by anyone but the player, see Hidden.
+    #[serde(default)]
+    pub hidden: bool,
+    /// What it does to pass the time while the player thinks, see IdleKind.
+    #[serde(default = "default_idles")]
+    pub idles: Vec<IdleKind>,
+    /// Out of 100, how likely it is to leave one of its spells behind
+    /// on a scroll when slain, see drop_scrolls.
+    #[serde(default)]
+    pub drops_scroll: u32,
+    /// Drawn instead of the sprite in ASCII mode. Defaults to the first
+    /// letter of the name.
+    #[serde(default)]
+    pub glyph: Option<char>,
+    /// What it transforms into after spending long enough awake.
+    #[serde(default)]
+    pub evolves: Option<Evolution>,
+}
+
+/// How hard a creature is to move around, see Bulk::resist.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bulk {
+    /// Flies a bit further when knocked back.
+    Light,
+    #[default]
+    Medium,
+    /// Knocked back half as far, and can't be flung by someone else's Dash.
+    Heavy,
+    /// Nothing moves it, not even conveyor belts.
+    Immovable,
+}
+
+impl Bulk {
+    /// How far a push of this strength actually sends the creature.
+    pub fn resist(&self, distance: i32) -> i32 {
+        match self {
+            Bulk::Light => distance + 1,
+            Bulk::Medium => distance,
+            Bulk::Heavy => distance / 2,
+            Bulk::Immovable => 0,
+        }
+    }
+
+    /// Whether something else than the creature itself can make it dash.
+    pub fn can_be_thrown(&self) -> bool {
+        *self < Bulk::Heavy
+    }
+}
+
+fn unknown_description() -> String {
+    "Unknown.".to_owned()
+}
+
+// NOTE: Wall-type creatures just get full HP to avoid displaying
+// their healthbar.
+fn default_hp() -> usize {
+    6
+}
+
+fn default_soul() -> Soul {
+    Soul::Unhinged
+}
+
+fn default_idles() -> Vec<IdleKind> {
+    vec![IdleKind::Shuffle, IdleKind::Blink]
+}
+
+impl SpeciesDefinition {
+    /// The character and colour of this species in ASCII mode, the colour
+    /// being the one its name is written in.
+    pub fn glyph(&self) -> (char, Color) {
+        let sections = split_text(&self.name);
+        let (name, color) = sections
+            .iter()
+            .find(|(section, _)| !section.trim().is_empty())
+            .map_or(("?", Color::WHITE), |(section, color)| {
+                (section.as_str(), color.0)
+            });
+        let glyph = self
+            .glyph
+            .or_else(|| name.chars().find(|c| c.is_alphanumeric()))
+            .unwrap_or('?');
+        (glyph, color)
+    }
+
+    pub fn footprint(&self) -> Option<Footprint> {
+        self.footprint
+            .map(|(width, height)| Footprint::rectangle(width, height))
+    }
+
+    /// The sprite of each tile of a composite creature, or None if it
+    /// is drawn as a single stretched sprite.
+    pub fn body_sprites(&self) -> Option<Vec<((i32, i32), usize)>> {
+        if self.body.is_empty() {
+            return None;
+        }
+        let footprint = self.footprint()?;
+        Some(
+            footprint
+                .offsets
+                .iter()
+                .map(|offset| {
+                    let sprite = self
+                        .body
+                        .iter()
+                        .find(|(part, _)| part == offset)
+                        .map_or(self.sprite, |(_, sprite)| *sprite);
+                    (*offset, sprite)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn weak_points(&self) -> Option<WeakPoints> {
+        if self.weak_points.is_empty() {
+            return None;
+        }
+        Some(WeakPoints {
+            points: self.weak_points.iter().cloned().collect(),
+        })
+    }
+
+    /// Hostile creatures get the death effect of their caste, see DeathEffects.
+    pub fn is_hostile(&self) -> bool {
+        self.components
+            .iter()
+            .any(|component| matches!(component, SpeciesComponent::Hunt | SpeciesComponent::Random))
+    }
+
+    /// Intangible creatures can be summoned on top of others.
+    pub fn is_naturally_intangible(&self) -> bool {
+        self.components
+            .iter()
+            .any(|component| matches!(component, SpeciesComponent::Intangible))
+    }
+}
+
+/// A species-specific component, see assign_species_components.
+#[derive(Deserialize, Clone)]
+pub enum SpeciesComponent {
+    Meleeproof,
+    Spellproof,
+    Intangible,
+    Fragile,
+    Invincible,
+    NoDropSoul,
+    Wall,
+    Door,
+    Dizzy,
+    Hunt,
+    Random,
+    Immobile,
+    Flying,
+    Interactable,
+    Lever,
+    PressurePlate,
+    Conveyor,
+    Splitter,
+    Chest { rolls: usize },
+    Mimic { species: Species },
+    Lock { key: KeyItem },
+    KeyPickup { item: KeyItem },
+    ItemPickup { item: Item },
+    ScrollPickup,
+    ShieldPickup { amount: usize, turns: usize },
+    Shrine { blessing: Blessing, turns: usize },
+    Pushable,
+    Railway,
+    Railbound,
+    Switch,
+    Speed(Speed),
+    MovementStyle(MovementStyle),
+    Cowardly(Cowardly),
+    Magnetic { species: Species },
+    Devours { tag: Tag },
+    Ephemeral { turns: usize },
+    Perceptive { radius: i32 },
+}
+
+impl SpeciesComponent {
+    pub fn insert(&self, entity: &mut EntityCommands) {
+        match self {
+            SpeciesComponent::Meleeproof => entity.insert(Meleeproof),
+            SpeciesComponent::Spellproof => entity.insert(Spellproof),
+            SpeciesComponent::Intangible => entity.insert(Intangible),
+            SpeciesComponent::Fragile => entity.insert(Fragile),
+            SpeciesComponent::Invincible => entity.insert(Invincible),
+            SpeciesComponent::NoDropSoul => entity.insert(NoDropSoul),
+            SpeciesComponent::Wall => entity.insert(Wall),
+            SpeciesComponent::Door => entity.insert(Door),
+            SpeciesComponent::Dizzy => entity.insert(Dizzy),
+            SpeciesComponent::Hunt => entity.insert(Hunt),
+            SpeciesComponent::Random => entity.insert(Random),
+            SpeciesComponent::Immobile => entity.insert(Immobile),
+            SpeciesComponent::Flying => entity.insert(Flying),
+            SpeciesComponent::Interactable => entity.insert(Interactable),
+            SpeciesComponent::Lever => entity.insert(Lever { on: false }),
+            SpeciesComponent::PressurePlate => entity.insert(PressurePlate),
+            SpeciesComponent::Conveyor => entity.insert(Conveyor),
+            SpeciesComponent::Splitter => entity.insert(Splitter { right: false }),
+            SpeciesComponent::Chest { rolls } => entity.insert(Chest { rolls: *rolls }),
+            SpeciesComponent::Mimic { species } => entity.insert(Mimic { species: *species }),
+            SpeciesComponent::Lock { key } => entity.insert(Lock { key: *key }),
+            SpeciesComponent::KeyPickup { item } => entity.insert(KeyPickup { item: *item }),
+            SpeciesComponent::ItemPickup { item } => entity.insert(ItemPickup { item: *item }),
+            SpeciesComponent::ScrollPickup => entity.insert(ScrollPickup),
+            SpeciesComponent::ShieldPickup { amount, turns } => entity.insert(ShieldPickup {
+                amount: *amount,
+                turns: *turns,
+            }),
+            SpeciesComponent::Shrine { blessing, turns } => entity.insert(Shrine {
+                blessing: *blessing,
+                turns: *turns,
+            }),
+            SpeciesComponent::Pushable => entity.insert(Pushable),
+            SpeciesComponent::Railway => entity.insert(Railway),
+            SpeciesComponent::Railbound => entity.insert(Railbound),
+            SpeciesComponent::Switch => entity.insert(Switch),
+            SpeciesComponent::Speed(speed) => entity.insert(speed.clone()),
+            SpeciesComponent::MovementStyle(style) => entity.insert(*style),
+            SpeciesComponent::Cowardly(cowardly) => entity.insert(*cowardly),
+            SpeciesComponent::Magnetic { species } => entity.insert(Magnetic {
+                species: *species,
+                conductor: None,
+            }),
+            SpeciesComponent::Devours { tag } => entity.insert(Devours { tag: *tag }),
+            SpeciesComponent::Ephemeral { turns } => entity.insert(Ephemeral { turns: *turns }),
+            SpeciesComponent::Perceptive { radius } => {
+                entity.insert(Perceptive { radius: *radius })
+            }
+        };
+    }
+}
+
+/// The contents of creatures.ron.
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct CreatureDefinitions(pub HashMap<Species, SpeciesDefinition>);
+
+/// The contents of spellbooks.ron.
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct SpellbookDefinitions(pub HashMap<Species, HashMap<Soul, Spell>>);
+
+/// Reads any asset written as a single RON value.
+pub struct RonLoader<A>(PhantomData<A>);
+
+impl<A> Default for RonLoader<A> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/// Every species' definition and starting spells, by Species.
+#[derive(Resource)]
+pub struct SpeciesRegistry {
+    definitions: HashMap<Species, SpeciesDefinition>,
+    spellbooks: HashMap<Species, HashMap<Soul, Spell>>,
+    /// Definitions from the active content packs, which win over creatures.ron.
+    overrides: HashMap<Species, SpeciesDefinition>,
+}
+
+impl Default for SpeciesRegistry {
+    /// The definitions the game was built with. The simulation cannot wait
+    /// for the asset server before summoning its first creatures, and headless
+    /// worlds have no asset folder at all.
+    fn default() -> Self {
+        let definitions: CreatureDefinitions =
+            ron::from_str(include_str!("../assets/creatures.ron"))
+                .expect("The built-in creatures.ron is invalid");
+        let spellbooks: SpellbookDefinitions =
+            ron::from_str(include_str!("../assets/spellbooks.ron"))
+                .expect("The built-in spellbooks.ron is invalid");
+        Self {
+            definitions: definitions.0,
+            spellbooks: spellbooks.0,
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+impl SpeciesRegistry {
+    pub fn get(&self, species: &Species) -> &SpeciesDefinition {
+        self.overrides
+            .get(species)
+            .or_else(|| self.definitions.get(species))
+            .unwrap_or_else(|| panic!("{:?} is missing from creatures.ron", species))
+    }
+
+    /// Replace a species' definition until its pack is switched off, even if
+    /// creatures.ron is reloaded. See ContentPack.
+    pub fn override_species(&mut self, species: Species, definition: SpeciesDefinition) {
+        self.overrides.insert(species, definition);
+    }
+
+    /// Go back to the creatures.ron definition of a species.
+    pub fn remove_override(&mut self, species: &Species) {
+        self.overrides.remove(species);
+    }
+
+    /// The spells a creature of this species is born with, without its DeathEffect.
+    pub fn spellbook(&self, species: &Species) -> Spellbook {
+        Spellbook {
+            spells: self
+                .spellbooks
+                .get(species)
+                .map(|spells| {
+                    spells
+                        .iter()
+                        .map(|(soul, spell)| (*soul, spell.clone()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The contents of death_effects.ron: the WhenRemoved spell of each caste, which
+/// every hostile creature of that caste gets when it is summoned.
+#[derive(Resource)]
+pub struct DeathEffects {
+    spells: HashMap<Soul, Spell>,
+}
+
+impl Default for DeathEffects {
+    fn default() -> Self {
+        Self {
+            spells: ron::from_str(include_str!("../assets/death_effects.ron"))
+                .expect("The built-in death_effects.ron is invalid"),
+        }
+    }
+}
+
+impl DeathEffects {
+    /// The death effect of this caste, if it has one.
+    pub fn of(&self, soul: &Soul) -> Option<DeathEffect> {
+        self.spells.get(soul).map(|spell| DeathEffect {
+            soul: *soul,
+            spell: spell.clone(),
+        })
+    }
+}
+
+#[derive(Resource)]
+struct CreatureDefinitionsHandle(Handle<CreatureDefinitions>);
+
+#[derive(Resource)]
+struct SpellbookDefinitionsHandle(Handle<SpellbookDefinitions>);
+
+// NOTE: Edits are only picked up while the game runs with bevy's
+// file_watcher feature, e.g. `cargo run --features bevy/file_watcher`.
+fn load_creature_definitions(asset_server: Res<AssetServer>, mut commands: Commands) {
+    commands.insert_resource(CreatureDefinitionsHandle(
+        asset_server.load("creatures.ron"),
+    ));
+    commands.insert_resource(SpellbookDefinitionsHandle(
+        asset_server.load("spellbooks.ron"),
+    ));
+}
+
+/// Once creatures.ron is loaded from the assets folder, it replaces the built-in
+/// definitions. Species missing from it keep their built-in definition.
+fn update_species_registry(
+    mut events: EventReader<AssetEvent<CreatureDefinitions>>,
+    definitions: Res<Assets<CreatureDefinitions>>,
+    handle: Option<Res<CreatureDefinitionsHandle>>,
+    mut registry: ResMut<SpeciesRegistry>,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event {
+            if *id != handle.0.id() {
+                continue;
+            }
+            if let Some(definitions) = definitions.get(*id) {
+                registry.definitions.extend(
+                    definitions
+                        .0
+                        .iter()
+                        .map(|(species, definition)| (*species, definition.clone())),
+                );
+            }
+        }
+    }
+}
+
+/// Once spellbooks.ron is loaded from the assets folder, it replaces the built-in
+/// spellbooks. When it is edited while the game runs, creatures already around
+/// relearn the spells of their species, so tweaks can be tried out on the spot.
+fn update_spellbooks(
+    mut events: EventReader<AssetEvent<SpellbookDefinitions>>,
+    spellbooks: Res<Assets<SpellbookDefinitions>>,
+    handle: Option<Res<SpellbookDefinitionsHandle>>,
+    mut registry: ResMut<SpeciesRegistry>,
+    // The player's spells are their own, made in the spell editor.
+    mut creatures: Query<(&Species, &mut Spellbook), Without<Player>>,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+    for event in events.read() {
+        let (AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }) = event
+        else {
+            continue;
+        };
+        if *id != handle.0.id() {
+            continue;
+        }
+        let Some(loaded) = spellbooks.get(*id) else {
+            continue;
+        };
+        registry.spellbooks.extend(
+            loaded
+                .0
+                .iter()
+                .map(|(species, spells)| (*species, spells.clone())),
+        );
+        if !matches!(event, AssetEvent::Modified { .. }) {
+            continue;
+        }
+        for (species, mut spellbook) in creatures.iter_mut() {
+            if !loaded.0.contains_key(species) {
+                continue;
+            }
+            *spellbook = registry.spellbook(species);
+        }
+        info!("Reloaded spellbooks.ron");
+    }
+}
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    creature::{Player, Species},
+    sets::{Animation, Cleanup, ControlState, NpcTurn, PlayerInput, SpellResolution},
+    species::SpeciesRegistry,
+    spells::{Axiom, SpellStack},
+    text::split_text,
+    ui::{AddMessage, Message},
+};
+
+pub struct SpectatePlugin;
+
+impl Plugin for SpectatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpellSpectator>();
+        app.add_systems(Startup, spawn_spectator_hud);
+        app.add_systems(
+            Update,
+            toggle_spectator
+                .run_if(in_state(ControlState::Player))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            watch_enemy_spells
+                .after(PlayerInput)
+                .before(SpellResolution),
+        );
+        // NOTE: Pausing everything which follows, not just the spell stack,
+        // keeps the NPCs from acting around a spell frozen mid-cast.
+        app.configure_sets(
+            Update,
+            (SpellResolution, Cleanup, NpcTurn).run_if(spectator_allows_step),
+        );
+        app.add_systems(
+            Update,
+            update_spectator_hud
+                .run_if(resource_changed::<SpellSpectator>)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// How long each axiom of a spectated spell stays on screen.
+const SPECTATE_STEP: Duration = Duration::from_millis(700);
+
+/// Slow motion for enemy spells: the first time each one is seen, it resolves
+/// one axiom at a time, each named on screen. Toggled with M.
+#[derive(Resource)]
+pub struct SpellSpectator {
+    pub enabled: bool,
+    /// Every enemy spell which was already spectated once.
+    seen: HashSet<Vec<Axiom>>,
+    /// The caster and axioms of the spell being spectated.
+    watching: Option<(Entity, Vec<Axiom>)>,
+    timer: Timer,
+    /// Whether the spell stack may advance this frame.
+    step_ready: bool,
+}
+
+impl Default for SpellSpectator {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seen: HashSet::new(),
+            watching: None,
+            timer: Timer::new(SPECTATE_STEP, TimerMode::Repeating),
+            step_ready: true,
+        }
+    }
+}
+
+fn spectator_allows_step(spectator: Res<SpellSpectator>) -> bool {
+    spectator.step_ready
+}
+
+/// M toggles slow motion for enemy spells. Turning it off lets a spell being
+/// spectated finish at once.
+fn toggle_spectator(
+    input: Res<ButtonInput<KeyCode>>,
+    mut spectator: ResMut<SpellSpectator>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if !input.just_pressed(KeyCode::KeyM) {
+        return;
+    }
+    spectator.enabled = !spectator.enabled;
+    if !spectator.enabled {
+        spectator.watching = None;
+        spectator.step_ready = true;
+    }
+    text.send(AddMessage {
+        message: Message::SpectatorToggled(spectator.enabled),
+    });
+}
+
+/// Pick the first enemy spell on the stack which was never spectated, then
+/// let the stack advance only once every SPECTATE_STEP until it is over.
+fn watch_enemy_spells(
+    mut spectator: ResMut<SpellSpectator>,
+    spell_stack: Res<SpellStack>,
+    player: Query<(), With<Player>>,
+    time: Res<Time>,
+) {
+    if !spectator.enabled {
+        return;
+    }
+    let still_casting = spectator.watching.as_ref().is_some_and(|(caster, axioms)| {
+        spell_stack
+            .spells
+            .iter()
+            .any(|synapse| synapse.caster == *caster && synapse.axioms == *axioms)
+    });
+    if !still_casting {
+        if let Some((_, axioms)) = spectator.watching.take() {
+            spectator.seen.insert(axioms);
+        }
+        let new_spell = spell_stack
+            .spells
+            .iter()
+            .find(|synapse| {
+                !player.contains(synapse.caster) && !spectator.seen.contains(&synapse.axioms)
+            })
+            .map(|synapse| (synapse.caster, synapse.axioms.clone()));
+        if new_spell.is_none() {
+            spectator.step_ready = true;
+            return;
+        }
+        spectator.watching = new_spell;
+        spectator.timer.reset();
+    }
+    // NOTE: The HUD is rebuilt whenever this resource changes, and the
+    // timer ticking every frame does not count.
+    spectator.bypass_change_detection().timer.tick(time.delta());
+    let step_ready = spectator.timer.just_finished();
+    if spectator.step_ready != step_ready {
+        spectator.step_ready = step_ready;
+    }
+}
+
+#[derive(Component)]
+pub struct SpectatorHud;
+
+fn spawn_spectator_hud(mut commands: Commands) {
+    commands.spawn((
+        SpectatorHud,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(35.),
+            bottom: Val::Px(4.),
+            max_width: Val::Percent(30.),
+            padding: UiRect::all(Val::Px(0.5)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+        Visibility::Hidden,
+    ));
+}
+
+/// Spell out the spectated spell axiom by axiom, the current one in yellow.
+fn update_spectator_hud(
+    spectator: Res<SpellSpectator>,
+    spell_stack: Res<SpellStack>,
+    species: Query<&Species>,
+    registry: Res<SpeciesRegistry>,
+    mut hud: Query<(Entity, &mut Visibility), With<SpectatorHud>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let Ok((hud, mut visibility)) = hud.get_single_mut() else {
+        return;
+    };
+    commands.entity(hud).despawn_descendants();
+    let Some(synapse) = spectator.watching.as_ref().and_then(|(caster, axioms)| {
+        spell_stack
+            .spells
+            .iter()
+            .find(|synapse| synapse.caster == *caster && synapse.axioms == *axioms)
+    }) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+    let caster = species
+        .get(synapse.caster)
+        .map_or("Something", |species| &registry.get(species).name);
+    let axioms: Vec<String> = synapse
+        .axioms
+        .iter()
+        .enumerate()
+        .map(|(step, axiom)| {
+            let name = format!("{:?}", axiom);
+            // Only the name of the axiom, not its parameters.
+            let name = name.split([' ', '(', '{']).next().unwrap_or_default();
+            if step == synapse.step {
+                format!("[y]{}[w]", name)
+            } else {
+                name.to_owned()
+            }
+        })
+        .collect();
+    let description = format!("{} casts: {}", caster, axioms.join(" > "));
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    commands.entity(hud).with_children(|parent| {
+        for (section, color) in split_text(&description) {
+            parent.spawn((TextSpan::new(section), font.clone(), color));
+        }
+    });
+}
@@ -8,18 +8,27 @@ use bevy::{
     prelude::*,
     utils::{HashMap, HashSet},
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    ai::{Noise, BLAST_NOISE},
+    conveyor::{Flung, ResidualMomentum},
     creature::{
-        CreatureFlags, EffectDuration, FlagEntity, Player, Soul, Species, Spellbook, Spellproof,
-        StatusEffect, StatusEffectsList, Summoned, Wall,
+        Conveyor, CreatureFlags, DamageKind, DeathEffect, EffectDuration, Facing, FlagEntity,
+        FlagQuery, Footprint, Player, Projectile, Soul, Species, SpellReflect, Spellbook,
+        Spellproof, StatusEffect, StatusEffectsList, Summoned, Tag, Tags, Wall,
     },
+    difficulty::GameDifficulty,
     events::{
-        AddStatusEffect, DamageOrHealCreature, RemoveCreature, SummonCreature, TeleportEntity,
-        TransformCreature,
+        strip_status_effect, AddStatusEffect, AlterMomentum, CreatureCollision,
+        DamageOrHealCreature, Deflect, DeflectKind, RemoveCreature, SummonCreature, TeleportEntity,
+        TransformCreature, TurnManager,
     },
     graphics::{EffectSequence, EffectType, PlaceMagicVfx},
-    map::{Map, Position},
+    map::{occupied_tiles, Map, Position},
+    script::{parse_script, ScriptCommand},
+    species::{Bulk, SpeciesRegistry},
+    ui::{AddMessage, Message},
     OrdDir,
 };
 
@@ -30,7 +39,51 @@ impl Plugin for SpellPlugin {
         app.init_resource::<Events<CastSpell>>();
         app.insert_resource(SpellStack { spells: Vec::new() });
         app.init_resource::<AxiomLibrary>();
+        app.init_resource::<AimMode>();
+        app.add_event::<SetAimMode>();
         app.add_event::<TriggerContingency>();
+        app.add_event::<SpellFizzled>();
+    }
+}
+
+/// Which direction momentum-based forms (MomentumBeam, Touch, Dash) are cast towards.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum AimMode {
+    /// The caster's last move. Aiming requires walking, or walking into a wall.
+    #[default]
+    Momentum,
+    /// The caster's Facing, if it has one. Creatures without one use their momentum.
+    Facing,
+}
+
+impl AimMode {
+    pub fn direction(&self, momentum: &OrdDir, facing: Option<&Facing>) -> OrdDir {
+        match (self, facing) {
+            (AimMode::Facing, Some(facing)) => facing.direction,
+            _ => *momentum,
+        }
+    }
+}
+
+/// Switch to another AimMode.
+#[derive(Event)]
+pub struct SetAimMode {
+    pub mode: AimMode,
+}
+
+pub fn set_aim_mode(
+    mut events: EventReader<SetAimMode>,
+    mut aim_mode: ResMut<AimMode>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        if *aim_mode == event.mode {
+            continue;
+        }
+        *aim_mode = event.mode;
+        text.send(AddMessage {
+            message: Message::AimMode(event.mode),
+        });
     }
 }
 
@@ -79,6 +132,22 @@ impl FromWorld for AxiomLibrary {
             discriminant(&Axiom::Touch),
             world.register_system(axiom_form_touch),
         );
+        axioms.library.insert(
+            discriminant(&Axiom::LineOfSight { range: 1 }),
+            world.register_system(axiom_form_line_of_sight),
+        );
+        axioms.library.insert(
+            discriminant(&Axiom::ChainFromTarget { jumps: 1, range: 1 }),
+            world.register_system(axiom_form_chain_from_target),
+        );
+        axioms.library.insert(
+            discriminant(&Axiom::CursorTarget),
+            world.register_system(axiom_form_cursor_target),
+        );
+        axioms.library.insert(
+            discriminant(&Axiom::Knockback { distance: 1 }),
+            world.register_system(axiom_function_knockback),
+        );
         axioms.library.insert(
             discriminant(&Axiom::Dash { max_distance: 1 }),
             world.register_system(axiom_function_dash),
@@ -93,6 +162,10 @@ impl FromWorld for AxiomLibrary {
             discriminant(&Axiom::PlaceStepTrap),
             world.register_system(axiom_function_place_step_trap),
         );
+        axioms.library.insert(
+            discriminant(&Axiom::LaunchProjectile),
+            world.register_system(axiom_function_launch_projectile),
+        );
         axioms.library.insert(
             discriminant(&Axiom::DevourWall),
             world.register_system(axiom_function_devour_wall),
@@ -101,6 +174,10 @@ impl FromWorld for AxiomLibrary {
             discriminant(&Axiom::Abjuration),
             world.register_system(axiom_function_abjuration),
         );
+        axioms.library.insert(
+            discriminant(&Axiom::RotateBelts),
+            world.register_system(axiom_function_rotate_belts),
+        );
         axioms.library.insert(
             discriminant(&Axiom::HealOrHarm { amount: 1 }),
             world.register_system(axiom_function_heal_or_harm),
@@ -113,6 +190,12 @@ impl FromWorld for AxiomLibrary {
             }),
             world.register_system(axiom_function_status_effect),
         );
+        axioms.library.insert(
+            discriminant(&Axiom::Cleanse {
+                effects: Vec::new(),
+            }),
+            world.register_system(axiom_function_cleanse),
+        );
         axioms.library.insert(
             discriminant(&Axiom::UpgradeStatusEffect {
                 effect: StatusEffect::Invincible,
@@ -150,6 +233,10 @@ impl FromWorld for AxiomLibrary {
             discriminant(&Axiom::PiercingBeams),
             world.register_system(axiom_mutator_piercing_beams),
         );
+        axioms.library.insert(
+            discriminant(&Axiom::Phasing),
+            world.register_system(axiom_mutator_phasing),
+        );
         axioms.library.insert(
             discriminant(&Axiom::PurgeTargets),
             world.register_system(axiom_mutator_purge_targets),
@@ -171,6 +258,10 @@ impl FromWorld for AxiomLibrary {
             }),
             world.register_system(axiom_mutator_filter_by_species),
         );
+        axioms.library.insert(
+            discriminant(&Axiom::FilterByTag { tag: Tag::Wall }),
+            world.register_system(axiom_mutator_filter_by_tag),
+        );
         axioms.library.insert(
             discriminant(&Axiom::LoopBack { steps: 1 }),
             world.register_system(axiom_mutator_loop_back),
@@ -179,6 +270,16 @@ impl FromWorld for AxiomLibrary {
             discriminant(&Axiom::ForceCast),
             world.register_system(axiom_function_force_cast),
         );
+        axioms.library.insert(
+            discriminant(&Axiom::Counterspell),
+            world.register_system(axiom_function_counterspell),
+        );
+        axioms.library.insert(
+            discriminant(&Axiom::Script {
+                source: String::new(),
+            }),
+            world.register_system(axiom_function_script),
+        );
         axioms
     }
 }
@@ -190,6 +291,28 @@ pub struct SpellStack {
     pub spells: Vec<SynapseData>,
 }
 
+impl SpellStack {
+    /// The index of each synapse currently targeting this creature, which was cast
+    /// by someone else and still has something hostile left to do.
+    pub fn hostile_synapses_targeting(&self, entity: Entity, map: &Map) -> Vec<usize> {
+        self.spells
+            .iter()
+            .enumerate()
+            .filter(|(_, synapse_data)| {
+                synapse_data.caster != entity
+                    && !synapse_data.synapse_flags.contains(&SynapseFlag::Terminate)
+                    && synapse_data.axioms[synapse_data.step..]
+                        .iter()
+                        .any(Axiom::is_hostile)
+                    && synapse_data
+                        .get_all_targeted_entities(map)
+                        .contains(&entity)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
 #[derive(Event, Debug)]
 /// Triggered when a creature performs an action corresponding to a certain Contingency.
 pub struct TriggerContingency {
@@ -200,23 +323,45 @@ pub struct TriggerContingency {
 pub fn trigger_contingency(
     mut events: EventReader<TriggerContingency>,
     spellbook: Query<&Spellbook>,
+    death_effects: Query<&DeathEffect>,
     mut cast_spell: EventWriter<CastSpell>,
+    difficulty: Res<GameDifficulty>,
+    turn_manager: Res<TurnManager>,
+    // The turn, and how many contingent spells were cast during it.
+    mut triggered: Local<(usize, usize)>,
 ) {
+    if triggered.0 != turn_manager.turn_count {
+        *triggered = (turn_manager.turn_count, 0);
+    }
     for event in events.read() {
-        if let Ok(spellbook) = spellbook.get(event.caster) {
-            for (soul, spell) in spellbook.spells.iter() {
-                if let Some(contingency_index) = spell
-                    .axioms
-                    .iter()
-                    .position(|axiom| axiom == &event.contingency)
-                {
-                    cast_spell.send(CastSpell {
-                        caster: event.caster,
-                        spell: spell.clone(),
-                        starting_step: contingency_index,
-                        soul_caste: *soul,
-                    });
+        let spells = spellbook
+            .get(event.caster)
+            .into_iter()
+            .flat_map(|spellbook| spellbook.spells.iter());
+        let death_effect = death_effects
+            .get(event.caster)
+            .ok()
+            .map(|death_effect| (&death_effect.soul, &death_effect.spell));
+        for (soul, spell) in spells.chain(death_effect) {
+            if let Some(contingency_index) = spell
+                .axioms
+                .iter()
+                .position(|axiom| axiom == &event.contingency)
+            {
+                // NOTE: Past the limit, contingencies silently fizzle.
+                if triggered.1 >= difficulty.contingency_limit {
+                    continue;
                 }
+                triggered.1 += 1;
+                cast_spell.send(CastSpell {
+                    caster: event.caster,
+                    spell: spell.clone(),
+                    starting_step: contingency_index,
+                    soul_caste: *soul,
+                    target: None,
+                    from_wheel: false,
+                    from_locked_slot: false,
+                });
             }
         }
     }
@@ -229,16 +374,34 @@ pub struct CastSpell {
     pub spell: Spell,
     pub starting_step: usize,
     pub soul_caste: Soul,
+    /// The tile picked in ControlState::Targeting, for Axiom::CursorTarget.
+    pub target: Option<Position>,
+    /// Whether a soul was paid for this spell on the Soul Wheel.
+    pub from_wheel: bool,
+    /// Whether it was cast from a Soul Wheel slot locked to its caste, for free.
+    pub from_locked_slot: bool,
 }
 
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 /// A spell is composed of a list of "Axioms", which will select tiles or execute an effect onto
 /// those tiles, in the order they are listed.
 pub struct Spell {
     pub axioms: Vec<Axiom>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// How much axiom cost a single soul pays for. See Spell::soul_cost.
+pub const SOUL_POWER: usize = 10;
+
+impl Spell {
+    /// How many souls of its caste casting this spell from the Soul Wheel takes,
+    /// including the one being cast. Long chains of axioms burn extra souls.
+    pub fn soul_cost(&self) -> usize {
+        let total: usize = self.axioms.iter().map(Axiom::cost).sum();
+        total.div_ceil(SOUL_POWER).max(1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 /// There are Form axioms, which target certain tiles, and Function axioms, which execute an effect
 /// onto those tiles.
 pub enum Axiom {
@@ -253,6 +416,10 @@ pub enum Axiom {
     WhenDealingDamage,
     // Triggers when this creature takes damage.
     WhenTakingDamage,
+    // Triggers when a circuit this creature is wired to gets powered.
+    WhenPowered,
+    // Triggers when a circuit this creature is wired to stops being powered.
+    WhenUnpowered,
 
     // FORMS
     /// Target the caster's tile.
@@ -270,18 +437,37 @@ pub enum Axiom {
     PlusBeam,
     /// Target all orthogonally adjacent tiles to the caster.
     Plus,
-    /// Target the tile adjacent to the caster, towards the caster's last move.
+    /// Target the tile adjacent to the caster, towards the caster's last move (see AimMode).
     Touch,
     /// Target a ring of `radius` around the caster.
     Halo {
         radius: i32,
     },
+    /// Target every tile the caster can see, up to `range` tiles away.
+    /// Walls are targeted, but hide everything behind them.
+    LineOfSight {
+        range: i32,
+    },
+    /// Starting from the targeted tile furthest from the caster, jump up to `jumps` times
+    /// to the nearest untargeted creature within `range` tiles, targeting each one.
+    ChainFromTarget {
+        jumps: usize,
+        range: i32,
+    },
+    /// Target the tile picked by the player with the targeting cursor.
+    /// Creatures without a cursor, like NPCs, target the player's tile instead.
+    CursorTarget,
 
     // FUNCTIONS
-    /// The targeted creatures dash in the direction of the caster's last move.
+    /// The targeted creatures dash in the direction of the caster's last move (see AimMode).
     Dash {
         max_distance: i32,
     },
+    /// The targeted creatures are pushed away from the caster. If something stops them
+    /// early, they slam into it: walls hurt them, other creatures are attacked.
+    Knockback {
+        distance: i32,
+    },
     /// The targeted passable tiles summon a new instance of species.
     SummonCreature {
         species: Species,
@@ -289,11 +475,17 @@ pub enum Axiom {
     /// The targeted tiles summon a step-triggered trap with following axioms as the payload.
     /// This terminates the spell.
     PlaceStepTrap,
+    /// The targeted tiles launch a projectile towards the caster's last move (see AimMode),
+    /// with following axioms as the payload, cast on whatever it hits.
+    /// This terminates the spell.
+    LaunchProjectile,
     /// Any targeted creature with the Wall component is removed.
     /// Each removed wall heals the caster +1.
     DevourWall,
     /// All creatures summoned by targeted creatures are removed.
     Abjuration,
+    /// Every conveyor belt on the targeted tiles turns clockwise.
+    RotateBelts,
     /// All targeted creatures heal or are harmed by this amount.
     HealOrHarm {
         amount: isize,
@@ -310,6 +502,11 @@ pub enum Axiom {
         potency: usize,
         stacks: EffectDuration,
     },
+    /// Remove these status effects from all targeted creatures.
+    /// If none are listed, remove every negative one.
+    Cleanse {
+        effects: Vec<StatusEffect>,
+    },
     /// Add a certain amount to the counter, for use with "TerminateIfCounter"
     IncrementCounter {
         amount: i32,
@@ -322,6 +519,13 @@ pub enum Axiom {
     /// Force all creatures on targeted tiles to cast the remainder of the spell.
     /// This terminates execution of the spell.
     ForceCast,
+    /// Terminate every hostile spell currently targeting the caster.
+    Counterspell,
+    /// Run a few commands written out by hand, see ScriptCommand.
+    /// For trying out new effects without writing a new axiom first.
+    Script {
+        source: String,
+    },
 
     // MUTATORS
     /// Any Teleport event will target all tiles between its start and destination tiles.
@@ -332,6 +536,8 @@ pub enum Axiom {
     UntargetCaster,
     /// All Beam-type Forms will pierce through non-Spellproof creatures.
     PiercingBeams,
+    /// Following Functions also affect targets hidden behind walls, see enforce_line_of_effect.
+    Phasing,
     /// Remove all targets.
     PurgeTargets,
     /// If the synapse's counter is [condition] than the value, terminate.
@@ -345,6 +551,10 @@ pub enum Axiom {
     FilterBySpecies {
         species: Species,
     },
+    /// Remove all targets not targeting a creature with this tag.
+    FilterByTag {
+        tag: Tag,
+    },
     // End this spell.
     Terminate,
     /// Only once, loop backwards `steps` in the axiom queue.
@@ -353,7 +563,182 @@ pub enum Axiom {
     },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+impl Axiom {
+    /// How much this axiom adds to the soul cost of its spell.
+    pub fn cost(&self) -> usize {
+        match self {
+            Axiom::WhenMoved
+            | Axiom::WhenSteppedOn
+            | Axiom::WhenRemoved
+            | Axiom::WhenDealingDamage
+            | Axiom::WhenTakingDamage
+            | Axiom::WhenPowered
+            | Axiom::WhenUnpowered
+            | Axiom::Terminate
+            | Axiom::TerminateIfCounter { .. }
+            | Axiom::PurgeTargets => 0,
+            Axiom::XBeam | Axiom::PlusBeam => 2,
+            Axiom::Halo { radius } => 1 + radius.unsigned_abs() as usize / 2,
+            Axiom::LineOfSight { range } => 1 + range.unsigned_abs() as usize / 2,
+            Axiom::ChainFromTarget { jumps, range } => jumps + range.unsigned_abs() as usize / 2,
+            Axiom::Dash { max_distance } => 1 + max_distance.unsigned_abs() as usize / 3,
+            Axiom::Knockback { distance } => 1 + distance.unsigned_abs() as usize / 3,
+            Axiom::HealOrHarm { amount } => amount.unsigned_abs().div_ceil(2),
+            Axiom::LoopBack { .. }
+            | Axiom::ForceCast
+            | Axiom::Counterspell
+            | Axiom::Script { .. } => 3,
+            Axiom::LaunchProjectile | Axiom::Phasing => 2,
+            _ => 1,
+        }
+    }
+
+    /// Whether this axiom waits for something to happen, instead of doing anything.
+    pub fn is_contingency(&self) -> bool {
+        matches!(
+            self,
+            Axiom::WhenMoved
+                | Axiom::WhenSteppedOn
+                | Axiom::WhenRemoved
+                | Axiom::WhenDealingDamage
+                | Axiom::WhenTakingDamage
+                | Axiom::WhenPowered
+                | Axiom::WhenUnpowered
+        )
+    }
+
+    /// Whether this axiom picks tiles to target.
+    pub fn is_form(&self) -> bool {
+        matches!(
+            self,
+            Axiom::Ego
+                | Axiom::Player
+                | Axiom::MomentumBeam
+                | Axiom::XBeam
+                | Axiom::PlusBeam
+                | Axiom::Plus
+                | Axiom::Touch
+                | Axiom::Halo { .. }
+                | Axiom::LineOfSight { .. }
+                | Axiom::ChainFromTarget { .. }
+                | Axiom::CursorTarget
+        )
+    }
+
+    /// Whether the spell ends for good after this axiom, until the next contingency.
+    // NOTE: PlaceStepTrap, LaunchProjectile and ForceCast end the spell too, but
+    // hand the rest of it to something else as its payload, see validate_spell.
+    pub fn terminates(&self) -> bool {
+        matches!(self, Axiom::Terminate)
+    }
+
+    /// Whether this axiom acts on the creatures in its targets, and so needs a clear line
+    /// from the caster to reach them. Forms pick their own tiles, and some, like Halo,
+    /// gladly pick tiles behind walls: this is where those get weeded out.
+    pub fn needs_line_of_effect(&self) -> bool {
+        matches!(
+            self,
+            Axiom::Dash { .. }
+                | Axiom::Knockback { .. }
+                | Axiom::SummonCreature { .. }
+                | Axiom::PlaceStepTrap
+                | Axiom::DevourWall
+                | Axiom::HealOrHarm { .. }
+                | Axiom::StatusEffect { .. }
+                | Axiom::UpgradeStatusEffect { .. }
+                | Axiom::Cleanse { .. }
+                | Axiom::Transform { .. }
+                | Axiom::ForceCast
+                | Axiom::Script { .. }
+                | Axiom::RotateBelts
+        )
+    }
+
+    /// Whether this Function does nothing unless there are creatures in its targets.
+    pub fn acts_on_creatures(&self) -> bool {
+        matches!(
+            self,
+            Axiom::Dash { .. }
+                | Axiom::Knockback { .. }
+                | Axiom::DevourWall
+                | Axiom::Abjuration
+                | Axiom::HealOrHarm { .. }
+                | Axiom::StatusEffect { .. }
+                | Axiom::UpgradeStatusEffect { .. }
+                | Axiom::Cleanse { .. }
+                | Axiom::Transform { .. }
+                | Axiom::ForceCast
+        )
+    }
+
+    /// Whether this Function acts on its targeted tiles, creatures or not.
+    pub fn acts_on_tiles(&self) -> bool {
+        matches!(
+            self,
+            Axiom::SummonCreature { .. }
+                | Axiom::PlaceStepTrap
+                | Axiom::LaunchProjectile
+                | Axiom::RotateBelts
+        )
+    }
+
+    /// Whether this axiom does something unwelcome to the creatures it targets.
+    pub fn is_hostile(&self) -> bool {
+        match self {
+            Axiom::HealOrHarm { amount } => *amount < 0,
+            Axiom::StatusEffect { effect, .. } => effect.is_negative(),
+            Axiom::Knockback { .. } | Axiom::DevourWall | Axiom::Transform { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Something which is probably a mistake in a spell, see validate_spell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpellWarning {
+    /// This Function has no Form before it to give it targets.
+    NoForm(usize),
+    /// A terminating axiom comes before this one, so it never runs.
+    Unreachable(usize),
+}
+
+/// Look for axioms which cannot do anything where they are. Each contingency
+/// starts the checks over, as it is where the spell starts when it triggers.
+/// So does each axiom which carries the rest of the spell as a payload.
+pub fn validate_spell(spell: &Spell) -> Vec<SpellWarning> {
+    let mut warnings = Vec::new();
+    let (mut has_form, mut terminated) = (false, false);
+    for (i, axiom) in spell.axioms.iter().enumerate() {
+        if axiom.is_contingency() {
+            (has_form, terminated) = (false, false);
+            continue;
+        }
+        if terminated {
+            warnings.push(SpellWarning::Unreachable(i));
+            continue;
+        }
+        // Trace turns movement into targets.
+        has_form |= axiom.is_form() || *axiom == Axiom::Trace;
+        // Projectiles are launched from each targeted tile, so they need some too.
+        let needs_targets = axiom.needs_line_of_effect()
+            || matches!(axiom, Axiom::Abjuration | Axiom::LaunchProjectile);
+        if needs_targets && !has_form {
+            warnings.push(SpellWarning::NoForm(i));
+        }
+        match axiom {
+            // The trap's spell starts with WhenSteppedOn, which picks no tiles.
+            Axiom::PlaceStepTrap => has_form = false,
+            // The projectile's payload starts with CursorTarget, on the tile it hits.
+            Axiom::LaunchProjectile => has_form = true,
+            // Each targeted creature casts the rest of the spell as is.
+            Axiom::ForceCast => has_form = false,
+            _ => terminated = axiom.terminates(),
+        }
+    }
+    warnings
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CounterCondition {
     LessThan,
     NotModuloOf { modulo: i32 },
@@ -368,32 +753,63 @@ pub struct SynapseData {
     pub axioms: Vec<Axiom>,
     /// The nth axiom currently being executed.
     pub step: usize,
+    /// Whether a soul was paid for this spell on the Soul Wheel.
+    from_wheel: bool,
+    /// Whether it was cast for free from a locked Soul Wheel slot.
+    from_locked_slot: bool,
     /// Who cast the spell.
     pub caster: Entity,
     /// Flags that alter the behaviour of an active synapse.
     synapse_flags: HashSet<SynapseFlag>,
     soul_caste: Soul,
+    /// The tile picked with the targeting cursor, if any.
+    cursor_target: Option<Position>,
+    /// Whether any Function found something to act on, see check_for_fizzles.
+    landed: bool,
+    /// Why the last Function to find nothing did so.
+    fizzle: Option<FizzleReason>,
 }
 
 impl SynapseData {
     /// Create a blank SynapseData.
-    fn new(caster: Entity, axioms: Vec<Axiom>, step: usize, soul_caste: Soul) -> Self {
+    fn new(
+        caster: Entity,
+        axioms: Vec<Axiom>,
+        step: usize,
+        soul_caste: Soul,
+        cursor_target: Option<Position>,
+        from_wheel: bool,
+        from_locked_slot: bool,
+    ) -> Self {
         SynapseData {
             targets: HashSet::new(),
             axioms,
             step,
+            from_wheel,
+            from_locked_slot,
             caster,
             synapse_flags: HashSet::new(),
             soul_caste,
+            cursor_target,
+            landed: false,
+            fizzle: None,
         }
     }
 
     /// Get the Entity of each creature standing on a tile inside `targets` and its position.
+    /// Large creatures are only affected once, no matter how many of their tiles are hit.
     fn get_all_targeted_entity_pos_pairs(&self, map: &Map) -> Vec<(Entity, Position)> {
         let mut targeted_pairs = Vec::new();
-        for target in &self.targets {
+        let mut already_targeted = HashSet::new();
+        // HashSets have no stable order. Large creatures must be hit on the same
+        // tile (and WeakPoint) every time, and creatures affected in the same order.
+        let mut targets: Vec<&Position> = self.targets.iter().collect();
+        targets.sort_by_key(|target| (target.x, target.y));
+        for target in targets {
             if let Some(creature) = map.get_entity_at(target.x, target.y) {
-                targeted_pairs.push((*creature, *target));
+                if already_targeted.insert(*creature) {
+                    targeted_pairs.push((*creature, *target));
+                }
             }
         }
         targeted_pairs
@@ -420,6 +836,8 @@ pub enum SynapseFlag {
     Trace,
     /// All Beam-type Forms will pierce non-Wall creatures.
     PiercingBeams,
+    /// Functions ignore the line of effect.
+    Phasing,
     /// A Counter, to go in tandem with TerminateIfCounter
     Counter { count: i32 },
 }
@@ -438,6 +856,9 @@ pub fn cast_new_spell(
             axioms,
             cast_spell.starting_step,
             cast_spell.soul_caste,
+            cast_spell.target,
+            cast_spell.from_wheel,
+            cast_spell.from_locked_slot,
         );
         // Send it off for processing - right away, for the spell stack is "last in, first out."
         spell_stack.spells.push(synapse_data);
@@ -462,11 +883,38 @@ fn axiom_form_ego(
         effect: EffectType::RedBlast,
         decay: 0.5,
         appear: 0.,
+        caste: Some(synapse_data.soul_caste),
     });
     // Add that caster's position to the targets.
     synapse_data.targets.insert(caster_position);
 }
 
+/// Target the tile picked with the targeting cursor, or the player's tile
+/// if there is none.
+fn axiom_form_cursor_target(
+    In(spell_idx): In<usize>,
+    mut magic_vfx: EventWriter<PlaceMagicVfx>,
+    mut spell_stack: ResMut<SpellStack>,
+    player: Query<&Position, With<Player>>,
+) {
+    let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
+    let Some(target) = synapse_data
+        .cursor_target
+        .or_else(|| player.get_single().ok().copied())
+    else {
+        return;
+    };
+    magic_vfx.send(PlaceMagicVfx {
+        targets: vec![target],
+        sequence: EffectSequence::Sequential { duration: 0.04 },
+        effect: EffectType::RedBlast,
+        decay: 0.5,
+        appear: 0.,
+        caste: Some(synapse_data.soul_caste),
+    });
+    synapse_data.targets.insert(target);
+}
+
 /// Target the player's tile.
 fn axiom_form_player(
     In(spell_idx): In<usize>,
@@ -485,6 +933,7 @@ fn axiom_form_player(
         effect: EffectType::RedBlast,
         decay: 0.5,
         appear: 0.,
+        caste: Some(synapse_data.soul_caste),
     });
     // Add that caster's position to the targets.
     synapse_data.targets.insert(player_position);
@@ -513,28 +962,50 @@ fn axiom_form_plus(
         effect: EffectType::GreenBlast,
         decay: 0.5,
         appear: 0.,
+        caste: Some(synapse_data.soul_caste),
     });
     synapse_data.targets.extend(&output);
 }
 
-/// The targeted creatures dash in the direction of the caster's last move.
+/// The targeted creatures dash in the direction of the caster's last move (see AimMode).
 fn axiom_function_dash(
     In(spell_idx): In<usize>,
     library: Res<AxiomLibrary>,
     mut commands: Commands,
     map: Res<Map>,
     spell_stack: Res<SpellStack>,
-    momentum: Query<&OrdDir>,
+    momentum: Query<(&OrdDir, Option<&Facing>)>,
+    aim_mode: Res<AimMode>,
     spellproof_query: Query<&Spellproof>,
     flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
+    mut residual: ResMut<ResidualMomentum>,
+    species: Query<&Species>,
+    registry: Res<SpeciesRegistry>,
 ) {
     let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
-    let caster_momentum = momentum.get(synapse_data.caster).unwrap();
+    let bulk_of = |entity: Entity| {
+        species
+            .get(entity)
+            .map_or(Bulk::default(), |species| registry.get(species).bulk)
+    };
+    let (caster_momentum, caster_facing) = momentum.get(synapse_data.caster).unwrap();
+    let caster_momentum = aim_mode.direction(caster_momentum, caster_facing);
     if let Axiom::Dash { max_distance } = synapse_data.axioms[synapse_data.step] {
         // For each (Entity, Position) on a targeted tile with a creature on it...
         for (dasher, dasher_pos) in synapse_data.get_all_targeted_entity_pos_pairs(&map) {
             // Spellproof entities cannot be affected.
             if is_spellproof(dasher, &flags, &spellproof_query) {
+                deflect.send(Deflect {
+                    entity: dasher,
+                    culprit: synapse_data.caster,
+                    kind: DeflectKind::Spell,
+                });
+                continue;
+            }
+            let dasher_bulk = bulk_of(dasher);
+            // Heavy creatures only dash of their own accord.
+            if dasher != synapse_data.caster && !dasher_bulk.can_be_thrown() {
                 continue;
             }
             // The dashing creature starts where it currently is standing.
@@ -543,17 +1014,46 @@ fn axiom_function_dash(
             let (off_x, off_y) = caster_momentum.as_offset();
             // The dash has a maximum travel distance of `max_distance`.
             let mut distance_travelled = 0;
+            let mut path = Vec::new();
             while distance_travelled < max_distance {
                 distance_travelled += 1;
                 // Stop dashing if a solid Creature is hit (not implemented: "and the dasher is not intangible").
-                if !map.is_passable(
+                let next = Position::new(
                     final_dash_destination.x + off_x,
                     final_dash_destination.y + off_y,
-                ) {
+                );
+                if !map.is_passable(next.x, next.y) {
+                    // Lighter creatures are shoved one tile aside, if there is room.
+                    let beyond = Position::new(next.x + off_x, next.y + off_y);
+                    if let Some(blocker) = map.get_entity_at(next.x, next.y) {
+                        if bulk_of(*blocker) < dasher_bulk && map.is_passable(beyond.x, beyond.y) {
+                            commands.run_system_with_input(
+                                library.teleport,
+                                (
+                                    TeleportEntity {
+                                        destination: beyond,
+                                        entity: *blocker,
+                                    },
+                                    spell_idx,
+                                ),
+                            );
+                            final_dash_destination = next;
+                            path.push(next);
+                        }
+                    }
                     break;
                 }
                 // Otherwise, keep offsetting the dashing creature's position.
                 final_dash_destination.shift(off_x, off_y);
+                path.push(final_dash_destination);
+            }
+            // Conveyor belts crossed on the way may carry it a little further.
+            if !path.is_empty() {
+                residual.flung.push(Flung {
+                    entity: dasher,
+                    direction: caster_momentum,
+                    path,
+                });
             }
 
             // Once finished, release the Teleport event.
@@ -575,20 +1075,116 @@ fn axiom_function_dash(
     }
 }
 
-/// Fire a beam from the caster, towards the caster's last move. Target all travelled tiles,
-/// including the first solid tile encountered, which stops the beam.
+/// How much damage a knocked back creature takes from slamming into a wall.
+const KNOCKBACK_SLAM_DAMAGE: isize = 1;
+
+/// The targeted creatures are pushed away from the caster, instead of
+/// towards the caster's last move like Dash.
+fn axiom_function_knockback(
+    In(spell_idx): In<usize>,
+    library: Res<AxiomLibrary>,
+    mut commands: Commands,
+    map: Res<Map>,
+    spell_stack: Res<SpellStack>,
+    creatures: Query<(&Position, Option<&Footprint>)>,
+    spellproof_query: Query<&Spellproof>,
+    flags: Query<&CreatureFlags>,
+    wall_query: Query<(), With<Wall>>,
+    mut deflect: EventWriter<Deflect>,
+    mut harm: EventWriter<DamageOrHealCreature>,
+    mut collision: EventWriter<CreatureCollision>,
+    mut residual: ResMut<ResidualMomentum>,
+    species: Query<&Species>,
+    registry: Res<SpeciesRegistry>,
+) {
+    let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
+    let caster_position = *creatures.get(synapse_data.caster).unwrap().0;
+    if let Axiom::Knockback { distance } = synapse_data.axioms[synapse_data.step] {
+        for (pushed, _) in synapse_data.get_all_targeted_entity_pos_pairs(&map) {
+            if is_spellproof(pushed, &flags, &spellproof_query) {
+                deflect.send(Deflect {
+                    entity: pushed,
+                    culprit: synapse_data.caster,
+                    kind: DeflectKind::Spell,
+                });
+                continue;
+            }
+            let (pushed_pos, footprint) = creatures.get(pushed).unwrap();
+            // The caster can't push itself away from itself.
+            let Some(direction) = OrdDir::direction_towards(caster_position, *pushed_pos) else {
+                continue;
+            };
+            let (off_x, off_y) = direction.as_offset();
+            let mut destination = *pushed_pos;
+            let mut path = Vec::new();
+            let mut blocker = None;
+            let distance = species.get(pushed).map_or(distance, |species| {
+                registry.get(species).bulk.resist(distance)
+            });
+            for _ in 0..distance {
+                let next = Position::new(destination.x + off_x, destination.y + off_y);
+                blocker = map.blocker_for(pushed, &occupied_tiles(next, footprint));
+                if blocker.is_some() {
+                    break;
+                }
+                destination = next;
+                path.push(next);
+            }
+            if let Some((tile, obstacle)) = blocker {
+                let is_wall = flags
+                    .get(obstacle)
+                    .is_ok_and(|flags| wall_query.flagged(flags));
+                if is_wall {
+                    harm.send(DamageOrHealCreature {
+                        entity: pushed,
+                        culprit: synapse_data.caster,
+                        hp_mod: -KNOCKBACK_SLAM_DAMAGE,
+                        tile: None,
+                        kind: DamageKind::Spell,
+                    });
+                } else {
+                    collision.send(CreatureCollision::new(pushed, obstacle, tile));
+                }
+            }
+            if destination != *pushed_pos {
+                residual.flung.push(Flung {
+                    entity: pushed,
+                    direction,
+                    path,
+                });
+                commands.run_system_with_input(
+                    library.teleport,
+                    (
+                        TeleportEntity {
+                            destination,
+                            entity: pushed,
+                        },
+                        spell_idx,
+                    ),
+                );
+            }
+        }
+    } else {
+        panic!()
+    }
+}
+
+/// Fire a beam from the caster, towards the caster's last move (see AimMode). Target all
+/// travelled tiles, including the first solid tile encountered, which stops the beam.
 fn axiom_form_momentum_beam(
     In(spell_idx): In<usize>,
     mut magic_vfx: EventWriter<PlaceMagicVfx>,
     map: Res<Map>,
     mut spell_stack: ResMut<SpellStack>,
-    position_and_momentum: Query<(&Position, &OrdDir)>,
+    position_and_momentum: Query<(&Position, &OrdDir, Option<&Facing>)>,
+    aim_mode: Res<AimMode>,
     spellproof_query: Query<&Spellproof>,
     flags: Query<&CreatureFlags>,
 ) {
     let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
-    let (caster_position, caster_momentum) =
+    let (caster_position, caster_momentum, caster_facing) =
         position_and_momentum.get(synapse_data.caster).unwrap();
+    let caster_momentum = aim_mode.direction(caster_momentum, caster_facing);
     // Start the beam where the caster is standing.
     // The beam travels in the direction of the caster's last move.
     let (off_x, off_y) = caster_momentum.as_offset();
@@ -613,6 +1209,7 @@ fn axiom_form_momentum_beam(
         },
         decay: 0.5,
         appear: 0.,
+        caste: Some(synapse_data.soul_caste),
     });
     // Add these tiles to `targets`.
     synapse_data.targets.extend(&output);
@@ -653,6 +1250,7 @@ fn axiom_form_xbeam(
             effect: EffectType::RedBlast,
             decay: 0.5,
             appear: 0.,
+            caste: Some(synapse_data.soul_caste),
         });
         // Add these tiles to `targets`.
         synapse_data.targets.extend(&output);
@@ -698,22 +1296,25 @@ fn axiom_form_plus_beam(
             },
             decay: 0.5,
             appear: 0.,
+            caste: Some(synapse_data.soul_caste),
         });
         // Add these tiles to `targets`.
         synapse_data.targets.extend(&output);
     }
 }
 
-/// Target the tile adjacent to the caster, towards the caster's last move.
+/// Target the tile adjacent to the caster, towards the caster's last move (see AimMode).
 fn axiom_form_touch(
     In(spell_idx): In<usize>,
     mut magic_vfx: EventWriter<PlaceMagicVfx>,
     mut spell_stack: ResMut<SpellStack>,
-    position_and_momentum: Query<(&Position, &OrdDir)>,
+    position_and_momentum: Query<(&Position, &OrdDir, Option<&Facing>)>,
+    aim_mode: Res<AimMode>,
 ) {
     let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
-    let (caster_position, caster_momentum) =
+    let (caster_position, caster_momentum, caster_facing) =
         position_and_momentum.get(synapse_data.caster).unwrap();
+    let caster_momentum = aim_mode.direction(caster_momentum, caster_facing);
     let (off_x, off_y) = caster_momentum.as_offset();
     let touch = Position::new(caster_position.x + off_x, caster_position.y + off_y);
     synapse_data.targets.insert(touch);
@@ -723,6 +1324,7 @@ fn axiom_form_touch(
         effect: EffectType::RedBlast,
         decay: 0.5,
         appear: 0.,
+        caste: Some(synapse_data.soul_caste),
     });
 }
 
@@ -750,6 +1352,7 @@ fn axiom_form_halo(
             effect: EffectType::GreenBlast,
             decay: 0.5,
             appear: 0.,
+            caste: Some(synapse_data.soul_caste),
         });
         // Add these tiles to `targets`.
         synapse_data.targets.extend(&circle);
@@ -758,6 +1361,90 @@ fn axiom_form_halo(
     }
 }
 
+/// Target every tile the caster can see, up to `range` tiles away.
+fn axiom_form_line_of_sight(
+    In(spell_idx): In<usize>,
+    mut magic_vfx: EventWriter<PlaceMagicVfx>,
+    mut spell_stack: ResMut<SpellStack>,
+    position: Query<&Position>,
+    flags: Query<&CreatureFlags>,
+    walls: Query<(), With<Wall>>,
+    map: Res<Map>,
+) {
+    let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
+    let caster_position = *position.get(synapse_data.caster).unwrap();
+    if let Axiom::LineOfSight { range } = synapse_data.axioms[synapse_data.step] {
+        let mut visible = map.visible_tiles(caster_position, range, |entity| {
+            flags.get(*entity).is_ok_and(|flags| walls.flagged(flags))
+        });
+        // The caster does not target itself.
+        visible.remove(&caster_position);
+        magic_vfx.send(PlaceMagicVfx {
+            targets: visible.iter().copied().collect(),
+            sequence: EffectSequence::Simultaneous,
+            effect: EffectType::GreenBlast,
+            decay: 0.5,
+            appear: 0.,
+            caste: Some(synapse_data.soul_caste),
+        });
+        synapse_data.targets.extend(visible);
+    } else {
+        panic!()
+    }
+}
+
+/// Jump from creature to creature, starting from the targeted tile furthest from the caster.
+/// Walls do not conduct.
+fn axiom_form_chain_from_target(
+    In(spell_idx): In<usize>,
+    mut magic_vfx: EventWriter<PlaceMagicVfx>,
+    mut spell_stack: ResMut<SpellStack>,
+    position: Query<&Position>,
+    flags: Query<&CreatureFlags>,
+    walls: Query<(), With<Wall>>,
+    map: Res<Map>,
+) {
+    let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
+    let caster_position = *position.get(synapse_data.caster).unwrap();
+    if let Axiom::ChainFromTarget { jumps, range } = synapse_data.axioms[synapse_data.step] {
+        let distance = |tile: &Position| {
+            (tile.x - caster_position.x).abs() + (tile.y - caster_position.y).abs()
+        };
+        // NOTE: Targets are a HashSet, ties are broken by position to stay deterministic.
+        let Some(mut current) = synapse_data
+            .targets
+            .iter()
+            .max_by_key(|tile| (distance(tile), -tile.x, -tile.y))
+            .copied()
+        else {
+            return;
+        };
+        let mut arcs = Vec::new();
+        for _ in 0..jumps {
+            let Some((next, _)) = map.nearest_creature(current, range, |tile, entity| {
+                *entity != synapse_data.caster
+                    && !synapse_data.targets.contains(tile)
+                    && flags.get(*entity).is_ok_and(|flags| !walls.flagged(flags))
+            }) else {
+                break;
+            };
+            arcs.extend(walk_grid(current, next).into_iter().skip(1));
+            synapse_data.targets.insert(next);
+            current = next;
+        }
+        magic_vfx.send(PlaceMagicVfx {
+            targets: arcs,
+            sequence: EffectSequence::Sequential { duration: 0.03 },
+            effect: EffectType::RedBlast,
+            decay: 0.5,
+            appear: 0.,
+            caste: Some(synapse_data.soul_caste),
+        });
+    } else {
+        panic!()
+    }
+}
+
 /// The targeted passable tiles summon a new instance of species.
 fn axiom_function_summon_creature(
     In(spell_idx): In<usize>,
@@ -776,6 +1463,8 @@ fn axiom_function_summon_creature(
                 summoner_tile: *caster_position,
                 summoner: Some(synapse_data.caster),
                 spellbook: None,
+                projectile: None,
+                owner: None,
             });
         }
     } else {
@@ -814,6 +1503,46 @@ fn axiom_function_place_step_trap(
                 None,
                 None,
             ])),
+            projectile: None,
+            // Whatever the trap slays is credited to whoever laid it.
+            owner: Some(synapse_data.caster),
+        });
+    }
+    synapse_data.synapse_flags.insert(SynapseFlag::Terminate);
+}
+
+/// The targeted tiles launch a projectile with the rest of the spell as its payload.
+fn axiom_function_launch_projectile(
+    In(spell_idx): In<usize>,
+    mut summon: EventWriter<SummonCreature>,
+    mut spell_stack: ResMut<SpellStack>,
+    momentum: Query<(&Position, &OrdDir, Option<&Facing>)>,
+    aim_mode: Res<AimMode>,
+) {
+    let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
+    let (caster_position, caster_momentum, caster_facing) =
+        momentum.get(synapse_data.caster).unwrap();
+    let velocity = aim_mode.direction(caster_momentum, caster_facing);
+    // It bursts on the tile it hits, see advance_projectiles.
+    let mut payload = vec![Axiom::CursorTarget];
+    payload.extend(synapse_data.axioms[synapse_data.step + 1..].to_vec());
+    for position in &synapse_data.targets {
+        summon.send(SummonCreature {
+            species: Species::Projectile,
+            position: *position,
+            momentum: velocity,
+            summoner_tile: *caster_position,
+            summoner: Some(synapse_data.caster),
+            spellbook: None,
+            projectile: Some(Projectile {
+                velocity,
+                owner: synapse_data.caster,
+                payload: Spell {
+                    axioms: payload.clone(),
+                },
+                caste: synapse_data.soul_caste,
+            }),
+            owner: Some(synapse_data.caster),
         });
     }
     synapse_data.synapse_flags.insert(SynapseFlag::Terminate);
@@ -871,15 +1600,13 @@ fn axiom_function_devour_wall(
     for entity in synapse_data.get_all_targeted_entities(&map) {
         let (is_wall, is_spellproof) = {
             let flags = flags.get(entity).unwrap();
-            (
-                wall_query.contains(flags.effects_flags)
-                    || wall_query.contains(flags.species_flags),
-                spellproof_query.contains(flags.effects_flags)
-                    || spellproof_query.contains(flags.species_flags),
-            )
+            (wall_query.flagged(flags), spellproof_query.flagged(flags))
         };
         if is_wall && !is_spellproof {
-            remove.send(RemoveCreature { entity });
+            remove.send(RemoveCreature {
+                entity,
+                culprit: None,
+            });
             total_heal = total_heal.saturating_add(1);
         }
     }
@@ -887,6 +1614,8 @@ fn axiom_function_devour_wall(
         entity: synapse_data.caster,
         culprit: synapse_data.caster,
         hp_mod: total_heal,
+        tile: None,
+        kind: DamageKind::Spell,
     });
 }
 
@@ -898,17 +1627,33 @@ fn axiom_function_heal_or_harm(
     map: Res<Map>,
     spellproof_query: Query<&Spellproof>,
     flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
+    mut noise: EventWriter<Noise>,
 ) {
     let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
     if let Axiom::HealOrHarm { amount } = synapse_data.axioms[synapse_data.step] {
-        for entity in synapse_data.get_all_targeted_entities(&map) {
+        for (entity, tile) in synapse_data.get_all_targeted_entity_pos_pairs(&map) {
+            // Harmful magic is loud, even when it is warded off.
+            if amount < 0 {
+                noise.send(Noise {
+                    origin: tile,
+                    radius: BLAST_NOISE,
+                });
+            }
             if is_spellproof(entity, &flags, &spellproof_query) {
+                deflect.send(Deflect {
+                    entity,
+                    culprit: synapse_data.caster,
+                    kind: DeflectKind::Spell,
+                });
                 continue;
             }
             heal.send(DamageOrHealCreature {
                 entity,
                 culprit: synapse_data.caster,
                 hp_mod: amount,
+                tile: Some(tile),
+                kind: DamageKind::Spell,
             });
         }
     } else {
@@ -924,6 +1669,7 @@ fn axiom_function_status_effect(
     map: Res<Map>,
     spellproof_query: Query<&Spellproof>,
     flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
 ) {
     let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
     if let Axiom::StatusEffect {
@@ -934,6 +1680,11 @@ fn axiom_function_status_effect(
     {
         for entity in synapse_data.get_all_targeted_entities(&map) {
             if is_spellproof(entity, &flags, &spellproof_query) {
+                deflect.send(Deflect {
+                    entity,
+                    culprit: synapse_data.caster,
+                    kind: DeflectKind::Spell,
+                });
                 continue;
             }
             status_effect.send(AddStatusEffect {
@@ -958,6 +1709,7 @@ fn axiom_function_upgrade_status_effect(
     map: Res<Map>,
     spellproof_query: Query<&Spellproof>,
     flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
 ) {
     let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
     if let Axiom::UpgradeStatusEffect {
@@ -968,6 +1720,11 @@ fn axiom_function_upgrade_status_effect(
     {
         for entity in synapse_data.get_all_targeted_entities(&map) {
             if is_spellproof(entity, &flags, &spellproof_query) {
+                deflect.send(Deflect {
+                    entity,
+                    culprit: synapse_data.caster,
+                    kind: DeflectKind::Spell,
+                });
                 continue;
             }
             let status_list = creature_status_effect.get(entity).unwrap();
@@ -986,6 +1743,60 @@ fn axiom_function_upgrade_status_effect(
     }
 }
 
+/// Strip status effects from all targeted creatures.
+fn axiom_function_cleanse(
+    In(spell_idx): In<usize>,
+    spell_stack: Res<SpellStack>,
+    map: Res<Map>,
+    mut creatures: Query<(&mut StatusEffectsList, &Species)>,
+    spellproof_query: Query<&Spellproof>,
+    flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
+    if let Axiom::Cleanse { effects } = &synapse_data.axioms[synapse_data.step] {
+        for entity in synapse_data.get_all_targeted_entities(&map) {
+            if is_spellproof(entity, &flags, &spellproof_query) {
+                deflect.send(Deflect {
+                    entity,
+                    culprit: synapse_data.caster,
+                    kind: DeflectKind::Spell,
+                });
+                continue;
+            }
+            let Ok((mut status_list, species)) = creatures.get_mut(entity) else {
+                continue;
+            };
+            let effects_flags = flags.get(entity).unwrap().effects_flags;
+            let mut cleansed = false;
+            for (effect, potency_and_stacks) in status_list.effects.iter_mut() {
+                let is_cleansed = if effects.is_empty() {
+                    effect.is_negative()
+                } else {
+                    effects.contains(effect)
+                };
+                if !is_cleansed || !potency_and_stacks.is_active() {
+                    continue;
+                }
+                // Same as expiring naturally at the end of a turn.
+                potency_and_stacks.potency = 0;
+                potency_and_stacks.stacks = EffectDuration::Finite { stacks: 0 };
+                strip_status_effect(*effect, effects_flags, &mut commands);
+                cleansed = true;
+            }
+            if cleansed {
+                text.send(AddMessage {
+                    message: Message::Cleansed(*species),
+                });
+            }
+        }
+    } else {
+        panic!();
+    }
+}
+
 fn axiom_function_increment_counter(
     In(spell_idx): In<usize>,
     mut spellbook: Query<&mut Spellbook>,
@@ -1035,35 +1846,68 @@ fn axiom_function_abjuration(
     summons: Query<(&Summoned, &FlagEntity)>,
     spellproof_query: Query<&Spellproof>,
     flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
 ) {
     let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
     for entity in synapse_data.get_all_targeted_entities(&map) {
         // Spellproof entities cannot be affected.
         if is_spellproof(entity, &flags, &spellproof_query) {
+            deflect.send(Deflect {
+                entity,
+                culprit: synapse_data.caster,
+                kind: DeflectKind::Spell,
+            });
             continue;
         }
         for (summoned_component, flag_entity) in summons.iter() {
             if summoned_component.summoner == entity {
                 remove.send(RemoveCreature {
                     entity: flag_entity.parent_creature,
+                    culprit: Some(synapse_data.caster),
                 });
             }
         }
     }
 }
 
+/// Every conveyor belt on the targeted tiles turns clockwise.
+// NOTE: Belts are Spellproof, but this is the one thing meant to affect them.
+fn axiom_function_rotate_belts(
+    In(spell_idx): In<usize>,
+    spell_stack: Res<SpellStack>,
+    belts: Query<(Entity, &Position, &OrdDir, &CreatureFlags)>,
+    conveyor_query: Query<(), With<Conveyor>>,
+    mut momentum: EventWriter<AlterMomentum>,
+) {
+    let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
+    for (entity, position, direction, flags) in belts.iter() {
+        if synapse_data.targets.contains(position) && conveyor_query.flagged(flags) {
+            momentum.send(AlterMomentum {
+                entity,
+                direction: direction.rotate_clockwise(),
+            });
+        }
+    }
+}
+
 fn axiom_function_transform(
     In(spell_idx): In<usize>,
     spell_stack: Res<SpellStack>,
     map: Res<Map>,
     spellproof_query: Query<&Spellproof>,
     flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
     mut transform: EventWriter<TransformCreature>,
 ) {
     let synapse_data = spell_stack.spells.get(spell_idx).unwrap();
     if let Axiom::Transform { species } = synapse_data.axioms[synapse_data.step] {
         for entity in synapse_data.get_all_targeted_entities(&map) {
             if is_spellproof(entity, &flags, &spellproof_query) {
+                deflect.send(Deflect {
+                    entity,
+                    culprit: synapse_data.caster,
+                    kind: DeflectKind::Spell,
+                });
                 continue;
             }
             transform.send(TransformCreature {
@@ -1088,6 +1932,12 @@ fn axiom_mutator_piercing_beams(In(spell_idx): In<usize>, mut spell_stack: ResMu
         .insert(SynapseFlag::PiercingBeams);
 }
 
+/// Following Functions also affect targets hidden behind walls.
+fn axiom_mutator_phasing(In(spell_idx): In<usize>, mut spell_stack: ResMut<SpellStack>) {
+    let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
+    synapse_data.synapse_flags.insert(SynapseFlag::Phasing);
+}
+
 /// All targeted tiles expand to also target their orthogonally adjacent tiles.
 fn axiom_mutator_spread(
     In(spell_idx): In<usize>,
@@ -1113,6 +1963,7 @@ fn axiom_mutator_spread(
             effect: EffectType::RedBlast,
             decay: 0.5,
             appear: 0.,
+            caste: Some(synapse_data.soul_caste),
         });
         synapse_data.targets.extend(&ord_dir_vec);
     }
@@ -1154,6 +2005,30 @@ fn axiom_mutator_filter_by_species(
     }
 }
 
+/// Remove all targets not targeting a creature with this tag.
+fn axiom_mutator_filter_by_tag(
+    In(spell_idx): In<usize>,
+    mut spell_stack: ResMut<SpellStack>,
+    flags: Query<&CreatureFlags>,
+    tags_query: Query<&Tags>,
+    map: Res<Map>,
+) {
+    let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
+    if let Axiom::FilterByTag { tag } = synapse_data.axioms[synapse_data.step] {
+        let mut retained_creatures = HashSet::new();
+        for (entity, position) in synapse_data.get_all_targeted_entity_pos_pairs(&map) {
+            let flags = flags.get(entity).unwrap();
+            if tags_query
+                .get(flags.species_flags)
+                .is_ok_and(|tags| tags.has(tag))
+            {
+                retained_creatures.insert(position);
+            }
+        }
+        synapse_data.targets = retained_creatures;
+    }
+}
+
 /// Only once, loop backwards `steps` in the axiom queue.
 fn axiom_mutator_loop_back(In(spell_idx): In<usize>, mut spell_stack: ResMut<SpellStack>) {
     let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
@@ -1168,6 +2043,28 @@ fn axiom_mutator_loop_back(In(spell_idx): In<usize>, mut spell_stack: ResMut<Spe
     }
 }
 
+/// Terminate every hostile spell currently targeting the caster.
+fn axiom_function_counterspell(
+    In(spell_idx): In<usize>,
+    mut spell_stack: ResMut<SpellStack>,
+    map: Res<Map>,
+    species: Query<&Species>,
+    mut text: EventWriter<AddMessage>,
+) {
+    let caster = spell_stack.spells[spell_idx].caster;
+    let countered = spell_stack.hostile_synapses_targeting(caster, &map);
+    for i in countered.iter() {
+        spell_stack.spells[*i]
+            .synapse_flags
+            .insert(SynapseFlag::Terminate);
+    }
+    if let (false, Ok(species)) = (countered.is_empty(), species.get(caster)) {
+        text.send(AddMessage {
+            message: Message::Countered(*species),
+        });
+    }
+}
+
 /// Force all creatures on targeted tiles to cast the remainder of the spell.
 /// This terminates execution of the spell.
 fn axiom_function_force_cast(
@@ -1189,11 +2086,144 @@ fn axiom_function_force_cast(
             },
             soul_caste: synapse_data.soul_caste,
             starting_step: 0,
+            // NOTE: The forced casters keep aiming where the original caster aimed.
+            target: synapse_data.cursor_target,
+            from_wheel: false,
+            from_locked_slot: false,
         });
     }
     synapse_data.synapse_flags.insert(SynapseFlag::Terminate);
 }
 
+/// Run each command of the script in order. Tiles added by `target` skip
+/// the line of effect check the targets before it went through.
+fn axiom_function_script(
+    In(spell_idx): In<usize>,
+    library: Res<AxiomLibrary>,
+    mut commands: Commands,
+    mut spell_stack: ResMut<SpellStack>,
+    map: Res<Map>,
+    position: Query<&Position>,
+    spellproof_query: Query<&Spellproof>,
+    flags: Query<&CreatureFlags>,
+    mut deflect: EventWriter<Deflect>,
+    mut heal: EventWriter<DamageOrHealCreature>,
+    mut summon: EventWriter<SummonCreature>,
+    mut noise: EventWriter<Noise>,
+) {
+    let synapse_data = spell_stack.spells.get_mut(spell_idx).unwrap();
+    let Axiom::Script { source } = &synapse_data.axioms[synapse_data.step] else {
+        panic!()
+    };
+    let script = match parse_script(source) {
+        Ok(script) => script,
+        Err(error) => {
+            warn!("{}", error);
+            return;
+        }
+    };
+    let caster = synapse_data.caster;
+    let caster_position = *position.get(caster).unwrap();
+    for command in script {
+        // Creatures which can be affected, for the commands which need them.
+        let targeted: Vec<(Entity, Position)> = if matches!(
+            command,
+            ScriptCommand::Damage(_) | ScriptCommand::Heal(_) | ScriptCommand::Push(..)
+        ) {
+            synapse_data
+                .get_all_targeted_entity_pos_pairs(&map)
+                .into_iter()
+                .filter(|(entity, _)| {
+                    let spellproof = is_spellproof(*entity, &flags, &spellproof_query);
+                    if spellproof {
+                        deflect.send(Deflect {
+                            entity: *entity,
+                            culprit: caster,
+                            kind: DeflectKind::Spell,
+                        });
+                    }
+                    !spellproof
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        match command {
+            ScriptCommand::Target(x, y) => {
+                synapse_data
+                    .targets
+                    .insert(Position::new(caster_position.x + x, caster_position.y + y));
+            }
+            ScriptCommand::Damage(amount) | ScriptCommand::Heal(amount) => {
+                let hp_mod = if matches!(command, ScriptCommand::Damage(_)) {
+                    -amount
+                } else {
+                    amount
+                };
+                for (entity, tile) in targeted {
+                    if hp_mod < 0 {
+                        noise.send(Noise {
+                            origin: tile,
+                            radius: BLAST_NOISE,
+                        });
+                    }
+                    heal.send(DamageOrHealCreature {
+                        entity,
+                        culprit: caster,
+                        hp_mod,
+                        tile: Some(tile),
+                        kind: DamageKind::Spell,
+                    });
+                }
+            }
+            ScriptCommand::Push(x, y) => {
+                for (entity, _) in targeted {
+                    let destination = *position.get(entity).unwrap();
+                    commands.run_system_with_input(
+                        library.teleport,
+                        (
+                            TeleportEntity {
+                                destination: Position::new(destination.x + x, destination.y + y),
+                                entity,
+                            },
+                            spell_idx,
+                        ),
+                    );
+                }
+            }
+            ScriptCommand::Summon(species) => {
+                for tile in synapse_data.targets.iter() {
+                    summon.send(SummonCreature {
+                        species,
+                        position: *tile,
+                        momentum: OrdDir::Down,
+                        summoner_tile: caster_position,
+                        summoner: Some(caster),
+                        spellbook: None,
+                        projectile: None,
+                        owner: None,
+                    });
+                }
+            }
+            ScriptCommand::Warp(x, y) => {
+                commands.run_system_with_input(
+                    library.teleport,
+                    (
+                        TeleportEntity {
+                            destination: Position::new(
+                                caster_position.x + x,
+                                caster_position.y + y,
+                            ),
+                            entity: caster,
+                        },
+                        spell_idx,
+                    ),
+                );
+            }
+        }
+    }
+}
+
 fn teleport_transmission(
     In((teleport_event, spell_idx)): In<(TeleportEntity, usize)>,
     position: Query<&Position>,
@@ -1216,6 +2246,7 @@ fn teleport_transmission(
                 effect: EffectType::RedBlast,
                 decay: 0.5,
                 appear: 0.,
+                caste: Some(synapse_data.soul_caste),
             });
             // Add these tiles to `targets`.
             synapse_data.targets.extend(&output);
@@ -1234,6 +2265,7 @@ fn linear_beam(
     queries: (&Query<&CreatureFlags>, &Query<&Spellproof>),
 ) -> Vec<Position> {
     let mut distance_travelled = 0;
+    let mut max_distance = max_distance;
     let mut output = Vec::new();
     // The beam has a maximum distance of max_distance.
     while distance_travelled < max_distance {
@@ -1241,6 +2273,10 @@ fn linear_beam(
         start.shift(off_x, off_y);
         // The new tile is always added, even if it is impassable...
         output.push(start);
+        // Large corpses are partial cover, which halves whatever range the beam had left.
+        if map.cover.contains(&start) {
+            max_distance = distance_travelled + (max_distance - distance_travelled) / 2;
+        }
         // But if it is impassable, the beam stops.
         if is_piercing {
             if let Some(possible_block) = map.get_entity_at(start.x, start.y) {
@@ -1255,6 +2291,153 @@ fn linear_beam(
     output
 }
 
+/// What a spell would do to a creature, as guessed by predict_spell.
+#[derive(Default)]
+pub struct PredictedOutcome {
+    /// A wall stands in the way, so none of this would actually happen.
+    pub blocked: bool,
+    pub hp_mod: isize,
+    pub effects: Vec<(StatusEffect, EffectDuration)>,
+}
+
+/// Everything predict_spell could guess about a spell.
+#[derive(Default)]
+pub struct SpellPrediction {
+    pub outcomes: HashMap<Entity, PredictedOutcome>,
+    /// Every tile the spell would land on, creature or not.
+    pub tiles: HashSet<Position>,
+}
+
+/// Walk through a spell's axioms without casting it, to guess what it would do to
+/// each creature. Spellproof creatures are left out, as they would deflect it.
+// NOTE: This stops at the first axiom it cannot guess the outcome of without actually
+// casting the spell, such as a Dash, which would move the caster somewhere else.
+pub fn predict_spell(
+    spell: &Spell,
+    caster_position: Position,
+    aim: OrdDir,
+    player_position: Option<Position>,
+    map: &Map,
+    queries: (&Query<&CreatureFlags>, &Query<&Spellproof>),
+    is_wall: impl Fn(&Position) -> bool,
+) -> SpellPrediction {
+    let mut outcomes: HashMap<Entity, PredictedOutcome> = HashMap::new();
+    let mut targets: HashSet<Position> = HashSet::new();
+    let mut tiles: HashSet<Position> = HashSet::new();
+    let (mut is_piercing, mut is_phasing) = (false, false);
+    let beams = |targets: &mut HashSet<Position>, directions: &[(i32, i32)], is_piercing| {
+        for (dx, dy) in directions {
+            targets.extend(linear_beam(
+                caster_position,
+                10,
+                *dx,
+                *dy,
+                map,
+                is_piercing,
+                queries,
+            ));
+        }
+    };
+    let affected = |targets: &HashSet<Position>| -> Vec<Entity> {
+        targets
+            .iter()
+            .filter_map(|tile| map.get_entity_at(tile.x, tile.y))
+            .filter(|entity| !is_spellproof(**entity, queries.0, queries.1))
+            .copied()
+            .collect()
+    };
+    for axiom in &spell.axioms {
+        // Same as enforce_line_of_effect, but what would have happened to the
+        // creatures behind walls is still shown, as blocked.
+        let mut blocked = HashSet::new();
+        if axiom.needs_line_of_effect() && !is_phasing {
+            blocked = targets
+                .iter()
+                .filter(|tile| !has_line_of_effect(caster_position, **tile, &is_wall))
+                .copied()
+                .collect();
+            targets.retain(|tile| !blocked.contains(tile));
+        }
+        let mut outcomes_of =
+            |targets: &HashSet<Position>, apply: &dyn Fn(&mut PredictedOutcome)| {
+                for (tiles, is_blocked) in [(targets, false), (&blocked, true)] {
+                    for entity in affected(tiles) {
+                        let outcome = outcomes.entry(entity).or_insert_with(|| PredictedOutcome {
+                            blocked: is_blocked,
+                            ..default()
+                        });
+                        // Real outcomes are not muddied by blocked ones.
+                        if outcome.blocked == is_blocked {
+                            apply(outcome);
+                        }
+                    }
+                }
+            };
+        match axiom {
+            // Contingencies do nothing when the spell is cast normally.
+            Axiom::WhenMoved
+            | Axiom::WhenSteppedOn
+            | Axiom::WhenRemoved
+            | Axiom::WhenDealingDamage
+            | Axiom::WhenTakingDamage
+            | Axiom::WhenPowered
+            | Axiom::WhenUnpowered => (),
+            Axiom::Ego => {
+                targets.insert(caster_position);
+            }
+            Axiom::Player => targets.extend(player_position),
+            Axiom::Touch => {
+                let (dx, dy) = aim.as_offset();
+                targets.insert(Position::new(
+                    caster_position.x + dx,
+                    caster_position.y + dy,
+                ));
+            }
+            Axiom::Plus => targets.extend(map.get_adjacent_tiles(caster_position)),
+            Axiom::MomentumBeam => beams(&mut targets, &[aim.as_offset()], is_piercing),
+            Axiom::XBeam => beams(
+                &mut targets,
+                &[(1, 1), (-1, 1), (1, -1), (-1, -1)],
+                is_piercing,
+            ),
+            Axiom::PlusBeam => beams(
+                &mut targets,
+                &[(0, 1), (1, 0), (0, -1), (-1, 0)],
+                is_piercing,
+            ),
+            Axiom::Halo { radius } => targets.extend(circle_around(&caster_position, *radius)),
+            Axiom::PiercingBeams => is_piercing = true,
+            Axiom::Phasing => is_phasing = true,
+            Axiom::Spread => {
+                let spread: Vec<Position> = targets
+                    .iter()
+                    .flat_map(|tile| map.get_adjacent_tiles(*tile))
+                    .collect();
+                targets.extend(spread);
+            }
+            Axiom::UntargetCaster => {
+                targets.remove(&caster_position);
+            }
+            Axiom::PurgeTargets => targets.clear(),
+            Axiom::HealOrHarm { amount } => {
+                outcomes_of(&targets, &|outcome| outcome.hp_mod += amount);
+                tiles.extend(targets.iter());
+            }
+            Axiom::StatusEffect { effect, stacks, .. } => {
+                outcomes_of(&targets, &|outcome| {
+                    outcome.effects.push((*effect, *stacks))
+                });
+                tiles.extend(targets.iter());
+            }
+            _ => break,
+        }
+    }
+    // Whatever comes next, be it a Function which could not be guessed or nothing,
+    // lands on the remaining targets.
+    tiles.extend(targets);
+    SpellPrediction { outcomes, tiles }
+}
+
 /// Generate the points across the outline of a circle.
 fn circle_around(center: &Position, radius: i32) -> Vec<Position> {
     let mut circle = Vec::new();
@@ -1286,6 +2469,101 @@ fn angle_from_center(center: &Position, point: &Position) -> f64 {
     (delta_y as f64).atan2(delta_x as f64)
 }
 
+/// Right before a Function which needs it (see Axiom::needs_line_of_effect) runs,
+/// drop every target the caster has no clear line to. Walls themselves can be
+/// affected, but not what hides behind them. Phasing synapses are exempt.
+pub fn enforce_line_of_effect(
+    mut spell_stack: ResMut<SpellStack>,
+    map: Res<Map>,
+    position: Query<&Position>,
+    flags_query: Query<&CreatureFlags>,
+    wall_query: Query<&Wall>,
+) {
+    let is_wall = |tile: &Position| {
+        map.get_entity_at(tile.x, tile.y)
+            .and_then(|entity| flags_query.get(*entity).ok())
+            .is_some_and(|flags| wall_query.flagged(flags))
+    };
+    for synapse_data in spell_stack.spells.iter_mut() {
+        if !synapse_data.axioms[synapse_data.step].needs_line_of_effect()
+            || synapse_data.synapse_flags.contains(&SynapseFlag::Phasing)
+        {
+            continue;
+        }
+        let Ok(caster_position) = position.get(synapse_data.caster) else {
+            continue;
+        };
+        synapse_data
+            .targets
+            .retain(|tile| has_line_of_effect(*caster_position, *tile, is_wall));
+    }
+}
+
+/// Right before a hostile axiom strikes a creature with SpellReflect, take it
+/// out of the targets and have it cast the rest of the spell back at the caster.
+/// The reflection is used up.
+pub fn reflect_spells(
+    mut spell_stack: ResMut<SpellStack>,
+    map: Res<Map>,
+    mut reflectors: Query<(&CreatureFlags, &mut StatusEffectsList, &Species)>,
+    reflect_query: Query<&SpellReflect>,
+    position: Query<&Position>,
+    mut cast_spell: EventWriter<CastSpell>,
+    mut text: EventWriter<AddMessage>,
+    mut commands: Commands,
+) {
+    let mut used_up = HashSet::new();
+    for synapse_data in spell_stack.spells.iter_mut() {
+        if !synapse_data.axioms[synapse_data.step].is_hostile() {
+            continue;
+        }
+        let Ok(caster_position) = position.get(synapse_data.caster) else {
+            continue;
+        };
+        let mut targeted = synapse_data.get_all_targeted_entities(&map);
+        // Sorted, so the same creature reflects first in every replay.
+        targeted.sort();
+        for entity in targeted {
+            if entity == synapse_data.caster || used_up.contains(&entity) {
+                continue;
+            }
+            let Ok((flags, mut status_list, species)) = reflectors.get_mut(entity) else {
+                continue;
+            };
+            if !reflect_query.flagged(flags) {
+                continue;
+            }
+            used_up.insert(entity);
+            synapse_data
+                .targets
+                .retain(|tile| map.get_entity_at(tile.x, tile.y) != Some(&entity));
+            let mut axioms = vec![Axiom::CursorTarget];
+            axioms.extend_from_slice(&synapse_data.axioms[synapse_data.step..]);
+            cast_spell.send(CastSpell {
+                caster: entity,
+                spell: Spell { axioms },
+                starting_step: 0,
+                soul_caste: synapse_data.soul_caste,
+                target: Some(*caster_position),
+                from_wheel: false,
+                from_locked_slot: false,
+            });
+            if let Some(reflect) = status_list.effects.get_mut(&StatusEffect::SpellReflect) {
+                reflect.potency = 0;
+                reflect.stacks = EffectDuration::Finite { stacks: 0 };
+            }
+            strip_status_effect(
+                StatusEffect::SpellReflect,
+                flags.effects_flags,
+                &mut commands,
+            );
+            text.send(AddMessage {
+                message: Message::SpellReflected(*species),
+            });
+        }
+    }
+}
+
 /// Get the spells active this turn.
 /// Get the next axiom, and runs its effects.
 pub fn process_axiom(
@@ -1307,8 +2585,69 @@ pub fn process_axiom(
     }
 }
 
+/// Why a spell did nothing at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FizzleReason {
+    /// Its Functions had no targets, or no creatures in them.
+    NoTargets,
+    /// Every creature it targeted was Spellproof.
+    Spellproof,
+}
+
+/// A spell ended without any of its Functions having anything to act on.
+#[derive(Event)]
+pub struct SpellFizzled {
+    pub caster: Entity,
+    pub soul_caste: Soul,
+    /// Whether a soul was paid for it on the Soul Wheel.
+    pub from_wheel: bool,
+    /// Whether it was cast from a locked slot, whose soul is never spent.
+    pub from_locked_slot: bool,
+    pub reason: FizzleReason,
+}
+
+/// Right before a Function runs, note whether it has anything to act on.
+/// A spell whose Functions never do is reported by cleanup_synapses.
+pub fn check_for_fizzles(
+    mut spell_stack: ResMut<SpellStack>,
+    map: Res<Map>,
+    flags: Query<&CreatureFlags>,
+    spellproof_query: Query<&Spellproof>,
+) {
+    for synapse_data in spell_stack.spells.iter_mut() {
+        let axiom = &synapse_data.axioms[synapse_data.step];
+        let reason = if axiom.acts_on_creatures() {
+            let creatures = synapse_data.get_all_targeted_entities(&map);
+            if creatures.is_empty() {
+                Some(FizzleReason::NoTargets)
+            } else if creatures
+                .iter()
+                .all(|creature| is_spellproof(*creature, &flags, &spellproof_query))
+            {
+                Some(FizzleReason::Spellproof)
+            } else {
+                None
+            }
+        } else if axiom.acts_on_tiles() && synapse_data.targets.is_empty() {
+            Some(FizzleReason::NoTargets)
+        } else if axiom.acts_on_tiles() {
+            None
+        } else {
+            // Forms and mutators neither land nor fizzle.
+            continue;
+        };
+        match reason {
+            Some(reason) => synapse_data.fizzle = Some(reason),
+            None => synapse_data.landed = true,
+        }
+    }
+}
+
 /// Remove all terminated spells.
-pub fn cleanup_synapses(mut spell_stack: ResMut<SpellStack>) {
+pub fn cleanup_synapses(
+    mut spell_stack: ResMut<SpellStack>,
+    mut fizzled: EventWriter<SpellFizzled>,
+) {
     let mut renewed_spells = Vec::new();
     let len = spell_stack.spells.len();
     for mut synapse_data in spell_stack.spells.drain(0..len) {
@@ -1325,6 +2664,14 @@ pub fn cleanup_synapses(mut spell_stack: ResMut<SpellStack>) {
             && !synapse_data.synapse_flags.contains(&SynapseFlag::Terminate)
         {
             renewed_spells.push(synapse_data);
+        } else if let (false, Some(reason)) = (synapse_data.landed, synapse_data.fizzle) {
+            fizzled.send(SpellFizzled {
+                caster: synapse_data.caster,
+                soul_caste: synapse_data.soul_caste,
+                from_wheel: synapse_data.from_wheel,
+                from_locked_slot: synapse_data.from_locked_slot,
+                reason,
+            });
         }
     }
     spell_stack.spells.append(&mut renewed_spells);
@@ -1364,11 +2711,23 @@ pub fn walk_grid(p0: Position, p1: Position) -> Vec<Position> {
     points
 }
 
+/// Whether nothing walls off `to` from `from`. The two ends themselves do not count.
+pub fn has_line_of_effect(
+    from: Position,
+    to: Position,
+    is_wall: impl Fn(&Position) -> bool,
+) -> bool {
+    let line = walk_grid(from, to);
+    line.iter()
+        .skip(1)
+        .take(line.len().saturating_sub(2))
+        .all(|tile| !is_wall(tile))
+}
+
 fn is_spellproof(
     entity: Entity,
     creature_flags: &Query<&CreatureFlags>,
     spellproof_query: &Query<&Spellproof>,
 ) -> bool {
-    spellproof_query.contains(creature_flags.get(entity).unwrap().effects_flags)
-        || spellproof_query.contains(creature_flags.get(entity).unwrap().species_flags)
+    spellproof_query.flagged(creature_flags.get(entity).unwrap())
 }
@@ -0,0 +1,280 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    caste::match_soul_with_string,
+    creature::{CreatureFlags, FlagQuery, Health, NoDropSoul, Owner, Player, Soul, Species},
+    events::{credited_culprit, DamageOrHealCreature, PassTurn, RemoveCreature, TurnManager},
+    focus::focus_confirm,
+    rng::{share_seed, GameRng},
+    sets::{Animation, ControlState, PlayerInput},
+    species::SpeciesRegistry,
+    spells::Axiom,
+    text::split_text,
+    ui::{AddMessage, AnnounceGameOver},
+};
+
+pub struct RunStatsPlugin;
+
+impl Plugin for RunStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>();
+        app.init_resource::<RunSummary>();
+        app.add_systems(Update, open_run_summary.in_set(Animation));
+        app.add_systems(OnEnter(ControlState::RunSummary), spawn_run_summary);
+        app.add_systems(OnExit(ControlState::RunSummary), despawn_run_summary);
+        app.add_systems(
+            Update,
+            run_summary_input
+                .run_if(in_state(ControlState::RunSummary))
+                .in_set(PlayerInput),
+        );
+    }
+}
+
+/// What the player accomplished since the start of the run.
+#[derive(Resource, Default, Clone, Debug, Serialize, Deserialize)]
+pub struct RunStats {
+    pub turns: usize,
+    /// Hostile creatures slain, by species.
+    pub defeated: HashMap<Species, usize>,
+    /// Souls dropped by slain creatures, by caste.
+    pub souls: HashMap<Soul, usize>,
+    /// Axioms learned from the recipe book, in order.
+    pub axioms_learned: Vec<Axiom>,
+    pub damage_dealt: usize,
+    /// Before shields and difficulty are applied.
+    pub damage_taken: usize,
+    /// Turns passed by letting the turn timer run out.
+    #[serde(default)]
+    pub timeouts: usize,
+    /// Hostile creatures slain by traps and projectiles the player left behind.
+    #[serde(default)]
+    pub proxy_kills: usize,
+    /// The world hash when the run ended, see hash_final_state.
+    #[serde(default)]
+    pub world_hash: Option<u64>,
+}
+
+pub fn track_run_stats(
+    mut damage: EventReader<DamageOrHealCreature>,
+    mut removals: EventReader<RemoveCreature>,
+    mut passes: EventReader<PassTurn>,
+    player: Query<(), With<Player>>,
+    creatures: Query<(&Species, &Soul, &Health, &CreatureFlags)>,
+    dying_flags: Query<&NoDropSoul>,
+    owners: Query<&Owner>,
+    registry: Res<SpeciesRegistry>,
+    turn_manager: Res<TurnManager>,
+    mut stats: ResMut<RunStats>,
+    mut last_turn: Local<usize>,
+) {
+    if turn_manager.turn_count != *last_turn {
+        *last_turn = turn_manager.turn_count;
+        stats.turns += 1;
+    }
+    stats.timeouts += passes.read().count();
+    for event in damage.read().filter(|event| event.hp_mod < 0) {
+        let amount = event.hp_mod.unsigned_abs();
+        if player.contains(event.entity) {
+            stats.damage_taken += amount;
+        } else if player.contains(credited_culprit(event.culprit, &owners)) {
+            stats.damage_dealt += amount;
+        }
+    }
+    let mut seen = HashSet::new();
+    for event in removals.read().filter(|event| seen.insert(event.entity)) {
+        // NOTE: Creatures cleared away by a respawn are still alive, and don't count.
+        let Ok((species, soul, health, flags)) = creatures.get(event.entity) else {
+            continue;
+        };
+        if health.hp > 0 || player.contains(event.entity) {
+            continue;
+        }
+        if registry.get(species).is_hostile() {
+            *stats.defeated.entry(*species).or_default() += 1;
+            let by_proxy = event.culprit.is_some_and(|culprit| {
+                !player.contains(culprit) && player.contains(credited_culprit(culprit, &owners))
+            });
+            if by_proxy {
+                stats.proxy_kills += 1;
+            }
+        }
+        let cannot_drop_soul = dying_flags.flagged(flags);
+        if !cannot_drop_soul && soul != &Soul::Empty {
+            *stats.souls.entry(*soul).or_default() += 1;
+        }
+    }
+}
+
+/// The statistics of the run which just ended, waiting to be shown.
+#[derive(Resource, Default)]
+pub struct RunSummary {
+    pub pending: Option<(bool, RunStats)>,
+}
+
+#[derive(Component)]
+pub struct RunSummaryPanel;
+
+/// Once the run is over, show how it went. If the player died, this waits
+/// for the review screen to be closed first, see review_input.
+fn open_run_summary(
+    mut events: EventReader<AnnounceGameOver>,
+    mut summary: ResMut<RunSummary>,
+    state: Res<State<ControlState>>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    for event in events.read() {
+        summary.pending = Some((event.victorious, event.stats.clone()));
+        let reviewing = *state.get() == ControlState::Review
+            || matches!(*next_state, NextState::Pending(ControlState::Review));
+        if !reviewing {
+            next_state.set(ControlState::RunSummary);
+        }
+    }
+}
+
+fn spawn_run_summary(
+    summary: Res<RunSummary>,
+    rng: Res<GameRng>,
+    registry: Res<SpeciesRegistry>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let Some((victorious, stats)) = &summary.pending else {
+        return;
+    };
+    let font = TextFont {
+        font: asset_server.load("fonts/Play-Regular.ttf"),
+        font_size: 1.5,
+        ..default()
+    };
+    let list = |entries: Vec<String>| {
+        if entries.is_empty() {
+            "[d]none[w]".to_owned()
+        } else {
+            entries.join(", ")
+        }
+    };
+    // Most common first.
+    let mut defeated: Vec<(&String, &usize)> = stats
+        .defeated
+        .iter()
+        .map(|(species, amount)| (&registry.get(species).name, amount))
+        .collect();
+    defeated.sort_by_key(|(name, amount)| (std::cmp::Reverse(**amount), *name));
+    let mut souls: Vec<(String, &usize)> = stats
+        .souls
+        .iter()
+        .map(|(soul, amount)| (match_soul_with_string(soul), amount))
+        .collect();
+    souls.sort_by_key(|(name, amount)| (std::cmp::Reverse(**amount), name.clone()));
+    let lines = [
+        if *victorious {
+            "[y]VICTORIOUS[w]".to_owned()
+        } else {
+            "[r]DEFEATED[w]".to_owned()
+        },
+        format!(
+            "Turns taken: [y]{}[w], of which [r]{}[w] ran out of time",
+            stats.turns, stats.timeouts
+        ),
+        format!(
+            "Damage dealt: [y]{}[w], damage taken: [r]{}[w]",
+            stats.damage_dealt, stats.damage_taken
+        ),
+        format!(
+            "Creatures defeated: {}",
+            list(
+                defeated
+                    .iter()
+                    .map(|(name, amount)| format!("{} x{}", name, amount))
+                    .collect()
+            )
+        ),
+        format!(
+            "Slain by your traps and projectiles: [y]{}[w]",
+            stats.proxy_kills
+        ),
+        format!(
+            "Souls harvested: {}",
+            list(
+                souls
+                    .iter()
+                    .map(|(name, amount)| format!("{} x{}", name, amount))
+                    .collect()
+            )
+        ),
+        format!(
+            "Axioms learned: {}",
+            list(
+                stats
+                    .axioms_learned
+                    .iter()
+                    .map(|axiom| format!("{:?}", axiom))
+                    .collect()
+            )
+        ),
+        format!(
+            "Seed: [y]{}[w], final world hash: [y]{}[w]",
+            rng.seed(),
+            stats
+                .world_hash
+                .map_or("unknown".to_owned(), |hash| format!("{:016x}", hash))
+        ),
+        "[d]Enter: start a new run, C: show the seed in the message log[w]".to_owned(),
+    ];
+    commands
+        .spawn((
+            RunSummaryPanel,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(20.),
+                width: Val::Percent(60.),
+                top: Val::Percent(5.),
+                padding: UiRect::all(Val::Px(1.)),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.9)),
+        ))
+        .with_children(|parent| {
+            for line in lines {
+                parent
+                    .spawn((Text::default(), font.clone()))
+                    .with_children(|line_parent| {
+                        for (section, color) in split_text(&line) {
+                            line_parent.spawn((TextSpan::new(section), font.clone(), color));
+                        }
+                    });
+            }
+        });
+}
+
+fn despawn_run_summary(
+    mut commands: Commands,
+    panel: Query<Entity, With<RunSummaryPanel>>,
+    mut summary: ResMut<RunSummary>,
+) {
+    for panel in panel.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    summary.pending = None;
+}
+
+fn run_summary_input(
+    input: Res<ButtonInput<KeyCode>>,
+    rng: Res<GameRng>,
+    mut text: EventWriter<AddMessage>,
+    mut next_state: ResMut<NextState<ControlState>>,
+) {
+    if input.just_pressed(KeyCode::KeyC) {
+        share_seed(&rng, &mut text);
+    }
+    if focus_confirm(&input) || input.just_pressed(KeyCode::Escape) {
+        next_state.set(ControlState::Player);
+    }
+}
@@ -0,0 +1,230 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+
+use crate::{
+    creature::{Conveyor, CreatureFlags, FlagQuery, Footprint, Hidden, Railway, Wall},
+    graphics::{apply_fog_of_war, SpriteSheetAtlas, VisualLayer},
+    map::{Map, Position},
+    sets::Animation,
+    vision::VisibilityMap,
+    OrdDir, TILE_SIZE,
+};
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (render_terrain, render_ground)
+                .run_if(resource_changed::<Map>.or(resource_changed::<VisibilityMap>))
+                .after(apply_fog_of_war)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// The single mesh in which all static terrain is drawn.
+#[derive(Component)]
+pub struct TerrainMesh;
+
+/// The single mesh in which the ground of every tile is drawn, see TileKind.
+#[derive(Component)]
+pub struct GroundMesh;
+
+/// A creature drawn as part of the TerrainMesh, instead of with its own sprite.
+#[derive(Component)]
+pub struct Batched;
+
+/// One tile of a mesh, as it was last drawn.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Quad {
+    position: Position,
+    rotation: Vec2,
+    uv: Rect,
+    color: [f32; 4],
+}
+
+/// Build a mesh out of square tiles.
+fn quad_mesh(quads: &[Quad]) -> Mesh {
+    let (mut positions, mut uvs, mut colors, mut indices) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for quad in quads {
+        let (min, max) = (quad.uv.min, quad.uv.max);
+        let first_vertex = positions.len() as u32;
+        for (corner, uv) in [
+            (Vec2::new(-1., -1.), Vec2::new(min.x, max.y)),
+            (Vec2::new(1., -1.), Vec2::new(max.x, max.y)),
+            (Vec2::new(1., 1.), Vec2::new(max.x, min.y)),
+            (Vec2::new(-1., 1.), Vec2::new(min.x, min.y)),
+        ] {
+            let vertex =
+                tile_center(&quad.position) + quad.rotation.rotate(corner * TILE_SIZE / 2.);
+            positions.push([vertex.x, vertex.y, 0.]);
+            uvs.push(uv.to_array());
+            colors.push(quad.color);
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| first_vertex + i));
+    }
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+fn tile_center(position: &Position) -> Vec2 {
+    Vec2::new(position.x as f32 * TILE_SIZE, position.y as f32 * TILE_SIZE)
+}
+
+/// Walls, rails and conveyors are still creatures, but drawing each of them as
+/// its own sprite is slow on large floors. Instead, their sprites are hidden,
+/// and they are all drawn at once as a single mesh.
+// NOTE: This runs after apply_fog_of_war, and reuses the tint it gave the sprites.
+// The Map changes every turn, the mesh is only rebuilt if one of its tiles did.
+pub fn render_terrain(
+    mut creatures: Query<
+        (
+            Entity,
+            &Position,
+            &CreatureFlags,
+            &OrdDir,
+            &Sprite,
+            &mut Visibility,
+            Has<Batched>,
+        ),
+        (Without<Footprint>, Without<Hidden>),
+    >,
+    static_query: Query<(), Or<(With<Wall>, With<Railway>, With<Conveyor>)>>,
+    terrain: Query<&Mesh2d, With<TerrainMesh>>,
+    atlases: Res<Assets<TextureAtlasLayout>>,
+    atlas_layout: Res<SpriteSheetAtlas>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut drawn: Local<Vec<Quad>>,
+    mut commands: Commands,
+) {
+    let Some(atlas) = atlases.get(&atlas_layout.handle) else {
+        return;
+    };
+    let atlas_size = atlas.size.as_vec2();
+    let mut quads = Vec::new();
+    for (entity, position, flags, momentum, sprite, mut visibility, is_batched) in
+        creatures.iter_mut()
+    {
+        if !static_query.flagged(flags) {
+            // Walls can stop being walls, through transformation.
+            if is_batched {
+                *visibility = Visibility::Inherited;
+                commands.entity(entity).remove::<Batched>();
+            }
+            continue;
+        }
+        if !is_batched {
+            *visibility = Visibility::Hidden;
+            commands.entity(entity).insert(Batched);
+        }
+        let Some(rect) = sprite
+            .texture_atlas
+            .as_ref()
+            .and_then(|texture_atlas| atlas.textures.get(texture_atlas.index))
+        else {
+            continue;
+        };
+        quads.push(Quad {
+            position: *position,
+            // The same rotation summon_creature gives to sprites.
+            rotation: Vec2::from_angle(match momentum {
+                OrdDir::Down => 0.,
+                OrdDir::Right => PI / 2.,
+                OrdDir::Up => PI,
+                OrdDir::Left => 3. * PI / 2.,
+            }),
+            uv: Rect::from_corners(
+                rect.min.as_vec2() / atlas_size,
+                rect.max.as_vec2() / atlas_size,
+            ),
+            color: sprite.color.to_linear().to_f32_array(),
+        });
+    }
+    // Queries have no stable order.
+    quads.sort_by_key(|quad| (quad.position.x, quad.position.y));
+    if let Ok(handle) = terrain.get_single() {
+        if *drawn == quads {
+            return;
+        }
+        meshes.insert(&handle.0, quad_mesh(&quads));
+    } else {
+        commands.spawn((
+            TerrainMesh,
+            Mesh2d(meshes.add(quad_mesh(&quads))),
+            MeshMaterial2d(materials.add(ColorMaterial::from(
+                asset_server.load::<Image>("spritesheet.png"),
+            ))),
+            Transform::default(),
+            VisualLayer::Terrain,
+        ));
+    }
+    *drawn = quads;
+}
+
+/// Draw water, lava, ice and mud as plain squares of colour under the creatures,
+/// tinted like everything else by what the player can see and remember.
+pub fn render_ground(
+    map: Res<Map>,
+    vision: Res<VisibilityMap>,
+    ground: Query<&Mesh2d, With<GroundMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut drawn: Local<Vec<Quad>>,
+    mut commands: Commands,
+) {
+    let mut quads: Vec<Quad> = map
+        .terrain
+        .iter()
+        .filter_map(|(position, kind)| {
+            let color = if vision.is_visible(position) {
+                kind.color()
+            } else if vision.is_remembered(position) {
+                kind.color().mix(&Color::BLACK, 0.65)
+            } else {
+                return None;
+            };
+            Some(Quad {
+                position: *position,
+                rotation: Vec2::X,
+                uv: Rect::default(),
+                color: color.to_linear().to_f32_array(),
+            })
+        })
+        .collect();
+    // HashMaps have no stable order.
+    quads.sort_by_key(|quad| (quad.position.x, quad.position.y));
+    if let Ok(handle) = ground.get_single() {
+        if *drawn == quads {
+            return;
+        }
+        meshes.insert(&handle.0, quad_mesh(&quads));
+    } else {
+        commands.spawn((
+            GroundMesh,
+            Mesh2d(meshes.add(quad_mesh(&quads))),
+            // Untextured, each tile only has its vertex colours.
+            MeshMaterial2d(materials.add(ColorMaterial::default())),
+            Transform::default(),
+            VisualLayer::Ground,
+        ));
+    }
+    *drawn = quads;
+}
@@ -10,10 +10,11 @@ use bevy::{
     text::TextColor,
 };
 
-use crate::creature::{Soul, Species};
+use crate::{creature::Soul, settings::ColorPalette};
 
 use regex::Regex;
 
+// NOTE: 1-11 are unused, species descriptions now live in creatures.ron.
 pub const LORE: &[&str] = &[
 "Unknown.",
 "Its melee attacks cause it to heal itself for 1 HP.",
@@ -44,22 +45,17 @@ pub const LORE: &[&str] = &[
 "Focused Thought Pierces the Veil - Form\nThe Caster shoots a linear beam in the direction of its Momentum, stopping at the first Creature hit. All Tiles touched, including the contacted Creature, are Targeted.",
 ];
 
-pub fn match_species_with_description(species: &Species) -> &str {
-    LORE[match species {
-        Species::Hunter => 1,
-        Species::Apiarist => 2,
-        Species::Tinker => 3,
-        Species::Oracle => 4,
-        Species::Shrike => 5,
-        Species::Second => 6,
-        Species::Airlock => 7,
-        Species::WeakWall => 8,
-        Species::Wall => 9,
-        Species::Player => 10,
-        Species::Abazon => 11,
-        _ => 0,
-    }]
-}
+/// Shown on the loading screen, one at random, while a floor is generated.
+pub const LOADING_TIPS: &[&str] = &[
+    "Faith's End was built as a prison, but nobody remembers who it was meant to hold.",
+    "[y]Saintly[w] souls mend, [y]Ordered[w] souls march, [y]Artistic[w] souls deceive.",
+    "[y]Unhinged[w] souls break walls, [y]Feral[w] souls hunt, [y]Vile[w] souls linger.",
+    "Every creature you slay leaves its soul behind. The Soul Wheel does not care whose it was.",
+    "The cages are rebuilt each time someone falls. The tower has had a lot of practice.",
+    "Doors open once every hostile creature in their room is slain.",
+    "A spell is only as good as the order of its axioms. Press [y]B[w] to rearrange them.",
+    "Creatures which are asleep will not notice you until you make some noise.",
+];
 
 pub fn match_soul_with_description(soul: &Soul) -> &str {
     LORE[match soul {
@@ -74,6 +70,14 @@ pub fn match_soul_with_description(soul: &Soul) -> &str {
     }]
 }
 
+/// The text without its colour tags.
+pub fn strip_color_tags(text: &str) -> String {
+    split_text(text)
+        .into_iter()
+        .map(|(section, _)| section)
+        .collect()
+}
+
 pub fn split_text(text: &str) -> Vec<(String, TextColor)> {
     let re = Regex::new(r"\[([^\]]+)\]").unwrap();
 
@@ -100,27 +104,58 @@ pub fn split_text(text: &str) -> Vec<(String, TextColor)> {
     output
 }
 
+/// Every colour tag which split_text understands.
+const COLOR_TAGS: &[char] = &[
+    'p', 'r', 'y', 'w', 'l', 'c', 'm', 'd', 'b', 's', 'a', 'o', 'g',
+];
+
+/// The colour of a tag under `palette`. ColorblindSafe keeps damage and healing
+/// apart by moving them to orange and blue, see Okabe & Ito.
+pub fn tag_color(tag: char, palette: ColorPalette) -> Option<Color> {
+    let recolored = match (palette, tag) {
+        (ColorPalette::Standard, _) => None,
+        (ColorPalette::ColorblindSafe, 'r') => Some(Color::srgb(0.9, 0.45, 0.)),
+        (ColorPalette::ColorblindSafe, 'l') => Some(Color::srgb(0.34, 0.71, 0.91)),
+        (ColorPalette::ColorblindSafe, 'g') => Some(Color::srgb(0.35, 0.55, 1.)),
+        (ColorPalette::ColorblindSafe, 'd') => Some(Color::srgb(0.6, 0.75, 0.85)),
+        (ColorPalette::ColorblindSafe, 'o') => Some(Color::srgb(0.9, 0.6, 0.)),
+        (ColorPalette::ColorblindSafe, _) => None,
+    };
+    recolored.or(match tag {
+        'p' => Some(VIOLET.into()),
+        'r' => Some(ORANGE_RED.into()),
+        'y' => Some(YELLOW.into()),
+        'w' => Some(WHITE.into()),
+        'l' => Some(LIME.into()),
+        'c' => Some(LIGHT_BLUE.into()),
+        'm' => Some(MAGENTA.into()),
+        'd' => Some(DARK_SEA_GREEN.into()),
+        'b' => Some(BURLYWOOD.into()),
+        's' => Some(DARK_SALMON.into()),
+        'a' => Some(ANTIQUE_WHITE.into()),
+        'o' => Some(Color::srgb(0.94, 0.55, 0.38)),
+        'g' => Some(Color::srgb(0.66, 0.82, 0.11)),
+        _ => None,
+    })
+}
+
+/// A colour which split_text gave to some tag under `from`, as it
+/// would be under `to`. Other colours are left alone.
+pub fn recolor_tag(color: Color, from: ColorPalette, to: ColorPalette) -> Option<Color> {
+    COLOR_TAGS
+        .iter()
+        .find(|tag| tag_color(**tag, from) == Some(color))
+        .and_then(|tag| tag_color(*tag, to))
+}
+
+// NOTE: Text is always split with the Standard palette, and recoloured
+// afterwards by recolor_text.
 fn match_char_code_with_color(some_char: Option<char>) -> Color {
     match some_char {
-        Some(char) => match char {
-            'p' => VIOLET.into(),
-            'r' => ORANGE_RED.into(),
-            'y' => YELLOW.into(),
-            'w' => WHITE.into(),
-            'l' => LIME.into(),
-            'c' => LIGHT_BLUE.into(),
-            'm' => MAGENTA.into(),
-            'd' => DARK_SEA_GREEN.into(),
-            'b' => BURLYWOOD.into(),
-            's' => DARK_SALMON.into(),
-            'a' => ANTIQUE_WHITE.into(),
-            'o' => Color::srgb(0.94, 0.55, 0.38),
-            'g' => Color::srgb(0.66, 0.82, 0.11),
-            _ => {
-                info!("Warning, an invalid color tag was used.");
-                Color::WHITE
-            }
-        },
+        Some(char) => tag_color(char, ColorPalette::Standard).unwrap_or_else(|| {
+            info!("Warning, an invalid color tag was used.");
+            Color::WHITE
+        }),
         None => panic!("There was no character in the text split!"),
     }
 }
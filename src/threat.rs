@@ -0,0 +1,79 @@
+use crate::{
+    creature::{Speed, Spellbook},
+    spells::Axiom,
+};
+
+/// How much damage a creature can deal in one turn, at best: its strongest
+/// spell or a melee attack, times how often it acts.
+// NOTE: This is a rough heuristic, it ignores status effects and spell reach.
+pub fn damage_per_turn(spellbook: &Spellbook, speed: Option<&Speed>) -> f32 {
+    let best_spell = spellbook
+        .spells
+        .values()
+        .map(|spell| {
+            spell
+                .axioms
+                .iter()
+                .map(|axiom| match axiom {
+                    Axiom::HealOrHarm { amount } if *amount < 0 => -*amount as usize,
+                    _ => 0,
+                })
+                .sum::<usize>()
+        })
+        .max()
+        .unwrap_or(0);
+    // Melee attacks deal 1 damage.
+    let damage = best_spell.max(1) as f32;
+    match speed {
+        Some(Speed::Fast { actions_per_turn }) => damage * *actions_per_turn as f32,
+        Some(Speed::Slow { wait_turns }) => damage / (*wait_turns + 1) as f32,
+        None => damage,
+    }
+}
+
+/// How strong a creature is: its health times the damage it deals each turn.
+/// Comparable between species, for spawn budgets and the like.
+pub fn creature_power(hp: usize, spellbook: &Spellbook, speed: Option<&Speed>) -> f32 {
+    hp as f32 * damage_per_turn(spellbook, speed)
+}
+
+/// How dangerous a creature is to the player right now, see ThreatLevel::assess.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThreatLevel {
+    Trivial,
+    Dangerous,
+    Deadly,
+}
+
+impl ThreatLevel {
+    /// Compare how many turns the player needs to slay a creature of this
+    /// `power`, one melee attack at a time, with how many it needs to slay
+    /// the player at `player_hp`.
+    pub fn assess(power: f32, player_hp: usize) -> Self {
+        let ratio = power / player_hp.max(1) as f32;
+        if ratio <= 1. {
+            ThreatLevel::Trivial
+        } else if ratio <= 2.5 {
+            ThreatLevel::Dangerous
+        } else {
+            ThreatLevel::Deadly
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ThreatLevel::Trivial => "[a]Trivial[w]",
+            ThreatLevel::Dangerous => "[y]Dangerous[w]",
+            ThreatLevel::Deadly => "[r]Deadly[w]",
+        }
+    }
+
+    /// The colour tag the creature's name is written in, see split_text.
+    pub fn tag(&self) -> &str {
+        match self {
+            ThreatLevel::Trivial => "[a]",
+            ThreatLevel::Dangerous => "[y]",
+            ThreatLevel::Deadly => "[r]",
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    creature::{Awake, Player, Species},
+    events::RespawnPlayer,
+    graphics::VisualLayer,
+    map::Position,
+    sets::{Animation, ControlState, PlayerInput},
+    ui::{AddMessage, Message},
+    vision::VisibilityMap,
+    TILE_SIZE,
+};
+
+/// How many past positions are remembered for each creature.
+const TRAIL_LENGTH: usize = 12;
+
+pub struct TrailPlugin;
+
+impl Plugin for TrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Trails>();
+        app.add_systems(
+            Update,
+            toggle_enemy_trails
+                .run_if(in_state(ControlState::Cursor))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            (record_trails, render_trails).chain().in_set(Animation),
+        );
+    }
+}
+
+/// Where the player, and the creatures chasing them, have recently been.
+#[derive(Resource, Default)]
+pub struct Trails {
+    paths: HashMap<Entity, VecDeque<Position>>,
+    /// Whether the trails of awake enemies are drawn while examining tiles.
+    pub show_enemy_trails: bool,
+}
+
+/// A faded footprint, left on a tile that was walked through.
+#[derive(Component)]
+pub struct TrailMark;
+
+/// G, while examining tiles, shows or hides the trails of enemies.
+fn toggle_enemy_trails(
+    input: Res<ButtonInput<KeyCode>>,
+    mut trails: ResMut<Trails>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if input.just_pressed(KeyCode::KeyG) {
+        trails.show_enemy_trails = !trails.show_enemy_trails;
+        text.send(AddMessage {
+            message: Message::EnemyTrails(trails.show_enemy_trails),
+        });
+    }
+}
+
+fn record_trails(
+    moved: Query<(Entity, &Position), (Changed<Position>, Or<(With<Player>, With<Awake>)>)>,
+    creatures: Query<(), With<Species>>,
+    mut respawn: EventReader<RespawnPlayer>,
+    mut trails: ResMut<Trails>,
+) {
+    // Old trails mean nothing on a new floor.
+    if respawn.read().count() > 0 {
+        trails.paths.clear();
+    }
+    for (entity, position) in moved.iter() {
+        let path = trails.paths.entry(entity).or_default();
+        if path.back() == Some(position) {
+            continue;
+        }
+        path.push_back(*position);
+        if path.len() > TRAIL_LENGTH + 1 {
+            path.pop_front();
+        }
+    }
+    if trails
+        .paths
+        .keys()
+        .any(|entity| !creatures.contains(*entity))
+    {
+        trails.paths.retain(|entity, _| creatures.contains(*entity));
+    }
+}
+
+/// Redraw every trail, the oldest footprints being the faintest. The last
+/// position of each path is where its creature stands, and is left bare.
+fn render_trails(
+    trails: Res<Trails>,
+    marks: Query<Entity, With<TrailMark>>,
+    player: Query<Entity, With<Player>>,
+    state: Res<State<ControlState>>,
+    vision: Res<VisibilityMap>,
+    mut commands: Commands,
+) {
+    if !trails.is_changed() && !state.is_changed() && !vision.is_changed() {
+        return;
+    }
+    for mark in marks.iter() {
+        commands.entity(mark).despawn();
+    }
+    let show_enemies = trails.show_enemy_trails && *state.get() == ControlState::Cursor;
+    for (entity, path) in trails.paths.iter() {
+        let is_player = player.contains(*entity);
+        if !is_player && !show_enemies {
+            continue;
+        }
+        let steps = path.len().saturating_sub(1);
+        for (age, position) in path.iter().take(steps).rev().enumerate() {
+            // Enemy trails do not give away anything the player cannot see.
+            if !is_player && !vision.is_visible(position) {
+                continue;
+            }
+            let opacity = 0.3 * (1. - age as f32 / TRAIL_LENGTH as f32);
+            let color = if is_player {
+                Color::srgba(0.9, 0.9, 1., opacity)
+            } else {
+                Color::srgba(1., 0.3, 0.2, opacity)
+            };
+            commands.spawn((
+                TrailMark,
+                Sprite {
+                    color,
+                    custom_size: Some(Vec2::splat(TILE_SIZE * 0.25)),
+                    ..default()
+                },
+                Transform::from_xyz(
+                    position.x as f32 * TILE_SIZE,
+                    position.y as f32 * TILE_SIZE,
+                    0.,
+                ),
+                VisualLayer::Decal,
+            ));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::{
+        CreatureFlags, FlagQuery, Hidden, Interactable, Perceptive, Player, Revealed, Species,
+    },
+    events::RemoveCreature,
+    interact::{interaction_target, Interact},
+    map::Position,
+    ui::{AddMessage, Message},
+};
+
+/// Remove a trap the player has found, without setting it off.
+#[derive(Event)]
+pub struct DisarmTrap {
+    pub entity: Entity,
+}
+
+/// Hidden traps are found by perceptive creatures walking up next to them, or,
+/// the hard way, by stepping on them. Found traps can be disarmed with E.
+pub fn reveal_traps(
+    hidden: Query<(Entity, &Position, &Species, &CreatureFlags), With<Hidden>>,
+    creatures: Query<(&Position, &CreatureFlags, Has<Player>), Without<Hidden>>,
+    perceptive: Query<&Perceptive>,
+    mut commands: Commands,
+    mut text: EventWriter<AddMessage>,
+) {
+    for (trap, trap_position, species, trap_flags) in hidden.iter() {
+        let sprung = creatures
+            .iter()
+            .any(|(position, _, is_player)| is_player && position == trap_position);
+        let spotted = creatures.iter().any(|(position, flags, _)| {
+            perceptive.get_flag(flags).is_ok_and(|perceptive| {
+                (position.x - trap_position.x)
+                    .abs()
+                    .max((position.y - trap_position.y).abs())
+                    <= perceptive.radius
+            })
+        });
+        if !spotted && !sprung {
+            continue;
+        }
+        // NOTE: A sprung trap is Fragile, and about to be removed.
+        commands.entity(trap).remove::<Hidden>();
+        if sprung {
+            text.send(AddMessage {
+                message: Message::TrapSprung(*species),
+            });
+            continue;
+        }
+        commands.entity(trap).try_insert(Revealed);
+        commands
+            .entity(trap_flags.effects_flags)
+            .try_insert(Interactable);
+        text.send(AddMessage {
+            message: Message::TrapSpotted(*species),
+        });
+    }
+}
+
+/// Interacting with a trap which was found disarms it.
+pub fn interact_with_traps(
+    mut events: EventReader<Interact>,
+    position: Query<&Position>,
+    creatures: Query<(Entity, &Position, &CreatureFlags)>,
+    interactable: Query<(), With<Interactable>>,
+    revealed: Query<(), With<Revealed>>,
+    mut disarm: EventWriter<DisarmTrap>,
+) {
+    for event in events.read() {
+        if let Some(entity) = interaction_target(event, &position, &creatures, &interactable)
+            .filter(|entity| revealed.contains(*entity))
+        {
+            disarm.send(DisarmTrap { entity });
+        }
+    }
+}
+
+pub fn disarm_trap(
+    mut events: EventReader<DisarmTrap>,
+    species: Query<&Species>,
+    mut remove: EventWriter<RemoveCreature>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        let Ok(species) = species.get(event.entity) else {
+            continue;
+        };
+        remove.send(RemoveCreature {
+            entity: event.entity,
+            culprit: None,
+        });
+        text.send(AddMessage {
+            message: Message::TrapDisarmed(*species),
+        });
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+
+use crate::{
+    creature::Player,
+    events::{EndTurn, PassTurn, PlayerAction, TurnManager},
+    input::keyboard_input,
+    map::floor_is_loading,
+    sets::{Animation, ControlState, PlayerInput},
+    spells::spell_stack_is_empty,
+    ui::{AddMessage, Message},
+};
+
+pub struct TurnTimerPlugin;
+
+impl Plugin for TurnTimerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TurnTimer>();
+        app.add_systems(Startup, spawn_turn_timer_bar);
+        app.add_systems(
+            Update,
+            (
+                cycle_turn_timer,
+                tick_turn_timer.run_if(spell_stack_is_empty.and(not(floor_is_loading))),
+            )
+                .chain()
+                .before(keyboard_input)
+                .run_if(in_state(ControlState::Player))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(Update, update_turn_timer_bar.in_set(Animation));
+    }
+}
+
+/// The time limits N cycles through, in seconds. None plays at your own pace.
+const TIME_LIMITS: [Option<u32>; 4] = [None, Some(10), Some(5), Some(3)];
+
+/// Score attack: the player has a few real seconds to act each turn, or the turn
+/// passes without them. Passing is recorded like any other action, so replays
+/// play out the same without any clock.
+#[derive(Resource, Default)]
+pub struct TurnTimer {
+    pub limit: Option<u32>,
+    elapsed: f32,
+    /// The turn the clock was last reset on.
+    turn: usize,
+}
+
+impl TurnTimer {
+    /// How much of this turn's time is left, from 1 to 0.
+    fn remaining(&self) -> f32 {
+        self.limit
+            .map_or(1., |limit| (1. - self.elapsed / limit as f32).clamp(0., 1.))
+    }
+}
+
+/// N cycles through the time limits.
+fn cycle_turn_timer(
+    input: Res<ButtonInput<KeyCode>>,
+    mut timer: ResMut<TurnTimer>,
+    mut text: EventWriter<AddMessage>,
+) {
+    if input.just_pressed(KeyCode::KeyN) {
+        let current = TIME_LIMITS
+            .iter()
+            .position(|limit| *limit == timer.limit)
+            .unwrap_or(0);
+        timer.limit = TIME_LIMITS[(current + 1) % TIME_LIMITS.len()];
+        timer.elapsed = 0.;
+        text.send(AddMessage {
+            message: Message::TurnTimer(timer.limit),
+        });
+    }
+}
+
+// NOTE: The clock stops while a menu is open or the player's spells resolve,
+// it only runs while the player could actually act. keyboard_input skips the
+// frame the turn is passed on, so the player never acts on the same turn.
+fn tick_turn_timer(
+    time: Res<Time>,
+    mut timer: ResMut<TurnTimer>,
+    mut turn_manager: ResMut<TurnManager>,
+    player: Query<(), With<Player>>,
+    mut turn_ended: EventReader<EndTurn>,
+    mut pass: EventWriter<PassTurn>,
+    mut turn_end: EventWriter<EndTurn>,
+) {
+    let Some(limit) = timer.limit else {
+        return;
+    };
+    // The player already acted, the turn is about to end by itself.
+    if turn_ended.read().count() > 0 {
+        timer.elapsed = 0.;
+        return;
+    }
+    if timer.turn != turn_manager.turn_count {
+        timer.turn = turn_manager.turn_count;
+        timer.elapsed = 0.;
+    }
+    timer.elapsed += time.delta_secs();
+    if timer.elapsed < limit as f32 || player.is_empty() {
+        return;
+    }
+    timer.elapsed = 0.;
+    pass.send(PassTurn);
+    turn_manager.action_this_turn = PlayerAction::Pass;
+    turn_end.send(EndTurn);
+}
+
+/// The time left this turn, shrinking under the initiative strip.
+#[derive(Component)]
+pub struct TurnTimerBar;
+
+#[derive(Component)]
+pub struct TurnTimerFill;
+
+fn spawn_turn_timer_bar(mut commands: Commands) {
+    commands
+        .spawn((
+            TurnTimerBar,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(35.),
+                top: Val::Px(6.5),
+                width: Val::Px(24.),
+                height: Val::Px(0.5),
+                display: Display::None,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+            PickingBehavior::IGNORE,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TurnTimerFill,
+                Node {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    ..default()
+                },
+                BackgroundColor(Color::srgb(1., 0.9, 0.2)),
+            ));
+        });
+}
+
+fn update_turn_timer_bar(
+    timer: Res<TurnTimer>,
+    mut bar: Query<&mut Node, (With<TurnTimerBar>, Without<TurnTimerFill>)>,
+    mut fill: Query<(&mut Node, &mut BackgroundColor), With<TurnTimerFill>>,
+) {
+    let (Ok(mut bar), Ok((mut fill, mut color))) = (bar.get_single_mut(), fill.get_single_mut())
+    else {
+        return;
+    };
+    bar.display = if timer.limit.is_some() {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    let remaining = timer.remaining();
+    fill.width = Val::Percent(remaining * 100.);
+    // The last stretch is in red.
+    color.0 = if remaining < 0.25 {
+        Color::srgb(1., 0.2, 0.2)
+    } else {
+        Color::srgb(1., 0.9, 0.2)
+    };
+}
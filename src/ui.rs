@@ -8,9 +8,23 @@ use bevy::{
 };
 
 use crate::{
-    creature::{Soul, Species},
-    graphics::SpriteSheetAtlas,
+    caste::match_soul_with_string,
+    chest::Loot,
+    creature::{Blessing, Soul, Species, StatusEffect},
+    difficulty::DifficultyPreset,
+    director::DirectorIntensity,
+    equipment::Item,
+    events::SoulWheel,
+    graphics::{get_caste_palette, EnemyPacing, SpriteSheetAtlas},
+    key_items::KeyItem,
+    map::Position,
+    rumble::RumbleIntensity,
+    settings::{ColorPalette, MessageCategory, Settings},
+    species::SpeciesRegistry,
+    spells::{AimMode, Axiom, FizzleReason},
+    stats::RunStats,
     text::{split_text, LORE},
+    vision::VisibilityMap,
 };
 
 pub struct UIPlugin;
@@ -19,6 +33,11 @@ impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup);
         app.add_systems(Update, on_resize_system);
+        app.add_systems(
+            Update,
+            tint_locked_slots
+                .run_if(resource_changed::<SoulWheel>.or(resource_changed::<Settings>)),
+        );
         app.add_event::<AnnounceGameOver>();
         app.add_event::<AddMessage>();
         app.add_event::<SlideMessages>();
@@ -36,6 +55,21 @@ pub struct SoulSlot {
     pub index: usize,
 }
 
+/// Slots locked to a caste are framed in its colour.
+fn tint_locked_slots(
+    mut slots: Query<(&SoulSlot, &mut BackgroundColor)>,
+    soul_wheel: Res<SoulWheel>,
+    settings: Res<Settings>,
+) {
+    for (slot, mut background) in slots.iter_mut() {
+        background.0 = soul_wheel.locks[slot.index].map_or(Color::NONE, |caste| {
+            get_caste_palette(&caste, settings.palette)
+                .primary
+                .with_alpha(0.5)
+        });
+    }
+}
+
 #[derive(Component)]
 pub struct FadingTitle {
     timer: Timer,
@@ -44,11 +78,22 @@ pub struct FadingTitle {
 #[derive(Event)]
 pub struct AnnounceGameOver {
     pub victorious: bool,
+    /// How the run went, see RunSummary.
+    pub stats: RunStats,
 }
 
-fn on_resize_system(mut resize_reader: EventReader<WindowResized>, mut scale: ResMut<UiScale>) {
-    for e in resize_reader.read() {
-        scale.0 = e.height * 16. / 1080.;
+/// The UI grows with the window, times the scale picked in the Settings.
+fn on_resize_system(
+    mut resize_reader: EventReader<WindowResized>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<Settings>,
+    mut scale: ResMut<UiScale>,
+) {
+    if resize_reader.read().count() == 0 && !settings.is_changed() {
+        return;
+    }
+    if let Ok(window) = window.get_single() {
+        scale.0 = window.height() * 16. / 1080. * settings.ui_scale;
     }
 }
 
@@ -314,6 +359,8 @@ fn setup(
                             for i in 0..8 {
                                 parent.spawn((
                                     SoulSlot { index: i },
+                                    // Hovering a slot previews its spell, see PreviewChip.
+                                    Interaction::default(),
                                     ImageNode {
                                         image: asset_server.load("spritesheet.png"),
                                         texture_atlas: Some(TextureAtlas {
@@ -1036,7 +1083,23 @@ pub enum InvalidAction {
     WheelFull,
     NoSoulsInPile,
     CannotMelee(Species),
+    /// The pushed creature, at the front of a chain which is blocked.
+    PushBlocked(Species),
     EmptySlotCast,
+    DoorLocked,
+    DoorBlocked,
+    DoorWired,
+    MissingKey(KeyItem),
+    /// Only one blessing can be held at a time.
+    AlreadyBlessed(Blessing),
+    /// This spell needs this many more souls of its caste.
+    NotEnoughSouls(Soul, usize),
+    /// Every soul of the recipe picked in the recipe book is already in place.
+    RecipeComplete,
+    /// All the WarpCharges of this floor were used up.
+    NoWarpCharges,
+    /// Every explored tile in reach is seen by an enemy.
+    NoSafeTile,
 }
 
 pub enum Message {
@@ -1044,11 +1107,99 @@ pub enum Message {
     HostileAttack(Species, isize),
     PlayerAttack(Species, isize),
     NoPlayerAttack(Species, Species, isize),
+    /// A trap or projectile left behind by the player hits something.
+    ProxyAttack(Species, Species, isize),
     PlayerIsInvincible(Species),
     HealSelf(isize),
     HealOther(Species, isize),
     CreatureHealsItself(Species, isize),
     InvalidAction(InvalidAction),
+    AimMode(AimMode),
+    EnemyPacing(EnemyPacing),
+    EnemyTrails(bool),
+    RumbleIntensity(RumbleIntensity),
+    /// The seed of this run, for the player to pass around.
+    RunSeed(u64),
+    TrapSpotted(Species),
+    TrapSprung(Species),
+    TrapDisarmed(Species),
+    DifficultySet(DifficultyPreset),
+    DirectorIntensitySet(DirectorIntensity),
+    /// The new time limit of each turn, see TurnTimer.
+    TurnTimer(Option<u32>),
+    EffectResisted(Species, StatusEffect),
+    Cleansed(Species),
+    SpellReflected(Species),
+    Countered(Species),
+    /// The caste of the spell, why it did nothing, and whether its soul was given back.
+    SpellFizzled(Soul, FizzleReason, bool),
+    SomethingStirs,
+    WeakPointDestroyed(Species),
+    Looted(Loot),
+    MimicRevealed(Species),
+    KeyItemFound(KeyItem),
+    ItemEquipped(Item),
+    ItemDropped(Item),
+    GrinderApproaches,
+    WanderersReturn,
+    SpellDeflected(Species),
+    MeleeDeflected(Species),
+    RecipeWoven(Axiom, Soul),
+    SpellEquipped(Soul),
+    ScrollLearned(Soul),
+    /// The caste of the scroll, and how many souls it gave.
+    ScrollUnravelled(Soul, usize),
+    /// How much the new shield soaks up, and for how many turns.
+    ShieldRaised(usize, usize),
+    ShieldAbsorbed(Species, usize),
+    ShieldFaded,
+    Scorched,
+    CorpseGrabbed(Species),
+    CorpseReleased(Species),
+    CorpseTooHeavy(Species),
+    NothingToGrab,
+    StuckInMud,
+    PulledFromMud,
+    CartDerailed,
+    SwitchThrown,
+    RegionEntered(String),
+    RegionExited(String),
+    Blessed(Blessing, usize),
+    BlessingFaded(Blessing),
+    SpectatorToggled(bool),
+    HighlightSaved(String),
+    HighlightFailed(String),
+    PaletteChanged(ColorPalette),
+    /// How many WarpCharges are left.
+    Warped(usize),
+    /// This creature will soon evolve, see mature_creatures.
+    EvolutionCharging(Species),
+    /// The species before and after.
+    Evolved(Species, Species),
+    /// The names of the content packs switched on this session.
+    ContentPacks(Vec<String>),
+    /// Something which happened on this tile, and is not shown if the
+    /// player cannot see it and the log hides off-screen events.
+    At(Box<Message>, Position),
+}
+
+impl Message {
+    pub fn category(&self) -> MessageCategory {
+        match self {
+            Message::HostileAttack(..)
+            | Message::PlayerAttack(..)
+            | Message::ProxyAttack(..)
+            | Message::PlayerIsInvincible(..)
+            | Message::ShieldAbsorbed(..) => MessageCategory::Combat,
+            Message::NoPlayerAttack(..) => MessageCategory::NpcCombat,
+            Message::HealSelf(..) | Message::HealOther(..) | Message::CreatureHealsItself(..) => {
+                MessageCategory::Healing
+            }
+            Message::InvalidAction(..) => MessageCategory::Warning,
+            Message::At(message, _) => message.category(),
+            _ => MessageCategory::General,
+        }
+    }
 }
 
 pub fn print_message_in_log(
@@ -1057,22 +1208,34 @@ pub fn print_message_in_log(
     log: Query<Entity, With<MessageLog>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    registry: Res<SpeciesRegistry>,
+    settings: Res<Settings>,
+    vision: Res<VisibilityMap>,
 ) {
-    for (i, event) in events.read().enumerate() {
-        let new_string = match &event.message {
+    let mut slid = false;
+    for event in events.read() {
+        let (message, tile) = match &event.message {
+            Message::At(message, tile) => (message.as_ref(), Some(tile)),
+            message => (message, None),
+        };
+        let off_screen = tile.is_some_and(|tile| !vision.is_visible(tile));
+        if !settings.log.shows(message.category()) || (off_screen && !settings.log.off_screen) {
+            continue;
+        }
+        let new_string = match message {
             Message::Tutorial => LORE[18],
             Message::HostileAttack(species, damage) => &format!(
                 "The {} hits you for [r]{}[w] damage.",
-                match_species_with_string(&species),
+                registry.get(species).name,
                 damage
             ),
             Message::PlayerIsInvincible(species) => &format!(
                 "The {} fails to hit you.",
-                match_species_with_string(&species)
+                registry.get(species).name
             ),
             Message::PlayerAttack(species, damage) => &format!(
                 "You hit the {} for [r]{}[w] damage.",
-                match_species_with_string(&species),
+                registry.get(species).name,
                 damage
             ),
             Message::HealSelf(damage) => {
@@ -1080,20 +1243,244 @@ pub fn print_message_in_log(
             }
             Message::HealOther(species, damage) => &format!(
                 "You heal the {} for [l]{}[w] health points.",
-                match_species_with_string(&species),
+                registry.get(species).name,
                 damage
             ),
             Message::CreatureHealsItself(species, damage) => &format!(
                 "The {} heals itself for [l]{}[w] health points.",
-                match_species_with_string(&species),
+                registry.get(species).name,
+                damage
+            ),
+            Message::ProxyAttack(culprit_species, victim_species, damage) => &format!(
+                "Your {} hits the {} for [r]{}[w] damage.",
+                registry.get(culprit_species).name,
+                registry.get(victim_species).name,
                 damage
             ),
             Message::NoPlayerAttack(culprit_species, victim_species, damage) => &format!(
                 "The {} hits the {} for [r]{}[w] damage.",
-                match_species_with_string(&culprit_species),
-                match_species_with_string(&victim_species),
+                registry.get(culprit_species).name,
+                registry.get(victim_species).name,
                 damage
             ),
+            Message::WeakPointDestroyed(species) => &format!(
+                "A weak point of the {} shatters!",
+                registry.get(species).name
+            ),
+            Message::Looted(loot) => &match loot {
+                Loot::Souls { caste, amount } => format!(
+                    "You find {} {} inside the reliquary.",
+                    amount,
+                    match_soul_with_string(caste)
+                ),
+                Loot::Axiom { caste, axiom } => format!(
+                    "You find a fragment of [y]{:?}[w], which could be woven into your {}.",
+                    axiom,
+                    match_soul_with_string(caste)
+                ),
+                Loot::Item(item) => format!(
+                    "You find the {} inside the reliquary, and put it on.",
+                    item.name()
+                ),
+                Loot::SlotLock { caste } => format!(
+                    "You find a seal inside the reliquary. One slot of your wheel now only holds {}, which it casts for free.",
+                    match_soul_with_string(caste)
+                ),
+                Loot::Loom => "You find a loom inside the reliquary. Recipes picked in the recipe book will now be woven whole, one soul per turn.".to_owned(),
+            },
+            Message::KeyItemFound(item) => &format!("You pick up the {}.", item.name()),
+            Message::ItemEquipped(item) => &format!("You put on the {}.", item.name()),
+            Message::ItemDropped(item) => &format!("You leave the {} behind.", item.name()),
+            Message::SpellDeflected(species) => &format!(
+                "[c]Your spell ripples harmlessly across the {}[c]'s wards.[w]",
+                registry.get(species).name
+            ),
+            Message::MeleeDeflected(species) => &format!(
+                "[c]The {}[c]'s attack glances off you.[w]",
+                registry.get(species).name
+            ),
+            Message::RecipeWoven(axiom, caste) => &format!(
+                "You weave [y]{:?}[w] into a new version of your {}.",
+                axiom,
+                match_soul_with_string(caste)
+            ),
+            Message::SpellEquipped(caste) => &format!(
+                "Your {} takes on its new form.",
+                match_soul_with_string(caste)
+            ),
+            Message::ScrollLearned(caste) => &format!(
+                "You commit the scroll to memory. Your {} could take on its form.",
+                match_soul_with_string(caste)
+            ),
+            Message::ScrollUnravelled(caste, amount) => &format!(
+                "The scroll unravels into {} {}.",
+                amount,
+                match_soul_with_string(caste)
+            ),
+            Message::ShieldRaised(amount, turns) => &format!(
+                "[c]A shield shimmers around you, soaking up {} damage for {} turns.[w]",
+                amount, turns
+            ),
+            Message::ShieldAbsorbed(species, amount) => &format!(
+                "[c]The shield of the[w] {} [c]soaks up {} damage.[w]",
+                registry.get(species).name,
+                amount
+            ),
+            Message::ShieldFaded => "[c]Your shield fades away.[w]",
+            Message::Scorched => "[r]The lava sears your feet![w]",
+            Message::CorpseGrabbed(species) => &format!(
+                "You grab the corpse of the {}. It will follow you, until you press G again.",
+                registry.get(species).name
+            ),
+            Message::CorpseReleased(species) => &format!(
+                "You let go of the corpse of the {}.",
+                registry.get(species).name
+            ),
+            Message::CorpseTooHeavy(species) => &format!(
+                "[y]The corpse of the {}[y] is far too heavy to drag.[w]",
+                registry.get(species).name
+            ),
+            Message::NothingToGrab => "[y]There is no corpse here to grab.[w]",
+            Message::StuckInMud => "[y]You sink into the mud.[w]",
+            Message::PulledFromMud => "[y]You pull yourself free of the mud.[w]",
+            Message::CartDerailed => "[y]An ore cart jumps off the end of its track![w]",
+            Message::SwitchThrown => "You throw the switch, rerouting the junction.",
+            Message::RegionEntered(name) => &format!("You enter the [y]{}[w].", name),
+            Message::RegionExited(name) => &format!("You leave the [y]{}[w].", name),
+            Message::Blessed(blessing, turns) => &format!(
+                "The shrine crumbles, granting you the {}: for {} turns, {}.",
+                blessing.name(),
+                turns,
+                blessing.description()
+            ),
+            Message::SpectatorToggled(true) => {
+                "Enemy spells you have never seen before will now play out one axiom at a time. Press M again to stop."
+            }
+            Message::SpectatorToggled(false) => "Enemy spells will now play out at full speed.",
+            Message::HighlightSaved(path) => {
+                &format!("The last few turns were saved as [y]{}[w].", path)
+            }
+            Message::PaletteChanged(palette) => {
+                &format!("The colours of the game are now [y]{}[w].", palette.name())
+            }
+            Message::EvolutionCharging(species) => &format!(
+                "The {} starts to glow, on the verge of evolving!",
+                registry.get(species).name
+            ),
+            Message::Evolved(from, into) => &format!(
+                "The {} evolves into the {}!",
+                registry.get(from).name,
+                registry.get(into).name
+            ),
+            Message::ContentPacks(names) => {
+                &format!("Seasonal content is active: [y]{}[w].", names.join(", "))
+            }
+            Message::Warped(charges) => &format!(
+                "Space folds around you, and you reappear out of sight. ([y]{}[w] warps left on this floor)",
+                charges
+            ),
+            Message::HighlightFailed(error) => {
+                &format!("[r]The highlight could not be saved: {}.[w]", error)
+            }
+            Message::BlessingFaded(blessing) => {
+                &format!("The {} fades away.", blessing.name())
+            }
+            Message::GrinderApproaches => {
+                "[r]You have lingered for too long. The grinder approaches, devouring the floor.[w]"
+            }
+            Message::WanderersReturn => {
+                "[y]Something stirs in the places you left behind.[w]"
+            }
+            Message::EffectResisted(species, effect) => &format!(
+                "The {} is immune to [c]{:?}[w].",
+                registry.get(species).name,
+                effect
+            ),
+            Message::Cleansed(species) => &format!(
+                "[l]The curses afflicting the {}[l] are washed away.[w]",
+                registry.get(species).name
+            ),
+            Message::SpellReflected(species) => &format!(
+                "[a]The spell bounces off the {}[a] and turns back on its caster![w]",
+                registry.get(species).name
+            ),
+            Message::SpellFizzled(soul, reason, refunded) => &format!(
+                "[d]Your {}[d] spell fizzles: {}.{}[w]",
+                match_soul_with_string(soul),
+                match reason {
+                    FizzleReason::NoTargets => "there was nothing to affect",
+                    FizzleReason::Spellproof => "everything it touched was Spellproof",
+                },
+                if *refunded {
+                    " The soul returns to you."
+                } else {
+                    ""
+                }
+            ),
+            Message::Countered(species) => &format!(
+                "[m]The {}[m] unravels the magic aimed at it.[w]",
+                registry.get(species).name
+            ),
+            Message::SomethingStirs => "[s]Something nearby stirs from its slumber...[w]",
+            Message::MimicRevealed(species) => &format!(
+                "[r]The reliquary was a {}[r] all along![w]",
+                registry.get(species).name
+            ),
+            Message::AimMode(aim_mode) => match aim_mode {
+                AimMode::Momentum => "[y]Your spells will now be aimed towards your last move.[w]",
+                AimMode::Facing => {
+                    "[y]Your spells will now be aimed towards your mouse cursor. Press F to switch back.[w]"
+                }
+            },
+            Message::EnemyPacing(pacing) => match pacing {
+                EnemyPacing::Instant => "[y]Enemy turns will now resolve instantly.[w]",
+                EnemyPacing::Fast => "[y]Enemy turns will now play quickly.[w]",
+                EnemyPacing::Readable => "[y]Enemy turns will now play one action at a time.[w]",
+                EnemyPacing::Slow => "[y]Enemy turns will now play slowly. Press T to cycle back.[w]",
+            },
+            Message::EnemyTrails(shown) => {
+                if *shown {
+                    "[y]The trails of enemies in sight will now be shown while examining.[w]"
+                } else {
+                    "[y]The trails of enemies will now be hidden. Press G to show them again.[w]"
+                }
+            }
+            Message::RumbleIntensity(intensity) => match intensity {
+                RumbleIntensity::Off => "[y]Controller rumble is now off. Press H to turn it back on.[w]",
+                RumbleIntensity::Low => "[y]Controller rumble is now faint.[w]",
+                RumbleIntensity::High => "[y]Controller rumble is now strong.[w]",
+            },
+            Message::TurnTimer(limit) => match limit {
+                Some(seconds) => &format!(
+                    "[y]Score attack! You now have [r]{}[y] seconds to act each turn, or it passes you by.[w]",
+                    seconds
+                ),
+                None => "[y]The turn timer is now off. Press N to turn it back on.[w]",
+            },
+            Message::TrapSpotted(species) => &format!(
+                "[y]You spot a hidden[w] {}[y]! Press E next to it to disarm it.[w]",
+                registry.get(species).name
+            ),
+            Message::TrapSprung(species) => &format!(
+                "[r]You stumble onto a hidden[w] {}[r]![w]",
+                registry.get(species).name
+            ),
+            Message::TrapDisarmed(species) => &format!(
+                "[y]You disarm the[w] {}[y].[w]",
+                registry.get(species).name
+            ),
+            Message::RunSeed(seed) => &format!(
+                "[y]This run's seed is[w] {}[y]. Type it in the difficulty menu, or start the game with --seed {}, to play it again.[w]",
+                seed, seed
+            ),
+            Message::DifficultySet(preset) => &format!(
+                "[y]The difficulty is now[w] {}[y].[w]",
+                preset.name()
+            ),
+            Message::DirectorIntensitySet(intensity) => &format!(
+                "[y]Wanderers will return to explored places:[w] {}[y]. (F4 to change)[w]",
+                intensity.name()
+            ),
             Message::InvalidAction(action) => match action {
                 InvalidAction::WheelFull => {
                     "[y]Your Soul Wheel is already full, cast some with 1-8 before drawing more![w]"
@@ -1104,13 +1491,58 @@ pub fn print_message_in_log(
                 InvalidAction::CannotMelee(species) => {
                     &format!(
                     "[y]You cannot hope to breach the {}[y]'s defenses![w]",
-                    match_species_with_string(&species)
+                    registry.get(species).name
                     )
                 }
                 InvalidAction::EmptySlotCast => {
                     "[y]That slot has nothing in it, you cannot cast it as a spell![w]"
                 }
+                InvalidAction::DoorLocked => {
+                    "[y]The door will not budge while hostile creatures are still awake![w]"
+                }
+                InvalidAction::PushBlocked(species) => &format!(
+                    "[y]The {}[y] won't budge, something blocks the way![w]",
+                    registry.get(species).name
+                ),
+                InvalidAction::DoorBlocked => {
+                    "[y]Something is standing in the doorway, the door cannot close![w]"
+                }
+                InvalidAction::DoorWired => {
+                    "[y]This door is wired to a lever or a pressure plate somewhere else![w]"
+                }
+                InvalidAction::NotEnoughSouls(caste, amount) => &format!(
+                    "[y]This spell is too long, and needs {} more {}[y] to be cast![w]",
+                    amount,
+                    match_soul_with_string(caste)
+                ),
+                InvalidAction::MissingKey(key) => &format!(
+                    "[y]This door will only open for someone carrying a {}[y]![w]",
+                    key.name()
+                ),
+                InvalidAction::AlreadyBlessed(blessing) => &format!(
+                    "[y]You already bear the {}[y], and cannot take another blessing until it fades.[w]",
+                    blessing.name()
+                ),
+                InvalidAction::RecipeComplete => {
+                    "[y]Every soul of this recipe is already in place![w]"
+                }
+                InvalidAction::NoWarpCharges => {
+                    "[y]You have no warps left, they will only return on the next floor![w]"
+                }
+                InvalidAction::NoSafeTile => {
+                    "[y]There is nowhere you have explored which is out of the enemies' sight![w]"
+                }
             },
+            Message::At(..) => unreachable!("Messages at a tile are unwrapped above"),
+        };
+        // The category colour replaces the white parts of the message.
+        let new_string = &match settings.message_colors.get(&message.category()) {
+            Some(tag) => format!(
+                "[{}]{}",
+                tag,
+                new_string.replace("[w]", &format!("[{}]", tag))
+            ),
+            None => new_string.to_owned(),
         };
         let mut new_text = Entity::PLACEHOLDER;
         commands.entity(log.single()).with_children(|parent| {
@@ -1125,8 +1557,9 @@ pub fn print_message_in_log(
         });
 
         // This should only happen once.
-        if i == 0 {
+        if !slid {
             slide.send(SlideMessages);
+            slid = true;
         }
     }
 }
@@ -1179,27 +1612,6 @@ pub fn slide_message_log(mut messages: Query<(&mut Node, &mut LogSlide)>, time:
     }
 }
 
-pub fn match_species_with_string(species: &Species) -> String {
-    let string = match species {
-        Species::Hunter => "[l]Scion of the Old World[w]",
-        Species::Apiarist => "[m]Brass Apiarist[w]",
-        Species::Tinker => "[d]Frenzied Dreamtinker[w]",
-        Species::Oracle => "[r]Anisychic Oracle[w]",
-        Species::Shrike => "[y]Jade Shrike[w]",
-        Species::Second => "[b]Emblem of Sin[w]",
-        Species::Trap => "[c]Psychic Prism[w]",
-        Species::Abazon => "[s]Terracotta Sentry[w]",
-        Species::Wall => "[a]Rampart of Nacre[w]",
-        Species::WeakWall => "[a]Rampart of Nacre[w]",
-        Species::Airlock => "[a]Quicksilver Curtains[w]",
-        Species::Player => "[p]Reality Anchor[w]",
-        Species::EpsilonTail => "[y]Rubberized Mecha-Segment[w]",
-        Species::EpsilonHead => "[y]Epsilon, Crowned by Truth[w]",
-        _ => &format!("{:?}", species),
-    };
-    string.to_owned()
-}
-
 pub fn spawn_split_text(
     new_string: &str,
     parent: &mut ChildBuilder,
@@ -0,0 +1,156 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    creature::{CreatureFlags, Door, FlagQuery, Player, Wall},
+    events::RespawnPlayer,
+    map::{Map, Position},
+    sets::{Animation, NpcTurn},
+};
+
+/// How many tiles away the player can see.
+pub const SIGHT_RADIUS: i32 = 10;
+
+pub struct VisionPlugin;
+
+impl Plugin for VisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VisibilityMap>();
+        app.add_systems(
+            Update,
+            compute_field_of_view.after(NpcTurn).before(Animation),
+        );
+    }
+}
+
+/// Which tiles the player can currently see, and which ones they saw before.
+#[derive(Resource, Default)]
+pub struct VisibilityMap {
+    pub visible: HashSet<Position>,
+    pub remembered: HashSet<Position>,
+}
+
+impl VisibilityMap {
+    pub fn is_visible(&self, position: &Position) -> bool {
+        self.visible.contains(position)
+    }
+
+    pub fn is_remembered(&self, position: &Position) -> bool {
+        self.remembered.contains(position)
+    }
+}
+
+/// Recompute what the player sees whenever something moved.
+pub fn compute_field_of_view(
+    player: Query<&Position, With<Player>>,
+    creatures: Query<&CreatureFlags>,
+    opaque: Query<(), Or<(With<Wall>, With<Door>)>>,
+    map: Res<Map>,
+    mut respawn: EventReader<RespawnPlayer>,
+    mut vision: ResMut<VisibilityMap>,
+) {
+    // A new run is a new cage, nothing about the old one is remembered.
+    if respawn.read().count() > 0 {
+        vision.remembered.clear();
+    }
+    let Ok(origin) = player.get_single() else {
+        return;
+    };
+    if !map.is_changed() && !vision.visible.is_empty() {
+        return;
+    }
+    let visible = map.visible_tiles(*origin, SIGHT_RADIUS, |entity| {
+        creatures
+            .get(*entity)
+            .is_ok_and(|flags| opaque.flagged(flags))
+    });
+    vision.remembered.extend(visible.iter().copied());
+    vision.visible = visible;
+}
+
+/// One row of tiles in a quadrant, `depth` tiles away from the origin,
+/// between two slopes.
+struct Row {
+    depth: i32,
+    start_slope: f32,
+    end_slope: f32,
+}
+
+impl Row {
+    fn columns(&self) -> std::ops::RangeInclusive<i32> {
+        // Round ties up at the start, and down at the end.
+        let min_col = (self.depth as f32 * self.start_slope + 0.5).floor() as i32;
+        let max_col = (self.depth as f32 * self.end_slope - 0.5).ceil() as i32;
+        min_col..=max_col
+    }
+
+    fn next(&self) -> Self {
+        Row {
+            depth: self.depth + 1,
+            start_slope: self.start_slope,
+            end_slope: self.end_slope,
+        }
+    }
+
+    /// Whether a floor tile in this row can be seen, while also being able
+    /// to see the origin from it.
+    fn is_symmetric(&self, col: i32) -> bool {
+        col as f32 >= self.depth as f32 * self.start_slope
+            && col as f32 <= self.depth as f32 * self.end_slope
+    }
+}
+
+fn slope(depth: i32, col: i32) -> f32 {
+    (2 * col - 1) as f32 / (2 * depth) as f32
+}
+
+/// Symmetric shadowcasting: if a tile can see another, that tile can also see it back.
+/// Walls are visible, but block the view of everything behind them.
+/// See https://www.albertford.com/shadowcasting/
+pub fn symmetric_shadowcasting(
+    origin: Position,
+    radius: i32,
+    is_opaque: impl Fn(Position) -> bool,
+) -> HashSet<Position> {
+    let mut visible = HashSet::new();
+    visible.insert(origin);
+    // Each quadrant is a transformation from (depth, column) to a tile.
+    let quadrants: [fn(Position, i32, i32) -> Position; 4] = [
+        |o, depth, col| Position::new(o.x + col, o.y + depth),
+        |o, depth, col| Position::new(o.x + col, o.y - depth),
+        |o, depth, col| Position::new(o.x + depth, o.y + col),
+        |o, depth, col| Position::new(o.x - depth, o.y + col),
+    ];
+    for transform in quadrants {
+        let mut rows = vec![Row {
+            depth: 1,
+            start_slope: -1.,
+            end_slope: 1.,
+        }];
+        while let Some(mut row) = rows.pop() {
+            if row.depth > radius {
+                continue;
+            }
+            let mut previous_was_wall = None;
+            for col in row.columns() {
+                let tile = transform(origin, row.depth, col);
+                let is_wall = is_opaque(tile);
+                if is_wall || row.is_symmetric(col) {
+                    visible.insert(tile);
+                }
+                if previous_was_wall == Some(true) && !is_wall {
+                    row.start_slope = slope(row.depth, col);
+                }
+                if previous_was_wall == Some(false) && is_wall {
+                    let mut next_row = row.next();
+                    next_row.end_slope = slope(row.depth, col);
+                    rows.push(next_row);
+                }
+                previous_was_wall = Some(is_wall);
+            }
+            if previous_was_wall == Some(false) {
+                rows.push(row.next());
+            }
+        }
+    }
+    visible
+}
@@ -0,0 +1,221 @@
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    creature::{Awake, CreatureFlags, Door, FlagQuery, Player, Species, Wall},
+    events::{EndTurn, PlayerAction, RespawnCage, TeleportEntity, TurnManager},
+    graphics::{EffectSequence, EffectType, PlaceMagicVfx},
+    map::{Map, Position},
+    sets::{Animation, ControlState, PlayerInput},
+    species::SpeciesRegistry,
+    spells::spell_stack_is_empty,
+    ui::{AddMessage, InvalidAction, Message},
+    vision::{VisibilityMap, SIGHT_RADIUS},
+};
+
+pub struct WarpPlugin;
+
+impl Plugin for WarpPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_warp_pips);
+        app.add_systems(
+            Update,
+            warp_input
+                .run_if(spell_stack_is_empty.and(in_state(ControlState::Player)))
+                .in_set(PlayerInput),
+        );
+        app.add_systems(
+            Update,
+            update_warp_pips
+                .run_if(resource_changed::<WarpCharges>)
+                .in_set(Animation),
+        );
+    }
+}
+
+/// How many emergency warps the player gets on each floor.
+pub const WARP_CHARGES: usize = 2;
+
+/// The emergency warps the player has left on this floor, see PanicWarp.
+#[derive(Resource, Serialize, Deserialize, Clone)]
+pub struct WarpCharges {
+    pub charges: usize,
+}
+
+impl Default for WarpCharges {
+    fn default() -> Self {
+        Self {
+            charges: WARP_CHARGES,
+        }
+    }
+}
+
+/// `entity` teleports to the nearest explored tile no awake hostile creature
+/// can see. This takes a turn and one of the WarpCharges.
+#[derive(Event)]
+pub struct PanicWarp {
+    pub entity: Entity,
+}
+
+/// Backquote warps away in an emergency.
+fn warp_input(
+    input: Res<ButtonInput<KeyCode>>,
+    player: Query<Entity, With<Player>>,
+    mut warp: EventWriter<PanicWarp>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut turn_end: EventWriter<EndTurn>,
+) {
+    if !input.just_pressed(KeyCode::Backquote) {
+        return;
+    }
+    if let Ok(player) = player.get_single() {
+        warp.send(PanicWarp { entity: player });
+        turn_manager.action_this_turn = PlayerAction::Warp;
+        turn_end.send(EndTurn);
+    }
+}
+
+/// Every floor, including the first one of a new run, refills the WarpCharges.
+pub fn restore_warp_charges(
+    mut events: EventReader<RespawnCage>,
+    mut charges: ResMut<WarpCharges>,
+) {
+    if events.read().count() > 0 {
+        *charges = WarpCharges::default();
+    }
+}
+
+/// Every tile seen by at least one awake hostile creature.
+fn enemy_sight(
+    map: &Map,
+    creatures: &Query<(&Position, &Species, &CreatureFlags), (With<Awake>, Without<Player>)>,
+    flags: &Query<&CreatureFlags>,
+    opaque: &Query<(), Or<(With<Wall>, With<Door>)>>,
+    registry: &SpeciesRegistry,
+) -> HashSet<Position> {
+    let mut seen = HashSet::new();
+    for (position, species, _) in creatures.iter() {
+        if !registry.get(species).is_hostile() {
+            continue;
+        }
+        seen.extend(map.visible_tiles(*position, SIGHT_RADIUS, |entity| {
+            flags.get(*entity).is_ok_and(|flags| opaque.flagged(flags))
+        }));
+    }
+    seen
+}
+
+/// The destination is the closest by walking distance, so the player does
+/// not end up on the other side of a wall from where they were.
+pub fn panic_warp(
+    mut events: EventReader<PanicWarp>,
+    warpers: Query<&Position>,
+    enemies: Query<(&Position, &Species, &CreatureFlags), (With<Awake>, Without<Player>)>,
+    flags: Query<&CreatureFlags>,
+    opaque: Query<(), Or<(With<Wall>, With<Door>)>>,
+    registry: Res<SpeciesRegistry>,
+    vision: Res<VisibilityMap>,
+    map: Res<Map>,
+    mut charges: ResMut<WarpCharges>,
+    mut turn_manager: ResMut<TurnManager>,
+    mut teleport: EventWriter<TeleportEntity>,
+    mut magic_vfx: EventWriter<PlaceMagicVfx>,
+    mut text: EventWriter<AddMessage>,
+) {
+    for event in events.read() {
+        let Ok(origin) = warpers.get(event.entity) else {
+            continue;
+        };
+        if charges.charges == 0 {
+            text.send(AddMessage {
+                message: Message::InvalidAction(InvalidAction::NoWarpCharges),
+            });
+            turn_manager.action_this_turn = PlayerAction::Invalid;
+            continue;
+        }
+        let watched = enemy_sight(&map, &enemies, &flags, &opaque, &registry);
+        let distances = map.dijkstra_map(&[*origin], |tile| map.is_passable(tile.x, tile.y));
+        let Some(destination) = distances
+            .iter()
+            .filter(|(tile, distance)| {
+                **distance > 0 && vision.is_remembered(tile) && !watched.contains(*tile)
+            })
+            .min_by_key(|(tile, distance)| (**distance, tile.x, tile.y))
+            .map(|(tile, _)| *tile)
+        else {
+            text.send(AddMessage {
+                message: Message::InvalidAction(InvalidAction::NoSafeTile),
+            });
+            turn_manager.action_this_turn = PlayerAction::Invalid;
+            continue;
+        };
+        charges.charges -= 1;
+        teleport.send(TeleportEntity::new(
+            event.entity,
+            destination.x,
+            destination.y,
+        ));
+        for (targets, effect, appear) in [
+            (vec![*origin], EffectType::Airlock, 0.),
+            (vec![destination], EffectType::XCross, 0.2),
+        ] {
+            magic_vfx.send(PlaceMagicVfx {
+                targets,
+                sequence: EffectSequence::Simultaneous,
+                effect,
+                decay: 1.,
+                appear,
+                caste: None,
+            });
+        }
+        text.send(AddMessage {
+            message: Message::Warped(charges.charges),
+        });
+    }
+}
+
+#[derive(Component)]
+pub struct WarpPips;
+
+/// One of the WarpPips, lit while its charge is unused.
+#[derive(Component)]
+pub struct WarpPip(usize);
+
+fn spawn_warp_pips(mut commands: Commands) {
+    commands
+        .spawn((
+            WarpPips,
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(4.),
+                top: Val::Percent(26.),
+                column_gap: Val::Px(0.5),
+                padding: UiRect::all(Val::Px(0.5)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0., 0., 0., 0.8)),
+        ))
+        .with_children(|parent| {
+            for index in 0..WARP_CHARGES {
+                parent.spawn((
+                    WarpPip(index),
+                    Node {
+                        width: Val::Px(1.),
+                        height: Val::Px(1.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::NONE),
+                ));
+            }
+        });
+}
+
+fn update_warp_pips(charges: Res<WarpCharges>, mut pips: Query<(&WarpPip, &mut BackgroundColor)>) {
+    for (pip, mut color) in pips.iter_mut() {
+        color.0 = if pip.0 < charges.charges {
+            Color::srgb(0.4, 0.8, 1.)
+        } else {
+            Color::srgb(0.2, 0.2, 0.2)
+        };
+    }
+}
@@ -0,0 +1,108 @@
+//! Flag entities left behind by removed and transformed creatures, see audit_flag_entities.
+
+use bevy::prelude::*;
+use redesign_tgfp::*;
+
+fn summon_crowd(app: &mut App, species: Species) {
+    let player_pos = *app
+        .world_mut()
+        .query_filtered::<&Position, With<Player>>()
+        .single(app.world());
+    // Far away from the player, so that nothing interrupts the crowd.
+    for x in 0..8 {
+        for y in 0..8 {
+            app.world_mut().send_event(SummonCreature {
+                position: Position::new(player_pos.x + 100 + x, player_pos.y + y),
+                species,
+                momentum: OrdDir::Down,
+                summoner_tile: player_pos,
+                summoner: None,
+                spellbook: None,
+                projectile: None,
+                owner: None,
+            });
+        }
+    }
+    settle_turn(app);
+}
+
+fn crowd(app: &mut App) -> Vec<Entity> {
+    app.world_mut()
+        .query_filtered::<(Entity, &Species), Without<Player>>()
+        .iter(app.world())
+        .filter(|(_, species)| matches!(species, Species::Tinker | Species::Hunter))
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+fn assert_no_orphans(app: &mut App) {
+    let world = app.world_mut();
+    let flag_entities: Vec<(Entity, Entity)> = world
+        .query::<(Entity, &FlagEntity)>()
+        .iter(world)
+        .map(|(entity, flag)| (entity, flag.parent_creature))
+        .collect();
+    for (flag_entity, parent_creature) in flag_entities {
+        let flags = world
+            .get::<CreatureFlags>(parent_creature)
+            .unwrap_or_else(|| panic!("Flag entity {} lost its creature.", flag_entity));
+        assert!(
+            flags.effects_flags == flag_entity || flags.species_flags == flag_entity,
+            "Creature {} does not point back to its flag entity {}.",
+            parent_creature,
+            flag_entity
+        );
+    }
+    // Body parts and other children go away with their creature.
+    let parents: Vec<Entity> = world
+        .query::<&Parent>()
+        .iter(world)
+        .map(|p| p.get())
+        .collect();
+    for parent in parents {
+        assert!(
+            world.get_entity(parent).is_ok(),
+            "A child outlived {}.",
+            parent
+        );
+    }
+}
+
+#[test]
+fn mass_removal_leaves_no_orphans() {
+    let mut app = headless_app();
+    settle_turn(&mut app);
+    for round in 0..3 {
+        summon_crowd(&mut app, Species::Tinker);
+        let crowd = crowd(&mut app);
+        assert!(
+            crowd.len() >= 64,
+            "Round {}: the crowd was not summoned.",
+            round
+        );
+        for (i, entity) in crowd.into_iter().enumerate() {
+            if i % 2 == 0 {
+                app.world_mut().send_event(RemoveCreature {
+                    entity,
+                    culprit: None,
+                });
+            } else {
+                app.world_mut().send_event(TransformCreature {
+                    entity,
+                    new_species: Species::Hunter,
+                });
+            }
+        }
+        settle_turn(&mut app);
+        assert_no_orphans(&mut app);
+    }
+    // Then clear out everything which was left, which may well clear the floor.
+    for entity in crowd(&mut app) {
+        app.world_mut().send_event(RemoveCreature {
+            entity,
+            culprit: None,
+        });
+    }
+    settle_turn(&mut app);
+    assert_no_orphans(&mut app);
+}
@@ -0,0 +1,48 @@
+//! The spawn director, run in a headless world.
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use redesign_tgfp::*;
+
+fn count_hunters(app: &mut App) -> usize {
+    app.world_mut()
+        .query::<&Species>()
+        .iter(app.world())
+        .filter(|species| **species == Species::Hunter)
+        .count()
+}
+
+#[test]
+fn wanderers_return_to_explored_places() {
+    let mut app = headless_app();
+    {
+        let mut config = app.world_mut().resource_mut::<LevelGenConfig>();
+        // Caves, which are wide enough to leave places behind.
+        config.next_layout();
+        config.next_layout();
+        config.seed = Some(7);
+        // A floor with nothing awake on it is cleared at once, and starts over.
+        config.creatures = 1;
+        config.species = vec![(Species::Hunter, 1)];
+    }
+    settle_turn(&mut app);
+    apply_player_command(
+        app.world_mut(),
+        PlayerCommand::SetDirectorIntensity(DirectorIntensity::High),
+    );
+    settle_turn(&mut app);
+    assert_eq!(count_hunters(&mut app), 1);
+
+    // Wander around, so some explored places end up out of sight.
+    let mut rng = StdRng::seed_from_u64(0);
+    let directions = [OrdDir::Up, OrdDir::Right, OrdDir::Down, OrdDir::Left];
+    for _ in 0..500 {
+        let direction = *directions.choose(&mut rng).unwrap();
+        apply_player_command(app.world_mut(), PlayerCommand::Step(direction));
+        settle_turn(&mut app);
+        if count_hunters(&mut app) > 1 {
+            return;
+        }
+    }
+    panic!("No wanderers came back after 500 steps.");
+}
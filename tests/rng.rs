@@ -0,0 +1,20 @@
+//! Saving and restoring the GameRng.
+
+use rand::{Rng, RngCore};
+use redesign_tgfp::*;
+
+#[test]
+fn restored_rng_picks_up_where_it_left_off() {
+    let mut rng = GameRng::new(42);
+    rng.next_u32();
+    rng.next_u64();
+    let mut bytes = [0; 7];
+    rng.fill_bytes(&mut bytes);
+    let _: f32 = rng.gen();
+    let mut restored = GameRng::from_state(rng.state());
+    for _ in 0..100 {
+        assert_eq!(rng.next_u32(), restored.next_u32());
+        assert_eq!(rng.next_u64(), restored.next_u64());
+    }
+    assert_eq!(rng.state(), restored.state());
+}
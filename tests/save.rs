@@ -0,0 +1,80 @@
+//! Saving a run and restoring it, see write_save and restore_save.
+
+use bevy::prelude::*;
+use redesign_tgfp::*;
+
+fn player(app: &mut App) -> (Entity, Position) {
+    let (entity, position) = app
+        .world_mut()
+        .query_filtered::<(Entity, &Position), With<Player>>()
+        .single(app.world());
+    (entity, *position)
+}
+
+#[test]
+fn restored_world_hashes_the_same() {
+    let mut app = headless_app();
+    settle_turn(&mut app);
+    let (player, player_pos) = player(&mut app);
+    // Far away from the player, so that nothing interrupts the Warden.
+    let warden_pos = Position::new(player_pos.x + 100, player_pos.y);
+    app.world_mut().send_event(SummonCreature {
+        position: warden_pos,
+        species: Species::Warden,
+        momentum: OrdDir::Down,
+        summoner_tile: player_pos,
+        summoner: None,
+        spellbook: None,
+        projectile: None,
+        owner: None,
+    });
+    settle_turn(&mut app);
+    let warden = app
+        .world_mut()
+        .query::<(Entity, &Species)>()
+        .iter(app.world())
+        .find(|(_, species)| **species == Species::Warden)
+        .map(|(entity, _)| entity)
+        .expect("The Warden was not summoned");
+    // Break its face, which must stay broken once restored.
+    app.world_mut().send_event(DamageOrHealCreature {
+        entity: warden,
+        culprit: player,
+        hp_mod: -3,
+        tile: Some(Position::new(warden_pos.x + 1, warden_pos.y + 1)),
+        kind: DamageKind::Other,
+    });
+    settle_turn(&mut app);
+
+    let save = write_save(app.world_mut());
+    let saved_hash = save.world_hash;
+    assert_eq!(saved_hash, world_hash(app.world_mut()));
+    restore_save(app.world_mut(), save);
+    // The creatures are summoned back during this frame.
+    app.update();
+    finish_restore(app.world_mut());
+    assert_eq!(saved_hash, world_hash(app.world_mut()));
+}
+
+#[test]
+fn restored_action_log_matches_the_save() {
+    let mut app = headless_app();
+    // Headless worlds do not record their actions, this stands in for record_player_actions.
+    app.init_resource::<ActionLog>();
+    settle_turn(&mut app);
+    let before_save = vec![
+        (0, PlayerCommand::DrawSoul),
+        (1, PlayerCommand::Step(OrdDir::Up)),
+    ];
+    app.world_mut().resource_mut::<ActionLog>().actions = before_save.clone();
+
+    let save = write_save(app.world_mut());
+    assert_eq!(save.actions, before_save);
+    // Played after saving, then undone by loading.
+    app.world_mut()
+        .resource_mut::<ActionLog>()
+        .actions
+        .push((2, PlayerCommand::Step(OrdDir::Down)));
+    restore_save(app.world_mut(), save);
+    assert_eq!(app.world().resource::<ActionLog>().actions, before_save);
+}
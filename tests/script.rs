@@ -0,0 +1,67 @@
+//! Reading the commands of an Axiom::Script, see parse_script.
+
+use redesign_tgfp::*;
+
+fn error(source: &str) -> ScriptError {
+    parse_script(source).expect_err("The script should not have been read")
+}
+
+#[test]
+fn commands_split_on_semicolons_and_newlines() {
+    let commands = parse_script("target 1 0; target -1 0\n  damage 2 ;heal 1\nwarp 0 -2;")
+        .expect("The script should have been read");
+    assert_eq!(
+        commands,
+        vec![
+            ScriptCommand::Target(1, 0),
+            ScriptCommand::Target(-1, 0),
+            ScriptCommand::Damage(2),
+            ScriptCommand::Heal(1),
+            ScriptCommand::Warp(0, -2),
+        ]
+    );
+}
+
+#[test]
+fn comments_are_ignored() {
+    let commands = parse_script("// Only a comment.\npush 0 1 // damage 5; heal 5\n\n//")
+        .expect("The script should have been read");
+    assert_eq!(commands, vec![ScriptCommand::Push(0, 1)]);
+}
+
+#[test]
+fn summon_reads_species_names() {
+    assert_eq!(
+        parse_script("summon Shrike"),
+        Ok(vec![ScriptCommand::Summon(Species::Shrike)])
+    );
+    let error = error("summon Shrike; summon Dragonfly");
+    assert_eq!(error.command, 1);
+    assert_eq!(error.message, "\"Dragonfly\" is not a species");
+}
+
+#[test]
+fn wrong_argument_count_is_reported() {
+    let error = error("target 1 0\ndamage 1 2");
+    assert_eq!(error.command, 1);
+    assert_eq!(error.message, "\"damage\" takes 1 argument(s), not 2");
+    assert_eq!(
+        error.to_string(),
+        "Script command 2: \"damage\" takes 1 argument(s), not 2"
+    );
+}
+
+#[test]
+fn bad_numbers_are_reported() {
+    let error = error("warp 0 up");
+    assert_eq!(error.command, 0);
+    assert_eq!(error.message, "\"up\" is not a number");
+}
+
+#[test]
+fn unknown_commands_are_reported() {
+    assert_eq!(
+        error("// A comment first.\n;explode").message,
+        "\"explode\" is not a command"
+    );
+}
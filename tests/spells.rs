@@ -0,0 +1,74 @@
+//! Checking spells for mistakes, see validate_spell.
+
+use std::collections::HashMap;
+
+use redesign_tgfp::*;
+
+#[test]
+fn shipped_spellbooks_have_no_warnings() {
+    let spellbooks: HashMap<Species, HashMap<Soul, Spell>> =
+        ron::from_str(include_str!("../assets/spellbooks.ron")).expect("spellbooks.ron is invalid");
+    for (species, spells) in spellbooks {
+        for (soul, spell) in spells {
+            assert_eq!(
+                validate_spell(&spell),
+                Vec::new(),
+                "{:?} spell of {:?}",
+                soul,
+                species
+            );
+        }
+    }
+}
+
+#[test]
+fn shipped_death_effects_have_no_warnings() {
+    let death_effects: HashMap<Soul, Spell> =
+        ron::from_str(include_str!("../assets/death_effects.ron"))
+            .expect("death_effects.ron is invalid");
+    for (soul, spell) in death_effects {
+        assert_eq!(
+            validate_spell(&spell),
+            Vec::new(),
+            "{:?} death effect",
+            soul
+        );
+    }
+}
+
+#[test]
+fn axioms_after_terminate_are_unreachable() {
+    let spell = Spell {
+        axioms: vec![
+            Axiom::Ego,
+            Axiom::Terminate,
+            Axiom::HealOrHarm { amount: 1 },
+            Axiom::WhenMoved,
+            Axiom::Ego,
+            Axiom::HealOrHarm { amount: 1 },
+        ],
+    };
+    assert_eq!(validate_spell(&spell), vec![SpellWarning::Unreachable(2)]);
+}
+
+#[test]
+fn payloads_start_over() {
+    // The projectile bursts on the tile it hits, which is the form of its payload.
+    let projectile = Spell {
+        axioms: vec![
+            Axiom::Ego,
+            Axiom::LaunchProjectile,
+            Axiom::HealOrHarm { amount: -1 },
+        ],
+    };
+    assert_eq!(validate_spell(&projectile), Vec::new());
+    // Traps are triggered by WhenSteppedOn, which picks no tiles.
+    let trap = Spell {
+        axioms: vec![
+            Axiom::Ego,
+            Axiom::PlaceStepTrap,
+            Axiom::HealOrHarm { amount: -1 },
+        ],
+    };
+    assert_eq!(validate_spell(&trap), vec![SpellWarning::NoForm(2)]);
+}